@@ -0,0 +1,274 @@
+use dex::{self, ClassDef, DexError, DexFile, Integrity};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Seek};
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Zip(ZipError),
+    Io(String, io::Error),
+    Dex(String, DexError),
+    /// The APK has no classes.dex
+    NoClasses,
+}
+
+/// A DEX image and the entry it was read from. Version 041 containers
+/// yield several images from one entry.
+#[derive(Debug)]
+pub struct DexEntry {
+    name: String,
+    dex: DexFile,
+}
+
+/// A class defined again in a later DEX file. The runtime only ever sees
+/// the first definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateClass {
+    descriptor: String,
+    first: usize,
+    duplicate: usize,
+}
+
+/// All classes of an APK: classes.dex, classes2.dex, ... in the order the
+/// runtime loads them
+#[derive(Debug)]
+pub struct Program {
+    entries: Vec<DexEntry>,
+    integrity: Vec<(String, Integrity)>,
+    /// Entry and class_def index of the definition of every class
+    classes: HashMap<String, (usize, usize)>,
+    duplicates: Vec<DuplicateClass>,
+    skipped: Vec<String>,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Zip(ref error) => write!(f, "{}", error),
+            LoadError::Io(ref name, ref error) => write!(f, "{}: {}", name, error),
+            LoadError::Dex(ref name, ref error) => write!(f, "{}: {}", name, error),
+            LoadError::NoClasses => write!(f, "classes.dex is missing"),
+        }
+    }
+}
+
+impl DexEntry {
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn dex(&self) -> &DexFile {
+        return &self.dex;
+    }
+}
+
+impl DuplicateClass {
+    pub fn descriptor(&self) -> &str {
+        return &self.descriptor;
+    }
+
+    /// Index into `Program::entries` of the definition in use
+    pub fn first(&self) -> usize {
+        return self.first;
+    }
+
+    /// Index into `Program::entries` of the ignored definition
+    pub fn duplicate(&self) -> usize {
+        return self.duplicate;
+    }
+}
+
+impl Program {
+    pub fn entries(&self) -> &[DexEntry] {
+        return &self.entries;
+    }
+
+    /// Checksum and signature of every DEX entry, by entry name
+    pub fn integrity(&self) -> &[(String, Integrity)] {
+        return &self.integrity;
+    }
+
+    pub fn duplicates(&self) -> &[DuplicateClass] {
+        return &self.duplicates;
+    }
+
+    /// classesN.dex entries after a gap in the numbering. The runtime stops
+    /// at the first missing number, so these are never loaded.
+    pub fn skipped(&self) -> &[String] {
+        return &self.skipped;
+    }
+
+    /// Looks up a class by its descriptor, e.g. `Ljava/lang/Object;`
+    pub fn class(&self, descriptor: &str) -> Option<(&DexFile, &ClassDef)> {
+        return self.classes.get(descriptor).map(|&(entry, idx)| {
+            let dex = &self.entries[entry].dex;
+            (dex, &dex.class_defs()[idx])
+        });
+    }
+
+    /// Every class the runtime sees, in loading order. Duplicates are left
+    /// out.
+    pub fn classes(&self) -> Vec<(&DexFile, &ClassDef)> {
+        let mut list = vec![];
+        for (entry, dex_entry) in self.entries.iter().enumerate() {
+            let dex = &dex_entry.dex;
+            for (idx, class) in dex.class_defs().iter().enumerate() {
+                let descriptor = dex.type_descriptor(class.class_idx()).unwrap_or("");
+                if self.classes.get(descriptor) == Some(&(entry, idx)) {
+                    list.push((dex, class));
+                }
+            }
+        }
+        return list;
+    }
+
+    fn add(&mut self, name: String, dex: DexFile) {
+        let entry = self.entries.len();
+        for (idx, class) in dex.class_defs().iter().enumerate() {
+            let descriptor = dex.type_descriptor(class.class_idx()).unwrap_or("").to_string();
+            match self.classes.get(&descriptor) {
+                Some(&(first, _)) => {
+                    self.duplicates.push(DuplicateClass {
+                        descriptor: descriptor,
+                        first: first,
+                        duplicate: entry,
+                    })
+                }
+                None => {
+                    self.classes.insert(descriptor, (entry, idx));
+                }
+            }
+        }
+        self.entries.push(DexEntry {
+            name: name,
+            dex: dex,
+        });
+    }
+}
+
+/// The position of a classesN.dex entry in the loading order, classes.dex
+/// being the first
+fn dex_number(name: &str) -> Option<u32> {
+    if !name.starts_with("classes") || !name.ends_with(".dex") {
+        return None;
+    }
+    let number = &name["classes".len()..name.len() - ".dex".len()];
+    if number.is_empty() {
+        return Some(1);
+    }
+    // The runtime builds the names itself, so classes02.dex or classes1.dex
+    // are never loaded
+    match number.parse::<u32>() {
+        Ok(n) if n >= 2 && !number.starts_with('0') => Some(n),
+        _ => None,
+    }
+}
+
+/// Reads and parses the DEX files of an APK
+pub fn load<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<Program, LoadError> {
+    let mut names: Vec<(u32, String)> = vec![];
+    for i in 0..zip.len() {
+        let file = zip.by_index(i).map_err(LoadError::Zip)?;
+        if let Some(number) = dex_number(file.name()) {
+            names.push((number, file.name().to_string()));
+        }
+    }
+    names.sort();
+
+    let mut program = Program {
+        entries: vec![],
+        integrity: vec![],
+        classes: HashMap::new(),
+        duplicates: vec![],
+        skipped: vec![],
+    };
+    for (i, (number, name)) in names.into_iter().enumerate() {
+        if number != i as u32 + 1 {
+            program.skipped.push(name);
+            continue;
+        }
+        let mut data: Vec<u8> = Vec::new();
+        zip.by_name(&name)
+            .map_err(LoadError::Zip)?
+            .read_to_end(&mut data)
+            .map_err(|e| LoadError::Io(name.clone(), e))?;
+
+        if let Some(integrity) = dex::verify_integrity(&data) {
+            program.integrity.push((name.clone(), integrity));
+        }
+        for dex in dex::parse_container(data).map_err(|e| LoadError::Dex(name.clone(), e))? {
+            program.add(name.clone(), dex);
+        }
+    }
+    if program.entries.is_empty() {
+        return Err(LoadError::NoClasses);
+    }
+    return Ok(program);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assembler;
+
+    fn assembled(sources: &[&str]) -> DexFile {
+        return dex::parse(assembler::assemble(sources).unwrap()).unwrap();
+    }
+
+    fn program(dexes: Vec<DexFile>) -> Program {
+        let mut program = Program {
+            entries: vec![],
+            integrity: vec![],
+            classes: HashMap::new(),
+            duplicates: vec![],
+            skipped: vec![],
+        };
+        for (i, dex) in dexes.into_iter().enumerate() {
+            program.add(format!("classes{}.dex", i + 1), dex);
+        }
+        return program;
+    }
+
+    #[test]
+    fn dex_number_follows_the_runtime_naming() {
+        assert_eq!(dex_number("classes.dex"), Some(1));
+        assert_eq!(dex_number("classes2.dex"), Some(2));
+        assert_eq!(dex_number("classes12.dex"), Some(12));
+        assert_eq!(dex_number("classes1.dex"), None);
+        assert_eq!(dex_number("classes02.dex"), None);
+        assert_eq!(dex_number("classes-2.dex"), None);
+        assert_eq!(dex_number("classes99999999999.dex"), None);
+        assert_eq!(dex_number("classes.dex.bak"), None);
+        assert_eq!(dex_number("assets/classes.dex2"), None);
+        assert_eq!(dex_number("classes"), None);
+    }
+
+    #[test]
+    fn first_definition_wins() {
+        let first = assembled(&[".class public LA;\n.super Ljava/lang/Object;\n",
+                                ".class public LB;\n.super LA;\n"]);
+        let second = assembled(&[".class public final LA;\n.super Ljava/lang/Object;\n",
+                                 ".class public LC;\n.super LA;\n"]);
+        let program = program(vec![first, second]);
+
+        assert_eq!(program.duplicates(),
+                   &[DuplicateClass {
+                         descriptor: "LA;".to_string(),
+                         first: 0,
+                         duplicate: 1,
+                     }]);
+        let (dex, class) = program.class("LA;").unwrap();
+        assert!(std::ptr::eq(dex, program.entries()[0].dex()));
+        assert_eq!(class.access_flags() & 0x10, 0);
+        assert!(program.class("LC;").is_some());
+        assert!(program.class("LD;").is_none());
+
+        let descriptors: Vec<&str> = program.classes()
+            .iter()
+            .map(|&(dex, class)| dex.type_descriptor(class.class_idx()).unwrap())
+            .collect();
+        assert_eq!(descriptors, vec!["LA;", "LB;", "LC;"]);
+    }
+}
@@ -0,0 +1,926 @@
+use axml::{self, AxmlError, Chunk, ResValue, StringPool};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Chunk types in RES_TABLE_TYPE
+const RES_TABLE_TYPE: u16 = 0x0002;
+const RES_TABLE_PACKAGE_TYPE: u16 = 0x0200;
+const RES_TABLE_TYPE_TYPE: u16 = 0x0201;
+const RES_TABLE_TYPE_SPEC_TYPE: u16 = 0x0202;
+
+const APP_PACKAGE_ID: u32 = 0x7f;
+
+// ResTable_type flags
+const FLAG_SPARSE: u8 = 0x01;
+const FLAG_OFFSET16: u8 = 0x02;
+
+// ResTable_entry flags
+const FLAG_COMPLEX: u16 = 0x0001;
+const FLAG_COMPACT: u16 = 0x0008;
+
+/// A decoded resources.arsc
+#[derive(Debug, Clone)]
+pub struct ResourceTable {
+    /// The pool string values refer to
+    strings: StringPool,
+    packages: Vec<Package>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Package {
+    id: u32,
+    name: String,
+    types: Vec<ResourceType>,
+}
+
+/// All entries of one type, e.g. `string`, in every configuration
+#[derive(Debug, Clone)]
+pub struct ResourceType {
+    id: u8,
+    name: String,
+    /// The configuration changes each entry varies with, from the
+    /// ResTable_typeSpec
+    spec_flags: Vec<u32>,
+    configs: Vec<ConfigEntries>,
+}
+
+/// The entries a ResTable_type defines for one configuration
+#[derive(Debug, Clone)]
+pub struct ConfigEntries {
+    config: Config,
+    entries: BTreeMap<u16, Entry>,
+}
+
+/// A ResTable_config, the qualifiers a value applies for
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    mcc: u16,
+    mnc: u16,
+    language: String,
+    region: String,
+    orientation: u8,
+    touchscreen: u8,
+    density: u16,
+    keyboard: u8,
+    navigation: u8,
+    input_flags: u8,
+    screen_width: u16,
+    screen_height: u16,
+    sdk_version: u16,
+    screen_layout: u8,
+    ui_mode: u8,
+    smallest_screen_width_dp: u16,
+    screen_width_dp: u16,
+    screen_height_dp: u16,
+    locale_script: String,
+    locale_variant: String,
+    screen_layout2: u8,
+    color_mode: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    key: String,
+    flags: u16,
+    value: EntryValue,
+}
+
+#[derive(Debug, Clone)]
+pub enum EntryValue {
+    Simple(ResValue),
+    /// A bag, as used by styles, arrays and plurals: the parent it
+    /// inherits from and its values keyed by attribute or other id
+    Complex {
+        parent: u32,
+        values: Vec<(u32, ResValue)>,
+    },
+}
+
+/// A resource looked up by id
+#[derive(Debug, Clone, Copy)]
+pub struct Resource<'a> {
+    id: u32,
+    package: &'a Package,
+    ty: &'a ResourceType,
+}
+
+impl ResourceTable {
+    pub fn strings(&self) -> &StringPool {
+        return &self.strings;
+    }
+
+    pub fn packages(&self) -> &[Package] {
+        return &self.packages;
+    }
+
+    /// Looks up a resource id such as 0x7f040001
+    pub fn resource(&self, id: u32) -> Option<Resource<'_>> {
+        let package = self.packages.iter().find(|p| p.id == id >> 24)?;
+        let ty = package.types.iter().find(|t| t.id as u32 == (id >> 16) & 0xff)?;
+        let resource = Resource {
+            id: id,
+            package: package,
+            ty: ty,
+        };
+        if resource.values().is_empty() {
+            return None;
+        }
+        return Some(resource);
+    }
+}
+
+impl Package {
+    pub fn id(&self) -> u32 {
+        return self.id;
+    }
+
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn types(&self) -> &[ResourceType] {
+        return &self.types;
+    }
+}
+
+impl ResourceType {
+    pub fn id(&self) -> u8 {
+        return self.id;
+    }
+
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn spec_flags(&self) -> &[u32] {
+        return &self.spec_flags;
+    }
+
+    pub fn configs(&self) -> &[ConfigEntries] {
+        return &self.configs;
+    }
+}
+
+impl ConfigEntries {
+    pub fn config(&self) -> &Config {
+        return &self.config;
+    }
+
+    /// The entries by their index within the type
+    pub fn entries(&self) -> &BTreeMap<u16, Entry> {
+        return &self.entries;
+    }
+}
+
+impl Entry {
+    /// The name of the resource
+    pub fn key(&self) -> &str {
+        return &self.key;
+    }
+
+    pub fn flags(&self) -> u16 {
+        return self.flags;
+    }
+
+    pub fn value(&self) -> &EntryValue {
+        return &self.value;
+    }
+}
+
+impl<'a> Resource<'a> {
+    pub fn id(&self) -> u32 {
+        return self.id;
+    }
+
+    pub fn package(&self) -> &'a Package {
+        return self.package;
+    }
+
+    pub fn type_name(&self) -> &'a str {
+        return &self.ty.name;
+    }
+
+    /// The entry name, `app_name` for `@string/app_name`
+    pub fn name(&self) -> &'a str {
+        return self.values().first().map(|&(_, entry)| entry.key()).unwrap_or("");
+    }
+
+    /// The name as written in references, `string/app_name`. Resources of
+    /// packages other than the app's own, 0x7f, name their package as in
+    /// `android:style/Theme`.
+    pub fn qualified_name(&self) -> String {
+        if self.package.id == APP_PACKAGE_ID {
+            return format!("{}/{}", self.type_name(), self.name());
+        }
+        return format!("{}:{}/{}", self.package.name, self.type_name(), self.name());
+    }
+
+    /// The definitions of the resource in each configuration
+    pub fn values(&self) -> Vec<(&'a Config, &'a Entry)> {
+        let index = self.id as u16;
+        return self.ty
+            .configs
+            .iter()
+            .filter_map(|c| c.entries.get(&index).map(|entry| (&c.config, entry)))
+            .collect();
+    }
+
+    /// The value in the default configuration, if there is one
+    pub fn default_value(&self) -> Option<&'a Entry> {
+        return self.values()
+            .into_iter()
+            .find(|&(config, _)| config.is_default())
+            .map(|(_, entry)| entry);
+    }
+}
+
+impl Config {
+    pub fn is_default(&self) -> bool {
+        return *self == Config::default();
+    }
+
+    pub fn language(&self) -> &str {
+        return &self.language;
+    }
+
+    pub fn region(&self) -> &str {
+        return &self.region;
+    }
+
+    pub fn density(&self) -> u16 {
+        return self.density;
+    }
+
+    pub fn sdk_version(&self) -> u16 {
+        return self.sdk_version;
+    }
+}
+
+/// The qualifiers in the order of resource directory names, `en-rUS-v21`.
+/// The default configuration is empty.
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts: Vec<String> = vec![];
+        if self.mcc != 0 {
+            parts.push(format!("mcc{}", self.mcc));
+        }
+        if self.mnc != 0 {
+            parts.push(format!("mnc{}", self.mnc));
+        }
+        if !self.locale_script.is_empty() || !self.locale_variant.is_empty() ||
+           self.region.len() == 3 {
+            // BCP 47 tags cannot be written as language-rREGION
+            let mut tag = format!("b+{}", self.language);
+            for part in [&self.locale_script, &self.region, &self.locale_variant].iter() {
+                if !part.is_empty() {
+                    tag.push_str(&format!("+{}", part));
+                }
+            }
+            parts.push(tag);
+        } else if !self.language.is_empty() {
+            parts.push(self.language.clone());
+            if !self.region.is_empty() {
+                parts.push(format!("r{}", self.region));
+            }
+        }
+        match self.screen_layout & 0xc0 {
+            0x40 => parts.push("ldltr".to_string()),
+            0x80 => parts.push("ldrtl".to_string()),
+            _ => {}
+        }
+        if self.smallest_screen_width_dp != 0 {
+            parts.push(format!("sw{}dp", self.smallest_screen_width_dp));
+        }
+        if self.screen_width_dp != 0 {
+            parts.push(format!("w{}dp", self.screen_width_dp));
+        }
+        if self.screen_height_dp != 0 {
+            parts.push(format!("h{}dp", self.screen_height_dp));
+        }
+        let qualifiers: [(u8, &[(u8, &str)]); 11] =
+            [(self.screen_layout & 0x0f,
+              &[(1, "small"), (2, "normal"), (3, "large"), (4, "xlarge")]),
+             (self.screen_layout & 0x30, &[(0x10, "notlong"), (0x20, "long")]),
+             (self.screen_layout2 & 0x03, &[(1, "notround"), (2, "round")]),
+             (self.color_mode & 0x03, &[(1, "nowidecg"), (2, "widecg")]),
+             (self.color_mode & 0x0c, &[(0x04, "lowdr"), (0x08, "highdr")]),
+             (self.orientation, &[(1, "port"), (2, "land"), (3, "square")]),
+             (self.ui_mode & 0x0f,
+              &[(2, "desk"), (3, "car"), (4, "television"), (5, "appliance"), (6, "watch"),
+                (7, "vrheadset")]),
+             (self.ui_mode & 0x30, &[(0x10, "notnight"), (0x20, "night")]),
+             (self.touchscreen, &[(1, "notouch"), (2, "stylus"), (3, "finger")]),
+             (self.input_flags & 0x03, &[(1, "keysexposed"), (2, "keyshidden"), (3, "keyssoft")]),
+             (self.keyboard, &[(1, "nokeys"), (2, "qwerty"), (3, "12key")])];
+        for (i, &(value, names)) in qualifiers.iter().enumerate() {
+            // Density goes between night mode and the touchscreen
+            if i == 8 {
+                match self.density {
+                    0 => {}
+                    120 => parts.push("ldpi".to_string()),
+                    160 => parts.push("mdpi".to_string()),
+                    213 => parts.push("tvdpi".to_string()),
+                    240 => parts.push("hdpi".to_string()),
+                    320 => parts.push("xhdpi".to_string()),
+                    480 => parts.push("xxhdpi".to_string()),
+                    640 => parts.push("xxxhdpi".to_string()),
+                    0xfffe => parts.push("anydpi".to_string()),
+                    0xffff => parts.push("nodpi".to_string()),
+                    density => parts.push(format!("{}dpi", density)),
+                }
+            }
+            if let Some(&(_, name)) = names.iter().find(|&&(v, _)| v == value) {
+                parts.push(name.to_string());
+            }
+        }
+        match self.input_flags & 0x0c {
+            0x04 => parts.push("navexposed".to_string()),
+            0x08 => parts.push("navhidden".to_string()),
+            _ => {}
+        }
+        match self.navigation {
+            1 => parts.push("nonav".to_string()),
+            2 => parts.push("dpad".to_string()),
+            3 => parts.push("trackball".to_string()),
+            4 => parts.push("wheel".to_string()),
+            _ => {}
+        }
+        if self.screen_width != 0 || self.screen_height != 0 {
+            parts.push(format!("{}x{}", self.screen_width, self.screen_height));
+        }
+        if self.sdk_version != 0 {
+            parts.push(format!("v{}", self.sdk_version));
+        }
+        write!(f, "{}", parts.join("-"))
+    }
+}
+
+impl fmt::Display for EntryValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EntryValue::Simple(ref value) => write!(f, "{}", value),
+            EntryValue::Complex { parent, ref values } => {
+                write!(f, "(parent @0x{:08x})", parent)?;
+                for &(name, ref value) in values.iter() {
+                    write!(f, " 0x{:08x}={}", name, value)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A language or region code. Three letter codes are packed into two
+/// bytes, five bits per letter, with the high bit set.
+fn locale_code(bytes: [u8; 2], base: u8) -> String {
+    if bytes[0] & 0x80 != 0 {
+        let letters = [bytes[1] & 0x1f,
+                       ((bytes[1] & 0xe0) >> 5) | ((bytes[0] & 0x03) << 3),
+                       (bytes[0] & 0x7c) >> 2];
+        return letters.iter().map(|&l| (base + l) as char).collect();
+    }
+    return bytes.iter().take_while(|&&b| b != 0).map(|&b| b as char).collect();
+}
+
+/// A fixed size, zero padded ASCII field
+fn ascii(bytes: &[u8]) -> String {
+    return bytes.iter().take_while(|&&b| b != 0).map(|&b| b as char).collect();
+}
+
+/// Reads a ResTable_config. It grew over time, so fields past the size it
+/// was written with are zero.
+fn config(data: &[u8], offset: usize) -> Result<Config, AxmlError> {
+    let size = axml::u32_at(data, offset)? as usize;
+    let bytes = match data.get(offset..offset + size) {
+        Some(bytes) => bytes,
+        None => return Err(AxmlError::new(offset, "data ends unexpectedly".to_string())),
+    };
+    let mut padded = bytes.to_vec();
+    padded.resize(::std::cmp::max(size, 64), 0);
+    let b = &padded;
+    let u16_le = |i: usize| b[i] as u16 | (b[i + 1] as u16) << 8;
+    return Ok(Config {
+        mcc: u16_le(4),
+        mnc: u16_le(6),
+        language: locale_code([b[8], b[9]], b'a'),
+        region: locale_code([b[10], b[11]], b'0'),
+        orientation: b[12],
+        touchscreen: b[13],
+        density: u16_le(14),
+        keyboard: b[16],
+        navigation: b[17],
+        input_flags: b[18],
+        screen_width: u16_le(20),
+        screen_height: u16_le(22),
+        sdk_version: u16_le(24),
+        screen_layout: b[28],
+        ui_mode: b[29],
+        smallest_screen_width_dp: u16_le(30),
+        screen_width_dp: u16_le(32),
+        screen_height_dp: u16_le(34),
+        locale_script: ascii(&b[36..40]),
+        locale_variant: ascii(&b[40..48]),
+        screen_layout2: b[48],
+        color_mode: b[49],
+    });
+}
+
+/// Reads the ResTable_entry at `offset` with its value or bag
+fn entry(data: &[u8],
+         offset: usize,
+         strings: &StringPool,
+         keys: &StringPool)
+         -> Result<Entry, AxmlError> {
+    let size = axml::u16_at(data, offset)?;
+    let flags = axml::u16_at(data, offset + 2)?;
+
+    // Compact entries keep the key in the size field and the value type
+    // in the upper byte of the flags
+    let (key, value) = if flags & FLAG_COMPACT != 0 {
+        let raw = axml::u32_at(data, offset + 4)?;
+        let res_value = [8,
+                         0,
+                         0,
+                         (flags >> 8) as u8,
+                         raw as u8,
+                         (raw >> 8) as u8,
+                         (raw >> 16) as u8,
+                         (raw >> 24) as u8];
+        let value = axml::res_value(&res_value, 0, strings)
+            .map_err(|e| AxmlError::new(offset, e.message().to_string()))?;
+        (size as u32, EntryValue::Simple(value))
+    } else if flags & FLAG_COMPLEX != 0 {
+        let parent = axml::u32_at(data, offset + 8)?;
+        let count = axml::u32_at(data, offset + 12)? as usize;
+        let mut values = vec![];
+        for i in 0..count {
+            let position = offset + size as usize + i * 12;
+            values.push((axml::u32_at(data, position)?,
+                         axml::res_value(data, position + 4, strings)?));
+        }
+        (axml::u32_at(data, offset + 4)?,
+         EntryValue::Complex {
+            parent: parent,
+            values: values,
+        })
+    } else {
+        (axml::u32_at(data, offset + 4)?,
+         EntryValue::Simple(axml::res_value(data, offset + size as usize, strings)?))
+    };
+
+    let key = match keys.string(key) {
+        Some(key) => key.to_string(),
+        None => return Err(AxmlError::new(offset, format!("key index {} out of range", key))),
+    };
+    return Ok(Entry {
+        key: key,
+        flags: flags,
+        value: value,
+    });
+}
+
+/// Reads a ResTable_type: the entries of one type in one configuration
+fn type_chunk(data: &[u8],
+              chunk: &Chunk,
+              strings: &StringPool,
+              keys: &StringPool)
+              -> Result<ConfigEntries, AxmlError> {
+    let flags = (axml::u16_at(data, chunk.offset + 8)? >> 8) as u8;
+    let entry_count = axml::u32_at(data, chunk.offset + 12)? as usize;
+    let entries_start = chunk.offset + axml::u32_at(data, chunk.offset + 16)? as usize;
+    let config = config(data, chunk.offset + 20)?;
+
+    let mut offsets: Vec<(u16, usize)> = vec![];
+    for i in 0..entry_count {
+        if flags & FLAG_SPARSE != 0 {
+            let position = chunk.body() + i * 4;
+            let index = axml::u16_at(data, position)?;
+            offsets.push((index, axml::u16_at(data, position + 2)? as usize * 4));
+        } else if flags & FLAG_OFFSET16 != 0 {
+            match axml::u16_at(data, chunk.body() + i * 2)? {
+                0xffff => {}
+                offset => offsets.push((i as u16, offset as usize * 4)),
+            }
+        } else {
+            match axml::u32_at(data, chunk.body() + i * 4)? {
+                axml::NO_ENTRY => {}
+                offset => offsets.push((i as u16, offset as usize)),
+            }
+        }
+    }
+
+    let mut entries = BTreeMap::new();
+    for (index, offset) in offsets {
+        entries.insert(index, entry(data, entries_start + offset, strings, keys)?);
+    }
+    return Ok(ConfigEntries {
+        config: config,
+        entries: entries,
+    });
+}
+
+/// Reads a ResTable_package with its type and key names, type specs and
+/// types
+fn package(data: &[u8], chunk: &Chunk, strings: &StringPool) -> Result<Package, AxmlError> {
+    let id = axml::u32_at(data, chunk.offset + 8)?;
+    let mut name_units = vec![];
+    for i in 0..128 {
+        match axml::u16_at(data, chunk.offset + 12 + i * 2)? {
+            0 => break,
+            unit => name_units.push(unit),
+        }
+    }
+    let type_strings = chunk.offset + axml::u32_at(data, chunk.offset + 268)? as usize;
+    let key_strings = chunk.offset + axml::u32_at(data, chunk.offset + 276)? as usize;
+    // Shared libraries may number their types from an offset
+    let type_id_offset = if chunk.header_size >= 288 {
+        axml::u32_at(data, chunk.offset + 284)?
+    } else {
+        0
+    };
+
+    let mut type_names = StringPool::default();
+    let mut keys = StringPool::default();
+    let mut types: Vec<ResourceType> = vec![];
+    let mut offset = chunk.body();
+    while offset < chunk.end() {
+        let child = axml::chunk(data, offset, chunk.end())?;
+        match child.chunk_type {
+            axml::RES_STRING_POOL_TYPE if offset == type_strings => {
+                type_names = axml::string_pool(data, &child)?;
+            }
+            axml::RES_STRING_POOL_TYPE if offset == key_strings => {
+                keys = axml::string_pool(data, &child)?;
+            }
+            RES_TABLE_TYPE_SPEC_TYPE | RES_TABLE_TYPE_TYPE => {
+                let type_id = axml::u16_at(data, offset + 8)? as u8;
+                let name_idx = (type_id as u32).wrapping_sub(1 + type_id_offset);
+                let name = match type_names.string(name_idx) {
+                    Some(name) => name.to_string(),
+                    None => {
+                        return Err(AxmlError::new(offset, format!("unknown type id {}", type_id)))
+                    }
+                };
+                if !types.iter().any(|t| t.id == type_id) {
+                    types.push(ResourceType {
+                        id: type_id,
+                        name: name,
+                        spec_flags: vec![],
+                        configs: vec![],
+                    });
+                }
+                let ty = types.iter_mut().find(|t| t.id == type_id).unwrap();
+                if child.chunk_type == RES_TABLE_TYPE_SPEC_TYPE {
+                    let entry_count = axml::u32_at(data, offset + 12)? as usize;
+                    for i in 0..entry_count {
+                        ty.spec_flags.push(axml::u32_at(data, child.body() + i * 4)?);
+                    }
+                } else {
+                    ty.configs.push(type_chunk(data, &child, strings, &keys)?);
+                }
+            }
+            // Library and overlayable chunks are not needed for lookups
+            _ => {}
+        }
+        offset = child.end();
+    }
+
+    return Ok(Package {
+        id: id,
+        name: String::from_utf16_lossy(&name_units),
+        types: types,
+    });
+}
+
+/// Decodes a resources.arsc
+pub fn decode(data: &[u8]) -> Result<ResourceTable, AxmlError> {
+    let header = axml::chunk(data, 0, data.len())?;
+    if header.chunk_type != RES_TABLE_TYPE {
+        return Err(AxmlError::new(0,
+                                  format!("not a resource table, chunk type 0x{:04x}",
+                                          header.chunk_type)));
+    }
+
+    let mut strings = StringPool::default();
+    let mut packages = vec![];
+    let mut offset = header.body();
+    while offset < header.end() {
+        let chunk = axml::chunk(data, offset, header.end())?;
+        match chunk.chunk_type {
+            axml::RES_STRING_POOL_TYPE => strings = axml::string_pool(data, &chunk)?,
+            RES_TABLE_PACKAGE_TYPE => packages.push(package(data, &chunk, &strings)?),
+            _ => {}
+        }
+        offset = chunk.end();
+    }
+    return Ok(ResourceTable {
+        strings: strings,
+        packages: packages,
+    });
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use axml::tests::{chunk_bytes, pool_bytes, u32s, value_bytes};
+
+    const TYPE_STRING: u8 = 0x03;
+    const TYPE_INT_DEC: u8 = 0x10;
+    const TYPE_INT_COLOR_RGB8: u8 = 0x1d;
+
+    /// A ResTable_config of 64 bytes
+    pub fn config_bytes(language: &[u8; 2], region: &[u8; 2], sdk_version: u16) -> Vec<u8> {
+        let mut config = vec![0u8; 64];
+        config[0] = 64;
+        config[8..10].copy_from_slice(language);
+        config[10..12].copy_from_slice(region);
+        config[24..26].copy_from_slice(&sdk_version.to_le_bytes());
+        return config;
+    }
+
+    pub fn simple_entry(key: u32, data_type: u8, data: u32) -> Vec<u8> {
+        let mut entry = vec![8, 0, 0, 0];
+        entry.extend_from_slice(&u32s(&[key]));
+        entry.extend_from_slice(&value_bytes(data_type, data));
+        return entry;
+    }
+
+    pub fn bag_entry(key: u32, parent: u32, values: &[(u32, u8, u32)]) -> Vec<u8> {
+        let mut entry = vec![16, 0, FLAG_COMPLEX as u8, 0];
+        entry.extend_from_slice(&u32s(&[key, parent, values.len() as u32]));
+        for &(name, data_type, data) in values {
+            entry.extend_from_slice(&u32s(&[name]));
+            entry.extend_from_slice(&value_bytes(data_type, data));
+        }
+        return entry;
+    }
+
+    /// A ResTable_type with the given offset array and entry data
+    fn raw_type_bytes(type_id: u8,
+                      flags: u8,
+                      config: &[u8],
+                      count: u32,
+                      offsets: &[u8],
+                      entries: &[u8])
+                      -> Vec<u8> {
+        let header_size = 20 + config.len();
+        let mut body = vec![type_id, flags, 0, 0];
+        body.extend_from_slice(&u32s(&[count, (header_size + offsets.len()) as u32]));
+        body.extend_from_slice(config);
+        body.extend_from_slice(offsets);
+        body.extend_from_slice(entries);
+        return chunk_bytes(RES_TABLE_TYPE_TYPE, header_size as u16, &body);
+    }
+
+    /// A dense ResTable_type, None for the entries it does not define
+    pub fn type_bytes(type_id: u8, config: &[u8], entries: &[Option<Vec<u8>>]) -> Vec<u8> {
+        let mut offsets = vec![];
+        let mut data = vec![];
+        for entry in entries {
+            match *entry {
+                Some(ref entry) => {
+                    offsets.push(data.len() as u32);
+                    data.extend_from_slice(entry);
+                }
+                None => offsets.push(axml::NO_ENTRY),
+            }
+        }
+        return raw_type_bytes(type_id, 0, config, entries.len() as u32, &u32s(&offsets), &data);
+    }
+
+    pub fn spec_bytes(type_id: u8, flags: &[u32]) -> Vec<u8> {
+        let mut body = vec![type_id, 0, 0, 0];
+        body.extend_from_slice(&u32s(&[flags.len() as u32]));
+        body.extend_from_slice(&u32s(flags));
+        return chunk_bytes(RES_TABLE_TYPE_SPEC_TYPE, 16, &body);
+    }
+
+    pub fn package_bytes(id: u32,
+                         name: &str,
+                         types: &[&str],
+                         keys: &[&str],
+                         chunks: &[Vec<u8>])
+                         -> Vec<u8> {
+        let type_pool = pool_bytes(types, false, &[]);
+        let key_pool = pool_bytes(keys, true, &[]);
+        let mut body = u32s(&[id]);
+        let mut name_units = vec![0u16; 128];
+        for (i, unit) in name.encode_utf16().enumerate() {
+            name_units[i] = unit;
+        }
+        for unit in name_units {
+            body.extend_from_slice(&unit.to_le_bytes());
+        }
+        body.extend_from_slice(&u32s(&[288, 0, 288 + type_pool.len() as u32, 0, 0]));
+        body.extend_from_slice(&type_pool);
+        body.extend_from_slice(&key_pool);
+        body.extend_from_slice(&chunks.concat());
+        return chunk_bytes(RES_TABLE_PACKAGE_TYPE, 288, &body);
+    }
+
+    pub fn table_bytes(strings: &[u8], packages: &[Vec<u8>]) -> Vec<u8> {
+        let mut body = u32s(&[packages.len() as u32]);
+        body.extend_from_slice(strings);
+        body.extend_from_slice(&packages.concat());
+        return chunk_bytes(RES_TABLE_TYPE, 12, &body);
+    }
+
+    fn table() -> ResourceTable {
+        let strings = pool_bytes(&["Hello", "Hallo", "Tap here", "b"],
+                                 false,
+                                 &[&[], &[], &[(3, 0, 2)]]);
+        let default = config_bytes(&[0, 0], &[0, 0], 0);
+        let app = package_bytes(0x7f,
+                                "com.example",
+                                &["string", "style"],
+                                &["app_name", "hint", "AppTheme"],
+                                &[spec_bytes(1, &[0x4, 0]),
+                                  type_bytes(1,
+                                             &default,
+                                             &[Some(simple_entry(0, TYPE_STRING, 0)),
+                                               Some(simple_entry(1, TYPE_STRING, 2))]),
+                                  type_bytes(1,
+                                             &config_bytes(b"de", &[0, 0], 0),
+                                             &[Some(simple_entry(0, TYPE_STRING, 1)), None]),
+                                  type_bytes(2,
+                                             &default,
+                                             &[Some(bag_entry(2,
+                                                              0x01030005,
+                                                              &[(0x01010098,
+                                                                 TYPE_INT_COLOR_RGB8,
+                                                                 0xff00ff00)]))])]);
+        let mut themes: Vec<Option<Vec<u8>>> = vec![None; 5];
+        themes.push(Some(bag_entry(0, 0, &[])));
+        let android = package_bytes(0x01,
+                                    "android",
+                                    &["attr", "id", "style"],
+                                    &["Theme"],
+                                    &[type_bytes(3, &default, &themes)]);
+        return decode(&table_bytes(&strings, &[app, android])).unwrap();
+    }
+
+    fn simple(entry: &Entry) -> &ResValue {
+        match *entry.value() {
+            EntryValue::Simple(ref value) => value,
+            ref other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn resources_are_found_by_id() {
+        let table = table();
+        assert_eq!(table.packages().len(), 2);
+        let package = &table.packages()[0];
+        assert_eq!((package.id(), package.name()), (0x7f, "com.example"));
+        assert_eq!(package.types()[0].name(), "string");
+        assert_eq!(package.types()[0].spec_flags(), &[0x4, 0]);
+        assert_eq!(package.types()[0].configs().len(), 2);
+
+        let app_name = table.resource(0x7f010000).unwrap();
+        assert_eq!(app_name.id(), 0x7f010000);
+        assert_eq!(app_name.name(), "app_name");
+        assert_eq!(app_name.type_name(), "string");
+        assert_eq!(app_name.qualified_name(), "string/app_name");
+        assert_eq!(simple(app_name.default_value().unwrap()),
+                   &ResValue::String("Hello".to_string()));
+        let values = app_name.values();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[1].0.language(), "de");
+        assert_eq!(values[1].0.to_string(), "de");
+        assert_eq!(simple(values[1].1), &ResValue::String("Hallo".to_string()));
+
+        // Only the default configuration defines the hint
+        let hint = table.resource(0x7f010001).unwrap();
+        assert_eq!(hint.values().len(), 1);
+        assert_eq!(simple(hint.default_value().unwrap()),
+                   &ResValue::StyledString("<b>Tap</b> here".to_string()));
+
+        let theme = table.resource(0x01030005).unwrap();
+        assert_eq!(theme.qualified_name(), "android:style/Theme");
+
+        assert!(table.resource(0x7f010002).is_none());
+        assert!(table.resource(0x7f030000).is_none());
+        assert!(table.resource(0x01030004).is_none());
+        assert!(table.resource(0x02010000).is_none());
+    }
+
+    #[test]
+    fn bags_keep_their_parent_and_values() {
+        let table = table();
+        let style = table.resource(0x7f020000).unwrap();
+        let entry = style.default_value().unwrap();
+        assert_eq!(entry.key(), "AppTheme");
+        assert_eq!(entry.flags(), FLAG_COMPLEX);
+        match *entry.value() {
+            EntryValue::Complex { parent, ref values } => {
+                assert_eq!(parent, 0x01030005);
+                assert_eq!(values, &[(0x01010098, ResValue::ColorRgb8(0xff00ff00))]);
+            }
+            ref other => panic!("{:?}", other),
+        }
+        assert_eq!(entry.value().to_string(), "(parent @0x01030005) 0x01010098=#00ff00");
+    }
+
+    #[test]
+    fn sparse_offset16_and_compact_entries() {
+        let default = config_bytes(&[0, 0], &[0, 0], 0);
+        let entry = simple_entry(0, TYPE_INT_DEC, 7);
+        // Entry 3 only, its offset divided by 4
+        let sparse = raw_type_bytes(1, FLAG_SPARSE, &default, 1, &[3, 0, 0, 0], &entry);
+        // 16 bit offsets divided by 4, 0xffff for none
+        let offset16 = raw_type_bytes(1,
+                                      FLAG_OFFSET16,
+                                      &config_bytes(b"fr", &[0, 0], 0),
+                                      2,
+                                      &[0xff, 0xff, 0, 0],
+                                      &entry);
+        // The key in the size field, the value type in the flags
+        let compact_flags = FLAG_COMPACT | (TYPE_INT_DEC as u16) << 8;
+        let mut compact = vec![0, 0];
+        compact.extend_from_slice(&compact_flags.to_le_bytes());
+        compact.extend_from_slice(&u32s(&[9]));
+        let compact = raw_type_bytes(1,
+                                     0,
+                                     &config_bytes(b"it", &[0, 0], 0),
+                                     1,
+                                     &u32s(&[0]),
+                                     &compact);
+        let package = package_bytes(0x7f,
+                                    "p",
+                                    &["integer"],
+                                    &["answer"],
+                                    &[sparse, offset16, compact]);
+        let table = decode(&table_bytes(&pool_bytes(&[], false, &[]), &[package])).unwrap();
+
+        let configs = table.packages()[0].types()[0].configs();
+        assert_eq!(configs[0].entries().keys().collect::<Vec<_>>(), vec![&3]);
+        assert_eq!(configs[1].entries().keys().collect::<Vec<_>>(), vec![&1]);
+        let compact = &configs[2].entries()[&0];
+        assert_eq!(compact.key(), "answer");
+        assert_eq!(simple(compact).to_string(), "9");
+        assert_eq!(simple(&configs[0].entries()[&3]), &ResValue::IntDec(7));
+    }
+
+    fn config_string(bytes: &[u8]) -> String {
+        return config(bytes, 0).unwrap().to_string();
+    }
+
+    #[test]
+    fn configs_display_as_qualifiers() {
+        assert_eq!(config_string(&config_bytes(&[0, 0], &[0, 0], 0)), "");
+        assert!(config(&config_bytes(&[0, 0], &[0, 0], 0), 0).unwrap().is_default());
+        assert_eq!(config_string(&config_bytes(b"en", b"US", 21)), "en-rUS-v21");
+
+        // Three letter codes are packed, and need the BCP 47 form
+        let fil = config(&config_bytes(&[0xad, 0x05], &[0, 0], 0), 0).unwrap();
+        assert_eq!(fil.language(), "fil");
+        let latin_america = config(&config_bytes(b"es", &[0xa4, 0x24], 0), 0).unwrap();
+        assert_eq!(latin_america.region(), "419");
+        assert_eq!(latin_america.to_string(), "b+es+419");
+
+        let mut bytes = config_bytes(&[0, 0], &[0, 0], 28);
+        bytes[12] = 2; // land
+        bytes[14..16].copy_from_slice(&240u16.to_le_bytes());
+        bytes[29] = 0x20; // night
+        assert_eq!(config(&bytes, 0).unwrap().density(), 240);
+        assert_eq!(config_string(&bytes), "land-night-hdpi-v28");
+
+        // Older, shorter configs read as zero past their size
+        let mut short = config_bytes(b"en", &[0, 0], 4)[..28].to_vec();
+        short[0] = 28;
+        assert_eq!(config(&short, 0).unwrap().sdk_version(), 4);
+        // A size past the end of the data
+        assert!(config(&short[..20], 0).is_err());
+    }
+
+    fn error(data: &[u8]) -> String {
+        return decode(data).unwrap_err().message().to_string();
+    }
+
+    #[test]
+    fn malformed_tables_are_errors() {
+        let strings = pool_bytes(&["x"], false, &[]);
+        assert_eq!(error(&strings), "not a resource table, chunk type 0x0001");
+        assert_eq!(error(&[2, 0, 12, 0]), "data ends unexpectedly");
+
+        let default = config_bytes(&[0, 0], &[0, 0], 0);
+        let package = |chunks: &[Vec<u8>]| {
+            return table_bytes(&strings, &[package_bytes(0x7f, "p", &["string"], &["k"], chunks)]);
+        };
+        assert_eq!(error(&package(&[spec_bytes(2, &[0])])), "unknown type id 2");
+        assert_eq!(error(&package(&[spec_bytes(0, &[0])])), "unknown type id 0");
+        assert_eq!(error(&package(&[type_bytes(1, &default, &[Some(simple_entry(5, 3, 0))])])),
+                   "key index 5 out of range");
+        assert_eq!(error(&package(&[type_bytes(1, &default, &[Some(simple_entry(0, 3, 9))])])),
+                   "string index 9 out of range");
+        let far = raw_type_bytes(1, 0, &default, 1, &u32s(&[0x7FFFFFF0]), &[]);
+        assert_eq!(error(&package(&[far])), "data ends unexpectedly");
+        // More entries than the chunk has offsets for
+        let many = raw_type_bytes(1, 0, &default, 0x3FFFFFFF, &[], &[]);
+        assert_eq!(error(&package(&[many])), "data ends unexpectedly");
+        let mut bag = bag_entry(0, 0, &[(0x01010000, 3, 0)]);
+        bag[12] = 0xFF;
+        assert_eq!(error(&package(&[type_bytes(1, &default, &[Some(bag)])])),
+                   "data ends unexpectedly");
+    }
+}
@@ -0,0 +1,1715 @@
+use dalvik::{Format, IndexType, OPCODES};
+use dex::{MethodHandleType, Visibility};
+use smali;
+use std::collections::HashMap;
+use std::fmt;
+use writer::{self, Annotation, AnnotationValue, CallSiteRef, Class, Code, DebugEvent, DebugInfo,
+             Field, FieldRef, Fixup, Method, MethodHandleRef, MethodHandleTarget, MethodRef, Proto,
+             Reference, Try, Value};
+
+const ACC_PRIVATE: u32 = 0x2;
+const ACC_STATIC: u32 = 0x8;
+const ACC_CONSTRUCTOR: u32 = 0x10000;
+
+/// An error in smali source, with the 1-based line it was found on.
+/// Errors raised while writing the DEX file have line 0.
+#[derive(Debug)]
+pub struct SmaliError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SmaliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn error<T>(line: usize, message: String) -> Result<T, SmaliError> {
+    return Err(SmaliError {
+        line: line,
+        message: message,
+    });
+}
+
+/// Cuts off a trailing `#` comment, leaving string and char literals alone
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' => return &line[..i],
+            None => {}
+        }
+    }
+    return line;
+}
+
+fn split_first_word(line: &str) -> (&str, &str) {
+    match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim()),
+        None => (line, ""),
+    }
+}
+
+/// Splits instruction operands at commas outside of literals, braces and
+/// parentheses
+fn split_operands(operands: &str) -> Vec<String> {
+    let mut list = vec![];
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut depth = 0;
+    for c in operands.chars() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '{' || c == '(' => depth += 1,
+            None if c == '}' || c == ')' => depth -= 1,
+            None if c == ',' && depth == 0 => {
+                list.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            None => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        list.push(current.trim().to_string());
+    }
+    return list;
+}
+
+/// Splits a concatenation of type descriptors such as `IJ[Ljava/lang/String;`
+fn parse_type_list(types: &str) -> Result<Vec<String>, String> {
+    let bytes = types.as_bytes();
+    let mut list = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        while i < bytes.len() && bytes[i] == b'[' {
+            i += 1;
+        }
+        match bytes.get(i) {
+            Some(&b'L') => {
+                match types[i..].find(';') {
+                    Some(end) => i += end + 1,
+                    None => return Err(format!("unterminated type descriptor in {}", types)),
+                }
+            }
+            Some(&c) if b"ZBSCIJFDV".contains(&c) => i += 1,
+            _ => return Err(format!("invalid type descriptor in {}", types)),
+        }
+        list.push(types[start..i].to_string());
+    }
+    return Ok(list);
+}
+
+fn parse_type(descriptor: &str) -> Result<String, String> {
+    let list = parse_type_list(descriptor)?;
+    if list.len() != 1 {
+        return Err(format!("expected a single type, found {}", descriptor));
+    }
+    return Ok(descriptor.to_string());
+}
+
+/// Parses a prototype of the form `(Args)Ret`
+fn parse_proto(proto: &str) -> Result<Proto, String> {
+    if !proto.starts_with('(') {
+        return Err(format!("invalid prototype {}", proto));
+    }
+    let end = match proto.find(')') {
+        Some(end) => end,
+        None => return Err(format!("invalid prototype {}", proto)),
+    };
+    return Ok(Proto {
+        parameters: parse_type_list(&proto[1..end])?,
+        return_type: parse_type(&proto[end + 1..])?,
+    });
+}
+
+fn split_member(reference: &str) -> Result<(String, &str), String> {
+    match reference.find("->") {
+        Some(i) => Ok((parse_type(&reference[..i])?, &reference[i + 2..])),
+        None => Err(format!("invalid member reference {}", reference)),
+    }
+}
+
+/// Parses `name:Type`, optionally prefixed with the owning class
+fn parse_field(member: &str, class: &str) -> Result<FieldRef, String> {
+    match member.find(':') {
+        Some(i) => {
+            Ok(FieldRef {
+                class: class.to_string(),
+                name: member[..i].to_string(),
+                ty: parse_type(&member[i + 1..])?,
+            })
+        }
+        None => Err(format!("invalid field {}", member)),
+    }
+}
+
+/// Parses `name(Args)Ret`, optionally prefixed with the owning class
+fn parse_method(member: &str, class: &str) -> Result<MethodRef, String> {
+    match member.find('(') {
+        Some(i) => {
+            Ok(MethodRef {
+                class: class.to_string(),
+                name: member[..i].to_string(),
+                proto: parse_proto(&member[i..])?,
+            })
+        }
+        None => Err(format!("invalid method {}", member)),
+    }
+}
+
+fn parse_field_ref(reference: &str) -> Result<FieldRef, String> {
+    let (class, member) = split_member(reference)?;
+    return parse_field(member, &class);
+}
+
+fn parse_method_ref(reference: &str) -> Result<MethodRef, String> {
+    let (class, member) = split_member(reference)?;
+    return parse_method(member, &class);
+}
+
+/// Parses a method handle such as `invoke-static@Lcls;->name()V`
+fn parse_method_handle(token: &str) -> Result<MethodHandleRef, String> {
+    let (kind, member) = match token.find('@') {
+        Some(i) => (MethodHandleType::from_name(&token[..i]), &token[i + 1..]),
+        None => (None, token),
+    };
+    let kind = match kind {
+        Some(kind) => kind,
+        None => return Err(format!("invalid method handle {}", token)),
+    };
+    let target = if kind.is_field() {
+        MethodHandleTarget::Field(parse_field_ref(member)?)
+    } else {
+        MethodHandleTarget::Method(parse_method_ref(member)?)
+    };
+    return Ok(MethodHandleRef {
+        kind: kind,
+        target: target,
+    });
+}
+
+/// Position of the parenthesis closing the one at `open`, skipping literals
+fn closing_paren(text: &str, open: usize) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut depth = 0;
+    for (i, c) in text[open..].char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '(' => depth += 1,
+            None if c == ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            None => {}
+        }
+    }
+    return None;
+}
+
+/// Parses a call site as baksmali writes it:
+/// `call_site_0("name", (Args)Ret, extra args...)@Lcls;->bootstrap(...)`.
+/// A bootstrap given as plain method reference is an invoke-static handle.
+fn parse_call_site(token: &str) -> Result<CallSiteRef, String> {
+    let invalid = || format!("invalid call site {}", token);
+    let open = token.find('(').ok_or_else(&invalid)?;
+    let close = closing_paren(token, open).ok_or_else(&invalid)?;
+    if !token[close + 1..].starts_with('@') {
+        return Err(invalid());
+    }
+    let arguments = split_operands(&token[open + 1..close]);
+    if arguments.len() < 2 {
+        return Err(invalid());
+    }
+
+    let bootstrap = &token[close + 2..];
+    let bootstrap = match parse_method_handle(bootstrap) {
+        Ok(handle) => handle,
+        Err(_) => {
+            MethodHandleRef {
+                kind: MethodHandleType::InvokeStatic,
+                target: MethodHandleTarget::Method(parse_method_ref(bootstrap)?),
+            }
+        }
+    };
+    let mut extra = vec![];
+    for argument in arguments[2..].iter() {
+        extra.push(parse_untyped_value(argument)?);
+    }
+    return Ok(CallSiteRef {
+        bootstrap: bootstrap,
+        name: parse_string(&arguments[0])?,
+        proto: parse_proto(&arguments[1])?,
+        arguments: extra,
+    });
+}
+
+/// Reverses `dalvik::escape_string`, returning UTF-16 code units
+fn unescape(literal: &str, quote: char) -> Result<Vec<u16>, String> {
+    if literal.len() < 2 || !literal.starts_with(quote) || !literal.ends_with(quote) {
+        return Err(format!("invalid literal {}", literal));
+    }
+    let mut units = vec![];
+    let mut chars = literal[1..literal.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0u16; 2];
+            units.extend_from_slice(c.encode_utf16(&mut buffer));
+            continue;
+        }
+        match chars.next() {
+            Some('n') => units.push(0x0A),
+            Some('r') => units.push(0x0D),
+            Some('t') => units.push(0x09),
+            Some('b') => units.push(0x08),
+            Some('f') => units.push(0x0C),
+            Some('0') => units.push(0x00),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                match u16::from_str_radix(&hex, 16) {
+                    Ok(unit) if hex.len() == 4 => units.push(unit),
+                    _ => return Err(format!("invalid escape \\u{} in {}", hex, literal)),
+                }
+            }
+            Some(c @ '"') | Some(c @ '\'') | Some(c @ '\\') => units.push(c as u16),
+            _ => return Err(format!("invalid escape in {}", literal)),
+        }
+    }
+    return Ok(units);
+}
+
+fn parse_string(literal: &str) -> Result<String, String> {
+    return String::from_utf16(&unescape(literal, '"')?)
+        .map_err(|_| format!("unpaired surrogate in {}", literal));
+}
+
+fn parse_char(literal: &str) -> Result<u16, String> {
+    let units = unescape(literal, '\'')?;
+    if units.len() != 1 {
+        return Err(format!("invalid char literal {}", literal));
+    }
+    return Ok(units[0]);
+}
+
+/// Parses an integer literal: decimal or `0x` hex, optionally negative and
+/// with one of the `t`, `s` or `L` width suffixes baksmali writes
+fn parse_int(literal: &str) -> Result<i64, String> {
+    let mut digits = literal.trim();
+    if digits.ends_with(['t', 'T', 's', 'S', 'l', 'L']) {
+        digits = &digits[..digits.len() - 1];
+    }
+    let negative = digits.starts_with('-');
+    if negative {
+        digits = &digits[1..];
+    }
+    let value = if digits.starts_with("0x") || digits.starts_with("0X") {
+        u64::from_str_radix(&digits[2..], 16)
+    } else {
+        digits.parse::<u64>()
+    };
+    match value {
+        Ok(value) if negative && value > 1 << 63 => Err(format!("{} is out of range", literal)),
+        Ok(value) if negative => Ok((value as i64).wrapping_neg()),
+        Ok(value) => Ok(value as i64),
+        Err(_) => Err(format!("invalid number {}", literal)),
+    }
+}
+
+/// Fails unless `value` fits into `bits` bits, either signed or as the
+/// unsigned bit pattern smali also accepts for hex literals
+fn check_width(value: i64, bits: u32, literal: &str) -> Result<i64, String> {
+    if bits < 64 && (value < -(1i64 << (bits - 1)) || value >= 1i64 << bits) {
+        return Err(format!("literal {} does not fit into {} bits", literal, bits));
+    }
+    return Ok(value);
+}
+
+fn parse_float(literal: &str) -> Result<f32, String> {
+    let digits = literal.trim_end_matches(['f', 'F']);
+    match digits {
+        "Infinity" => Ok(f32::INFINITY),
+        "-Infinity" => Ok(f32::NEG_INFINITY),
+        "NaN" => Ok(f32::NAN),
+        _ => digits.parse::<f32>().map_err(|_| format!("invalid float {}", literal)),
+    }
+}
+
+fn parse_double(literal: &str) -> Result<f64, String> {
+    let digits = literal.trim_end_matches(['d', 'D']);
+    match digits {
+        "Infinity" => Ok(f64::INFINITY),
+        "-Infinity" => Ok(f64::NEG_INFINITY),
+        "NaN" => Ok(f64::NAN),
+        _ => digits.parse::<f64>().map_err(|_| format!("invalid double {}", literal)),
+    }
+}
+
+/// Parses an instruction literal. Besides integers, smali accepts float and
+/// double literals whose bit pattern is loaded.
+fn parse_literal(literal: &str) -> Result<i64, String> {
+    let hex = literal.starts_with("0x") || literal.starts_with("-0x");
+    if !hex && (literal.contains('.') || literal.contains("Infinity") || literal.contains("NaN")) {
+        if literal.ends_with('f') || literal.ends_with('F') {
+            return Ok(parse_float(literal)?.to_bits() as i32 as i64);
+        }
+        return Ok(parse_double(literal)?.to_bits() as i64);
+    }
+    return parse_int(literal);
+}
+
+/// Parses a value whose type follows from its syntax alone, as in call site
+/// arguments: suffixes tell integer widths apart, `f` marks floats and
+/// numbers with a fraction or exponent are doubles.
+fn parse_untyped_value(literal: &str) -> Result<Value, String> {
+    let literal = literal.trim();
+    let value = match literal {
+        "null" => Value::Null,
+        "true" => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        _ if literal.starts_with('"') => Value::String(unescape(literal, '"')?),
+        _ if literal.starts_with('\'') => Value::Char(parse_char(literal)?),
+        _ if literal.starts_with('(') => Value::MethodType(parse_proto(literal)?),
+        _ if literal.starts_with(".enum ") => Value::Enum(parse_field_ref(literal[6..].trim())?),
+        _ if literal.starts_with('{') && literal.ends_with('}') => {
+            let mut values = vec![];
+            for item in split_operands(&literal[1..literal.len() - 1]) {
+                values.push(parse_untyped_value(&item)?);
+            }
+            Value::Array(values)
+        }
+        _ if literal.starts_with('L') || literal.starts_with('[') => {
+            match literal.find("->") {
+                Some(i) if literal[i..].contains('(') => Value::Method(parse_method_ref(literal)?),
+                Some(_) => Value::Field(parse_field_ref(literal)?),
+                None => Value::Type(parse_type(literal)?),
+            }
+        }
+        _ if literal.contains('@') => Value::MethodHandle(parse_method_handle(literal)?),
+        _ => {
+            let hex = literal.starts_with("0x") || literal.starts_with("-0x");
+            let floating = !hex &&
+                           (literal.contains('.') || literal.contains('e') ||
+                            literal.contains("Infinity") ||
+                            literal.contains("NaN") ||
+                            literal.ends_with(['d', 'D', 'f', 'F']));
+            if floating && literal.ends_with(['f', 'F']) {
+                Value::Float(parse_float(literal)?)
+            } else if floating {
+                Value::Double(parse_double(literal)?)
+            } else {
+                let value = parse_int(literal)?;
+                match literal.chars().last() {
+                    Some('t') | Some('T') => Value::Byte(check_width(value, 8, literal)? as i8),
+                    Some('s') | Some('S') => Value::Short(check_width(value, 16, literal)? as i16),
+                    Some('l') | Some('L') => Value::Long(value),
+                    _ => Value::Int(check_width(value, 32, literal)? as i32),
+                }
+            }
+        }
+    };
+    return Ok(value);
+}
+
+/// Parses a static field initializer according to the field type
+fn parse_value(literal: &str, ty: &str) -> Result<Value, String> {
+    if literal == "null" {
+        return Ok(Value::Null);
+    }
+    let value = match ty {
+        "Z" => {
+            match literal {
+                "true" => Value::Boolean(true),
+                "false" => Value::Boolean(false),
+                _ => return Err(format!("invalid boolean {}", literal)),
+            }
+        }
+        "B" => Value::Byte(check_width(parse_int(literal)?, 8, literal)? as i8),
+        "S" => Value::Short(check_width(parse_int(literal)?, 16, literal)? as i16),
+        "C" if literal.starts_with('\'') => Value::Char(parse_char(literal)?),
+        "C" => Value::Char(check_width(parse_int(literal)?, 16, literal)? as u16),
+        "I" => Value::Int(check_width(parse_int(literal)?, 32, literal)? as i32),
+        "J" => Value::Long(parse_int(literal)?),
+        "F" => Value::Float(parse_float(literal)?),
+        "D" => Value::Double(parse_double(literal)?),
+        _ => parse_untyped_value(literal)?,
+    };
+    return Ok(value);
+}
+
+fn parse_access_flags(words: &[&str]) -> Result<u32, String> {
+    let mut flags = 0;
+    for word in words {
+        match smali::access_flag(word) {
+            Some(flag) => flags |= flag,
+            None => return Err(format!("unknown access flag {}", word)),
+        }
+    }
+    return Ok(flags);
+}
+
+/// Parses the rest of an `.annotation` directive, consuming its elements up
+/// to `.end annotation`
+fn parse_annotation(lines: &[&str], i: &mut usize, header: &str) -> Result<Annotation, SmaliError> {
+    let line_no = *i;
+    let (visibility, ty) = split_first_word(header);
+    let visibility = match Visibility::from_name(visibility) {
+        Some(visibility) => visibility,
+        None => return error(line_no, format!("unknown annotation visibility {}", visibility)),
+    };
+    let ty = parse_type(ty).or_else(|e| error(line_no, e))?;
+    return Ok(Annotation {
+        visibility: visibility,
+        value: parse_annotation_value(lines, i, ty, ".end annotation")?,
+    });
+}
+
+/// Parses `name = value` elements up to the `end` directive. A trailing comma
+/// after `end` separates the annotation from the next item of an array.
+fn parse_annotation_value(lines: &[&str],
+                          i: &mut usize,
+                          ty: String,
+                          end: &str)
+                          -> Result<AnnotationValue, SmaliError> {
+    let start = *i;
+    let mut elements = vec![];
+    while *i < lines.len() {
+        let line_no = *i + 1;
+        let line = strip_comment(lines[*i]).trim();
+        *i += 1;
+        if line.is_empty() {
+            continue;
+        }
+        if line.trim_end_matches(',') == end {
+            return Ok(AnnotationValue {
+                ty: ty,
+                elements: elements,
+            });
+        }
+        match line.find(" = ") {
+            Some(eq) => {
+                let value = parse_element_value(lines, i, &line[eq + 3..])?;
+                elements.push((line[..eq].trim().to_string(), value));
+            }
+            None => {
+                return error(line_no, format!("expected an annotation element, found {}", line))
+            }
+        }
+    }
+    return error(start, format!("missing {}", end));
+}
+
+/// Parses an annotation element value, which may continue over the following
+/// lines for arrays and subannotations
+fn parse_element_value(lines: &[&str], i: &mut usize, text: &str) -> Result<Value, SmaliError> {
+    let line_no = *i;
+    let text = text.trim();
+    if let Some(ty) = text.strip_prefix(".subannotation ") {
+        let ty = parse_type(ty.trim()).or_else(|e| error(line_no, e))?;
+        let value = parse_annotation_value(lines, i, ty, ".end subannotation")?;
+        return Ok(Value::Annotation(value));
+    }
+    if text != "{" {
+        return parse_untyped_value(text).or_else(|e| error(line_no, e));
+    }
+
+    let mut values = vec![];
+    while *i < lines.len() {
+        let line = strip_comment(lines[*i]).trim();
+        *i += 1;
+        if line.is_empty() {
+            continue;
+        }
+        if line.trim_end_matches(',') == "}" {
+            return Ok(Value::Array(values));
+        }
+        // Items that end on this line carry the separating comma themselves
+        let item = line.strip_suffix(',').unwrap_or(line);
+        values.push(parse_element_value(lines, i, item)?);
+    }
+    return error(line_no, "unterminated array".to_string());
+}
+
+enum BranchWidth {
+    Byte,
+    Short,
+    Int,
+}
+
+/// A branch or payload operand waiting for its label to be defined
+struct Branch {
+    line: usize,
+    opcode: u8,
+    address: usize,
+    position: usize,
+    width: BranchWidth,
+    label: String,
+}
+
+/// Switch targets are relative to the switch instruction, which is only
+/// known once every instruction referencing the payload has been seen.
+struct SwitchPayload {
+    line: usize,
+    address: usize,
+    targets: Vec<(usize, String)>,
+}
+
+struct Catch {
+    line: usize,
+    exception: Option<String>,
+    start: String,
+    end: String,
+    handler: String,
+}
+
+struct MethodAssembler {
+    ins: u16,
+    registers: Option<u16>,
+    outs: u16,
+    insns: Vec<u16>,
+    fixups: Vec<Fixup>,
+    labels: HashMap<String, usize>,
+    branches: Vec<Branch>,
+    payloads: Vec<SwitchPayload>,
+    catches: Vec<Catch>,
+    parameter_names: Vec<Option<String>>,
+    debug_events: Vec<(u32, DebugEvent)>,
+}
+
+fn label_name(token: &str) -> Result<String, String> {
+    if token.starts_with(':') && token.len() > 1 {
+        return Ok(token[1..].to_string());
+    }
+    return Err(format!("expected a label, found {}", token));
+}
+
+impl MethodAssembler {
+    fn register(&self, token: &str) -> Result<u16, String> {
+        let number = token.get(1..).and_then(|n| n.parse::<u16>().ok());
+        match (token.chars().next(), number) {
+            (Some('v'), Some(n)) => Ok(n),
+            (Some('p'), Some(n)) => {
+                match self.registers {
+                    Some(registers) if registers >= self.ins && n < self.ins => {
+                        Ok(registers - self.ins + n)
+                    }
+                    _ => Err(format!("parameter register {} out of range", token)),
+                }
+            }
+            _ => Err(format!("expected a register, found {}", token)),
+        }
+    }
+
+    fn register_max(&self, token: &str, bits: u32) -> Result<u16, String> {
+        let register = self.register(token)?;
+        if register as u32 >= 1 << bits {
+            return Err(format!("register {} does not fit into {} bits", token, bits));
+        }
+        return Ok(register);
+    }
+
+    /// Parses `{v0, v1}` or `{v0 .. v5}` into the list of registers
+    fn register_list(&self, token: &str, range: bool) -> Result<Vec<u16>, String> {
+        if !token.starts_with('{') || !token.ends_with('}') {
+            return Err(format!("expected a register list, found {}", token));
+        }
+        let inner = token[1..token.len() - 1].trim();
+        if inner.is_empty() {
+            return Ok(vec![]);
+        }
+        if range {
+            let bounds: Vec<&str> = inner.split("..").map(|r| r.trim()).collect();
+            let first = self.register(bounds[0])?;
+            let last = match bounds.len() {
+                1 => first,
+                2 => self.register(bounds[1])?,
+                _ => return Err(format!("invalid register range {}", token)),
+            };
+            if last < first {
+                return Err(format!("invalid register range {}", token));
+            }
+            return Ok((first as u32..last as u32 + 1).map(|r| r as u16).collect());
+        }
+        let mut list = vec![];
+        for register in inner.split(',') {
+            list.push(self.register_max(register.trim(), 4)?);
+        }
+        return Ok(list);
+    }
+
+    /// Maps the register of a `.param` directive to the parameter it holds
+    fn parameter(&self, token: &str, proto: &Proto, access_flags: u32) -> Result<usize, String> {
+        let register = match self.registers {
+            Some(registers) if registers >= self.ins => {
+                self.register(token)?.checked_sub(registers - self.ins)
+            }
+            _ if token.starts_with('p') => token[1..].parse::<u16>().ok(),
+            _ => None,
+        };
+        let mut next = if access_flags & ACC_STATIC != 0 { 0 } else { 1 };
+        for (i, param) in proto.parameters.iter().enumerate() {
+            if register == Some(next) {
+                return Ok(i);
+            }
+            next += if param == "J" || param == "D" { 2 } else { 1 };
+        }
+        return Err(format!("{} does not hold a parameter", token));
+    }
+
+    /// Parses the operands of a `.local` directive, `vR, "name":Ltype;`
+    /// with an optional generic signature after another comma
+    fn local(&self, operands: &[String]) -> Result<DebugEvent, String> {
+        let register = self.register(operands.first().map(|r| r.as_str()).unwrap_or(""))?;
+        let (name, ty) = match operands.get(1) {
+            // Type descriptors never contain a colon, names might
+            Some(local) => {
+                match local.rfind(':') {
+                    Some(colon) => (&local[..colon], &local[colon + 1..]),
+                    None => return Err(format!("invalid local {}", local)),
+                }
+            }
+            None => ("null", "null"),
+        };
+        return Ok(DebugEvent::StartLocal {
+            register: register as u32,
+            name: debug_string(name)?,
+            ty: if ty == "null" { None } else { Some(parse_type(ty)?) },
+            signature: match operands.get(2) {
+                Some(signature) => debug_string(signature)?,
+                None => None,
+            },
+        });
+    }
+
+    fn debug_event(&mut self, event: DebugEvent) {
+        self.debug_events.push((self.insns.len() as u32, event));
+    }
+
+    fn reference(&self, index_type: IndexType, token: &str) -> Result<Reference, String> {
+        match index_type {
+            IndexType::String => Ok(Reference::String(unescape(token, '"')?)),
+            IndexType::Type => Ok(Reference::Type(parse_type(token)?)),
+            IndexType::Field => Ok(Reference::Field(parse_field_ref(token)?)),
+            IndexType::Method | IndexType::MethodAndProto => {
+                Ok(Reference::Method(parse_method_ref(token)?))
+            }
+            IndexType::Proto => Ok(Reference::Proto(parse_proto(token)?)),
+            IndexType::CallSite => Ok(Reference::CallSite(parse_call_site(token)?)),
+            IndexType::MethodHandle => Ok(Reference::MethodHandle(parse_method_handle(token)?)),
+            IndexType::None => Err(format!("{} takes no reference", token)),
+        }
+    }
+
+    fn branch(&mut self, line: usize, opcode: u8, position: usize, width: BranchWidth, token: &str)
+              -> Result<(), String> {
+        let address = self.insns.len();
+        self.branches.push(Branch {
+            line: line,
+            opcode: opcode,
+            address: address,
+            position: address + position,
+            width: width,
+            label: label_name(token)?,
+        });
+        return Ok(());
+    }
+
+    fn instruction(&mut self, line: usize, opcode: u8, operands: &[String]) -> Result<(), String> {
+        let op = &OPCODES[opcode as usize];
+        let expected = match op.format {
+            Format::Invalid => return Err(format!("unknown instruction {}", op.name)),
+            Format::F10x => 0,
+            Format::F11x | Format::F10t | Format::F20t | Format::F30t => 1,
+            Format::F12x | Format::F11n | Format::F22x | Format::F21t | Format::F21s |
+            Format::F21h | Format::F21c | Format::F32x | Format::F31i | Format::F31t |
+            Format::F31c | Format::F35c | Format::F3rc | Format::F51l => 2,
+            Format::F23x | Format::F22b | Format::F22t | Format::F22s | Format::F22c |
+            Format::F45cc | Format::F4rcc => 3,
+        };
+        if operands.len() != expected {
+            return Err(format!("{} expects {} operands, found {}",
+                               op.name,
+                               expected,
+                               operands.len()));
+        }
+
+        let o = opcode as u16;
+        let address = self.insns.len();
+        let mut units: Vec<u16> = vec![];
+        match op.format {
+            Format::Invalid => {}
+            Format::F10x => units.push(o),
+            Format::F12x => {
+                let a = self.register_max(&operands[0], 4)?;
+                let b = self.register_max(&operands[1], 4)?;
+                units.push(o | a << 8 | b << 12);
+            }
+            Format::F11n => {
+                let a = self.register_max(&operands[0], 4)?;
+                let literal = parse_literal(&operands[1])?;
+                if !(-8..=7).contains(&literal) {
+                    return Err(format!("literal {} does not fit into 4 bits", operands[1]));
+                }
+                units.push(o | a << 8 | ((literal as u16) & 0xF) << 12);
+            }
+            Format::F11x => units.push(o | self.register_max(&operands[0], 8)? << 8),
+            Format::F10t => {
+                units.push(o);
+                self.branch(line, opcode, 0, BranchWidth::Byte, &operands[0])?;
+            }
+            Format::F20t => {
+                units.extend_from_slice(&[o, 0]);
+                self.branch(line, opcode, 1, BranchWidth::Short, &operands[0])?;
+            }
+            Format::F22x => {
+                units.push(o | self.register_max(&operands[0], 8)? << 8);
+                units.push(self.register(&operands[1])?);
+            }
+            Format::F21t => {
+                units.extend_from_slice(&[o | self.register_max(&operands[0], 8)? << 8, 0]);
+                self.branch(line, opcode, 1, BranchWidth::Short, &operands[1])?;
+            }
+            Format::F21s => {
+                let literal = parse_literal(&operands[1])?;
+                if !(-0x8000..=0x7FFF).contains(&literal) {
+                    return Err(format!("literal {} does not fit into 16 bits", operands[1]));
+                }
+                units.push(o | self.register_max(&operands[0], 8)? << 8);
+                units.push(literal as u16);
+            }
+            Format::F21h => {
+                let literal = parse_literal(&operands[1])?;
+                let shift = if opcode == 0x15 { 16 } else { 48 };
+                if opcode == 0x15 {
+                    check_width(literal, 32, &operands[1])?;
+                }
+                if literal & ((1i64 << shift) - 1) != 0 {
+                    return Err(format!("literal {} has low bits set", operands[1]));
+                }
+                units.push(o | self.register_max(&operands[0], 8)? << 8);
+                units.push((literal >> shift) as u16);
+            }
+            Format::F21c | Format::F31c => {
+                units.push(o | self.register_max(&operands[0], 8)? << 8);
+                units.push(0);
+                if op.format == Format::F31c {
+                    units.push(0);
+                }
+                self.fixups.push(Fixup {
+                    position: address + 1,
+                    wide: op.format == Format::F31c,
+                    reference: self.reference(op.index_type, &operands[1])?,
+                });
+            }
+            Format::F23x => {
+                units.push(o | self.register_max(&operands[0], 8)? << 8);
+                units.push(self.register_max(&operands[1], 8)? |
+                           self.register_max(&operands[2], 8)? << 8);
+            }
+            Format::F22b => {
+                let literal = parse_literal(&operands[2])?;
+                if !(-0x80..=0x7F).contains(&literal) {
+                    return Err(format!("literal {} does not fit into 8 bits", operands[2]));
+                }
+                units.push(o | self.register_max(&operands[0], 8)? << 8);
+                units.push(self.register_max(&operands[1], 8)? | (literal as u8 as u16) << 8);
+            }
+            Format::F22t | Format::F22s | Format::F22c => {
+                let a = self.register_max(&operands[0], 4)?;
+                let b = self.register_max(&operands[1], 4)?;
+                units.push(o | a << 8 | b << 12);
+                match op.format {
+                    Format::F22t => {
+                        units.push(0);
+                        self.branch(line, opcode, 1, BranchWidth::Short, &operands[2])?;
+                    }
+                    Format::F22s => {
+                        let literal = parse_literal(&operands[2])?;
+                        if !(-0x8000..=0x7FFF).contains(&literal) {
+                            return Err(format!("literal {} does not fit into 16 bits",
+                                               operands[2]));
+                        }
+                        units.push(literal as u16);
+                    }
+                    _ => {
+                        units.push(0);
+                        self.fixups.push(Fixup {
+                            position: address + 1,
+                            wide: false,
+                            reference: self.reference(op.index_type, &operands[2])?,
+                        });
+                    }
+                }
+            }
+            Format::F30t => {
+                units.extend_from_slice(&[o, 0, 0]);
+                self.branch(line, opcode, 1, BranchWidth::Int, &operands[0])?;
+            }
+            Format::F32x => {
+                units.push(o);
+                units.push(self.register(&operands[0])?);
+                units.push(self.register(&operands[1])?);
+            }
+            Format::F31i => {
+                let literal = parse_literal(&operands[1])?;
+                if literal < i32::MIN as i64 || literal > u32::MAX as i64 {
+                    return Err(format!("literal {} does not fit into 32 bits", operands[1]));
+                }
+                units.push(o | self.register_max(&operands[0], 8)? << 8);
+                units.push(literal as u16);
+                units.push((literal >> 16) as u16);
+            }
+            Format::F31t => {
+                units.extend_from_slice(&[o | self.register_max(&operands[0], 8)? << 8, 0, 0]);
+                self.branch(line, opcode, 1, BranchWidth::Int, &operands[1])?;
+            }
+            Format::F35c | Format::F45cc => {
+                let registers = self.register_list(&operands[0], false)?;
+                if registers.len() > 5 {
+                    return Err(format!("{} takes at most 5 registers", op.name));
+                }
+                let mut regs = [0u16; 5];
+                regs[..registers.len()].copy_from_slice(&registers);
+                units.push(o | regs[4] << 8 | (registers.len() as u16) << 12);
+                units.push(0);
+                units.push(regs[0] | regs[1] << 4 | regs[2] << 8 | regs[3] << 12);
+                self.fixups.push(Fixup {
+                    position: address + 1,
+                    wide: false,
+                    reference: self.reference(op.index_type, &operands[1])?,
+                });
+                if op.format == Format::F45cc {
+                    units.push(0);
+                    self.fixups.push(Fixup {
+                        position: address + 3,
+                        wide: false,
+                        reference: self.reference(IndexType::Proto, &operands[2])?,
+                    });
+                }
+                if op.name.starts_with("invoke-") {
+                    self.outs = self.outs.max(registers.len() as u16);
+                }
+            }
+            Format::F3rc | Format::F4rcc => {
+                let registers = self.register_list(&operands[0], true)?;
+                if registers.len() > 255 {
+                    return Err(format!("{} takes at most 255 registers", op.name));
+                }
+                units.push(o | (registers.len() as u16) << 8);
+                units.push(0);
+                units.push(registers.first().cloned().unwrap_or(0));
+                self.fixups.push(Fixup {
+                    position: address + 1,
+                    wide: false,
+                    reference: self.reference(op.index_type, &operands[1])?,
+                });
+                if op.format == Format::F4rcc {
+                    units.push(0);
+                    self.fixups.push(Fixup {
+                        position: address + 3,
+                        wide: false,
+                        reference: self.reference(IndexType::Proto, &operands[2])?,
+                    });
+                }
+                if op.name.starts_with("invoke-") {
+                    self.outs = self.outs.max(registers.len() as u16);
+                }
+            }
+            Format::F51l => {
+                let literal = parse_literal(&operands[1])?;
+                units.push(o | self.register_max(&operands[0], 8)? << 8);
+                for i in 0..4 {
+                    units.push((literal >> (i * 16)) as u16);
+                }
+            }
+        }
+
+        self.insns.extend(units);
+        return Ok(());
+    }
+
+    /// Payloads must start on a 4-byte boundary. Labels placed right before
+    /// the payload directive move along with it past the padding nop.
+    fn align_payload(&mut self) {
+        if self.insns.len().is_multiple_of(2) {
+            return;
+        }
+        let address = self.insns.len();
+        self.insns.push(0);
+        for target in self.labels.values_mut() {
+            if *target == address {
+                *target += 1;
+            }
+        }
+    }
+
+    /// Reads the lines of a payload directive up to its `.end` line
+    fn payload_lines<'a>(lines: &[&'a str], i: &mut usize, end: &str)
+                         -> Result<Vec<(usize, &'a str)>, SmaliError> {
+        let start = *i;
+        let mut body = vec![];
+        while *i < lines.len() {
+            let line = strip_comment(lines[*i]).trim();
+            *i += 1;
+            if line == end {
+                return Ok(body);
+            }
+            if !line.is_empty() {
+                body.push((*i, line));
+            }
+        }
+        return error(start, format!("missing {}", end));
+    }
+
+    fn packed_switch(&mut self, line: usize, first_key: &str, body: Vec<(usize, &str)>)
+                     -> Result<(), String> {
+        let first_key = check_width(parse_int(first_key)?, 32, first_key)?;
+        if body.len() > 0xFFFF {
+            return Err("packed-switch with more than 65535 targets".to_string());
+        }
+        self.align_payload();
+        let address = self.insns.len();
+        self.insns.push(0x0100);
+        self.insns.push(body.len() as u16);
+        self.insns.push(first_key as u16);
+        self.insns.push((first_key >> 16) as u16);
+        let mut targets = vec![];
+        for (_, target) in body {
+            targets.push((self.insns.len(), label_name(target)?));
+            self.insns.extend_from_slice(&[0, 0]);
+        }
+        self.payloads.push(SwitchPayload {
+            line: line,
+            address: address,
+            targets: targets,
+        });
+        return Ok(());
+    }
+
+    fn sparse_switch(&mut self, line: usize, body: Vec<(usize, &str)>) -> Result<(), String> {
+        if body.len() > 0xFFFF {
+            return Err("sparse-switch with more than 65535 targets".to_string());
+        }
+        self.align_payload();
+        let address = self.insns.len();
+        self.insns.push(0x0200);
+        self.insns.push(body.len() as u16);
+        let mut labels = vec![];
+        for &(_, entry) in body.iter() {
+            let parts: Vec<&str> = entry.split("->").map(|p| p.trim()).collect();
+            if parts.len() != 2 {
+                return Err(format!("invalid sparse-switch entry {}", entry));
+            }
+            let key = check_width(parse_int(parts[0])?, 32, parts[0])?;
+            self.insns.push(key as u16);
+            self.insns.push((key >> 16) as u16);
+            labels.push(label_name(parts[1])?);
+        }
+        let mut targets = vec![];
+        for label in labels {
+            targets.push((self.insns.len(), label));
+            self.insns.extend_from_slice(&[0, 0]);
+        }
+        self.payloads.push(SwitchPayload {
+            line: line,
+            address: address,
+            targets: targets,
+        });
+        return Ok(());
+    }
+
+    fn array_data(&mut self, width: &str, body: Vec<(usize, &str)>) -> Result<(), String> {
+        let width = parse_int(width)?;
+        if width != 1 && width != 2 && width != 4 && width != 8 {
+            return Err(format!("invalid array-data element width {}", width));
+        }
+        let mut data: Vec<u8> = vec![];
+        for (_, entry) in body {
+            for literal in entry.split_whitespace() {
+                let value = if literal.starts_with('\'') {
+                    parse_char(literal)? as i64
+                } else {
+                    check_width(parse_literal(literal)?, width as u32 * 8, literal)?
+                };
+                for i in 0..width {
+                    data.push((value >> (i * 8)) as u8);
+                }
+            }
+        }
+        let count = data.len() as u64 / width as u64;
+        if count > 0xFFFFFFFF {
+            return Err("array-data with more than 2^32 elements".to_string());
+        }
+        self.align_payload();
+        self.insns.push(0x0300);
+        self.insns.push(width as u16);
+        self.insns.push(count as u16);
+        self.insns.push((count >> 16) as u16);
+        for pair in data.chunks(2) {
+            self.insns.push(pair[0] as u16 | (*pair.get(1).unwrap_or(&0) as u16) << 8);
+        }
+        return Ok(());
+    }
+
+    fn catch(&mut self, line: usize, exception: Option<String>, operands: &str)
+             -> Result<(), String> {
+        // {:start .. :end} :handler
+        let close = match operands.find('}') {
+            Some(close) if operands.starts_with('{') => close,
+            _ => return Err(format!("invalid catch range {}", operands)),
+        };
+        let range: Vec<&str> = operands[1..close].split("..").map(|r| r.trim()).collect();
+        if range.len() != 2 {
+            return Err(format!("invalid catch range {}", operands));
+        }
+        self.catches.push(Catch {
+            line: line,
+            exception: exception,
+            start: label_name(range[0])?,
+            end: label_name(range[1])?,
+            handler: label_name(operands[close + 1..].trim())?,
+        });
+        return Ok(());
+    }
+
+    fn label(&self, line: usize, name: &str) -> Result<usize, SmaliError> {
+        match self.labels.get(name) {
+            Some(address) => Ok(*address),
+            None => error(line, format!("undefined label :{}", name)),
+        }
+    }
+
+    /// Resolves labels and turns the .catch directives into try items
+    fn finish(mut self) -> Result<Option<Code>, SmaliError> {
+        let registers = match self.registers {
+            Some(registers) => registers,
+            None if self.insns.is_empty() => return Ok(None),
+            None => return error(0, "method has code but no .registers".to_string()),
+        };
+
+        let mut switches: HashMap<usize, usize> = HashMap::new();
+        for branch in self.branches.iter() {
+            let target = self.label(branch.line, &branch.label)?;
+            let offset = target as i64 - branch.address as i64;
+            match branch.width {
+                BranchWidth::Byte => {
+                    if !(-0x80..=0x7F).contains(&offset) {
+                        let message = format!("branch to :{} out of range", branch.label);
+                        return error(branch.line, message);
+                    }
+                    self.insns[branch.position] |= (offset as u8 as u16) << 8;
+                }
+                BranchWidth::Short => {
+                    if !(-0x8000..=0x7FFF).contains(&offset) {
+                        let message = format!("branch to :{} out of range", branch.label);
+                        return error(branch.line, message);
+                    }
+                    self.insns[branch.position] = offset as u16;
+                }
+                BranchWidth::Int => {
+                    self.insns[branch.position] = offset as u16;
+                    self.insns[branch.position + 1] = (offset >> 16) as u16;
+                }
+            }
+            if branch.opcode == 0x2b || branch.opcode == 0x2c {
+                switches.insert(target, branch.address);
+            }
+        }
+
+        for payload in self.payloads.iter() {
+            let base = match switches.get(&payload.address) {
+                Some(base) => *base,
+                None => return error(payload.line, "switch payload is never used".to_string()),
+            };
+            for &(position, ref label) in payload.targets.iter() {
+                let offset = self.label(payload.line, label)? as i64 - base as i64;
+                self.insns[position] = offset as u16;
+                self.insns[position + 1] = (offset >> 16) as u16;
+            }
+        }
+
+        // Split overlapping .catch ranges into disjoint try items
+        let mut ranges = vec![];
+        let mut bounds = vec![];
+        for catch in self.catches.iter() {
+            let start = self.label(catch.line, &catch.start)?;
+            let end = self.label(catch.line, &catch.end)?;
+            let handler = self.label(catch.line, &catch.handler)? as u32;
+            if end <= start {
+                return error(catch.line, "empty catch range".to_string());
+            }
+            ranges.push((start, end, catch.exception.clone(), handler));
+            bounds.push(start);
+            bounds.push(end);
+        }
+        bounds.sort();
+        bounds.dedup();
+
+        let mut tries: Vec<Try> = vec![];
+        for window in bounds.windows(2) {
+            let (start, end) = (window[0], window[1]);
+            let mut handlers: Vec<(String, u32)> = vec![];
+            let mut catch_all = None;
+            for &(s, e, ref exception, handler) in ranges.iter() {
+                if s > start || e < end {
+                    continue;
+                }
+                match *exception {
+                    Some(ref ty) => {
+                        if !handlers.iter().any(|h| h.0 == *ty) {
+                            handlers.push((ty.clone(), handler));
+                        }
+                    }
+                    None => catch_all = catch_all.or(Some(handler)),
+                }
+            }
+            if handlers.is_empty() && catch_all.is_none() {
+                continue;
+            }
+            if let Some(last) = tries.last_mut() {
+                if last.start_addr as usize + last.insn_count as usize == start &&
+                   last.handlers == handlers && last.catch_all == catch_all &&
+                   end - last.start_addr as usize <= 0xFFFF {
+                    last.insn_count = (end - last.start_addr as usize) as u16;
+                    continue;
+                }
+            }
+            if end - start > 0xFFFF {
+                return error(0, "try block longer than 65535 code units".to_string());
+            }
+            tries.push(Try {
+                start_addr: start as u32,
+                insn_count: (end - start) as u16,
+                handlers: handlers,
+                catch_all: catch_all,
+            });
+        }
+
+        let debug_info = if self.debug_events.is_empty() &&
+                            self.parameter_names.iter().all(|name| name.is_none()) {
+            None
+        } else {
+            Some(DebugInfo {
+                parameter_names: self.parameter_names,
+                events: self.debug_events,
+            })
+        };
+
+        return Ok(Some(Code {
+            registers_size: registers,
+            ins_size: self.ins,
+            outs_size: self.outs,
+            insns: self.insns,
+            fixups: self.fixups,
+            tries: tries,
+            debug_info: debug_info,
+        }));
+    }
+}
+
+/// A string of the debug info, which may be `null`
+fn debug_string(literal: &str) -> Result<Option<String>, String> {
+    if literal == "null" {
+        return Ok(None);
+    }
+    return Ok(Some(parse_string(literal)?));
+}
+
+/// Number of argument registers a method receives, including `this`.
+/// Dalvik limits these to 255.
+fn ins_size(proto: &Proto, access_flags: u32) -> Result<u16, String> {
+    let mut ins = if access_flags & ACC_STATIC != 0 { 0 } else { 1 };
+    for param in proto.parameters.iter() {
+        ins += if param == "J" || param == "D" { 2 } else { 1 };
+        if ins > 255 {
+            return Err("method takes more than 255 argument registers".to_string());
+        }
+    }
+    return Ok(ins);
+}
+
+fn opcode_by_name(name: &str) -> Option<u8> {
+    return OPCODES.iter()
+        .position(|op| op.name == name && op.format != Format::Invalid)
+        .map(|i| i as u8);
+}
+
+/// Assembles the body of a method, consuming lines up to `.end method`.
+/// Annotations inside `.param` ... `.end param` belong to that parameter,
+/// all others to the method itself. Debug directives apply at the address
+/// of the next instruction.
+fn parse_method_body(lines: &[&str],
+                     i: &mut usize,
+                     method: MethodRef,
+                     access_flags: u32)
+                     -> Result<Method, SmaliError> {
+    let start = *i;
+    let mut annotations = vec![];
+    let mut parameter_annotations = vec![vec![]; method.proto.parameters.len()];
+    let mut pending: Vec<Annotation> = vec![];
+    let mut parameter: Option<usize> = None;
+    let mut asm = MethodAssembler {
+        ins: ins_size(&method.proto, access_flags).or_else(|e| error(start, e))?,
+        registers: None,
+        outs: 0,
+        insns: vec![],
+        fixups: vec![],
+        labels: HashMap::new(),
+        branches: vec![],
+        payloads: vec![],
+        catches: vec![],
+        parameter_names: vec![None; method.proto.parameters.len()],
+        debug_events: vec![],
+    };
+
+    while *i < lines.len() {
+        let line_no = *i + 1;
+        let line = strip_comment(lines[*i]).trim();
+        *i += 1;
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix(':') {
+            if asm.labels.insert(name.to_string(), asm.insns.len()).is_some() {
+                return error(line_no, format!("duplicate label {}", line));
+            }
+            continue;
+        }
+
+        let (keyword, rest) = split_first_word(line);
+        let end_param = keyword == ".end" && rest == "param";
+        if keyword != ".annotation" && !end_param {
+            annotations.append(&mut pending);
+            parameter = None;
+        }
+        let result = match keyword {
+            ".end" if rest == "method" => {
+                let code = asm.finish().map_err(|e| {
+                        SmaliError {
+                            line: if e.line == 0 { start } else { e.line },
+                            message: e.message,
+                        }
+                    })?;
+                return Ok(Method {
+                    method: method,
+                    access_flags: access_flags,
+                    code: code,
+                    annotations: annotations,
+                    parameter_annotations: parameter_annotations,
+                });
+            }
+            ".registers" | ".locals" => {
+                let base = if keyword == ".locals" { asm.ins as i64 } else { 0 };
+                match parse_int(rest) {
+                    Ok(count) if count >= 0 && count + base <= 0xFFFF => {
+                        asm.registers = Some((count + base) as u16);
+                        Ok(())
+                    }
+                    Ok(_) => Err(format!("{} {} exceeds 65535 registers", keyword, rest)),
+                    Err(e) => Err(e),
+                }
+            }
+            ".catch" => {
+                let (exception, operands) = split_first_word(rest);
+                match parse_type(exception) {
+                    Ok(exception) => asm.catch(line_no, Some(exception), operands),
+                    Err(e) => Err(e),
+                }
+            }
+            ".catchall" => asm.catch(line_no, None, rest),
+            ".packed-switch" => {
+                let body = MethodAssembler::payload_lines(lines, i, ".end packed-switch")?;
+                asm.packed_switch(line_no, rest, body)
+            }
+            ".sparse-switch" => {
+                let body = MethodAssembler::payload_lines(lines, i, ".end sparse-switch")?;
+                asm.sparse_switch(line_no, body)
+            }
+            ".array-data" => {
+                let body = MethodAssembler::payload_lines(lines, i, ".end array-data")?;
+                asm.array_data(rest, body)
+            }
+            ".annotation" => {
+                pending.push(parse_annotation(lines, i, rest)?);
+                Ok(())
+            }
+            ".param" => {
+                let operands = split_operands(rest);
+                let register = operands.first().map(|r| r.as_str()).unwrap_or("");
+                match asm.parameter(register, &method.proto, access_flags) {
+                    Ok(index) => {
+                        parameter = Some(index);
+                        match operands.get(1) {
+                            Some(name) => {
+                                debug_string(name).map(|name| asm.parameter_names[index] = name)
+                            }
+                            None => Ok(()),
+                        }
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            ".end" if rest == "param" => {
+                match parameter.take() {
+                    Some(index) => parameter_annotations[index].append(&mut pending),
+                    None => annotations.append(&mut pending),
+                }
+                Ok(())
+            }
+            ".line" => {
+                match parse_int(rest) {
+                    Ok(line) if (0..=0xFFFFFFFF).contains(&line) => {
+                        asm.debug_event(DebugEvent::Line(line as u32));
+                        Ok(())
+                    }
+                    Ok(_) => Err(format!("line number {} out of range", rest)),
+                    Err(e) => Err(e),
+                }
+            }
+            ".local" => asm.local(&split_operands(rest)).map(|event| asm.debug_event(event)),
+            ".end" | ".restart" if rest.starts_with("local ") => {
+                let (_, register) = split_first_word(rest);
+                asm.register(register).map(|register| {
+                    asm.debug_event(if keyword == ".end" {
+                        DebugEvent::EndLocal(register as u32)
+                    } else {
+                        DebugEvent::RestartLocal(register as u32)
+                    })
+                })
+            }
+            ".prologue" => {
+                asm.debug_event(DebugEvent::PrologueEnd);
+                Ok(())
+            }
+            ".epilogue" => {
+                asm.debug_event(DebugEvent::EpilogueBegin);
+                Ok(())
+            }
+            ".source" => debug_string(rest).map(|name| asm.debug_event(DebugEvent::SetFile(name))),
+            _ => {
+                match opcode_by_name(keyword) {
+                    Some(opcode) => asm.instruction(line_no, opcode, &split_operands(rest)),
+                    None => Err(format!("unknown instruction or directive {}", keyword)),
+                }
+            }
+        };
+        if let Err(message) = result {
+            return error(line_no, message);
+        }
+    }
+    return error(start, "missing .end method".to_string());
+}
+
+/// Parses the smali source of a single class
+pub fn parse_class(source: &str) -> Result<Class, SmaliError> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut class: Option<Class> = None;
+    // Annotations belong to the field before them only if `.end field` follows
+    let mut pending: Vec<Annotation> = vec![];
+    let mut last_field_static = false;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line_no = i + 1;
+        let line = strip_comment(lines[i]).trim();
+        i += 1;
+        if line.is_empty() {
+            continue;
+        }
+
+        let (keyword, rest) = split_first_word(line);
+        if keyword == ".class" {
+            let words: Vec<&str> = rest.split_whitespace().collect();
+            let (descriptor, flags) = match words.split_last() {
+                Some((descriptor, flags)) => (descriptor, flags),
+                None => return error(line_no, "missing class descriptor".to_string()),
+            };
+            let flags = parse_access_flags(flags).or_else(|e| error(line_no, e))?;
+            class = Some(Class {
+                descriptor: parse_type(descriptor).or_else(|e| error(line_no, e))?,
+                access_flags: flags,
+                superclass: None,
+                interfaces: vec![],
+                source_file: None,
+                annotations: vec![],
+                static_fields: vec![],
+                instance_fields: vec![],
+                direct_methods: vec![],
+                virtual_methods: vec![],
+            });
+            continue;
+        }
+
+        let class = match class.as_mut() {
+            Some(class) => class,
+            None => return error(line_no, format!("{} before .class", keyword)),
+        };
+        let end_field = keyword == ".end" && rest == "field";
+        if keyword != ".annotation" && !end_field {
+            class.annotations.append(&mut pending);
+        }
+        let result: Result<(), String> = match keyword {
+            ".super" if class.superclass.is_some() => Err("duplicate .super".to_string()),
+            ".super" => parse_type(rest).map(|ty| class.superclass = Some(ty)),
+            ".implements" => parse_type(rest).map(|ty| class.interfaces.push(ty)),
+            ".source" if class.source_file.is_some() => Err("duplicate .source".to_string()),
+            ".source" => parse_string(rest).map(|source| class.source_file = Some(source)),
+            ".field" => {
+                let (declaration, value) = match rest.find(" = ") {
+                    Some(eq) => (&rest[..eq], Some(rest[eq + 3..].trim())),
+                    None => (rest, None),
+                };
+                let words: Vec<&str> = declaration.split_whitespace().collect();
+                match words.split_last() {
+                    Some((member, flags)) => {
+                        parse_access_flags(flags).and_then(|flags| {
+                            // DEX files only store initial values of static fields
+                            if value.is_some() && flags & ACC_STATIC == 0 {
+                                return Err("only static fields take an initial value".to_string());
+                            }
+                            let field = parse_field(member, &class.descriptor)?;
+                            let value = match value {
+                                Some(value) => Some(parse_value(value, &field.ty)?),
+                                None => None,
+                            };
+                            let field = Field {
+                                field: field,
+                                access_flags: flags,
+                                value: value,
+                                annotations: vec![],
+                            };
+                            last_field_static = flags & ACC_STATIC != 0;
+                            if last_field_static {
+                                class.static_fields.push(field);
+                            } else {
+                                class.instance_fields.push(field);
+                            }
+                            Ok(())
+                        })
+                    }
+                    None => Err("missing field declaration".to_string()),
+                }
+            }
+            ".method" => {
+                let words: Vec<&str> = rest.split_whitespace().collect();
+                let (member, flags) = match words.split_last() {
+                    Some((member, flags)) => (member, flags),
+                    None => return error(line_no, "missing method declaration".to_string()),
+                };
+                let flags = parse_access_flags(flags).or_else(|e| error(line_no, e))?;
+                let method = parse_method(member, &class.descriptor)
+                    .or_else(|e| error(line_no, e))?;
+                let method = parse_method_body(&lines, &mut i, method, flags)?;
+                if flags & (ACC_STATIC | ACC_PRIVATE | ACC_CONSTRUCTOR) != 0 {
+                    class.direct_methods.push(method);
+                } else {
+                    class.virtual_methods.push(method);
+                }
+                Ok(())
+            }
+            ".annotation" => {
+                pending.push(parse_annotation(&lines, &mut i, rest)?);
+                Ok(())
+            }
+            ".end" if rest == "field" => {
+                let field = if last_field_static {
+                    class.static_fields.last_mut()
+                } else {
+                    class.instance_fields.last_mut()
+                };
+                match field {
+                    Some(field) => {
+                        field.annotations.append(&mut pending);
+                        Ok(())
+                    }
+                    None => Err("unexpected .end field".to_string()),
+                }
+            }
+            _ => Err(format!("unexpected {}", keyword)),
+        };
+        if let Err(message) = result {
+            return error(line_no, message);
+        }
+    }
+
+    match class {
+        Some(mut class) => {
+            class.annotations.append(&mut pending);
+            Ok(class)
+        }
+        None => error(0, "missing .class directive".to_string()),
+    }
+}
+
+/// Assembles smali sources, one class each, into a DEX file
+pub fn assemble(sources: &[&str]) -> Result<Vec<u8>, SmaliError> {
+    let mut classes: Vec<Class> = vec![];
+    for source in sources {
+        let class = parse_class(source)?;
+        if classes.iter().any(|c| c.descriptor == class.descriptor) {
+            return error(0, format!("duplicate class {}", class.descriptor));
+        }
+        classes.push(class);
+    }
+    return writer::write(&classes).or_else(|e| error(0, e));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dalvik;
+    use dex;
+
+    fn class(body: &str) -> String {
+        return format!(".class public LTest;\n.super Ljava/lang/Object;\n{}", body);
+    }
+
+    fn assemble_error(body: &str) -> SmaliError {
+        return assemble(&[&class(body)]).unwrap_err();
+    }
+
+    #[test]
+    fn assembles_branches_switches_and_catches() {
+        let source = class("
+.method public static run(I)I
+    .registers 2
+    :try_start
+    packed-switch p0, :switch
+    const/4 v0, -0x1
+    goto :end
+    :one
+    const/16 v0, 0x100
+    :try_end
+    .catchall {:try_start .. :try_end} :end
+    :end
+    return v0
+    :switch
+    .packed-switch 0x1
+        :one
+    .end packed-switch
+.end method
+");
+        let dex = dex::parse(assemble(&[&source]).unwrap()).unwrap();
+        let data = dex.class_defs()[0].class_data().unwrap();
+        let code = data.direct_methods()[0].code().unwrap();
+        assert_eq!(code.registers_size(), 2);
+        assert_eq!(code.ins_size(), 1);
+        assert_eq!(code.tries().len(), 1);
+        assert_eq!(dalvik::disassemble(&dex, code.insns()),
+                   vec!["packed-switch v1, :pswitch_data_8",
+                        "const/4 v0, -0x1",
+                        "goto :goto_7",
+                        ":pswitch_5",
+                        "const/16 v0, 0x100",
+                        ":goto_7",
+                        "return v0",
+                        ":pswitch_data_8",
+                        ".packed-switch 0x1",
+                        "    :pswitch_5",
+                        ".end packed-switch"]);
+    }
+
+    #[test]
+    fn parses_static_values_and_literals() {
+        assert_eq!(parse_int("-0x80t").unwrap(), -0x80);
+        assert_eq!(parse_int("0xffffffffffffffffL").unwrap(), -1);
+        assert!(parse_int("-0x8000000000000001").is_err());
+        match parse_value("0xff", "B").unwrap() {
+            Value::Byte(value) => assert_eq!(value, -1),
+            _ => panic!("expected a byte"),
+        }
+        assert!(parse_value("0x100", "B").is_err());
+        assert!(parse_value("-0x80000001", "I").is_err());
+        assert!(parse_untyped_value("0x10000s").is_err());
+        assert!(parse_value("0x10000", "C").is_err());
+    }
+
+    #[test]
+    fn rejects_literals_that_do_not_fit() {
+        let method = |line: &str| {
+            format!(".method static f()V\n    .registers 1\n    {}\n    return-void\n.end method",
+                    line)
+        };
+        assert!(assemble_error(&method("const/4 v0, 0x8")).message.contains("4 bits"));
+        let error = assemble_error(&method("const/high16 v0, 0x100000000"));
+        assert!(error.message.contains("32 bits"));
+        assert!(assemble_error(&method(".line -1")).message.contains("out of range"));
+        let error = assemble_error(&method(".array-data 1\n 0x100t\n.end array-data"));
+        assert!(error.message.contains("8 bits"));
+        let switch = ".sparse-switch\n 0x100000000 -> :a\n.end sparse-switch";
+        let error = assemble_error(&method(switch));
+        assert!(error.message.contains("32 bits"));
+    }
+
+    #[test]
+    fn rejects_data_the_dex_file_cannot_hold() {
+        let error = assemble_error(".field private x:I = 0x1");
+        assert_eq!(error.line, 3);
+        assert!(error.message.contains("static"));
+        assert!(assemble_error(".source \"a\"\n.source \"b\"").message.contains("duplicate"));
+        assert!(assemble_error(".super LOther;").message.contains("duplicate"));
+        let error = assemble_error(".method static f()V\n    .locals 65536\n.end method");
+        assert!(error.message.contains("65535"));
+    }
+
+    #[test]
+    fn register_ranges_reach_the_last_register() {
+        let source = class("
+.method static f()V
+    .registers 65535
+    invoke-static/range {v65533 .. v65534}, LTest;->g(II)V
+    return-void
+.end method
+");
+        assert!(assemble(&[&source]).is_ok());
+    }
+
+    #[test]
+    fn reports_the_line_of_broken_code() {
+        let method = ".method static f()V\n    .registers 1\n    goto :nowhere\n.end method";
+        let error = assemble_error(method);
+        assert_eq!(error.line, 5);
+        assert_eq!(error.message, "undefined label :nowhere");
+        let error = assemble_error(".method static f()V\n    .registers 1\n    :a\n    :a\n");
+        assert_eq!(error.message, "duplicate label :a");
+        assert_eq!(assemble_error(".method static f()V\n").message, "missing .end method");
+        let error = assemble_error(".method static f()V\n    frobnicate v0\n.end method");
+        assert_eq!(error.line, 4);
+    }
+}
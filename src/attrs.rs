@@ -0,0 +1,1314 @@
+/// The namespace of the attributes defined by the Android framework
+pub const ANDROID_NAMESPACE: &str = "http://schemas.android.com/apk/res/android";
+
+/// The name of a framework attribute, e.g. `versionCode` for 0x0101021b.
+/// The framework looks attributes up by id alone, so this holds even when
+/// the string pool name was blanked or renamed.
+pub fn attribute_name(id: u32) -> Option<&'static str> {
+    return ATTRIBUTES.binary_search_by_key(&id, |&(attr, _)| attr)
+        .ok()
+        .map(|i| ATTRIBUTES[i].1);
+}
+
+/// Attribute ids from frameworks/base/core/res/res/values/public.xml, sorted
+/// by id. Complete up to directBootAware (0x01010505, API 24); past that only
+/// manifest attributes are listed, and other ids keep their string pool name.
+const ATTRIBUTES: &[(u32, &str)] = &[
+    (0x01010000, "theme"),
+    (0x01010001, "label"),
+    (0x01010002, "icon"),
+    (0x01010003, "name"),
+    (0x01010004, "manageSpaceActivity"),
+    (0x01010005, "allowClearUserData"),
+    (0x01010006, "permission"),
+    (0x01010007, "readPermission"),
+    (0x01010008, "writePermission"),
+    (0x01010009, "protectionLevel"),
+    (0x0101000a, "permissionGroup"),
+    (0x0101000b, "sharedUserId"),
+    (0x0101000c, "hasCode"),
+    (0x0101000d, "persistent"),
+    (0x0101000e, "enabled"),
+    (0x0101000f, "debuggable"),
+    (0x01010010, "exported"),
+    (0x01010011, "process"),
+    (0x01010012, "taskAffinity"),
+    (0x01010013, "multiprocess"),
+    (0x01010014, "finishOnTaskLaunch"),
+    (0x01010015, "clearTaskOnLaunch"),
+    (0x01010016, "stateNotNeeded"),
+    (0x01010017, "excludeFromRecents"),
+    (0x01010018, "authorities"),
+    (0x01010019, "syncable"),
+    (0x0101001a, "initOrder"),
+    (0x0101001b, "grantUriPermissions"),
+    (0x0101001c, "priority"),
+    (0x0101001d, "launchMode"),
+    (0x0101001e, "screenOrientation"),
+    (0x0101001f, "configChanges"),
+    (0x01010020, "description"),
+    (0x01010021, "targetPackage"),
+    (0x01010022, "handleProfiling"),
+    (0x01010023, "functionalTest"),
+    (0x01010024, "value"),
+    (0x01010025, "resource"),
+    (0x01010026, "mimeType"),
+    (0x01010027, "scheme"),
+    (0x01010028, "host"),
+    (0x01010029, "port"),
+    (0x0101002a, "path"),
+    (0x0101002b, "pathPrefix"),
+    (0x0101002c, "pathPattern"),
+    (0x0101002d, "action"),
+    (0x0101002e, "data"),
+    (0x0101002f, "targetClass"),
+    (0x01010030, "colorForeground"),
+    (0x01010031, "colorBackground"),
+    (0x01010032, "backgroundDimAmount"),
+    (0x01010033, "disabledAlpha"),
+    (0x01010034, "textAppearance"),
+    (0x01010035, "textAppearanceInverse"),
+    (0x01010036, "textColorPrimary"),
+    (0x01010037, "textColorPrimaryDisableOnly"),
+    (0x01010038, "textColorSecondary"),
+    (0x01010039, "textColorPrimaryInverse"),
+    (0x0101003a, "textColorSecondaryInverse"),
+    (0x0101003b, "textColorPrimaryNoDisable"),
+    (0x0101003c, "textColorSecondaryNoDisable"),
+    (0x0101003d, "textColorPrimaryInverseNoDisable"),
+    (0x0101003e, "textColorSecondaryInverseNoDisable"),
+    (0x0101003f, "textColorHintInverse"),
+    (0x01010040, "textAppearanceLarge"),
+    (0x01010041, "textAppearanceMedium"),
+    (0x01010042, "textAppearanceSmall"),
+    (0x01010043, "textAppearanceLargeInverse"),
+    (0x01010044, "textAppearanceMediumInverse"),
+    (0x01010045, "textAppearanceSmallInverse"),
+    (0x01010046, "textCheckMark"),
+    (0x01010047, "textCheckMarkInverse"),
+    (0x01010048, "buttonStyle"),
+    (0x01010049, "buttonStyleSmall"),
+    (0x0101004a, "buttonStyleInset"),
+    (0x0101004b, "buttonStyleToggle"),
+    (0x0101004c, "galleryItemBackground"),
+    (0x0101004d, "listPreferredItemHeight"),
+    (0x0101004e, "expandableListPreferredItemPaddingLeft"),
+    (0x0101004f, "expandableListPreferredChildPaddingLeft"),
+    (0x01010050, "expandableListPreferredItemIndicatorLeft"),
+    (0x01010051, "expandableListPreferredItemIndicatorRight"),
+    (0x01010052, "expandableListPreferredChildIndicatorLeft"),
+    (0x01010053, "expandableListPreferredChildIndicatorRight"),
+    (0x01010054, "windowBackground"),
+    (0x01010055, "windowFrame"),
+    (0x01010056, "windowNoTitle"),
+    (0x01010057, "windowIsFloating"),
+    (0x01010058, "windowIsTranslucent"),
+    (0x01010059, "windowContentOverlay"),
+    (0x0101005a, "windowTitleSize"),
+    (0x0101005b, "windowTitleStyle"),
+    (0x0101005c, "windowTitleBackgroundStyle"),
+    (0x0101005d, "alertDialogStyle"),
+    (0x0101005e, "panelBackground"),
+    (0x0101005f, "panelFullBackground"),
+    (0x01010060, "panelColorForeground"),
+    (0x01010061, "panelColorBackground"),
+    (0x01010062, "panelTextAppearance"),
+    (0x01010063, "scrollbarSize"),
+    (0x01010064, "scrollbarThumbHorizontal"),
+    (0x01010065, "scrollbarThumbVertical"),
+    (0x01010066, "scrollbarTrackHorizontal"),
+    (0x01010067, "scrollbarTrackVertical"),
+    (0x01010068, "scrollbarAlwaysDrawHorizontalTrack"),
+    (0x01010069, "scrollbarAlwaysDrawVerticalTrack"),
+    (0x0101006a, "absListViewStyle"),
+    (0x0101006b, "autoCompleteTextViewStyle"),
+    (0x0101006c, "checkboxStyle"),
+    (0x0101006d, "dropDownListViewStyle"),
+    (0x0101006e, "editTextStyle"),
+    (0x0101006f, "expandableListViewStyle"),
+    (0x01010070, "galleryStyle"),
+    (0x01010071, "gridViewStyle"),
+    (0x01010072, "imageButtonStyle"),
+    (0x01010073, "imageWellStyle"),
+    (0x01010074, "listViewStyle"),
+    (0x01010075, "listViewWhiteStyle"),
+    (0x01010076, "popupWindowStyle"),
+    (0x01010077, "progressBarStyle"),
+    (0x01010078, "progressBarStyleHorizontal"),
+    (0x01010079, "progressBarStyleSmall"),
+    (0x0101007a, "progressBarStyleLarge"),
+    (0x0101007b, "seekBarStyle"),
+    (0x0101007c, "ratingBarStyle"),
+    (0x0101007d, "ratingBarStyleSmall"),
+    (0x0101007e, "radioButtonStyle"),
+    (0x0101007f, "scrollbarStyle"),
+    (0x01010080, "scrollViewStyle"),
+    (0x01010081, "spinnerStyle"),
+    (0x01010082, "starStyle"),
+    (0x01010083, "tabWidgetStyle"),
+    (0x01010084, "textViewStyle"),
+    (0x01010085, "webViewStyle"),
+    (0x01010086, "dropDownItemStyle"),
+    (0x01010087, "spinnerDropDownItemStyle"),
+    (0x01010088, "dropDownHintAppearance"),
+    (0x01010089, "spinnerItemStyle"),
+    (0x0101008a, "mapViewStyle"),
+    (0x0101008b, "preferenceScreenStyle"),
+    (0x0101008c, "preferenceCategoryStyle"),
+    (0x0101008d, "preferenceInformationStyle"),
+    (0x0101008e, "preferenceStyle"),
+    (0x0101008f, "checkBoxPreferenceStyle"),
+    (0x01010090, "yesNoPreferenceStyle"),
+    (0x01010091, "dialogPreferenceStyle"),
+    (0x01010092, "editTextPreferenceStyle"),
+    (0x01010093, "ringtonePreferenceStyle"),
+    (0x01010094, "preferenceLayoutChild"),
+    (0x01010095, "textSize"),
+    (0x01010096, "typeface"),
+    (0x01010097, "textStyle"),
+    (0x01010098, "textColor"),
+    (0x01010099, "textColorHighlight"),
+    (0x0101009a, "textColorHint"),
+    (0x0101009b, "textColorLink"),
+    (0x0101009c, "state_focused"),
+    (0x0101009d, "state_window_focused"),
+    (0x0101009e, "state_enabled"),
+    (0x0101009f, "state_checkable"),
+    (0x010100a0, "state_checked"),
+    (0x010100a1, "state_selected"),
+    (0x010100a2, "state_active"),
+    (0x010100a3, "state_single"),
+    (0x010100a4, "state_first"),
+    (0x010100a5, "state_middle"),
+    (0x010100a6, "state_last"),
+    (0x010100a7, "state_pressed"),
+    (0x010100a8, "state_expanded"),
+    (0x010100a9, "state_empty"),
+    (0x010100aa, "state_above_anchor"),
+    (0x010100ab, "ellipsize"),
+    (0x010100ac, "x"),
+    (0x010100ad, "y"),
+    (0x010100ae, "windowAnimationStyle"),
+    (0x010100af, "gravity"),
+    (0x010100b0, "autoLink"),
+    (0x010100b1, "linksClickable"),
+    (0x010100b2, "entries"),
+    (0x010100b3, "layout_gravity"),
+    (0x010100b4, "windowEnterAnimation"),
+    (0x010100b5, "windowExitAnimation"),
+    (0x010100b6, "windowShowAnimation"),
+    (0x010100b7, "windowHideAnimation"),
+    (0x010100b8, "activityOpenEnterAnimation"),
+    (0x010100b9, "activityOpenExitAnimation"),
+    (0x010100ba, "activityCloseEnterAnimation"),
+    (0x010100bb, "activityCloseExitAnimation"),
+    (0x010100bc, "taskOpenEnterAnimation"),
+    (0x010100bd, "taskOpenExitAnimation"),
+    (0x010100be, "taskCloseEnterAnimation"),
+    (0x010100bf, "taskCloseExitAnimation"),
+    (0x010100c0, "taskToFrontEnterAnimation"),
+    (0x010100c1, "taskToFrontExitAnimation"),
+    (0x010100c2, "taskToBackEnterAnimation"),
+    (0x010100c3, "taskToBackExitAnimation"),
+    (0x010100c4, "orientation"),
+    (0x010100c5, "keycode"),
+    (0x010100c6, "fullDark"),
+    (0x010100c7, "topDark"),
+    (0x010100c8, "centerDark"),
+    (0x010100c9, "bottomDark"),
+    (0x010100ca, "fullBright"),
+    (0x010100cb, "topBright"),
+    (0x010100cc, "centerBright"),
+    (0x010100cd, "bottomBright"),
+    (0x010100ce, "bottomMedium"),
+    (0x010100cf, "centerMedium"),
+    (0x010100d0, "id"),
+    (0x010100d1, "tag"),
+    (0x010100d2, "scrollX"),
+    (0x010100d3, "scrollY"),
+    (0x010100d4, "background"),
+    (0x010100d5, "padding"),
+    (0x010100d6, "paddingLeft"),
+    (0x010100d7, "paddingTop"),
+    (0x010100d8, "paddingRight"),
+    (0x010100d9, "paddingBottom"),
+    (0x010100da, "focusable"),
+    (0x010100db, "focusableInTouchMode"),
+    (0x010100dc, "visibility"),
+    (0x010100dd, "fitsSystemWindows"),
+    (0x010100de, "scrollbars"),
+    (0x010100df, "fadingEdge"),
+    (0x010100e0, "fadingEdgeLength"),
+    (0x010100e1, "nextFocusLeft"),
+    (0x010100e2, "nextFocusRight"),
+    (0x010100e3, "nextFocusUp"),
+    (0x010100e4, "nextFocusDown"),
+    (0x010100e5, "clickable"),
+    (0x010100e6, "longClickable"),
+    (0x010100e7, "saveEnabled"),
+    (0x010100e8, "drawingCacheQuality"),
+    (0x010100e9, "duplicateParentState"),
+    (0x010100ea, "clipChildren"),
+    (0x010100eb, "clipToPadding"),
+    (0x010100ec, "layoutAnimation"),
+    (0x010100ed, "animationCache"),
+    (0x010100ee, "persistentDrawingCache"),
+    (0x010100ef, "alwaysDrawnWithCache"),
+    (0x010100f0, "addStatesFromChildren"),
+    (0x010100f1, "descendantFocusability"),
+    (0x010100f2, "layout"),
+    (0x010100f3, "inflatedId"),
+    (0x010100f4, "layout_width"),
+    (0x010100f5, "layout_height"),
+    (0x010100f6, "layout_margin"),
+    (0x010100f7, "layout_marginLeft"),
+    (0x010100f8, "layout_marginTop"),
+    (0x010100f9, "layout_marginRight"),
+    (0x010100fa, "layout_marginBottom"),
+    (0x010100fb, "listSelector"),
+    (0x010100fc, "drawSelectorOnTop"),
+    (0x010100fd, "stackFromBottom"),
+    (0x010100fe, "scrollingCache"),
+    (0x010100ff, "textFilterEnabled"),
+    (0x01010100, "transcriptMode"),
+    (0x01010101, "cacheColorHint"),
+    (0x01010102, "dial"),
+    (0x01010103, "hand_hour"),
+    (0x01010104, "hand_minute"),
+    (0x01010105, "format"),
+    (0x01010106, "checked"),
+    (0x01010107, "button"),
+    (0x01010108, "checkMark"),
+    (0x01010109, "foreground"),
+    (0x0101010a, "measureAllChildren"),
+    (0x0101010b, "groupIndicator"),
+    (0x0101010c, "childIndicator"),
+    (0x0101010d, "indicatorLeft"),
+    (0x0101010e, "indicatorRight"),
+    (0x0101010f, "childIndicatorLeft"),
+    (0x01010110, "childIndicatorRight"),
+    (0x01010111, "childDivider"),
+    (0x01010112, "animationDuration"),
+    (0x01010113, "spacing"),
+    (0x01010114, "horizontalSpacing"),
+    (0x01010115, "verticalSpacing"),
+    (0x01010116, "stretchMode"),
+    (0x01010117, "columnWidth"),
+    (0x01010118, "numColumns"),
+    (0x01010119, "src"),
+    (0x0101011a, "antialias"),
+    (0x0101011b, "filter"),
+    (0x0101011c, "dither"),
+    (0x0101011d, "scaleType"),
+    (0x0101011e, "adjustViewBounds"),
+    (0x0101011f, "maxWidth"),
+    (0x01010120, "maxHeight"),
+    (0x01010121, "tint"),
+    (0x01010122, "baselineAlignBottom"),
+    (0x01010123, "cropToPadding"),
+    (0x01010124, "textOn"),
+    (0x01010125, "textOff"),
+    (0x01010126, "baselineAligned"),
+    (0x01010127, "baselineAlignedChildIndex"),
+    (0x01010128, "weightSum"),
+    (0x01010129, "divider"),
+    (0x0101012a, "dividerHeight"),
+    (0x0101012b, "choiceMode"),
+    (0x0101012c, "itemTextAppearance"),
+    (0x0101012d, "horizontalDivider"),
+    (0x0101012e, "verticalDivider"),
+    (0x0101012f, "headerBackground"),
+    (0x01010130, "itemBackground"),
+    (0x01010131, "itemIconDisabledAlpha"),
+    (0x01010132, "rowHeight"),
+    (0x01010133, "maxRows"),
+    (0x01010134, "maxItemsPerRow"),
+    (0x01010135, "moreIcon"),
+    (0x01010136, "max"),
+    (0x01010137, "progress"),
+    (0x01010138, "secondaryProgress"),
+    (0x01010139, "indeterminate"),
+    (0x0101013a, "indeterminateOnly"),
+    (0x0101013b, "indeterminateDrawable"),
+    (0x0101013c, "progressDrawable"),
+    (0x0101013d, "indeterminateDuration"),
+    (0x0101013e, "indeterminateBehavior"),
+    (0x0101013f, "minWidth"),
+    (0x01010140, "minHeight"),
+    (0x01010141, "interpolator"),
+    (0x01010142, "thumb"),
+    (0x01010143, "thumbOffset"),
+    (0x01010144, "numStars"),
+    (0x01010145, "rating"),
+    (0x01010146, "stepSize"),
+    (0x01010147, "isIndicator"),
+    (0x01010148, "checkedButton"),
+    (0x01010149, "stretchColumns"),
+    (0x0101014a, "shrinkColumns"),
+    (0x0101014b, "collapseColumns"),
+    (0x0101014c, "layout_column"),
+    (0x0101014d, "layout_span"),
+    (0x0101014e, "bufferType"),
+    (0x0101014f, "text"),
+    (0x01010150, "hint"),
+    (0x01010151, "textScaleX"),
+    (0x01010152, "cursorVisible"),
+    (0x01010153, "maxLines"),
+    (0x01010154, "lines"),
+    (0x01010155, "height"),
+    (0x01010156, "minLines"),
+    (0x01010157, "maxEms"),
+    (0x01010158, "ems"),
+    (0x01010159, "width"),
+    (0x0101015a, "minEms"),
+    (0x0101015b, "scrollHorizontally"),
+    (0x0101015c, "password"),
+    (0x0101015d, "singleLine"),
+    (0x0101015e, "selectAllOnFocus"),
+    (0x0101015f, "includeFontPadding"),
+    (0x01010160, "maxLength"),
+    (0x01010161, "shadowColor"),
+    (0x01010162, "shadowDx"),
+    (0x01010163, "shadowDy"),
+    (0x01010164, "shadowRadius"),
+    (0x01010165, "numeric"),
+    (0x01010166, "digits"),
+    (0x01010167, "phoneNumber"),
+    (0x01010168, "inputMethod"),
+    (0x01010169, "capitalize"),
+    (0x0101016a, "autoText"),
+    (0x0101016b, "editable"),
+    (0x0101016c, "freezesText"),
+    (0x0101016d, "drawableTop"),
+    (0x0101016e, "drawableBottom"),
+    (0x0101016f, "drawableLeft"),
+    (0x01010170, "drawableRight"),
+    (0x01010171, "drawablePadding"),
+    (0x01010172, "completionHint"),
+    (0x01010173, "completionHintView"),
+    (0x01010174, "completionThreshold"),
+    (0x01010175, "dropDownSelector"),
+    (0x01010176, "popupBackground"),
+    (0x01010177, "inAnimation"),
+    (0x01010178, "outAnimation"),
+    (0x01010179, "flipInterval"),
+    (0x0101017a, "fillViewport"),
+    (0x0101017b, "prompt"),
+    (0x0101017c, "startYear"),
+    (0x0101017d, "endYear"),
+    (0x0101017e, "mode"),
+    (0x0101017f, "layout_x"),
+    (0x01010180, "layout_y"),
+    (0x01010181, "layout_weight"),
+    (0x01010182, "layout_toLeftOf"),
+    (0x01010183, "layout_toRightOf"),
+    (0x01010184, "layout_above"),
+    (0x01010185, "layout_below"),
+    (0x01010186, "layout_alignBaseline"),
+    (0x01010187, "layout_alignLeft"),
+    (0x01010188, "layout_alignTop"),
+    (0x01010189, "layout_alignRight"),
+    (0x0101018a, "layout_alignBottom"),
+    (0x0101018b, "layout_alignParentLeft"),
+    (0x0101018c, "layout_alignParentTop"),
+    (0x0101018d, "layout_alignParentRight"),
+    (0x0101018e, "layout_alignParentBottom"),
+    (0x0101018f, "layout_centerInParent"),
+    (0x01010190, "layout_centerHorizontal"),
+    (0x01010191, "layout_centerVertical"),
+    (0x01010192, "layout_alignWithParentIfMissing"),
+    (0x01010193, "layout_scale"),
+    (0x01010194, "visible"),
+    (0x01010195, "variablePadding"),
+    (0x01010196, "constantSize"),
+    (0x01010197, "oneshot"),
+    (0x01010198, "duration"),
+    (0x01010199, "drawable"),
+    (0x0101019a, "shape"),
+    (0x0101019b, "innerRadiusRatio"),
+    (0x0101019c, "thicknessRatio"),
+    (0x0101019d, "startColor"),
+    (0x0101019e, "endColor"),
+    (0x0101019f, "useLevel"),
+    (0x010101a0, "angle"),
+    (0x010101a1, "type"),
+    (0x010101a2, "centerX"),
+    (0x010101a3, "centerY"),
+    (0x010101a4, "gradientRadius"),
+    (0x010101a5, "color"),
+    (0x010101a6, "dashWidth"),
+    (0x010101a7, "dashGap"),
+    (0x010101a8, "radius"),
+    (0x010101a9, "topLeftRadius"),
+    (0x010101aa, "topRightRadius"),
+    (0x010101ab, "bottomLeftRadius"),
+    (0x010101ac, "bottomRightRadius"),
+    (0x010101ad, "left"),
+    (0x010101ae, "top"),
+    (0x010101af, "right"),
+    (0x010101b0, "bottom"),
+    (0x010101b1, "minLevel"),
+    (0x010101b2, "maxLevel"),
+    (0x010101b3, "fromDegrees"),
+    (0x010101b4, "toDegrees"),
+    (0x010101b5, "pivotX"),
+    (0x010101b6, "pivotY"),
+    (0x010101b7, "insetLeft"),
+    (0x010101b8, "insetRight"),
+    (0x010101b9, "insetTop"),
+    (0x010101ba, "insetBottom"),
+    (0x010101bb, "shareInterpolator"),
+    (0x010101bc, "fillBefore"),
+    (0x010101bd, "fillAfter"),
+    (0x010101be, "startOffset"),
+    (0x010101bf, "repeatCount"),
+    (0x010101c0, "repeatMode"),
+    (0x010101c1, "zAdjustment"),
+    (0x010101c2, "fromXScale"),
+    (0x010101c3, "toXScale"),
+    (0x010101c4, "fromYScale"),
+    (0x010101c5, "toYScale"),
+    (0x010101c6, "fromXDelta"),
+    (0x010101c7, "toXDelta"),
+    (0x010101c8, "fromYDelta"),
+    (0x010101c9, "toYDelta"),
+    (0x010101ca, "fromAlpha"),
+    (0x010101cb, "toAlpha"),
+    (0x010101cc, "delay"),
+    (0x010101cd, "animation"),
+    (0x010101ce, "animationOrder"),
+    (0x010101cf, "columnDelay"),
+    (0x010101d0, "rowDelay"),
+    (0x010101d1, "direction"),
+    (0x010101d2, "directionPriority"),
+    (0x010101d3, "factor"),
+    (0x010101d4, "cycles"),
+    (0x010101d5, "searchMode"),
+    (0x010101d6, "searchSuggestAuthority"),
+    (0x010101d7, "searchSuggestPath"),
+    (0x010101d8, "searchSuggestSelection"),
+    (0x010101d9, "searchSuggestIntentAction"),
+    (0x010101da, "searchSuggestIntentData"),
+    (0x010101db, "queryActionMsg"),
+    (0x010101dc, "suggestActionMsg"),
+    (0x010101dd, "suggestActionMsgColumn"),
+    (0x010101de, "menuCategory"),
+    (0x010101df, "orderInCategory"),
+    (0x010101e0, "checkBehavior"),
+    (0x010101e1, "title"),
+    (0x010101e2, "titleCondensed"),
+    (0x010101e3, "alphabeticShortcut"),
+    (0x010101e4, "numericShortcut"),
+    (0x010101e5, "checkable"),
+    (0x010101e6, "selectable"),
+    (0x010101e7, "orderingFromXml"),
+    (0x010101e8, "key"),
+    (0x010101e9, "summary"),
+    (0x010101ea, "order"),
+    (0x010101eb, "widgetLayout"),
+    (0x010101ec, "dependency"),
+    (0x010101ed, "defaultValue"),
+    (0x010101ee, "shouldDisableView"),
+    (0x010101ef, "summaryOn"),
+    (0x010101f0, "summaryOff"),
+    (0x010101f1, "disableDependentsState"),
+    (0x010101f2, "dialogTitle"),
+    (0x010101f3, "dialogMessage"),
+    (0x010101f4, "dialogIcon"),
+    (0x010101f5, "positiveButtonText"),
+    (0x010101f6, "negativeButtonText"),
+    (0x010101f7, "dialogLayout"),
+    (0x010101f8, "entryValues"),
+    (0x010101f9, "ringtoneType"),
+    (0x010101fa, "showDefault"),
+    (0x010101fb, "showSilent"),
+    (0x010101fc, "scaleWidth"),
+    (0x010101fd, "scaleHeight"),
+    (0x010101fe, "scaleGravity"),
+    (0x010101ff, "ignoreGravity"),
+    (0x01010200, "foregroundGravity"),
+    (0x01010201, "tileMode"),
+    (0x01010202, "targetActivity"),
+    (0x01010203, "alwaysRetainTaskState"),
+    (0x01010204, "allowTaskReparenting"),
+    (0x01010205, "searchButtonText"),
+    (0x01010206, "colorForegroundInverse"),
+    (0x01010207, "textAppearanceButton"),
+    (0x01010208, "listSeparatorTextViewStyle"),
+    (0x01010209, "streamType"),
+    (0x0101020a, "clipOrientation"),
+    (0x0101020b, "centerColor"),
+    (0x0101020c, "minSdkVersion"),
+    (0x0101020d, "windowFullscreen"),
+    (0x0101020e, "unselectedAlpha"),
+    (0x0101020f, "progressBarStyleSmallTitle"),
+    (0x01010210, "ratingBarStyleIndicator"),
+    (0x01010211, "apiKey"),
+    (0x01010212, "textColorTertiary"),
+    (0x01010213, "textColorTertiaryInverse"),
+    (0x01010214, "listDivider"),
+    (0x01010215, "soundEffectsEnabled"),
+    (0x01010216, "keepScreenOn"),
+    (0x01010217, "lineSpacingExtra"),
+    (0x01010218, "lineSpacingMultiplier"),
+    (0x01010219, "listChoiceIndicatorSingle"),
+    (0x0101021a, "listChoiceIndicatorMultiple"),
+    (0x0101021b, "versionCode"),
+    (0x0101021c, "versionName"),
+    (0x0101021d, "marqueeRepeatLimit"),
+    (0x0101021e, "windowNoDisplay"),
+    (0x0101021f, "backgroundDimEnabled"),
+    (0x01010220, "inputType"),
+    (0x01010221, "isDefault"),
+    (0x01010222, "windowDisablePreview"),
+    (0x01010223, "privateImeOptions"),
+    (0x01010224, "editorExtras"),
+    (0x01010225, "settingsActivity"),
+    (0x01010226, "fastScrollEnabled"),
+    (0x01010227, "reqTouchScreen"),
+    (0x01010228, "reqKeyboardType"),
+    (0x01010229, "reqHardKeyboard"),
+    (0x0101022a, "reqNavigation"),
+    (0x0101022b, "windowSoftInputMode"),
+    (0x0101022c, "imeFullscreenBackground"),
+    (0x0101022d, "noHistory"),
+    (0x0101022e, "headerDividersEnabled"),
+    (0x0101022f, "footerDividersEnabled"),
+    (0x01010230, "candidatesTextStyleSpans"),
+    (0x01010231, "smoothScrollbar"),
+    (0x01010232, "reqFiveWayNav"),
+    (0x01010233, "keyBackground"),
+    (0x01010234, "keyTextSize"),
+    (0x01010235, "labelTextSize"),
+    (0x01010236, "keyTextColor"),
+    (0x01010237, "keyPreviewLayout"),
+    (0x01010238, "keyPreviewOffset"),
+    (0x01010239, "keyPreviewHeight"),
+    (0x0101023a, "verticalCorrection"),
+    (0x0101023b, "popupLayout"),
+    (0x0101023c, "state_long_pressable"),
+    (0x0101023d, "keyWidth"),
+    (0x0101023e, "keyHeight"),
+    (0x0101023f, "horizontalGap"),
+    (0x01010240, "verticalGap"),
+    (0x01010241, "rowEdgeFlags"),
+    (0x01010242, "codes"),
+    (0x01010243, "popupKeyboard"),
+    (0x01010244, "popupCharacters"),
+    (0x01010245, "keyEdgeFlags"),
+    (0x01010246, "isModifier"),
+    (0x01010247, "isSticky"),
+    (0x01010248, "isRepeatable"),
+    (0x01010249, "iconPreview"),
+    (0x0101024a, "keyOutputText"),
+    (0x0101024b, "keyLabel"),
+    (0x0101024c, "keyIcon"),
+    (0x0101024d, "keyboardMode"),
+    (0x0101024e, "isScrollContainer"),
+    (0x0101024f, "fillEnabled"),
+    (0x01010250, "updatePeriodMillis"),
+    (0x01010251, "initialLayout"),
+    (0x01010252, "voiceSearchMode"),
+    (0x01010253, "voiceLanguageModel"),
+    (0x01010254, "voicePromptText"),
+    (0x01010255, "voiceLanguage"),
+    (0x01010256, "voiceMaxResults"),
+    (0x01010257, "bottomOffset"),
+    (0x01010258, "topOffset"),
+    (0x01010259, "allowSingleTap"),
+    (0x0101025a, "handle"),
+    (0x0101025b, "content"),
+    (0x0101025c, "animateOnClick"),
+    (0x0101025d, "configure"),
+    (0x0101025e, "hapticFeedbackEnabled"),
+    (0x0101025f, "innerRadius"),
+    (0x01010260, "thickness"),
+    (0x01010261, "sharedUserLabel"),
+    (0x01010262, "dropDownWidth"),
+    (0x01010263, "dropDownAnchor"),
+    (0x01010264, "imeOptions"),
+    (0x01010265, "imeActionLabel"),
+    (0x01010266, "imeActionId"),
+    (0x01010268, "imeExtractEnterAnimation"),
+    (0x01010269, "imeExtractExitAnimation"),
+    (0x0101026a, "tension"),
+    (0x0101026b, "extraTension"),
+    (0x0101026c, "anyDensity"),
+    (0x0101026d, "searchSuggestThreshold"),
+    (0x0101026e, "includeInGlobalSearch"),
+    (0x0101026f, "onClick"),
+    (0x01010270, "targetSdkVersion"),
+    (0x01010271, "maxSdkVersion"),
+    (0x01010272, "testOnly"),
+    (0x01010273, "contentDescription"),
+    (0x01010274, "gestureStrokeWidth"),
+    (0x01010275, "gestureColor"),
+    (0x01010276, "uncertainGestureColor"),
+    (0x01010277, "fadeOffset"),
+    (0x01010278, "fadeDuration"),
+    (0x01010279, "gestureStrokeType"),
+    (0x0101027a, "gestureStrokeLengthThreshold"),
+    (0x0101027b, "gestureStrokeSquarenessThreshold"),
+    (0x0101027c, "gestureStrokeAngleThreshold"),
+    (0x0101027d, "eventsInterceptionEnabled"),
+    (0x0101027e, "fadeEnabled"),
+    (0x0101027f, "backupAgent"),
+    (0x01010280, "allowBackup"),
+    (0x01010281, "glEsVersion"),
+    (0x01010282, "queryAfterZeroResults"),
+    (0x01010283, "dropDownHeight"),
+    (0x01010284, "smallScreens"),
+    (0x01010285, "normalScreens"),
+    (0x01010286, "largeScreens"),
+    (0x01010287, "progressBarStyleInverse"),
+    (0x01010288, "progressBarStyleSmallInverse"),
+    (0x01010289, "progressBarStyleLargeInverse"),
+    (0x0101028a, "searchSettingsDescription"),
+    (0x0101028b, "textColorPrimaryInverseDisableOnly"),
+    (0x0101028c, "autoUrlDetect"),
+    (0x0101028d, "resizeable"),
+    (0x0101028e, "required"),
+    (0x0101028f, "accountType"),
+    (0x01010290, "contentAuthority"),
+    (0x01010291, "userVisible"),
+    (0x01010292, "windowShowWallpaper"),
+    (0x01010293, "wallpaperOpenEnterAnimation"),
+    (0x01010294, "wallpaperOpenExitAnimation"),
+    (0x01010295, "wallpaperCloseEnterAnimation"),
+    (0x01010296, "wallpaperCloseExitAnimation"),
+    (0x01010297, "wallpaperIntraOpenEnterAnimation"),
+    (0x01010298, "wallpaperIntraOpenExitAnimation"),
+    (0x01010299, "wallpaperIntraCloseEnterAnimation"),
+    (0x0101029a, "wallpaperIntraCloseExitAnimation"),
+    (0x0101029b, "supportsUploading"),
+    (0x0101029c, "killAfterRestore"),
+    (0x0101029d, "restoreNeedsApplication"),
+    (0x0101029e, "smallIcon"),
+    (0x0101029f, "accountPreferences"),
+    (0x010102a0, "textAppearanceSearchResultSubtitle"),
+    (0x010102a1, "textAppearanceSearchResultTitle"),
+    (0x010102a2, "summaryColumn"),
+    (0x010102a3, "detailColumn"),
+    (0x010102a4, "detailSocialSummary"),
+    (0x010102a5, "thumbnail"),
+    (0x010102a6, "detachWallpaper"),
+    (0x010102a7, "finishOnCloseSystemDialogs"),
+    (0x010102a8, "scrollbarFadeDuration"),
+    (0x010102a9, "scrollbarDefaultDelayBeforeFade"),
+    (0x010102aa, "fadeScrollbars"),
+    (0x010102ab, "colorBackgroundCacheHint"),
+    (0x010102ac, "dropDownHorizontalOffset"),
+    (0x010102ad, "dropDownVerticalOffset"),
+    (0x010102ae, "quickContactBadgeStyleWindowSmall"),
+    (0x010102af, "quickContactBadgeStyleWindowMedium"),
+    (0x010102b0, "quickContactBadgeStyleWindowLarge"),
+    (0x010102b1, "quickContactBadgeStyleSmallWindowSmall"),
+    (0x010102b2, "quickContactBadgeStyleSmallWindowMedium"),
+    (0x010102b3, "quickContactBadgeStyleSmallWindowLarge"),
+    (0x010102b4, "author"),
+    (0x010102b5, "autoStart"),
+    (0x010102b6, "expandableListViewWhiteStyle"),
+    (0x010102b7, "installLocation"),
+    (0x010102b8, "vmSafeMode"),
+    (0x010102b9, "webTextViewStyle"),
+    (0x010102ba, "restoreAnyVersion"),
+    (0x010102bb, "tabStripLeft"),
+    (0x010102bc, "tabStripRight"),
+    (0x010102bd, "tabStripEnabled"),
+    (0x010102be, "logo"),
+    (0x010102bf, "xlargeScreens"),
+    (0x010102c0, "immersive"),
+    (0x010102c1, "overScrollMode"),
+    (0x010102c2, "overScrollHeader"),
+    (0x010102c3, "overScrollFooter"),
+    (0x010102c4, "filterTouchesWhenObscured"),
+    (0x010102c5, "textSelectHandleLeft"),
+    (0x010102c6, "textSelectHandleRight"),
+    (0x010102c7, "textSelectHandle"),
+    (0x010102c8, "textSelectHandleWindowStyle"),
+    (0x010102c9, "popupAnimationStyle"),
+    (0x010102ca, "screenSize"),
+    (0x010102cb, "screenDensity"),
+    (0x010102cc, "allContactsName"),
+    (0x010102cd, "windowActionBar"),
+    (0x010102ce, "actionBarStyle"),
+    (0x010102cf, "navigationMode"),
+    (0x010102d0, "displayOptions"),
+    (0x010102d1, "subtitle"),
+    (0x010102d2, "customNavigationLayout"),
+    (0x010102d3, "hardwareAccelerated"),
+    (0x010102d4, "measureWithLargestChild"),
+    (0x010102d5, "animateFirstView"),
+    (0x010102d6, "dropDownSpinnerStyle"),
+    (0x010102d7, "actionDropDownStyle"),
+    (0x010102d8, "actionButtonStyle"),
+    (0x010102d9, "showAsAction"),
+    (0x010102da, "previewImage"),
+    (0x010102db, "actionModeBackground"),
+    (0x010102dc, "actionModeCloseDrawable"),
+    (0x010102dd, "windowActionModeOverlay"),
+    (0x010102de, "valueFrom"),
+    (0x010102df, "valueTo"),
+    (0x010102e0, "valueType"),
+    (0x010102e1, "propertyName"),
+    (0x010102e2, "ordering"),
+    (0x010102e3, "fragment"),
+    (0x010102e4, "windowActionBarOverlay"),
+    (0x010102e5, "fragmentOpenEnterAnimation"),
+    (0x010102e6, "fragmentOpenExitAnimation"),
+    (0x010102e7, "fragmentCloseEnterAnimation"),
+    (0x010102e8, "fragmentCloseExitAnimation"),
+    (0x010102e9, "fragmentFadeEnterAnimation"),
+    (0x010102ea, "fragmentFadeExitAnimation"),
+    (0x010102eb, "actionBarSize"),
+    (0x010102ec, "imeSubtypeLocale"),
+    (0x010102ed, "imeSubtypeMode"),
+    (0x010102ee, "imeSubtypeExtraValue"),
+    (0x010102ef, "splitMotionEvents"),
+    (0x010102f0, "listChoiceBackgroundIndicator"),
+    (0x010102f1, "spinnerMode"),
+    (0x010102f2, "animateLayoutChanges"),
+    (0x010102f3, "actionBarTabStyle"),
+    (0x010102f4, "actionBarTabBarStyle"),
+    (0x010102f5, "actionBarTabTextStyle"),
+    (0x010102f6, "actionOverflowButtonStyle"),
+    (0x010102f7, "actionModeCloseButtonStyle"),
+    (0x010102f8, "titleTextStyle"),
+    (0x010102f9, "subtitleTextStyle"),
+    (0x010102fa, "iconifiedByDefault"),
+    (0x010102fb, "actionLayout"),
+    (0x010102fc, "actionViewClass"),
+    (0x010102fd, "activatedBackgroundIndicator"),
+    (0x010102fe, "state_activated"),
+    (0x010102ff, "listPopupWindowStyle"),
+    (0x01010300, "popupMenuStyle"),
+    (0x01010301, "textAppearanceLargePopupMenu"),
+    (0x01010302, "textAppearanceSmallPopupMenu"),
+    (0x01010303, "breadCrumbTitle"),
+    (0x01010304, "breadCrumbShortTitle"),
+    (0x01010305, "listDividerAlertDialog"),
+    (0x01010306, "textColorAlertDialogListItem"),
+    (0x01010307, "loopViews"),
+    (0x01010308, "dialogTheme"),
+    (0x01010309, "alertDialogTheme"),
+    (0x0101030a, "dividerVertical"),
+    (0x0101030b, "homeAsUpIndicator"),
+    (0x0101030c, "enterFadeDuration"),
+    (0x0101030d, "exitFadeDuration"),
+    (0x0101030e, "selectableItemBackground"),
+    (0x0101030f, "autoAdvanceViewId"),
+    (0x01010310, "useIntrinsicSizeAsMinimum"),
+    (0x01010311, "actionModeCutDrawable"),
+    (0x01010312, "actionModeCopyDrawable"),
+    (0x01010313, "actionModePasteDrawable"),
+    (0x01010314, "textEditPasteWindowLayout"),
+    (0x01010315, "textEditNoPasteWindowLayout"),
+    (0x01010316, "textIsSelectable"),
+    (0x01010317, "windowEnableSplitTouch"),
+    (0x01010318, "indeterminateProgressStyle"),
+    (0x01010319, "progressBarPadding"),
+    (0x0101031a, "animationResolution"),
+    (0x0101031b, "state_accelerated"),
+    (0x0101031c, "baseline"),
+    (0x0101031d, "homeLayout"),
+    (0x0101031e, "opacity"),
+    (0x0101031f, "alpha"),
+    (0x01010320, "transformPivotX"),
+    (0x01010321, "transformPivotY"),
+    (0x01010322, "translationX"),
+    (0x01010323, "translationY"),
+    (0x01010324, "scaleX"),
+    (0x01010325, "scaleY"),
+    (0x01010326, "rotation"),
+    (0x01010327, "rotationX"),
+    (0x01010328, "rotationY"),
+    (0x01010329, "showDividers"),
+    (0x0101032a, "dividerPadding"),
+    (0x0101032b, "borderlessButtonStyle"),
+    (0x0101032c, "dividerHorizontal"),
+    (0x0101032d, "itemPadding"),
+    (0x0101032e, "buttonBarStyle"),
+    (0x0101032f, "buttonBarButtonStyle"),
+    (0x01010330, "segmentedButtonStyle"),
+    (0x01010331, "staticWallpaperPreview"),
+    (0x01010332, "allowParallelSyncs"),
+    (0x01010333, "isAlwaysSyncable"),
+    (0x01010334, "verticalScrollbarPosition"),
+    (0x01010335, "fastScrollAlwaysVisible"),
+    (0x01010336, "fastScrollThumbDrawable"),
+    (0x01010337, "fastScrollPreviewBackgroundLeft"),
+    (0x01010338, "fastScrollPreviewBackgroundRight"),
+    (0x01010339, "fastScrollTrackDrawable"),
+    (0x0101033a, "fastScrollOverlayPosition"),
+    (0x0101033b, "customTokens"),
+    (0x0101033c, "nextFocusForward"),
+    (0x0101033d, "firstDayOfWeek"),
+    (0x0101033e, "showWeekNumber"),
+    (0x0101033f, "minDate"),
+    (0x01010340, "maxDate"),
+    (0x01010341, "shownWeekCount"),
+    (0x01010342, "selectedWeekBackgroundColor"),
+    (0x01010343, "focusedMonthDateColor"),
+    (0x01010344, "unfocusedMonthDateColor"),
+    (0x01010345, "weekNumberColor"),
+    (0x01010346, "weekSeparatorLineColor"),
+    (0x01010347, "selectedDateVerticalBar"),
+    (0x01010348, "weekDayTextAppearance"),
+    (0x01010349, "dateTextAppearance"),
+    (0x0101034a, "solidColor"),
+    (0x0101034b, "spinnersShown"),
+    (0x0101034c, "calendarViewShown"),
+    (0x0101034d, "state_multiline"),
+    (0x0101034e, "detailsElementBackground"),
+    (0x0101034f, "textColorHighlightInverse"),
+    (0x01010350, "textColorLinkInverse"),
+    (0x01010351, "editTextColor"),
+    (0x01010352, "editTextBackground"),
+    (0x01010353, "horizontalScrollViewStyle"),
+    (0x01010354, "layerType"),
+    (0x01010355, "alertDialogIcon"),
+    (0x01010356, "windowMinWidthMajor"),
+    (0x01010357, "windowMinWidthMinor"),
+    (0x01010358, "queryHint"),
+    (0x01010359, "fastScrollTextColor"),
+    (0x0101035a, "largeHeap"),
+    (0x0101035b, "windowCloseOnTouchOutside"),
+    (0x0101035c, "datePickerStyle"),
+    (0x0101035d, "calendarViewStyle"),
+    (0x0101035e, "textEditSidePasteWindowLayout"),
+    (0x0101035f, "textEditSideNoPasteWindowLayout"),
+    (0x01010360, "actionMenuTextAppearance"),
+    (0x01010361, "actionMenuTextColor"),
+    (0x01010362, "textCursorDrawable"),
+    (0x01010363, "resizeMode"),
+    (0x01010364, "requiresSmallestWidthDp"),
+    (0x01010365, "compatibleWidthLimitDp"),
+    (0x01010366, "largestWidthLimitDp"),
+    (0x01010367, "state_hovered"),
+    (0x01010368, "state_drag_can_accept"),
+    (0x01010369, "state_drag_hovered"),
+    (0x0101036a, "stopWithTask"),
+    (0x0101036b, "switchTextOn"),
+    (0x0101036c, "switchTextOff"),
+    (0x0101036d, "switchPreferenceStyle"),
+    (0x0101036e, "switchTextAppearance"),
+    (0x0101036f, "track"),
+    (0x01010370, "switchMinWidth"),
+    (0x01010371, "switchPadding"),
+    (0x01010372, "thumbTextPadding"),
+    (0x01010373, "textSuggestionsWindowStyle"),
+    (0x01010374, "textEditSuggestionItemLayout"),
+    (0x01010375, "rowCount"),
+    (0x01010376, "rowOrderPreserved"),
+    (0x01010377, "columnCount"),
+    (0x01010378, "columnOrderPreserved"),
+    (0x01010379, "useDefaultMargins"),
+    (0x0101037a, "alignmentMode"),
+    (0x0101037b, "layout_row"),
+    (0x0101037c, "layout_rowSpan"),
+    (0x0101037d, "layout_columnSpan"),
+    (0x0101037e, "actionModeSelectAllDrawable"),
+    (0x0101037f, "isAuxiliary"),
+    (0x01010380, "accessibilityEventTypes"),
+    (0x01010381, "packageNames"),
+    (0x01010382, "accessibilityFeedbackType"),
+    (0x01010383, "notificationTimeout"),
+    (0x01010384, "accessibilityFlags"),
+    (0x01010385, "canRetrieveWindowContent"),
+    (0x01010386, "listPreferredItemHeightLarge"),
+    (0x01010387, "listPreferredItemHeightSmall"),
+    (0x01010388, "actionBarSplitStyle"),
+    (0x01010389, "actionProviderClass"),
+    (0x0101038a, "backgroundStacked"),
+    (0x0101038b, "backgroundSplit"),
+    (0x0101038c, "textAllCaps"),
+    (0x0101038d, "colorPressedHighlight"),
+    (0x0101038e, "colorLongPressedHighlight"),
+    (0x0101038f, "colorFocusedHighlight"),
+    (0x01010390, "colorActivatedHighlight"),
+    (0x01010391, "colorMultiSelectHighlight"),
+    (0x01010392, "drawableStart"),
+    (0x01010393, "drawableEnd"),
+    (0x01010394, "actionModeStyle"),
+    (0x01010395, "minResizeWidth"),
+    (0x01010396, "minResizeHeight"),
+    (0x01010397, "actionBarWidgetTheme"),
+    (0x01010398, "uiOptions"),
+    (0x01010399, "subtypeLocale"),
+    (0x0101039a, "subtypeExtraValue"),
+    (0x0101039b, "actionBarDivider"),
+    (0x0101039c, "actionBarItemBackground"),
+    (0x0101039d, "actionModeSplitBackground"),
+    (0x0101039e, "textAppearanceListItem"),
+    (0x0101039f, "textAppearanceListItemSmall"),
+    (0x010103a0, "targetDescriptions"),
+    (0x010103a1, "directionDescriptions"),
+    (0x010103a2, "overridesImplicitlyEnabledSubtype"),
+    (0x010103a3, "listPreferredItemPaddingLeft"),
+    (0x010103a4, "listPreferredItemPaddingRight"),
+    (0x010103a5, "requiresFadingEdge"),
+    (0x010103a6, "publicKey"),
+    (0x010103a7, "parentActivityName"),
+    (0x010103a9, "isolatedProcess"),
+    (0x010103aa, "importantForAccessibility"),
+    (0x010103ab, "keyboardLayout"),
+    (0x010103ac, "fontFamily"),
+    (0x010103ad, "mediaRouteButtonStyle"),
+    (0x010103ae, "mediaRouteTypes"),
+    (0x010103af, "supportsRtl"),
+    (0x010103b0, "textDirection"),
+    (0x010103b1, "textAlignment"),
+    (0x010103b2, "layoutDirection"),
+    (0x010103b3, "paddingStart"),
+    (0x010103b4, "paddingEnd"),
+    (0x010103b5, "layout_marginStart"),
+    (0x010103b6, "layout_marginEnd"),
+    (0x010103b7, "layout_toStartOf"),
+    (0x010103b8, "layout_toEndOf"),
+    (0x010103b9, "layout_alignStart"),
+    (0x010103ba, "layout_alignEnd"),
+    (0x010103bb, "layout_alignParentStart"),
+    (0x010103bc, "layout_alignParentEnd"),
+    (0x010103bd, "listPreferredItemPaddingStart"),
+    (0x010103be, "listPreferredItemPaddingEnd"),
+    (0x010103bf, "singleUser"),
+    (0x010103c0, "presentationTheme"),
+    (0x010103c1, "subtypeId"),
+    (0x010103c2, "initialKeyguardLayout"),
+    (0x010103c4, "widgetCategory"),
+    (0x010103c5, "permissionGroupFlags"),
+    (0x010103c6, "labelFor"),
+    (0x010103c7, "permissionFlags"),
+    (0x010103c8, "checkedTextViewStyle"),
+    (0x010103c9, "showOnLockScreen"),
+    (0x010103ca, "format12Hour"),
+    (0x010103cb, "format24Hour"),
+    (0x010103cc, "timeZone"),
+    (0x010103ce, "mirrorForRtl"),
+    (0x010103cf, "windowOverscan"),
+    (0x010103d0, "requiredForAllUsers"),
+    (0x010103d1, "indicatorStart"),
+    (0x010103d2, "indicatorEnd"),
+    (0x010103d3, "childIndicatorStart"),
+    (0x010103d4, "childIndicatorEnd"),
+    (0x010103d5, "restrictedAccountType"),
+    (0x010103d6, "requiredAccountType"),
+    (0x010103d7, "canRequestTouchExplorationMode"),
+    (0x010103d8, "canRequestEnhancedWebAccessibility"),
+    (0x010103d9, "canRequestFilterKeyEvents"),
+    (0x010103da, "layoutMode"),
+    (0x010103db, "ssp"),
+    (0x010103dc, "sspPrefix"),
+    (0x010103dd, "sspPattern"),
+    (0x010103ea, "autoMirrored"),
+    (0x010103eb, "supportsSwitchingToNextInputMethod"),
+    (0x010103ec, "requireDeviceUnlock"),
+    (0x010103ed, "apduServiceBanner"),
+    (0x010103ee, "accessibilityLiveRegion"),
+    (0x010103ef, "windowTranslucentStatus"),
+    (0x010103f0, "windowTranslucentNavigation"),
+    (0x010103f1, "advancedPrintOptionsActivity"),
+    (0x010103f2, "banner"),
+    (0x010103f3, "windowSwipeToDismiss"),
+    (0x010103f4, "isGame"),
+    (0x010103f5, "allowEmbedded"),
+    (0x010103f6, "setupActivity"),
+    (0x010103f7, "fastScrollStyle"),
+    (0x010103f8, "windowContentTransitions"),
+    (0x010103f9, "windowContentTransitionManager"),
+    (0x010103fa, "translationZ"),
+    (0x010103fb, "tintMode"),
+    (0x010103fc, "controlX1"),
+    (0x010103fd, "controlY1"),
+    (0x010103fe, "controlX2"),
+    (0x010103ff, "controlY2"),
+    (0x01010400, "transitionName"),
+    (0x01010401, "transitionGroup"),
+    (0x01010402, "viewportWidth"),
+    (0x01010403, "viewportHeight"),
+    (0x01010404, "fillColor"),
+    (0x01010405, "pathData"),
+    (0x01010406, "strokeColor"),
+    (0x01010407, "strokeWidth"),
+    (0x01010408, "trimPathStart"),
+    (0x01010409, "trimPathEnd"),
+    (0x0101040a, "trimPathOffset"),
+    (0x0101040b, "strokeLineCap"),
+    (0x0101040c, "strokeLineJoin"),
+    (0x0101040d, "strokeMiterLimit"),
+    (0x01010429, "colorControlNormal"),
+    (0x0101042a, "colorControlActivated"),
+    (0x0101042b, "colorButtonNormal"),
+    (0x0101042c, "colorControlHighlight"),
+    (0x0101042d, "persistableMode"),
+    (0x0101042e, "titleTextAppearance"),
+    (0x0101042f, "subtitleTextAppearance"),
+    (0x01010430, "slideEdge"),
+    (0x01010431, "actionBarTheme"),
+    (0x01010432, "textAppearanceListItemSecondary"),
+    (0x01010433, "colorPrimary"),
+    (0x01010434, "colorPrimaryDark"),
+    (0x01010435, "colorAccent"),
+    (0x01010436, "nestedScrollingEnabled"),
+    (0x01010437, "windowEnterTransition"),
+    (0x01010438, "windowExitTransition"),
+    (0x01010439, "windowSharedElementEnterTransition"),
+    (0x0101043a, "windowSharedElementExitTransition"),
+    (0x0101043b, "windowAllowReturnTransitionOverlap"),
+    (0x0101043c, "windowAllowEnterTransitionOverlap"),
+    (0x0101043d, "sessionService"),
+    (0x0101043e, "stackViewStyle"),
+    (0x0101043f, "switchStyle"),
+    (0x01010440, "elevation"),
+    (0x01010441, "excludeId"),
+    (0x01010442, "excludeClass"),
+    (0x01010443, "hideOnContentScroll"),
+    (0x01010444, "actionOverflowMenuStyle"),
+    (0x01010445, "documentLaunchMode"),
+    (0x01010446, "maxRecents"),
+    (0x01010447, "autoRemoveFromRecents"),
+    (0x01010448, "stateListAnimator"),
+    (0x01010449, "toId"),
+    (0x0101044a, "fromId"),
+    (0x0101044b, "reversible"),
+    (0x0101044c, "splitTrack"),
+    (0x0101044d, "targetName"),
+    (0x0101044e, "excludeName"),
+    (0x0101044f, "matchOrder"),
+    (0x01010450, "windowDrawsSystemBarBackgrounds"),
+    (0x01010451, "statusBarColor"),
+    (0x01010452, "navigationBarColor"),
+    (0x01010453, "contentInsetStart"),
+    (0x01010454, "contentInsetEnd"),
+    (0x01010455, "contentInsetLeft"),
+    (0x01010456, "contentInsetRight"),
+    (0x01010457, "paddingMode"),
+    (0x01010458, "layout_rowWeight"),
+    (0x01010459, "layout_columnWeight"),
+    (0x0101045a, "translateX"),
+    (0x0101045b, "translateY"),
+    (0x0101045c, "selectableItemBackgroundBorderless"),
+    (0x0101045d, "elegantTextHeight"),
+    (0x0101045e, "searchKeyphraseId"),
+    (0x0101045f, "searchKeyphrase"),
+    (0x01010460, "searchKeyphraseSupportedLocales"),
+    (0x01010461, "windowTransitionBackgroundFadeDuration"),
+    (0x01010462, "overlapAnchor"),
+    (0x01010463, "progressTint"),
+    (0x01010464, "progressTintMode"),
+    (0x01010465, "progressBackgroundTint"),
+    (0x01010466, "progressBackgroundTintMode"),
+    (0x01010467, "secondaryProgressTint"),
+    (0x01010468, "secondaryProgressTintMode"),
+    (0x01010469, "indeterminateTint"),
+    (0x0101046a, "indeterminateTintMode"),
+    (0x0101046b, "backgroundTint"),
+    (0x0101046c, "backgroundTintMode"),
+    (0x0101046d, "foregroundTint"),
+    (0x0101046e, "foregroundTintMode"),
+    (0x0101046f, "buttonTint"),
+    (0x01010470, "buttonTintMode"),
+    (0x01010471, "thumbTint"),
+    (0x01010472, "thumbTintMode"),
+    (0x01010473, "fullBackupOnly"),
+    (0x01010474, "propertyXName"),
+    (0x01010475, "propertyYName"),
+    (0x01010476, "relinquishTaskIdentity"),
+    (0x01010477, "tileModeX"),
+    (0x01010478, "tileModeY"),
+    (0x01010479, "actionModeShareDrawable"),
+    (0x0101047a, "actionModeFindDrawable"),
+    (0x0101047b, "actionModeWebSearchDrawable"),
+    (0x0101047c, "transitionVisibilityMode"),
+    (0x0101047d, "minimumHorizontalAngle"),
+    (0x0101047e, "minimumVerticalAngle"),
+    (0x0101047f, "maximumAngle"),
+    (0x01010480, "searchViewStyle"),
+    (0x01010481, "closeIcon"),
+    (0x01010482, "goIcon"),
+    (0x01010483, "searchIcon"),
+    (0x01010484, "voiceIcon"),
+    (0x01010485, "commitIcon"),
+    (0x01010486, "suggestionRowLayout"),
+    (0x01010487, "queryBackground"),
+    (0x01010488, "submitBackground"),
+    (0x01010489, "buttonBarPositiveButtonStyle"),
+    (0x0101048a, "buttonBarNeutralButtonStyle"),
+    (0x0101048b, "buttonBarNegativeButtonStyle"),
+    (0x0101048c, "popupElevation"),
+    (0x0101048d, "actionBarPopupTheme"),
+    (0x0101048e, "multiArch"),
+    (0x0101048f, "touchscreenBlocksFocus"),
+    (0x01010490, "windowElevation"),
+    (0x01010491, "launchTaskBehindTargetAnimation"),
+    (0x01010492, "launchTaskBehindSourceAnimation"),
+    (0x01010493, "restrictionType"),
+    (0x01010494, "dayOfWeekBackground"),
+    (0x01010495, "dayOfWeekTextAppearance"),
+    (0x01010496, "headerMonthTextAppearance"),
+    (0x01010497, "headerDayOfMonthTextAppearance"),
+    (0x01010498, "headerYearTextAppearance"),
+    (0x01010499, "yearListItemTextAppearance"),
+    (0x0101049a, "yearListSelectorColor"),
+    (0x0101049b, "calendarTextColor"),
+    (0x0101049c, "recognitionService"),
+    (0x0101049d, "timePickerStyle"),
+    (0x0101049e, "timePickerDialogTheme"),
+    (0x0101049f, "headerTimeTextAppearance"),
+    (0x010104a0, "headerAmPmTextAppearance"),
+    (0x010104a1, "numbersTextColor"),
+    (0x010104a2, "numbersBackgroundColor"),
+    (0x010104a3, "numbersSelectorColor"),
+    (0x010104a4, "amPmTextColor"),
+    (0x010104a5, "amPmBackgroundColor"),
+    (0x010104a6, "searchKeyphraseRecognitionFlags"),
+    (0x010104a7, "checkMarkTint"),
+    (0x010104a8, "checkMarkTintMode"),
+    (0x010104a9, "popupTheme"),
+    (0x010104aa, "toolbarStyle"),
+    (0x010104ab, "windowClipToOutline"),
+    (0x010104ac, "datePickerDialogTheme"),
+    (0x010104ad, "showText"),
+    (0x010104ae, "windowReturnTransition"),
+    (0x010104af, "windowReenterTransition"),
+    (0x010104b0, "windowSharedElementReturnTransition"),
+    (0x010104b1, "windowSharedElementReenterTransition"),
+    (0x010104b2, "resumeWhilePausing"),
+    (0x010104b3, "datePickerMode"),
+    (0x010104b4, "timePickerMode"),
+    (0x010104b5, "inset"),
+    (0x010104b6, "letterSpacing"),
+    (0x010104b7, "fontFeatureSettings"),
+    (0x010104b8, "outlineProvider"),
+    (0x010104b9, "contentAgeHint"),
+    (0x010104ba, "country"),
+    (0x010104bb, "windowSharedElementsUseOverlay"),
+    (0x010104bc, "reparent"),
+    (0x010104bd, "reparentWithOverlay"),
+    (0x010104be, "ambientShadowAlpha"),
+    (0x010104bf, "spotShadowAlpha"),
+    (0x010104c0, "navigationIcon"),
+    (0x010104c1, "navigationContentDescription"),
+    (0x010104c2, "fragmentExitTransition"),
+    (0x010104c3, "fragmentEnterTransition"),
+    (0x010104c4, "fragmentSharedElementEnterTransition"),
+    (0x010104c5, "fragmentReturnTransition"),
+    (0x010104c6, "fragmentSharedElementReturnTransition"),
+    (0x010104c7, "fragmentReenterTransition"),
+    (0x010104c8, "fragmentAllowEnterTransitionOverlap"),
+    (0x010104c9, "fragmentAllowReturnTransitionOverlap"),
+    (0x010104ca, "patternPathData"),
+    (0x010104cb, "strokeAlpha"),
+    (0x010104cc, "fillAlpha"),
+    (0x010104cd, "windowActivityTransitions"),
+    (0x010104ce, "colorEdgeEffect"),
+    (0x010104cf, "resizeClip"),
+    (0x010104d0, "collapseContentDescription"),
+    (0x010104d1, "accessibilityTraversalBefore"),
+    (0x010104d2, "accessibilityTraversalAfter"),
+    (0x010104d3, "dialogPreferredPadding"),
+    (0x010104d4, "searchHintIcon"),
+    (0x010104d5, "revisionCode"),
+    (0x010104d6, "drawableTint"),
+    (0x010104d7, "drawableTintMode"),
+    (0x010104d8, "fraction"),
+    (0x010104d9, "trackTint"),
+    (0x010104da, "trackTintMode"),
+    (0x010104db, "start"),
+    (0x010104dc, "end"),
+    (0x010104dd, "breakStrategy"),
+    (0x010104de, "hyphenationFrequency"),
+    (0x010104df, "allowUndo"),
+    (0x010104e0, "windowLightStatusBar"),
+    (0x010104e1, "numbersInnerTextColor"),
+    (0x010104e2, "colorBackgroundFloating"),
+    (0x010104e3, "titleTextColor"),
+    (0x010104e4, "subtitleTextColor"),
+    (0x010104e5, "thumbPosition"),
+    (0x010104e6, "scrollIndicators"),
+    (0x010104e7, "contextClickable"),
+    (0x010104e8, "fingerprintAuthDrawable"),
+    (0x010104e9, "logoDescription"),
+    (0x010104ea, "extractNativeLibs"),
+    (0x010104eb, "fullBackupContent"),
+    (0x010104ec, "usesCleartextTraffic"),
+    (0x010104ed, "lockTaskMode"),
+    (0x010104ee, "autoVerify"),
+    (0x010104ef, "showForAllUsers"),
+    (0x010104f0, "supportsAssist"),
+    (0x010104f1, "supportsLaunchVoiceAssistFromKeyguard"),
+    (0x010104f2, "listMenuViewStyle"),
+    (0x010104f3, "subMenuArrow"),
+    (0x010104f4, "defaultWidth"),
+    (0x010104f5, "defaultHeight"),
+    (0x010104f6, "resizeableActivity"),
+    (0x010104f7, "supportsPictureInPicture"),
+    (0x010104f8, "titleMargin"),
+    (0x010104f9, "titleMarginStart"),
+    (0x010104fa, "titleMarginEnd"),
+    (0x010104fb, "titleMarginTop"),
+    (0x010104fc, "titleMarginBottom"),
+    (0x010104fd, "maxButtonHeight"),
+    (0x010104fe, "buttonGravity"),
+    (0x010104ff, "collapseIcon"),
+    (0x01010500, "level"),
+    (0x01010501, "contextPopupMenuStyle"),
+    (0x01010502, "textAppearancePopupMenuHeader"),
+    (0x01010503, "windowBackgroundFallback"),
+    (0x01010504, "defaultToDeviceProtectedStorage"),
+    (0x01010505, "directBootAware"),
+    (0x01010527, "networkSecurityConfig"),
+    (0x0101052c, "roundIcon"),
+    (0x01010549, "splitName"),
+    (0x0101054c, "targetSandboxVersion"),
+    (0x0101055b, "isFeatureSplit"),
+    (0x01010572, "compileSdkVersion"),
+    (0x01010573, "compileSdkVersionCodename"),
+    (0x0101057a, "appComponentFactory"),
+    (0x01010591, "isSplitRequired"),
+    (0x01010595, "usesNonSdkApi"),
+    (0x01010599, "foregroundServiceType"),
+    (0x01010603, "requestLegacyExternalStorage"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_is_sorted_without_duplicates() {
+        assert!(ATTRIBUTES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        let mut names: Vec<&str> = ATTRIBUTES.iter().map(|&(_, name)| name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), ATTRIBUTES.len());
+        assert!(ATTRIBUTES.iter().all(|&(id, _)| id >> 16 == 0x0101));
+    }
+
+    #[test]
+    fn framework_attributes_are_named() {
+        assert_eq!(attribute_name(0x01010000), Some("theme"));
+        assert_eq!(attribute_name(0x01010003), Some("name"));
+        assert_eq!(attribute_name(0x0101021b), Some("versionCode"));
+        assert_eq!(attribute_name(0x01010270), Some("targetSdkVersion"));
+        assert_eq!(attribute_name(0x010103a9), Some("isolatedProcess"));
+        assert_eq!(attribute_name(0x01010505), Some("directBootAware"));
+        assert_eq!(attribute_name(0x01010549), Some("splitName"));
+        assert_eq!(attribute_name(0x0101054c), Some("targetSandboxVersion"));
+        assert_eq!(attribute_name(0x0101055b), Some("isFeatureSplit"));
+        assert_eq!(attribute_name(0x01010572), Some("compileSdkVersion"));
+        assert_eq!(attribute_name(0x01010573), Some("compileSdkVersionCodename"));
+        assert_eq!(attribute_name(0x01010591), Some("isSplitRequired"));
+        assert_eq!(attribute_name(0x01010595), Some("usesNonSdkApi"));
+        assert_eq!(attribute_name(0x01010599), Some("foregroundServiceType"));
+        assert_eq!(attribute_name(0x01010603), Some("requestLegacyExternalStorage"));
+    }
+
+    #[test]
+    fn other_ids_are_unknown() {
+        assert_eq!(attribute_name(0), None);
+        assert_eq!(attribute_name(0x0100ffff), None);
+        assert_eq!(attribute_name(0x7f010000), None);
+        assert_eq!(attribute_name(0xffffffff), None);
+    }
+}
@@ -1,597 +1,1502 @@
 extern crate byteorder;
 
-use std::io::Cursor;
-use std::io::SeekFrom;
-use std::io::Seek;
-use self::byteorder::{ReadBytesExt, LittleEndian, BigEndian};
-use std::u16;
-use std::vec::*;
-
-#[derive(Debug)]
-enum RES_TYPE {
-    RES_NULL_TYPE,
-    RES_STRING_POOL_TYPE,
-    RES_TABLE_TYPE,
-    RES_XML_TYPE,
-
-    // Chunk types in RES_XML_TYPE
-    RES_XML_FIRST_CHUNK_TYPE,
-    RES_XML_START_NAMESPACE_TYPE,
-    RES_XML_END_NAMESPACE_TYPE,
-    RES_XML_START_ELEMENT_TYPE,
-    RES_XML_END_ELEMENT_TYPE,
-    RES_XML_CDATA_TYPE,
-    RES_XML_LAST_CHUNK_TYPE,
-    // This contains a uint32_t array mapping strings in the string
-    // pool back to resource identifiers.  It is optional.
-    RES_XML_RESOURCE_MAP_TYPE,
-
-    // Chunk types in RES_TABLE_TYPE
-    RES_TABLE_PACKAGE_TYPE,
-    RES_TABLE_TYPE_TYPE,
-    RES_TABLE_TYPE_SPEC_TYPE,
-}
-// enum {
-// Contains no data.
-// TYPE_NULL = 0x00,
-// The 'data' holds a ResTable_ref, a reference to another resource
-// table entry.
-// TYPE_REFERENCE = 0x01,
-// The 'data' holds an attribute resource identifier.
-// TYPE_ATTRIBUTE = 0x02,
-// The 'data' holds an index into the containing resource table's
-// global value string pool.
-// TYPE_STRING = 0x03,
-// The 'data' holds a single-precision floating point number.
-// TYPE_FLOAT = 0x04,
-// The 'data' holds a complex number encoding a dimension value,
-// such as "100in".
-// TYPE_DIMENSION = 0x05,
-// The 'data' holds a complex number encoding a fraction of a
-// container.
-// TYPE_FRACTION = 0x06,
-//
-// Beginning of integer flavors...
-// TYPE_FIRST_INT = 0x10,
-//
-// The 'data' is a raw integer value of the form n..n.
-// TYPE_INT_DEC = 0x10,
-// The 'data' is a raw integer value of the form 0xn..n.
-// TYPE_INT_HEX = 0x11,
-// The 'data' is either 0 or 1, for input "false" or "true" respectively.
-// TYPE_INT_BOOLEAN = 0x12,
-//
-// Beginning of color integer flavors...
-// TYPE_FIRST_COLOR_INT = 0x1c,
-//
-// The 'data' is a raw integer value of the form #aarrggbb.
-// TYPE_INT_COLOR_ARGB8 = 0x1c,
-// The 'data' is a raw integer value of the form #rrggbb.
-// TYPE_INT_COLOR_RGB8 = 0x1d,
-// The 'data' is a raw integer value of the form #argb.
-// TYPE_INT_COLOR_ARGB4 = 0x1e,
-// The 'data' is a raw integer value of the form #rgb.
-// TYPE_INT_COLOR_RGB4 = 0x1f,
-//
-// ...end of integer flavors.
-// TYPE_LAST_COLOR_INT = 0x1f,
-//
-// ...end of integer flavors.
-// TYPE_LAST_INT = 0x1f
-// };
-//   enum {
-// Where the unit type information is.  This gives us 16 possible
-// types, as defined below.
-// COMPLEX_UNIT_SHIFT = 0,
-// COMPLEX_UNIT_MASK = 0xf,
-//
-// TYPE_DIMENSION: Value is raw pixels.
-// COMPLEX_UNIT_PX = 0,
-// TYPE_DIMENSION: Value is Device Independent Pixels.
-// COMPLEX_UNIT_DIP = 1,
-// TYPE_DIMENSION: Value is a Scaled device independent Pixels.
-// COMPLEX_UNIT_SP = 2,
-// TYPE_DIMENSION: Value is in points.
-// COMPLEX_UNIT_PT = 3,
-// TYPE_DIMENSION: Value is in inches.
-// COMPLEX_UNIT_IN = 4,
-// TYPE_DIMENSION: Value is in millimeters.
-// COMPLEX_UNIT_MM = 5,
-//
-// TYPE_FRACTION: A basic fraction of the overall size.
-// COMPLEX_UNIT_FRACTION = 0,
-// TYPE_FRACTION: A fraction of the parent size.
-// COMPLEX_UNIT_FRACTION_PARENT = 1,
-//
-// Where the radix information is, telling where the decimal place
-// appears in the mantissa.  This give us 4 possible fixed point
-// representations as defined below.
-// COMPLEX_RADIX_SHIFT = 4,
-// COMPLEX_RADIX_MASK = 0x3,
-//
-// The mantissa is an integral number -- i.e., 0xnnnnnn.0
-// COMPLEX_RADIX_23p0 = 0,
-// The mantissa magnitude is 16 bits -- i.e, 0xnnnn.nn
-// COMPLEX_RADIX_16p7 = 1,
-// The mantissa magnitude is 8 bits -- i.e, 0xnn.nnnn
-// COMPLEX_RADIX_8p15 = 2,
-// The mantissa magnitude is 0 bits -- i.e, 0x0.nnnnnn
-// COMPLEX_RADIX_0p23 = 3,
-//
-// Where the actual value is.  This gives us 23 bits of
-// precision.  The top bit is the sign.
-// COMPLEX_MANTISSA_SHIFT = 8,
-// COMPLEX_MANTISSA_MASK = 0xffffff
-// };
-
-
-struct res_chunk_header {
-    position: u32,
-    res_type: u16,
-    res_enum_type: RES_TYPE,
-    header_size: u16,
-    total_size: u32,
-}
-impl res_chunk_header {
-    fn new(reader: &mut Cursor<&Vec<u8>>) -> res_chunk_header {
-        let pos = reader.position();
-        let c_type = reader.read_u16::<LittleEndian>().unwrap();
-        let h_size = reader.read_u16::<LittleEndian>().unwrap();
-        let t_size = reader.read_u32::<LittleEndian>().unwrap();
-
-        let e_type = match c_type {
-            0x0000 => RES_TYPE::RES_NULL_TYPE,
-            0x0001 => RES_TYPE::RES_STRING_POOL_TYPE,
-            0x0002 => RES_TYPE::RES_TABLE_TYPE,
-            0x0003 => RES_TYPE::RES_XML_TYPE,
-            0x0100 => RES_TYPE::RES_XML_START_NAMESPACE_TYPE,
-            0x0101 => RES_TYPE::RES_XML_END_NAMESPACE_TYPE,
-            0x0102 => RES_TYPE::RES_XML_START_ELEMENT_TYPE,
-            0x0103 => RES_TYPE::RES_XML_END_ELEMENT_TYPE,
-            0x0180 => RES_TYPE::RES_XML_RESOURCE_MAP_TYPE,
-            0x0200 => RES_TYPE::RES_TABLE_PACKAGE_TYPE,
-            0x0201 => RES_TYPE::RES_TABLE_TYPE_TYPE,
-            0x0202 => RES_TYPE::RES_TABLE_TYPE_SPEC_TYPE,
-            _ => RES_TYPE::RES_NULL_TYPE,
-
-        };
-
-        return res_chunk_header {
-            position: pos as u32,
-            res_type: c_type,
-            res_enum_type: e_type,
-            header_size: h_size,
-            total_size: t_size,
-        };
-    }
+use self::byteorder::{ByteOrder, LittleEndian};
+use arsc::{EntryValue, ResourceTable};
+use attrs;
+use std::fmt;
+
+// Chunk types, as in frameworks/base/libs/androidfw/ResourceTypes.h
+pub const RES_STRING_POOL_TYPE: u16 = 0x0001;
+const RES_XML_TYPE: u16 = 0x0003;
+
+// Chunk types in RES_XML_TYPE
+const RES_XML_START_NAMESPACE_TYPE: u16 = 0x0100;
+const RES_XML_START_ELEMENT_TYPE: u16 = 0x0102;
+const RES_XML_END_ELEMENT_TYPE: u16 = 0x0103;
+const RES_XML_CDATA_TYPE: u16 = 0x0104;
+// This contains a uint32_t array mapping strings in the string
+// pool back to resource identifiers.  It is optional.
+const RES_XML_RESOURCE_MAP_TYPE: u16 = 0x0180;
+
+// Res_value data types
+const TYPE_NULL: u8 = 0x00;
+const TYPE_REFERENCE: u8 = 0x01;
+const TYPE_ATTRIBUTE: u8 = 0x02;
+const TYPE_STRING: u8 = 0x03;
+const TYPE_FLOAT: u8 = 0x04;
+const TYPE_DIMENSION: u8 = 0x05;
+const TYPE_FRACTION: u8 = 0x06;
+const TYPE_DYNAMIC_REFERENCE: u8 = 0x07;
+const TYPE_DYNAMIC_ATTRIBUTE: u8 = 0x08;
+const TYPE_INT_DEC: u8 = 0x10;
+const TYPE_INT_HEX: u8 = 0x11;
+const TYPE_INT_BOOLEAN: u8 = 0x12;
+const TYPE_INT_COLOR_ARGB8: u8 = 0x1c;
+const TYPE_INT_COLOR_RGB8: u8 = 0x1d;
+const TYPE_INT_COLOR_ARGB4: u8 = 0x1e;
+const TYPE_INT_COLOR_RGB4: u8 = 0x1f;
+
+// TYPE_NULL data telling an explicitly empty value from an undefined one
+const DATA_NULL_EMPTY: u32 = 1;
+
+// Layout of the complex numbers of dimensions and fractions
+const COMPLEX_UNIT_SHIFT: u32 = 0;
+const COMPLEX_UNIT_MASK: u32 = 0xf;
+const COMPLEX_RADIX_SHIFT: u32 = 4;
+const COMPLEX_RADIX_MASK: u32 = 0x3;
+const COMPLEX_MANTISSA_SHIFT: u32 = 8;
+const COMPLEX_MANTISSA_MASK: u32 = 0xffffff;
+
+/// Scale of the mantissa, which is still shifted left by 8, for each
+/// radix: 23p0, 16p7, 8p15 and 0p23
+const RADIX_MULTS: [f32; 4] = [1.0 / (1 << 8) as f32,
+                               1.0 / (1 << 15) as f32,
+                               1.0 / (1 << 23) as f32,
+                               1.0 / (1u32 << 31) as f32];
+const DIMENSION_UNITS: [&str; 6] = ["px", "dip", "sp", "pt", "in", "mm"];
+const FRACTION_UNITS: [&str; 2] = ["%", "%p"];
+
+const UTF8_FLAG: u32 = 1 << 8;
+/// How many aliases are followed when inlining a value
+const MAX_ALIAS_DEPTH: usize = 16;
+pub const NO_ENTRY: u32 = 0xFFFFFFFF;
+
+/// A decoding failure and the file offset it occurred at
+#[derive(Debug, Clone, PartialEq)]
+pub struct AxmlError {
+    offset: usize,
+    message: String,
 }
-struct AXMLNode_element_attributes {
-    ns: u32,
-    name: u32,
-    raw_value: u32,
-    size: u16,
-    zero: u8,
-    data_type: u8,
-    data: u32,
+
+/// The header every chunk of a binary XML or resource table starts with
+#[derive(Debug, Clone, Copy)]
+pub struct Chunk {
+    pub offset: usize,
+    pub chunk_type: u16,
+    pub header_size: usize,
+    pub size: usize,
 }
 
-enum AXMLNode_content {
-    AXMLNode_namespace {
-        lineNumber: u32,
-        comment: u32,
-        prefix: u32,
-        uri: u32,
-    },
+/// A ResStringPool
+#[derive(Debug, Clone, Default)]
+pub struct StringPool {
+    strings: Vec<String>,
+    /// The spans of the first strings, as many as have styles
+    styles: Vec<Vec<Span>>,
+}
 
-    AXMLNode_element {
-        lineNumber: u32,
-        comment: u32,
-        ns: u32,
-        name: u32,
-        attribute_start: u16,
-        attribute_size: u16,
-        attribute_count: u16,
-        id_index: u16,
-        class_index: u16,
-        style_index: u16,
-        attributes: Vec<AXMLNode_element_attributes>,
-    },
-    AXMLNode_element_end {
-        lineNumber: u32,
-        comment: u32,
-        ns: u32,
-        name: u32,
-    },
+/// A ResStringPool_span: markup applied to a range of a styled string
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    tag: String,
+    first_char: u32,
+    last_char: u32,
 }
 
-struct AXMLNode {
-    children: Vec<AXMLNode>,
-    content: Option<AXMLNode_content>,
-    closing: Option<AXMLNode_content>,
-    node_type: RES_TYPE,
+/// A decoded binary XML file, such as a compiled AndroidManifest.xml
+#[derive(Debug, Clone)]
+pub struct Document {
+    root: Element,
 }
 
-struct res_chunk_root {
-    header: res_chunk_header,
-    strings: Vec<String>,
-    resource_map: Vec<u32>,
-    axml: Vec<AXMLNode>,
+#[derive(Debug, Clone, PartialEq)]
+pub struct Namespace {
+    prefix: String,
+    uri: String,
 }
 
-impl res_chunk_root {
-    fn new(mut reader: &mut Cursor<&Vec<u8>>) -> res_chunk_root {
+#[derive(Debug, Clone)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
 
-        let h = res_chunk_header::new(reader);
+#[derive(Debug, Clone)]
+pub struct Element {
+    line_number: u32,
+    namespace: Option<String>,
+    name: String,
+    /// The xmlns declarations made on this element
+    namespaces: Vec<Namespace>,
+    attributes: Vec<Attribute>,
+    children: Vec<Node>,
+}
 
-        let mut root = res_chunk_root {
-            header: h,
-            strings: vec![],
-            resource_map: vec![],
-            axml: vec![],
-        };
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    namespace: Option<String>,
+    name: String,
+    /// The attribute resource id from the resource map, e.g. 0x01010003
+    /// for android:name
+    resource_id: Option<u32>,
+    /// The original string, which aapt keeps for string values only
+    raw_value: Option<String>,
+    value: ResValue,
+}
 
-        while reader.position() < root.header.total_size as u64 {
-            let node = res_chunk_header::new(&mut reader);
-            match node.res_enum_type {
-                RES_TYPE::RES_STRING_POOL_TYPE => root.read_string_pool(reader, node),
-                RES_TYPE::RES_XML_RESOURCE_MAP_TYPE => root.read_resource_map(reader, node),
-                RES_TYPE::RES_XML_START_NAMESPACE_TYPE => root.read_root_node(reader, node),
-                _ => panic!("Unimplemented AXML Tag!"),
+/// A typed Res_value. Displays the way aapt dumps values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResValue {
+    /// Undefined, `@null`
+    Null,
+    /// Explicitly empty, `@empty`
+    Empty,
+    /// A resource id. Dynamic references of shared libraries are included.
+    Reference(u32),
+    /// A theme attribute id
+    Attribute(u32),
+    String(String),
+    /// A string with spans, as markup such as `Tap <b>here</b>`. The text
+    /// is escaped for XML.
+    StyledString(String),
+    Float(f32),
+    Dimension(Complex),
+    Fraction(Complex),
+    IntDec(i32),
+    IntHex(u32),
+    Boolean(bool),
+    /// All colors are stored expanded to #aarrggbb
+    ColorArgb8(u32),
+    ColorRgb8(u32),
+    ColorArgb4(u32),
+    ColorRgb4(u32),
+    Unknown {
+        data_type: u8,
+        data: u32,
+    },
+}
 
-            }
-        }
+/// The fixed point number of a dimension or fraction, with its unit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex(u32);
 
-        return root;
+impl AxmlError {
+    pub fn new(offset: usize, message: String) -> AxmlError {
+        return AxmlError {
+            offset: offset,
+            message: message,
+        };
     }
 
-    fn read_namespace_contents(&mut self,
-                               mut reader: &mut Cursor<&Vec<u8>>,
-                               node: res_chunk_header)
-                               -> AXMLNode {
+    pub fn offset(&self) -> usize {
+        return self.offset;
+    }
 
-        let lineNumber: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let comment: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let prefix: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let uri: u32 = reader.read_u32::<LittleEndian>().unwrap();
+    pub fn message(&self) -> &str {
+        return &self.message;
+    }
+}
 
-        let mut namespace = AXMLNode_content::AXMLNode_namespace {
-            lineNumber: lineNumber,
-            comment: comment,
-            prefix: prefix,
-            uri: uri,
-        };
+impl fmt::Display for AxmlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at 0x{:x}: {}", self.offset, self.message)
+    }
+}
 
-        let mut node = AXMLNode {
-            children: vec![],
-            closing: None,
-            content: Some(namespace),
-            node_type: node.res_enum_type,
-        };
-        return node;
+impl StringPool {
+    /// The string at `idx`, None for 0xFFFFFFFF and indices out of range
+    pub fn string(&self, idx: u32) -> Option<&str> {
+        return self.strings.get(idx as usize).map(|s| s.as_str());
     }
 
-    fn read_namespace_node(&mut self,
-                           mut reader: &mut Cursor<&Vec<u8>>,
-                           node: res_chunk_header)
-                           -> AXMLNode {
+    pub fn len(&self) -> usize {
+        return self.strings.len();
+    }
 
+    pub fn is_empty(&self) -> bool {
+        return self.strings.is_empty();
+    }
 
-        let mut root: AXMLNode = self.read_namespace_contents(reader, node);
+    /// The spans of the string at `idx`, empty for plain strings
+    pub fn spans(&self, idx: u32) -> &[Span] {
+        return self.styles.get(idx as usize).map(|spans| spans.as_slice()).unwrap_or(&[]);
+    }
 
-        loop {
-            let node = res_chunk_header::new(&mut reader);
-            let nextNode: u64 = (node.position + node.total_size) as u64;
-            match node.res_enum_type {
-                RES_TYPE::RES_XML_START_NAMESPACE_TYPE => {
-                    let mut namespace_node = self.read_namespace_node(reader, node);
-                    root.children.push(namespace_node);
-                }
-                RES_TYPE::RES_XML_START_ELEMENT_TYPE => {
-                    let mut element_node = self.read_element_node(reader, node);
-                    root.children.push(element_node);
-                }
-                RES_TYPE::RES_XML_END_NAMESPACE_TYPE => {
-                    let endnode = self.read_namespace_contents(reader, node);
-                    root.closing = Some(endnode.content.unwrap());
-                    break;
+    /// The string at `idx` with its spans turned back into markup, e.g.
+    /// `Tap <b>here</b>`. The text is escaped for XML.
+    pub fn styled_string(&self, idx: u32) -> Option<String> {
+        let string = self.string(idx)?;
+        let spans = self.spans(idx);
+        let units: Vec<u16> = string.encode_utf16().collect();
+        let mut out = String::new();
+        let mut open: Vec<&Span> = vec![];
+        let mut start = 0;
+        for position in 0..units.len() + 1 {
+            let closing = open.iter().any(|span| span.last_char as usize + 1 == position);
+            let opening = spans.iter().any(|span| span.first_char as usize == position);
+            if !closing && !opening && position < units.len() {
+                continue;
+            }
+            out.push_str(&escape(&String::from_utf16_lossy(&units[start..position])));
+            start = position;
+
+            // Close inner spans first, spans that were not properly nested
+            // are closed together with the one enclosing them
+            if let Some(i) = open.iter().position(|span| span.last_char as usize + 1 == position) {
+                for span in open.drain(i..).rev() {
+                    out.push_str(&format!("</{}>", span.name()));
                 }
-                _ => {}
+            }
+            for span in spans.iter().filter(|span| span.first_char as usize == position) {
+                out.push_str(&span.start_tag());
+                open.push(span);
             }
         }
-        return root;
-    }
-
-    fn read_element_end_node(&mut self,
-                             mut reader: &mut Cursor<&Vec<u8>>,
-                             node: res_chunk_header)
-                             -> AXMLNode_content {
-        let lineNumber: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let comment: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let ns: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let name: u32 = reader.read_u32::<LittleEndian>().unwrap();
-
-        let end_element = AXMLNode_content::AXMLNode_element_end {
-            lineNumber: lineNumber,
-            comment: comment,
-            ns: ns,
-            name: name,
-        };
-        return end_element;
+        for span in open.iter().rev() {
+            out.push_str(&format!("</{}>", span.name()));
+        }
+        return Some(out);
+    }
+}
+
+impl Span {
+    /// The tag name followed by its attributes, `b` or
+    /// `a;href=http://example.com`
+    pub fn tag(&self) -> &str {
+        return &self.tag;
+    }
+
+    /// Index of the first UTF-16 code unit the span covers
+    pub fn first_char(&self) -> u32 {
+        return self.first_char;
     }
 
-    fn read_element_attribute(&mut self,
-                              mut reader: &mut Cursor<&Vec<u8>>)
-                              -> AXMLNode_element_attributes {
+    /// Index of the last UTF-16 code unit the span covers
+    pub fn last_char(&self) -> u32 {
+        return self.last_char;
+    }
 
-        let ns: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let name: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let raw_value: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let size: u16 = reader.read_u16::<LittleEndian>().unwrap();
-        let data_type = reader.read_u16::<BigEndian>().unwrap() as u8;
-        let data: u32 = reader.read_u32::<LittleEndian>().unwrap();
+    pub fn name(&self) -> &str {
+        return self.tag.split(';').next().unwrap_or("");
+    }
 
-        return AXMLNode_element_attributes {
-            ns: ns,
-            name: name,
-            raw_value: raw_value,
-            size: size,
-            zero: 0,
-            data_type: data_type,
-            data: data,
-        };
+    /// The attributes of the tag as name and value pairs
+    pub fn attributes(&self) -> Vec<(&str, &str)> {
+        return self.tag
+            .split(';')
+            .skip(1)
+            .map(|attribute| {
+                match attribute.find('=') {
+                    Some(i) => (&attribute[..i], &attribute[i + 1..]),
+                    None => (attribute, ""),
+                }
+            })
+            .collect();
     }
 
-    fn read_element_node(&mut self,
-                         mut reader: &mut Cursor<&Vec<u8>>,
-                         node: res_chunk_header)
-                         -> AXMLNode {
-
-        let pos = reader.position();
-        let lineNumber: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let comment: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let ns: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let name: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let attribute_start: u16 = reader.read_u16::<LittleEndian>().unwrap();
-        let attribute_size: u16 = reader.read_u16::<LittleEndian>().unwrap();
-        let attribute_count: u16 = reader.read_u16::<LittleEndian>().unwrap();
-        let id_index: u16 = reader.read_u16::<LittleEndian>().unwrap();
-        let class_index: u16 = reader.read_u16::<LittleEndian>().unwrap();
-        let style_index: u16 = reader.read_u16::<LittleEndian>().unwrap();
-
-        if attribute_size != 0x14 {
-            panic!("Attribute size not where it is expected. please report as bug");
+    fn start_tag(&self) -> String {
+        let mut tag = format!("<{}", self.name());
+        for (name, value) in self.attributes() {
+            tag.push_str(&format!(" {}=\"{}\"", name, escape(value)));
         }
+        tag.push('>');
+        return tag;
+    }
+}
 
+impl Document {
+    pub fn root(&self) -> &Element {
+        return &self.root;
+    }
+}
 
-        let mut attrs: Vec<AXMLNode_element_attributes> = vec![];
+impl Namespace {
+    pub fn prefix(&self) -> &str {
+        return &self.prefix;
+    }
 
-        for i in 0..attribute_count {
-            let attr = self.read_element_attribute(reader);
-            attrs.push(attr);
-        }
+    pub fn uri(&self) -> &str {
+        return &self.uri;
+    }
+}
 
-        let mut element = AXMLNode_content::AXMLNode_element {
-            lineNumber: lineNumber,
-            comment: comment,
-            ns: ns,
-            name: name,
-            attribute_start: attribute_start,
-            attribute_size: attribute_size,
-            attribute_count: attribute_count,
-            id_index: id_index,
-            class_index: class_index,
-            style_index: style_index,
-            attributes: attrs,
-        };
+impl Element {
+    pub fn line_number(&self) -> u32 {
+        return self.line_number;
+    }
 
-        let mut root = AXMLNode {
-            children: vec![],
-            closing: None,
-            content: Some(element),
-            node_type: node.res_enum_type,
-        };
+    /// The namespace URI, None for elements without one
+    pub fn namespace(&self) -> Option<&str> {
+        return self.namespace.as_deref();
+    }
 
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
 
+    pub fn namespaces(&self) -> &[Namespace] {
+        return &self.namespaces;
+    }
 
-        let nextNode: u64 = (node.position + node.total_size) as u64;
-        reader.seek(SeekFrom::Start(nextNode));
+    pub fn attributes(&self) -> &[Attribute] {
+        return &self.attributes;
+    }
 
-        loop {
-            let node = res_chunk_header::new(&mut reader);
-            let nextNode: u64 = (node.position + node.total_size) as u64;
+    /// Looks up an attribute by namespace URI and name
+    pub fn attribute(&self, namespace: Option<&str>, name: &str) -> Option<&Attribute> {
+        return self.attributes.iter().find(|a| a.namespace() == namespace && a.name == name);
+    }
 
-            match node.res_enum_type {
-                RES_TYPE::RES_XML_START_NAMESPACE_TYPE => {
-                    let mut namespace_node = self.read_namespace_node(reader, node);
-                    root.children.push(namespace_node);
-                }
+    pub fn children(&self) -> &[Node] {
+        return &self.children;
+    }
 
-                RES_TYPE::RES_XML_START_ELEMENT_TYPE => {
-                    let mut element_node = self.read_element_node(reader, node);
-                    root.children.push(element_node);
+    /// The child elements, text left out
+    pub fn elements(&self) -> Vec<&Element> {
+        return self.children
+            .iter()
+            .filter_map(|node| {
+                match *node {
+                    Node::Element(ref element) => Some(element),
+                    Node::Text(_) => None,
                 }
+            })
+            .collect();
+    }
+}
 
-                RES_TYPE::RES_XML_END_ELEMENT_TYPE => {
-                    let end_element = self.read_element_end_node(reader, node);
-                    root.closing = Some(end_element);
-                    reader.seek(SeekFrom::Start(nextNode));
-                    break;
-                }
-                _ => {}
+impl Attribute {
+    /// The namespace URI, None for attributes without one
+    pub fn namespace(&self) -> Option<&str> {
+        return self.namespace.as_deref();
+    }
+
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn resource_id(&self) -> Option<u32> {
+        return self.resource_id;
+    }
+
+    pub fn raw_value(&self) -> Option<&str> {
+        return self.raw_value.as_deref();
+    }
+
+    pub fn value(&self) -> &ResValue {
+        return &self.value;
+    }
+}
+
+impl Complex {
+    pub fn data(&self) -> u32 {
+        return self.0;
+    }
+
+    /// One of the COMPLEX_UNIT_* values, see `DIMENSION_UNITS` and
+    /// `FRACTION_UNITS`
+    pub fn unit(&self) -> u8 {
+        return ((self.0 >> COMPLEX_UNIT_SHIFT) & COMPLEX_UNIT_MASK) as u8;
+    }
+
+    /// Where the binary point of the mantissa is, from 23p0 to 0p23
+    pub fn radix(&self) -> u8 {
+        return ((self.0 >> COMPLEX_RADIX_SHIFT) & COMPLEX_RADIX_MASK) as u8;
+    }
+
+    /// The signed 24 bit mantissa
+    pub fn mantissa(&self) -> i32 {
+        return (self.0 & (COMPLEX_MANTISSA_MASK << COMPLEX_MANTISSA_SHIFT)) as i32 >>
+               COMPLEX_MANTISSA_SHIFT;
+    }
+
+    pub fn value(&self) -> f32 {
+        let mantissa = (self.0 & (COMPLEX_MANTISSA_MASK << COMPLEX_MANTISSA_SHIFT)) as i32;
+        return mantissa as f32 * RADIX_MULTS[self.radix() as usize];
+    }
+}
+
+impl fmt::Display for ResValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ResValue::Null => write!(f, "@null"),
+            ResValue::Empty => write!(f, "@empty"),
+            ResValue::Reference(id) => write!(f, "@0x{:08x}", id),
+            ResValue::Attribute(id) => write!(f, "?0x{:08x}", id),
+            ResValue::String(ref string) => write!(f, "{}", string),
+            ResValue::StyledString(ref markup) => write!(f, "{}", markup),
+            ResValue::Float(value) => write!(f, "{:?}", value),
+            ResValue::Dimension(complex) => {
+                let unit = DIMENSION_UNITS.get(complex.unit() as usize).unwrap_or(&"");
+                write!(f, "{:?}{}", complex.value(), unit)
+            }
+            ResValue::Fraction(complex) => {
+                let unit = FRACTION_UNITS.get(complex.unit() as usize).unwrap_or(&"");
+                write!(f, "{:?}{}", complex.value() * 100.0, unit)
+            }
+            ResValue::IntDec(value) => write!(f, "{}", value),
+            ResValue::IntHex(value) => write!(f, "0x{:08x}", value),
+            ResValue::Boolean(value) => write!(f, "{}", value),
+            ResValue::ColorArgb8(color) => write!(f, "#{:08x}", color),
+            ResValue::ColorRgb8(color) => write!(f, "#{:06x}", color & 0xffffff),
+            ResValue::ColorArgb4(color) => {
+                write!(f,
+                       "#{:x}{:x}{:x}{:x}",
+                       color >> 28,
+                       (color >> 20) & 0xf,
+                       (color >> 12) & 0xf,
+                       (color >> 4) & 0xf)
+            }
+            ResValue::ColorRgb4(color) => {
+                write!(f,
+                       "#{:x}{:x}{:x}",
+                       (color >> 20) & 0xf,
+                       (color >> 12) & 0xf,
+                       (color >> 4) & 0xf)
+            }
+            ResValue::Unknown { data_type, data } => {
+                write!(f, "(type 0x{:02x})0x{:08x}", data_type, data)
             }
         }
-        return root;
     }
+}
 
-    fn read_root_node(&mut self, mut reader: &mut Cursor<&Vec<u8>>, node: res_chunk_header) {
+pub fn u16_at(data: &[u8], offset: usize) -> Result<u16, AxmlError> {
+    match data.get(offset..offset + 2) {
+        Some(bytes) => Ok(LittleEndian::read_u16(bytes)),
+        None => Err(AxmlError::new(offset, "data ends unexpectedly".to_string())),
+    }
+}
 
-        let mut namespace_node = self.read_namespace_node(reader, node);
-        self.axml.push(namespace_node);
+pub fn u32_at(data: &[u8], offset: usize) -> Result<u32, AxmlError> {
+    match data.get(offset..offset + 4) {
+        Some(bytes) => Ok(LittleEndian::read_u32(bytes)),
+        None => Err(AxmlError::new(offset, "data ends unexpectedly".to_string())),
+    }
+}
 
+/// Reads the header of the chunk at `offset` and checks that the chunk
+/// lies within `end`
+pub fn chunk(data: &[u8], offset: usize, end: usize) -> Result<Chunk, AxmlError> {
+    let chunk = Chunk {
+        offset: offset,
+        chunk_type: u16_at(data, offset)?,
+        header_size: u16_at(data, offset + 2)? as usize,
+        size: u32_at(data, offset + 4)? as usize,
+    };
+    if chunk.header_size < 8 || chunk.size < chunk.header_size ||
+       chunk.size > end.saturating_sub(offset) {
+        return Err(AxmlError::new(offset, format!("invalid chunk size {}", chunk.size)));
     }
+    return Ok(chunk);
+}
 
+impl Chunk {
+    /// Offset of the data following the header
+    pub fn body(&self) -> usize {
+        return self.offset + self.header_size;
+    }
 
-    fn read_resource_map(&mut self, reader: &mut Cursor<&Vec<u8>>, node: res_chunk_header) {
+    pub fn end(&self) -> usize {
+        return self.offset + self.size;
+    }
+}
 
-        let attr_byte_count = (node.total_size - node.header_size as u32) / 4;
+/// Reads a UTF-16 string: its length in code units, in one or, with the
+/// high bit set, two u16s, followed by the units
+fn utf16_string(data: &[u8], offset: usize) -> Result<String, AxmlError> {
+    let mut position = offset;
+    let mut length = u16_at(data, position)? as usize;
+    position += 2;
+    if length & 0x8000 != 0 {
+        length = ((length & 0x7FFF) << 16) | u16_at(data, position)? as usize;
+        position += 2;
+    }
+    // The length comes from the file, check it before allocating for it
+    let bytes = match data.get(position..).filter(|rest| rest.len() / 2 >= length) {
+        Some(rest) => &rest[..length * 2],
+        None => return Err(AxmlError::new(position, "data ends unexpectedly".to_string())),
+    };
+    let units: Vec<u16> = bytes.chunks(2).map(LittleEndian::read_u16).collect();
+    return Ok(String::from_utf16_lossy(&units));
+}
 
-        for i in 0..attr_byte_count as usize {
-            let res_id: u32 = reader.read_u32::<LittleEndian>().unwrap();
-            println!("Read resource id {} 0x{:08x}", self.strings[i], res_id);
-            self.resource_map.push(res_id);
+/// A UTF-8 length: one byte or, with the high bit set, two
+fn utf8_length(data: &[u8], position: &mut usize) -> Result<usize, AxmlError> {
+    let byte = |offset: usize| {
+        match data.get(offset) {
+            Some(&byte) => Ok(byte as usize),
+            None => Err(AxmlError::new(offset, "data ends unexpectedly".to_string())),
         }
+    };
+    let mut length = byte(*position)?;
+    *position += 1;
+    if length & 0x80 != 0 {
+        length = ((length & 0x7F) << 8) | byte(*position)?;
+        *position += 1;
     }
+    return Ok(length);
+}
 
-    fn read_string_pool(&mut self, reader: &mut Cursor<&Vec<u8>>, node: res_chunk_header) {
+/// Reads a UTF-8 string: its length in UTF-16 code units, then its length
+/// in bytes, followed by the bytes
+fn utf8_string(data: &[u8], offset: usize) -> Result<String, AxmlError> {
+    let mut position = offset;
+    utf8_length(data, &mut position)?;
+    let length = utf8_length(data, &mut position)?;
+    match data.get(position..).filter(|rest| rest.len() >= length) {
+        Some(rest) => Ok(String::from_utf8_lossy(&rest[..length]).into_owned()),
+        None => Err(AxmlError::new(position, "data ends unexpectedly".to_string())),
+    }
+}
 
+/// Reads a string pool chunk in either encoding, along with the spans of
+/// its styled strings
+pub fn string_pool(data: &[u8], chunk: &Chunk) -> Result<StringPool, AxmlError> {
+    let string_count = u32_at(data, chunk.offset + 8)? as usize;
+    let style_count = u32_at(data, chunk.offset + 12)? as usize;
+    let flags = u32_at(data, chunk.offset + 16)?;
+    let strings_start = chunk.offset + u32_at(data, chunk.offset + 20)? as usize;
+    let styles_start = chunk.offset + u32_at(data, chunk.offset + 24)? as usize;
+
+    let mut strings = vec![];
+    for i in 0..string_count {
+        let offset = strings_start + u32_at(data, chunk.body() + i * 4)? as usize;
+        if flags & UTF8_FLAG != 0 {
+            strings.push(utf8_string(data, offset)?);
+        } else {
+            strings.push(utf16_string(data, offset)?);
+        }
+    }
 
-        let string_count: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let style_count: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let flags: u32 = reader.read_u32::<LittleEndian>().unwrap();
-        let string_start: u32 = node.position + reader.read_u32::<LittleEndian>().unwrap();
-        let styles_start: u32 = node.position + reader.read_u32::<LittleEndian>().unwrap();
+    // Each style is a list of spans ended by 0xFFFFFFFF
+    let mut styles = vec![];
+    for i in 0..style_count {
+        let offset_position = chunk.body() + (string_count + i) * 4;
+        let mut position = styles_start + u32_at(data, offset_position)? as usize;
+        let mut spans = vec![];
+        loop {
+            let name = u32_at(data, position)?;
+            if name == NO_ENTRY {
+                break;
+            }
+            let tag = match strings.get(name as usize) {
+                Some(tag) => tag.clone(),
+                None => {
+                    return Err(AxmlError::new(position,
+                                              format!("string index {} out of range", name)))
+                }
+            };
+            spans.push(Span {
+                tag: tag,
+                first_char: u32_at(data, position + 4)?,
+                last_char: u32_at(data, position + 8)?,
+            });
+            position += 12;
+        }
+        styles.push(spans);
+    }
+    return Ok(StringPool {
+        strings: strings,
+        styles: styles,
+    });
+}
 
-        // TODO Debug
-        let pos = reader.position();
-        println!("Stringpool 0x{:04x}: strings {}, styles {}, flags: 0x{:04x}, string_start \
-                  0x{:04x}, styles_start 0x{:04x}",
-                 pos,
-                 string_count,
-                 style_count,
-                 flags,
-                 string_start,
-                 styles_start);
+fn string(pool: &StringPool, offset: usize, idx: u32) -> Result<String, AxmlError> {
+    match pool.string(idx) {
+        Some(string) => Ok(string.to_string()),
+        None => Err(AxmlError::new(offset, format!("string index {} out of range", idx))),
+    }
+}
 
-        let UTF8_FLAG = 1 << 8;
-        let isUTF8 = (flags & UTF8_FLAG) != 0;
-        if isUTF8 {
-            panic!("We are dealing with UTF-8. Not implemented yet");
+/// A string reference that may be 0xFFFFFFFF for none
+fn optional_string(pool: &StringPool,
+                   offset: usize,
+                   idx: u32)
+                   -> Result<Option<String>, AxmlError> {
+    if idx == NO_ENTRY {
+        return Ok(None);
+    }
+    return string(pool, offset, idx).map(Some);
+}
+
+/// Reads a Res_value: size, a zero byte, the data type and the data.
+/// Strings are looked up in `pool`.
+pub fn res_value(data: &[u8], offset: usize, pool: &StringPool) -> Result<ResValue, AxmlError> {
+    let data_type = (u16_at(data, offset + 2)? >> 8) as u8;
+    let value = u32_at(data, offset + 4)?;
+    return Ok(match data_type {
+        TYPE_NULL if value == DATA_NULL_EMPTY => ResValue::Empty,
+        TYPE_NULL => ResValue::Null,
+        TYPE_REFERENCE | TYPE_DYNAMIC_REFERENCE => ResValue::Reference(value),
+        TYPE_ATTRIBUTE | TYPE_DYNAMIC_ATTRIBUTE => ResValue::Attribute(value),
+        TYPE_STRING => {
+            let string = string(pool, offset, value)?;
+            if pool.spans(value).is_empty() {
+                ResValue::String(string)
+            } else {
+                ResValue::StyledString(pool.styled_string(value).unwrap_or(string))
+            }
         }
+        TYPE_FLOAT => ResValue::Float(f32::from_bits(value)),
+        TYPE_DIMENSION => ResValue::Dimension(Complex(value)),
+        TYPE_FRACTION => ResValue::Fraction(Complex(value)),
+        TYPE_INT_DEC => ResValue::IntDec(value as i32),
+        TYPE_INT_HEX => ResValue::IntHex(value),
+        TYPE_INT_BOOLEAN => ResValue::Boolean(value != 0),
+        TYPE_INT_COLOR_ARGB8 => ResValue::ColorArgb8(value),
+        TYPE_INT_COLOR_RGB8 => ResValue::ColorRgb8(value),
+        TYPE_INT_COLOR_ARGB4 => ResValue::ColorArgb4(value),
+        TYPE_INT_COLOR_RGB4 => ResValue::ColorRgb4(value),
+        _ => {
+            ResValue::Unknown {
+                data_type: data_type,
+                data: value,
+            }
+        }
+    });
+}
+
+/// Reads a RES_XML_START_ELEMENT_TYPE chunk. Its children are added by
+/// `decode` as they follow.
+fn start_element(data: &[u8],
+                 chunk: &Chunk,
+                 pool: &StringPool,
+                 resource_map: &[u32])
+                 -> Result<Element, AxmlError> {
+    let body = chunk.body();
+    let attribute_start = u16_at(data, body + 8)? as usize;
+    let attribute_size = u16_at(data, body + 10)? as usize;
+    let attribute_count = u16_at(data, body + 12)? as usize;
+
+    let mut attributes = vec![];
+    for i in 0..attribute_count {
+        let offset = body + attribute_start + i * attribute_size;
+        let name_idx = u32_at(data, offset + 4)?;
+        let resource_id = resource_map.get(name_idx as usize).cloned();
+        let mut namespace = optional_string(pool, offset, u32_at(data, offset)?)?;
+        let mut name = string(pool, offset, name_idx)?;
+        // Obfuscators blank or rename the pool strings of framework
+        // attributes, which the framework itself never reads
+        if let Some(framework_name) = resource_id.and_then(attrs::attribute_name) {
+            name = framework_name.to_string();
+            namespace = Some(attrs::ANDROID_NAMESPACE.to_string());
+        }
+        attributes.push(Attribute {
+            namespace: namespace,
+            name: name,
+            resource_id: resource_id,
+            raw_value: optional_string(pool, offset, u32_at(data, offset + 8)?)?,
+            value: res_value(data, offset + 12, pool)?,
+        });
+    }
 
-        if string_count != 0 {
-            for i in 0..string_count {
-                let string_pos: u32 = reader.read_u32::<LittleEndian>().unwrap();
-                let seek_pos = reader.position();
+    return Ok(Element {
+        line_number: u32_at(data, chunk.offset + 8)?,
+        namespace: optional_string(pool, body, u32_at(data, body)?)?,
+        name: string(pool, body, u32_at(data, body + 4)?)?,
+        namespaces: vec![],
+        attributes: attributes,
+        children: vec![],
+    });
+}
 
-                reader.seek(SeekFrom::Start((string_pos + string_start + 2) as u64));
+/// Decodes a binary XML file into its document tree
+pub fn decode(data: &[u8]) -> Result<Document, AxmlError> {
+    let header = chunk(data, 0, data.len())?;
+    if header.chunk_type != RES_XML_TYPE {
+        return Err(AxmlError::new(0, format!("not a binary XML file, chunk type 0x{:04x}",
+                                             header.chunk_type)));
+    }
 
-                let mut buffer = Vec::new();
-                let mut byte: u16 = 0xFFFF;
-                loop {
-                    match reader.read_u16::<LittleEndian>() {
-                        Err(why) => panic!("Could not read: {}", why),
-                        Ok(data) if data == 0x0000 => break,
-                        Ok(data) => buffer.push(data),
+    let mut pool = StringPool::default();
+    let mut resource_map: Vec<u32> = vec![];
+    // Namespaces are declared by the element started next
+    let mut namespaces: Vec<Namespace> = vec![];
+    let mut stack: Vec<Element> = vec![];
+    let mut root: Option<Element> = None;
+
+    let mut offset = header.body();
+    while offset < header.end() {
+        let chunk = chunk(data, offset, header.end())?;
+        let body = chunk.body();
+        match chunk.chunk_type {
+            RES_STRING_POOL_TYPE => pool = string_pool(data, &chunk)?,
+            RES_XML_RESOURCE_MAP_TYPE => {
+                resource_map.clear();
+                for position in (body..chunk.end()).step_by(4) {
+                    resource_map.push(u32_at(data, position)?);
+                }
+            }
+            RES_XML_START_NAMESPACE_TYPE => {
+                namespaces.push(Namespace {
+                    prefix: string(&pool, body, u32_at(data, body)?)?,
+                    uri: string(&pool, body, u32_at(data, body + 4)?)?,
+                });
+            }
+            RES_XML_START_ELEMENT_TYPE => {
+                let mut element = start_element(data, &chunk, &pool, &resource_map)?;
+                element.namespaces = ::std::mem::take(&mut namespaces);
+                stack.push(element);
+            }
+            RES_XML_END_ELEMENT_TYPE => {
+                let element = match stack.pop() {
+                    Some(element) => element,
+                    None => return Err(AxmlError::new(offset, "unbalanced end tag".to_string())),
+                };
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(Node::Element(element)),
+                    None if root.is_none() => root = Some(element),
+                    None => {
+                        return Err(AxmlError::new(offset, "more than one root element".to_string()))
                     }
                 }
+            }
+            RES_XML_CDATA_TYPE => {
+                let text = string(&pool, body, u32_at(data, body)?)?;
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(Node::Text(text));
+                }
+            }
+            // End namespace chunks carry nothing the tree does not already
+            // have
+            _ => {}
+        }
+        offset = chunk.end();
+    }
+
+    if !stack.is_empty() {
+        return Err(AxmlError::new(data.len(), "unclosed element".to_string()));
+    }
+    match root {
+        Some(root) => Ok(Document { root: root }),
+        None => Err(AxmlError::new(data.len(), "no root element".to_string())),
+    }
+}
 
-                let string = String::from_utf16_lossy(buffer.as_slice());
-                // Todo debug
-                println!("String {}:  0x{:04x} {} bytes: {}",
-                         i,
-                         string_pos,
-                         string.len(),
-                         string);
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    return escaped;
+}
 
-                self.strings.push(string);
+/// `name` qualified with the prefix `namespace` is bound to
+fn qualified_name(scope: &[&Namespace], namespace: Option<&str>, name: &str) -> String {
+    let uri = match namespace {
+        Some(uri) => uri,
+        None => return name.to_string(),
+    };
+    match scope.iter().rev().find(|ns| ns.uri == uri) {
+        Some(ns) => format!("{}:{}", ns.prefix, name),
+        None => name.to_string(),
+    }
+}
 
-                reader.seek(SeekFrom::Start(seek_pos as u64));
+/// Renders a reference by name, `@string/app_name`. With `inline_values`
+/// it is replaced by its value in the default configuration, as long as
+/// that is a plain value. None for unknown ids and alias cycles.
+fn reference_string(prefix: char,
+                    id: u32,
+                    resources: Option<&ResourceTable>,
+                    inline_values: bool)
+                    -> Option<String> {
+    let table = resources?;
+    let mut resource = table.resource(id)?;
+    if !inline_values || prefix != '@' {
+        return Some(format!("{}{}", prefix, resource.qualified_name()));
+    }
+    // Follows aliases such as @string/title -> @string/app_name, which a
+    // malformed table may have refer to each other
+    for _ in 0..=MAX_ALIAS_DEPTH {
+        let target = match resource.default_value().map(|entry| entry.value()) {
+            Some(EntryValue::Simple(ResValue::Reference(target))) => table.resource(*target),
+            Some(EntryValue::Simple(value)) => return Some(value.to_string()),
+            _ => None,
+        };
+        match target {
+            Some(target) => resource = target,
+            None => return Some(format!("{}{}", prefix, resource.qualified_name())),
+        }
+    }
+    return None;
+}
+
+/// The textual form of an attribute value, references resolved against
+/// `resources` where possible
+fn value_string(value: &ResValue,
+                resources: Option<&ResourceTable>,
+                inline_values: bool)
+                -> String {
+    let resolved = match *value {
+        ResValue::Reference(id) => reference_string('@', id, resources, inline_values),
+        ResValue::Attribute(id) => {
+            reference_string('?', id, resources, inline_values).or_else(|| {
+                attrs::attribute_name(id).map(|name| format!("?android:attr/{}", name))
+            })
+        }
+        _ => None,
+    };
+    return resolved.unwrap_or_else(|| value.to_string());
+}
+
+fn write_element<'a>(out: &mut String,
+                     element: &'a Element,
+                     scope: &mut Vec<&'a Namespace>,
+                     depth: usize,
+                     resources: Option<&ResourceTable>,
+                     inline_values: bool) {
+    let indent = "    ".repeat(depth);
+    let scope_len = scope.len();
+    scope.extend(element.namespaces.iter());
+
+    let name = qualified_name(scope, element.namespace(), &element.name);
+    out.push_str(&format!("{}<{}", indent, name));
+    for ns in element.namespaces.iter() {
+        out.push_str(&format!(" xmlns:{}=\"{}\"", ns.prefix, escape(&ns.uri)));
+    }
+    for attribute in element.attributes.iter() {
+        out.push_str(&format!(" {}=\"{}\"",
+                              qualified_name(scope, attribute.namespace(), &attribute.name),
+                              escape(&value_string(&attribute.value,
+                                                   resources,
+                                                   inline_values))));
+    }
+    if element.children.is_empty() {
+        out.push_str(" />\n");
+    } else {
+        out.push_str(">\n");
+        for child in element.children.iter() {
+            match *child {
+                Node::Element(ref child) => {
+                    write_element(out, child, scope, depth + 1, resources, inline_values)
+                }
+                Node::Text(ref text) => {
+                    out.push_str(&format!("{}    {}\n", indent, escape(text.trim())));
+                }
             }
         }
+        out.push_str(&format!("{}</{}>\n", indent, name));
+    }
+    scope.truncate(scope_len);
+}
+
+/// Renders a document as XML text
+pub fn to_xml(document: &Document) -> String {
+    return to_xml_with_resources(document, None, false);
+}
 
-        if style_count != 0 {
-            panic!("Need to parse styles");
+/// Renders a document as XML text, with the resource ids of references
+/// replaced by their names from `resources`. `inline_values` goes one
+/// step further and writes the default value of the resource instead.
+pub fn to_xml_with_resources(document: &Document,
+                             resources: Option<&ResourceTable>,
+                             inline_values: bool)
+                             -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    // Framework attributes named from their ids need the android prefix
+    // even when the xmlns declaration was stripped as well
+    if uses_namespace(&document.root, attrs::ANDROID_NAMESPACE) &&
+       !document.root.namespaces.iter().any(|ns| ns.uri == attrs::ANDROID_NAMESPACE) {
+        let mut root = document.root.clone();
+        // The prefix itself may still be bound to a mangled URI
+        let mut prefix = "android".to_string();
+        let mut n = 1;
+        while root.namespaces.iter().any(|ns| ns.prefix == prefix) {
+            prefix = format!("android{}", n);
+            n += 1;
         }
+        root.namespaces.insert(0,
+                               Namespace {
+                                   prefix: prefix,
+                                   uri: attrs::ANDROID_NAMESPACE.to_string(),
+                               });
+        write_element(&mut out, &root, &mut vec![], 0, resources, inline_values);
+        return out;
+    }
+    write_element(&mut out, &document.root, &mut vec![], 0, resources, inline_values);
+    return out;
+}
 
-        let nextNode: u64 = (node.position + node.total_size) as u64;
-        reader.seek(SeekFrom::Start(nextNode));
+fn uses_namespace(element: &Element, uri: &str) -> bool {
+    return element.namespace() == Some(uri) ||
+           element.attributes.iter().any(|a| a.namespace() == Some(uri)) ||
+           element.children.iter().any(|child| match *child {
+        Node::Element(ref child) => uses_namespace(child, uri),
+        Node::Text(_) => false,
+    });
+}
 
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// A chunk of `chunk_type` whose header is `header_size` bytes long,
+    /// the 8 bytes of type and sizes included
+    pub fn chunk_bytes(chunk_type: u16, header_size: u16, body: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&chunk_type.to_le_bytes());
+        data.extend_from_slice(&header_size.to_le_bytes());
+        data.extend_from_slice(&(body.len() as u32 + 8).to_le_bytes());
+        data.extend_from_slice(body);
+        return data;
     }
 
-    fn to_string(&self) -> String {
-        if self.axml.len() > 1 {
-            panic!("Multiple Namespaces not yet supported");
+    pub fn u32s(values: &[u32]) -> Vec<u8> {
+        return values.iter().flat_map(|value| value.to_le_bytes().to_vec()).collect();
+    }
+
+    /// A UTF-8 pool length: one byte, or two with the high bit set on the first
+    fn utf8_length_bytes(length: usize) -> Vec<u8> {
+        if length < 0x80 {
+            return vec![length as u8];
         }
+        return vec![0x80 | (length >> 8) as u8, length as u8];
+    }
 
-        return self.axml[0].string(&self.strings, 0);
+    /// A UTF-16 pool length: one unit, or two with the high bit set on the first
+    fn utf16_length_bytes(length: usize) -> Vec<u8> {
+        if length < 0x8000 {
+            return (length as u16).to_le_bytes().to_vec();
+        }
+        let mut bytes = (0x8000 | (length >> 16) as u16).to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(length as u16).to_le_bytes());
+        return bytes;
     }
-}
-impl AXMLNode {
-    fn string(&self, string_pool: &Vec<String>, depth: u16) -> String {
-        {
-            let content = self.content.as_ref().unwrap();
-            match content {
-                &AXMLNode_content::AXMLNode_namespace { ref lineNumber,
-                                                        ref comment,
-                                                        ref prefix,
-                                                        ref uri } => {
-                    for child in 0..self.children.len() {
-                        self.children[child].string(string_pool, depth);
-                    }
+
+    /// A string pool chunk. Each style is a list of (name, first, last)
+    /// spans of the string with the same index.
+    pub fn pool_bytes(strings: &[&str], utf8: bool, styles: &[&[(u32, u32, u32)]]) -> Vec<u8> {
+        let mut string_data = vec![];
+        let mut offsets = vec![];
+        for string in strings {
+            offsets.push(string_data.len() as u32);
+            if utf8 {
+                string_data.extend(utf8_length_bytes(string.encode_utf16().count()));
+                string_data.extend(utf8_length_bytes(string.len()));
+                string_data.extend_from_slice(string.as_bytes());
+                string_data.push(0);
+            } else {
+                let units: Vec<u16> = string.encode_utf16().collect();
+                string_data.extend(utf16_length_bytes(units.len()));
+                for unit in units {
+                    string_data.extend_from_slice(&unit.to_le_bytes());
                 }
-                &AXMLNode_content::AXMLNode_element { lineNumber,
-                                                      comment,
-                                                      ns,
-                                                      ref name,
-                                                      attribute_start,
-                                                      attribute_size,
-                                                      attribute_count,
-                                                      id_index,
-                                                      class_index,
-                                                      style_index,
-                                                      ref attributes } => {
-                    let index = *name as usize;
-                    for i in 0..depth {
-                        print!("  ");
-                    }
-                    print!("<{} ", string_pool[index]);
-
-                    for attr in 0..attributes.len() {
-                        let ns = attributes[attr].ns as usize;
-                        if ns <= string_pool.len() {
-                            print!("{}:", string_pool[ns - 1]);
-                        }
-
-                        let name = attributes[attr].name as usize;
-                        print!("{}", string_pool[name]);
-
-                        let data = attributes[attr].data as usize;
-                        if data <= string_pool.len() {
-                            print!("=\"{}\" ", string_pool[data]);
-                        } else {
-                            print!(" ");
-                        }
-                    }
-                    println!(">");
+                string_data.extend_from_slice(&[0, 0]);
+            }
+        }
+        while string_data.len() % 4 != 0 {
+            string_data.push(0);
+        }
+        let mut style_data = vec![];
+        for style in styles {
+            offsets.push(style_data.len() as u32);
+            for &(name, first, last) in style.iter() {
+                style_data.extend_from_slice(&u32s(&[name, first, last]));
+            }
+            style_data.extend_from_slice(&u32s(&[NO_ENTRY]));
+        }
 
-                    for child in 0..self.children.len() {
-                        self.children[child].string(string_pool, depth + 1);
-                    }
+        let strings_start = 28 + offsets.len() as u32 * 4;
+        let styles_start = if styles.is_empty() {
+            0
+        } else {
+            strings_start + string_data.len() as u32
+        };
+        let flags = if utf8 { UTF8_FLAG } else { 0 };
+        let mut body = u32s(&[strings.len() as u32,
+                              styles.len() as u32,
+                              flags,
+                              strings_start,
+                              styles_start]);
+        body.extend_from_slice(&u32s(&offsets));
+        body.extend_from_slice(&string_data);
+        body.extend_from_slice(&style_data);
+        return chunk_bytes(RES_STRING_POOL_TYPE, 28, &body);
+    }
 
-                    for i in 0..depth {
-                        print!("  ");
-                    }
-                    println!("</{}>", string_pool[index]);
-                }
-                _ => {
-                    panic!("WHAT IS LOVE?");
-                }
-            };
+    /// A Res_value of `data_type`
+    pub fn value_bytes(data_type: u8, data: u32) -> Vec<u8> {
+        let mut bytes = vec![8, 0, 0, data_type];
+        bytes.extend_from_slice(&data.to_le_bytes());
+        return bytes;
+    }
+
+    fn node(chunk_type: u16, fields: &[u32]) -> Vec<u8> {
+        let mut body = u32s(&[1, NO_ENTRY]);
+        body.extend_from_slice(&u32s(fields));
+        return chunk_bytes(chunk_type, 16, &body);
+    }
 
+    /// A start element with (namespace, name, raw value, type, data)
+    /// attributes
+    fn start(namespace: u32, name: u32, attributes: &[(u32, u32, u32, u8, u32)]) -> Vec<u8> {
+        let mut body = u32s(&[1, NO_ENTRY, namespace, name]);
+        for &field in [20u16, 20, attributes.len() as u16, 0, 0, 0].iter() {
+            body.extend_from_slice(&field.to_le_bytes());
         }
+        for &(namespace, name, raw_value, data_type, data) in attributes {
+            body.extend_from_slice(&u32s(&[namespace, name, raw_value]));
+            body.extend_from_slice(&value_bytes(data_type, data));
+        }
+        return chunk_bytes(RES_XML_START_ELEMENT_TYPE, 16, &body);
+    }
 
+    fn end(namespace: u32, name: u32) -> Vec<u8> {
+        return node(RES_XML_END_ELEMENT_TYPE, &[namespace, name]);
+    }
 
-        return "".to_string();
+    fn document(chunks: &[Vec<u8>]) -> Vec<u8> {
+        return chunk_bytes(RES_XML_TYPE, 8, &chunks.concat());
+    }
+
+    const ANDROID_URI: &str = "http://schemas.android.com/apk/res/android";
+
+    const STRINGS: [&str; 8] = ["versionCode",
+                                "package",
+                                "android",
+                                "http://schemas.android.com/apk/res/android",
+                                "manifest",
+                                "com.example",
+                                "application",
+                                "  hello & bye  "];
+
+    fn manifest() -> Vec<u8> {
+        let mut cdata = u32s(&[1, NO_ENTRY, 7]);
+        cdata.extend_from_slice(&value_bytes(TYPE_NULL, 0));
+        return document(&[pool_bytes(&STRINGS, false, &[]),
+                          chunk_bytes(RES_XML_RESOURCE_MAP_TYPE, 8, &u32s(&[0x0101021b])),
+                          node(RES_XML_START_NAMESPACE_TYPE, &[2, 3]),
+                          start(NO_ENTRY,
+                                4,
+                                &[(3, 0, NO_ENTRY, TYPE_INT_DEC, 3),
+                                  (NO_ENTRY, 1, 5, TYPE_STRING, 5)]),
+                          start(NO_ENTRY, 6, &[]),
+                          chunk_bytes(RES_XML_CDATA_TYPE, 16, &cdata),
+                          end(NO_ENTRY, 6),
+                          end(NO_ENTRY, 4),
+                          node(0x0101, &[2, 3])])
+    }
+
+    #[test]
+    fn decode_builds_the_document_tree() {
+        let document = decode(&manifest()).unwrap();
+        let root = document.root();
+        assert_eq!(root.name(), "manifest");
+        assert_eq!(root.namespace(), None);
+        assert_eq!(root.line_number(), 1);
+        assert_eq!(root.namespaces(),
+                   &[Namespace {
+                         prefix: "android".to_string(),
+                         uri: ANDROID_URI.to_string(),
+                     }]);
+
+        let version = root.attribute(Some(ANDROID_URI), "versionCode").unwrap();
+        assert_eq!(version.resource_id(), Some(0x0101021b));
+        assert_eq!(version.raw_value(), None);
+        assert_eq!(version.value(), &ResValue::IntDec(3));
+        let package = root.attribute(None, "package").unwrap();
+        assert_eq!(package.resource_id(), None);
+        assert_eq!(package.raw_value(), Some("com.example"));
+        assert!(root.attribute(None, "versionCode").is_none());
+
+        let children = root.elements();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].name(), "application");
+        match children[0].children() {
+            [Node::Text(text)] => assert_eq!(text, "  hello & bye  "),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn to_xml_writes_prefixes_and_escapes_text() {
+        assert_eq!(to_xml(&decode(&manifest()).unwrap()),
+                   "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<manifest xmlns:android=\"http://schemas.android.com/apk/res/android\" \
+                    android:versionCode=\"3\" package=\"com.example\">
+    <application>
+        hello &amp; bye
+    </application>
+</manifest>
+");
+    }
+
+    #[test]
+    fn framework_attributes_are_named_by_id() {
+        // An obfuscated pool: the attribute name is blank and there is no
+        // xmlns declaration
+        let data = document(&[pool_bytes(&["", "manifest"], true, &[]),
+                              chunk_bytes(RES_XML_RESOURCE_MAP_TYPE, 8, &u32s(&[0x01010003])),
+                              start(NO_ENTRY, 1, &[(NO_ENTRY, 0, NO_ENTRY, TYPE_INT_HEX, 0)]),
+                              end(NO_ENTRY, 1)]);
+        let document = decode(&data).unwrap();
+        let attribute = &document.root().attributes()[0];
+        assert_eq!(attribute.name(), "name");
+        assert_eq!(attribute.namespace(), Some(ANDROID_URI));
+        assert_eq!(to_xml(&document),
+                   "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<manifest xmlns:android=\"http://schemas.android.com/apk/res/android\" \
+                    android:name=\"0x00000000\" />
+");
+    }
+
+    fn error(data: &[u8]) -> String {
+        return decode(data).unwrap_err().message().to_string();
+    }
+
+    #[test]
+    fn malformed_documents_are_errors() {
+        let pool = pool_bytes(&["a"], false, &[]);
+        assert_eq!(error(&pool), "not a binary XML file, chunk type 0x0001");
+        assert_eq!(error(&[3, 0, 8]), "data ends unexpectedly");
+        assert_eq!(error(&document(::std::slice::from_ref(&pool))), "no root element");
+        assert_eq!(error(&document(&[pool.clone(), end(NO_ENTRY, 0)])),
+                   "unbalanced end tag");
+        assert_eq!(error(&document(&[pool.clone(), start(NO_ENTRY, 0, &[])])),
+                   "unclosed element");
+        assert_eq!(error(&document(&[pool.clone(),
+                                     start(NO_ENTRY, 0, &[]),
+                                     end(NO_ENTRY, 0),
+                                     start(NO_ENTRY, 0, &[]),
+                                     end(NO_ENTRY, 0)])),
+                   "more than one root element");
+        assert_eq!(error(&document(&[pool.clone(), start(NO_ENTRY, 1, &[]), end(NO_ENTRY, 1)])),
+                   "string index 1 out of range");
+
+        // A chunk claiming more bytes than its parent has
+        let mut data = document(&[pool.clone(), start(NO_ENTRY, 0, &[]), end(NO_ENTRY, 0)]);
+        let position = 8 + pool.len() + 4;
+        data[position..position + 4].copy_from_slice(&0x7FFFFFFFu32.to_le_bytes());
+        assert_eq!(error(&data), "invalid chunk size 2147483647");
+        // A header shorter than the type and sizes
+        data[position..position + 4].copy_from_slice(&36u32.to_le_bytes());
+        data[position - 2] = 4;
+        assert_eq!(error(&data), "invalid chunk size 36");
+
+        // Attributes past the end of the file
+        let mut data = document(&[pool.clone(),
+                                  start(NO_ENTRY, 0, &[(NO_ENTRY, 0, NO_ENTRY, TYPE_NULL, 0)]),
+                                  end(NO_ENTRY, 0)]);
+        let attribute_start = 8 + pool.len() + 16 + 8;
+        data[attribute_start..attribute_start + 2].copy_from_slice(&[0xFF, 0xFF]);
+        assert_eq!(error(&data), "data ends unexpectedly");
+    }
+
+    fn value(data_type: u8, data: u32) -> ResValue {
+        let pool = StringPool {
+            strings: vec!["text".to_string()],
+            styles: vec![],
+        };
+        return res_value(&value_bytes(data_type, data), 0, &pool).unwrap();
     }
-}
 
+    #[test]
+    fn res_values_are_typed() {
+        assert_eq!(value(TYPE_NULL, 0), ResValue::Null);
+        assert_eq!(value(TYPE_NULL, DATA_NULL_EMPTY), ResValue::Empty);
+        assert_eq!(value(TYPE_REFERENCE, 0x7f010000), ResValue::Reference(0x7f010000));
+        assert_eq!(value(TYPE_DYNAMIC_REFERENCE, 0x02010000), ResValue::Reference(0x02010000));
+        assert_eq!(value(TYPE_ATTRIBUTE, 0x01010000), ResValue::Attribute(0x01010000));
+        assert_eq!(value(TYPE_DYNAMIC_ATTRIBUTE, 0x02010000), ResValue::Attribute(0x02010000));
+        assert_eq!(value(TYPE_STRING, 0), ResValue::String("text".to_string()));
+        assert_eq!(value(TYPE_FLOAT, 1.5f32.to_bits()), ResValue::Float(1.5));
+        assert_eq!(value(TYPE_DIMENSION, 0x1001), ResValue::Dimension(Complex(0x1001)));
+        assert_eq!(value(TYPE_FRACTION, 0x4010), ResValue::Fraction(Complex(0x4010)));
+        assert_eq!(value(TYPE_INT_DEC, 0xFFFFFFFF), ResValue::IntDec(-1));
+        assert_eq!(value(TYPE_INT_HEX, 0xFF), ResValue::IntHex(0xFF));
+        assert_eq!(value(TYPE_INT_BOOLEAN, 0xFFFFFFFF), ResValue::Boolean(true));
+        assert_eq!(value(TYPE_INT_BOOLEAN, 0), ResValue::Boolean(false));
+        assert_eq!(value(TYPE_INT_COLOR_ARGB8, 1), ResValue::ColorArgb8(1));
+        assert_eq!(value(TYPE_INT_COLOR_RGB8, 2), ResValue::ColorRgb8(2));
+        assert_eq!(value(TYPE_INT_COLOR_ARGB4, 3), ResValue::ColorArgb4(3));
+        assert_eq!(value(TYPE_INT_COLOR_RGB4, 4), ResValue::ColorRgb4(4));
+        assert_eq!(value(0x20, 5),
+                   ResValue::Unknown {
+                       data_type: 0x20,
+                       data: 5,
+                   });
+    }
 
-pub fn decode(xml_data: &Vec<u8>) -> String {
-    println!("axml::decode {} bytes of data", xml_data.len());
+    #[test]
+    fn res_values_display_like_aapt() {
+        let strings: Vec<String> = [value(TYPE_NULL, 0),
+                                    value(TYPE_NULL, DATA_NULL_EMPTY),
+                                    value(TYPE_REFERENCE, 0x7f010000),
+                                    value(TYPE_ATTRIBUTE, 0x01010000),
+                                    value(TYPE_STRING, 0),
+                                    value(TYPE_FLOAT, 1.5f32.to_bits()),
+                                    value(TYPE_INT_DEC, 0xFFFFFFFF),
+                                    value(TYPE_INT_HEX, 0xFF),
+                                    value(TYPE_INT_BOOLEAN, 1),
+                                    value(TYPE_INT_COLOR_ARGB8, 0x80ff0000),
+                                    value(TYPE_INT_COLOR_RGB8, 0xff00ff00),
+                                    value(TYPE_INT_COLOR_ARGB4, 0xff112233),
+                                    value(TYPE_INT_COLOR_RGB4, 0xff112233),
+                                    value(0x20, 1)]
+            .iter()
+            .map(|value| value.to_string())
+            .collect();
+        assert_eq!(strings,
+                   ["@null",
+                    "@empty",
+                    "@0x7f010000",
+                    "?0x01010000",
+                    "text",
+                    "1.5",
+                    "-1",
+                    "0x000000ff",
+                    "true",
+                    "#80ff0000",
+                    "#00ff00",
+                    "#f123",
+                    "#123",
+                    "(type 0x20)0x00000001"]);
+    }
 
-    let mut reader = Cursor::new(xml_data);
+    #[test]
+    fn complex_values_have_units_and_radixes() {
+        // 16dip: mantissa 16, radix 23p0, unit 1
+        let dip = Complex(0x1001);
+        assert_eq!((dip.mantissa(), dip.radix(), dip.unit()), (16, 0, 1));
+        assert_eq!(ResValue::Dimension(dip).to_string(), "16.0dip");
+        // -2px, the mantissa is signed
+        let negative = Complex(0xFFFFFE00);
+        assert_eq!(negative.mantissa(), -2);
+        assert_eq!(ResValue::Dimension(negative).to_string(), "-2.0px");
+        // 0.5 in 16p7 is a fraction of 50%, or 50%p with unit 1
+        assert_eq!(Complex(0x4010).value(), 0.5);
+        assert_eq!(ResValue::Fraction(Complex(0x4010)).to_string(), "50.0%");
+        assert_eq!(ResValue::Fraction(Complex(0x4011)).to_string(), "50.0%p");
+        // 0.25 in 0p23
+        assert_eq!(Complex(0x20_0000 << 8 | 3 << 4).value(), 0.25);
+        // Units out of range are left out
+        assert_eq!(ResValue::Dimension(Complex(0x100f)).to_string(), "16.0");
+        assert_eq!(ResValue::Fraction(Complex(0x100f)).to_string(), "1600.0");
+    }
 
-    let root_node = res_chunk_root::new(&mut reader);
+    #[test]
+    fn malformed_res_values_are_errors() {
+        let pool = StringPool::default();
+        let error = res_value(&value_bytes(TYPE_STRING, 0), 0, &pool).unwrap_err();
+        assert_eq!(error.message(), "string index 0 out of range");
+        let error = res_value(&value_bytes(TYPE_INT_DEC, 0)[..6], 0, &pool).unwrap_err();
+        assert_eq!(error, AxmlError::new(4, "data ends unexpectedly".to_string()));
+    }
 
-    return root_node.to_string();
+    #[test]
+    fn string_pools_decode_both_encodings() {
+        let strings = ["", "abc", "\u{e9}t\u{e9}", "\u{1F600}"];
+        for &utf8 in [false, true].iter() {
+            let data = pool_bytes(&strings, utf8, &[]);
+            let pool = string_pool(&data, &chunk(&data, 0, data.len()).unwrap()).unwrap();
+            assert_eq!(pool.len(), 4);
+            for (i, string) in strings.iter().enumerate() {
+                assert_eq!(pool.string(i as u32), Some(*string));
+            }
+            assert_eq!(pool.string(4), None);
+            assert_eq!(pool.string(NO_ENTRY), None);
+        }
+    }
+
+    #[test]
+    fn string_pools_hold_long_strings() {
+        // 150 UTF-16 units in 300 UTF-8 bytes; UTF-8 lengths stop at 0x7fff, so
+        // only UTF-16 pools get the string of 0x9000 units
+        let accented = "\u{e9}".repeat(150);
+        let long = "a".repeat(0x9000);
+        let utf8_strings = [accented.as_str(), "end"];
+        let utf16_strings = [accented.as_str(), long.as_str(), "end"];
+        for &(utf8, strings) in [(true, &utf8_strings[..]), (false, &utf16_strings[..])].iter() {
+            let data = pool_bytes(strings, utf8, &[]);
+            let pool = string_pool(&data, &chunk(&data, 0, data.len()).unwrap()).unwrap();
+            for (i, string) in strings.iter().enumerate() {
+                assert_eq!(pool.string(i as u32), Some(*string));
+            }
+        }
+    }
+
+    #[test]
+    fn long_string_lengths_take_two_units() {
+        // UTF-16: 0x8000 | high 15 bits, then the low 16 bits
+        let mut data = vec![0x00, 0x80, 0x03, 0x00];
+        data.extend_from_slice(&[b'a', 0, b'b', 0, b'c', 0, 0, 0]);
+        assert_eq!(utf16_string(&data, 0).unwrap(), "abc");
+        // UTF-8: both lengths take a second byte with the high bit set
+        let mut data = vec![0x80, 0x03, 0x80, 0x03];
+        data.extend_from_slice(b"abc\0");
+        assert_eq!(utf8_string(&data, 0).unwrap(), "abc");
+        let mut data = vec![0x81, 0x2C, 0x81, 0x2C];
+        data.extend_from_slice(&[b'x'; 300]);
+        assert_eq!(utf8_string(&data, 0).unwrap().len(), 300);
+    }
+
+    #[test]
+    fn string_lengths_past_the_data_are_errors() {
+        // 0x7FFFFFFF code units would be a 4 GiB allocation
+        let data = [0xFF, 0xFF, 0xFF, 0xFF, b'a', 0];
+        assert_eq!(utf16_string(&data, 0).unwrap_err(),
+                   AxmlError::new(4, "data ends unexpectedly".to_string()));
+        assert_eq!(utf16_string(&[2, 0, b'a', 0], 0).unwrap_err().offset(), 2);
+        assert_eq!(utf16_string(&[0x00, 0x80], 0).unwrap_err().offset(), 2);
+        assert!(utf16_string(&[], 0).is_err());
+        assert!(utf16_string(&[0, 0], 4).is_err());
+
+        assert_eq!(utf8_string(&[0xFF, 0xFF, 0xFF, 0xFF, b'a'], 0).unwrap_err(),
+                   AxmlError::new(4, "data ends unexpectedly".to_string()));
+        assert_eq!(utf8_string(&[1, 2, b'a'], 0).unwrap_err().offset(), 2);
+        assert_eq!(utf8_string(&[1, 0x81], 0).unwrap_err().offset(), 2);
+        assert!(utf8_string(&[0, 0], 4).is_err());
+
+        // A string offset pointing past the chunk
+        let mut data = pool_bytes(&["abc"], false, &[]);
+        data[28..32].copy_from_slice(&0xFFFFu32.to_le_bytes());
+        assert!(string_pool(&data, &chunk(&data, 0, data.len()).unwrap()).is_err());
+        // More strings than there are offsets
+        let mut data = pool_bytes(&["abc"], true, &[]);
+        data[8..12].copy_from_slice(&0xFFFFFFu32.to_le_bytes());
+        assert!(string_pool(&data, &chunk(&data, 0, data.len()).unwrap()).is_err());
+    }
+
+    fn styled_pool(strings: &[&str], styles: &[&[(u32, u32, u32)]]) -> StringPool {
+        let data = pool_bytes(strings, true, styles);
+        return string_pool(&data, &chunk(&data, 0, data.len()).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn styled_strings_become_markup() {
+        let pool = styled_pool(&["Tap here now", "b", "i"], &[&[(1, 0, 2), (2, 4, 7)]]);
+        assert_eq!(pool.spans(0)[1],
+                   Span {
+                       tag: "i".to_string(),
+                       first_char: 4,
+                       last_char: 7,
+                   });
+        assert!(pool.spans(1).is_empty());
+        assert_eq!(pool.styled_string(0).unwrap(), "<b>Tap</b> <i>here</i> now");
+        assert_eq!(pool.styled_string(1).unwrap(), "b");
+        assert_eq!(pool.styled_string(3), None);
+
+        // Nested spans, and spans running to the end
+        let pool = styled_pool(&["bold italic", "b", "i"], &[&[(1, 0, 10), (2, 5, 10)]]);
+        assert_eq!(pool.styled_string(0).unwrap(), "<b>bold <i>italic</i></b>");
+    }
+
+    #[test]
+    fn styled_strings_escape_text_and_attributes() {
+        let pool = styled_pool(&["a<b & c", "a;href=http://x/?a=1&b=\"2\"", "u"],
+                               &[&[(1, 0, 2), (2, 6, 6)]]);
+        assert_eq!(pool.spans(0)[0].name(), "a");
+        assert_eq!(pool.spans(0)[0].attributes(), vec![("href", "http://x/?a=1&b=\"2\"")]);
+        assert_eq!(pool.styled_string(0).unwrap(),
+                   "<a href=\"http://x/?a=1&amp;b=&quot;2&quot;\">a&lt;b</a> &amp; <u>c</u>");
+
+        // Positions count UTF-16 code units, a surrogate pair takes two
+        let pool = styled_pool(&["\u{1F600}x", "b"], &[&[(1, 2, 2)]]);
+        assert_eq!(pool.styled_string(0).unwrap(), "\u{1F600}<b>x</b>");
+    }
+
+    #[test]
+    fn styled_string_values_keep_their_markup() {
+        let pool = styled_pool(&["Tap here", "b", "i", "plain"], &[&[(1, 0, 2), (2, 4, 7)]]);
+        let value = res_value(&value_bytes(TYPE_STRING, 0), 0, &pool).unwrap();
+        assert_eq!(value, ResValue::StyledString("<b>Tap</b> <i>here</i>".to_string()));
+        assert_eq!(value.to_string(), "<b>Tap</b> <i>here</i>");
+        let value = res_value(&value_bytes(TYPE_STRING, 3), 0, &pool).unwrap();
+        assert_eq!(value, ResValue::String("plain".to_string()));
+
+        // Attribute values cannot hold elements, the markup is escaped
+        let data = document(&[pool_bytes(&["Tap", "b", "hint", "view"], false, &[&[(1, 0, 2)]]),
+                              start(NO_ENTRY, 3, &[(NO_ENTRY, 2, 0, TYPE_STRING, 0)]),
+                              end(NO_ENTRY, 3)]);
+        assert_eq!(to_xml(&decode(&data).unwrap()),
+                   "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<view hint=\"&lt;b&gt;Tap&lt;/b&gt;\" />
+");
+    }
+
+    #[test]
+    fn malformed_styles_are_errors() {
+        let error = |data: &[u8]| {
+            return string_pool(data, &chunk(data, 0, data.len()).unwrap())
+                .unwrap_err()
+                .message()
+                .to_string();
+        };
+        // A span naming a tag that is not in the pool
+        assert_eq!(error(&pool_bytes(&["text"], true, &[&[(7, 0, 1)]])),
+                   "string index 7 out of range");
+        // A span list without its 0xFFFFFFFF end
+        let mut data = pool_bytes(&["text", "b"], true, &[&[(1, 0, 1)]]);
+        let len = data.len();
+        data.truncate(len - 4);
+        let size = data.len() as u32;
+        data[4..8].copy_from_slice(&size.to_le_bytes());
+        assert_eq!(error(&data), "data ends unexpectedly");
+    }
+
+    fn alias_table() -> ResourceTable {
+        use arsc::tests::{config_bytes, package_bytes, simple_entry, table_bytes, type_bytes};
+        let reference = |key: u32, target: u32| Some(simple_entry(key, TYPE_REFERENCE, target));
+        let mut entries = vec![Some(simple_entry(0, TYPE_STRING, 0)),
+                               reference(1, 0x7f010000),
+                               reference(2, 0x7f010003),
+                               reference(3, 0x7f010002),
+                               reference(4, 0x7f010004),
+                               reference(5, 0x7f0100ff)];
+        // A chain of MAX_ALIAS_DEPTH aliases ending in app_name
+        for i in 0..MAX_ALIAS_DEPTH as u32 {
+            entries.push(reference(6, if i == 0 { 0x7f010000 } else { 0x7f010005 + i }));
+        }
+        let package = package_bytes(0x7f,
+                                    "com.example",
+                                    &["string"],
+                                    &["app_name", "title", "a", "b", "self", "dangling",
+                                      "chain"],
+                                    &[type_bytes(1, &config_bytes(&[0, 0], &[0, 0], 0), &entries)]);
+        let data = table_bytes(&pool_bytes(&["Hello"], false, &[]), &[package]);
+        return ::arsc::decode(&data).unwrap();
+    }
+
+    #[test]
+    fn references_are_named_and_inlined() {
+        let table = alias_table();
+        let name = |value: ResValue, inline: bool| value_string(&value, Some(&table), inline);
+        assert_eq!(name(ResValue::Reference(0x7f010001), false), "@string/title");
+        assert_eq!(name(ResValue::Reference(0x7f010001), true), "Hello");
+        assert_eq!(name(ResValue::Attribute(0x7f010001), true), "?string/title");
+        assert_eq!(name(ResValue::Reference(0x7f010005), true), "@string/dangling");
+        assert_eq!(name(ResValue::Reference(0x7f020000), true), "@0x7f020000");
+        assert_eq!(name(ResValue::Attribute(0x01010000), false), "?android:attr/theme");
+        assert_eq!(value_string(&ResValue::Reference(0x7f010000), None, true), "@0x7f010000");
+
+        // The longest chain followed
+        let last = 0x7f010006 + MAX_ALIAS_DEPTH as u32 - 1;
+        assert_eq!(name(ResValue::Reference(last), true), "Hello");
+    }
+
+    #[test]
+    fn alias_cycles_fall_back_to_the_id() {
+        let table = alias_table();
+        let name = |id: u32| value_string(&ResValue::Reference(id), Some(&table), true);
+        assert_eq!(name(0x7f010002), "@0x7f010002");
+        assert_eq!(name(0x7f010003), "@0x7f010003");
+        assert_eq!(name(0x7f010004), "@0x7f010004");
+        // Named as usual when values are not inlined
+        assert_eq!(value_string(&ResValue::Reference(0x7f010002), Some(&table), false),
+                   "@string/a");
+    }
+
+    #[test]
+    fn to_xml_names_references() {
+        let data = document(&[pool_bytes(&["label", "application"], false, &[]),
+                              chunk_bytes(RES_XML_RESOURCE_MAP_TYPE, 8, &u32s(&[0x01010001])),
+                              start(NO_ENTRY,
+                                    1,
+                                    &[(NO_ENTRY, 0, NO_ENTRY, TYPE_REFERENCE, 0x7f010002)]),
+                              end(NO_ENTRY, 1)]);
+        let document = decode(&data).unwrap();
+        let table = alias_table();
+        assert!(to_xml_with_resources(&document, Some(&table), false)
+            .contains(" android:label=\"@string/a\" />"));
+        assert!(to_xml_with_resources(&document, Some(&table), true)
+            .contains(" android:label=\"@0x7f010002\" />"));
+    }
 }
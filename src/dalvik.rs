@@ -0,0 +1,1158 @@
+use dex::{Annotation, DexFile, EncodedAnnotation, EncodedValue, MethodHandleType};
+use std::collections::BTreeMap;
+
+/// Instruction formats as named in the Dalvik bytecode specification. The
+/// first digit is the size in code units, the second the register count
+/// and the letter the kind of additional data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Invalid,
+    F10x,
+    F12x,
+    F11n,
+    F11x,
+    F10t,
+    F20t,
+    F22x,
+    F21t,
+    F21s,
+    F21h,
+    F21c,
+    F23x,
+    F22b,
+    F22t,
+    F22s,
+    F22c,
+    F30t,
+    F32x,
+    F31i,
+    F31t,
+    F31c,
+    F35c,
+    F3rc,
+    F45cc,
+    F4rcc,
+    F51l,
+}
+
+impl Format {
+    /// Width of an instruction of this format in 16-bit code units
+    pub fn size(&self) -> usize {
+        match *self {
+            Format::Invalid => 1,
+            Format::F10x | Format::F12x | Format::F11n | Format::F11x | Format::F10t => 1,
+            Format::F20t | Format::F22x | Format::F21t | Format::F21s | Format::F21h |
+            Format::F21c | Format::F23x | Format::F22b | Format::F22t | Format::F22s |
+            Format::F22c => 2,
+            Format::F30t | Format::F32x | Format::F31i | Format::F31t | Format::F31c |
+            Format::F35c | Format::F3rc => 3,
+            Format::F45cc | Format::F4rcc => 4,
+            Format::F51l => 5,
+        }
+    }
+}
+
+/// What the index operand of an instruction refers to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndexType {
+    None,
+    String,
+    Type,
+    Field,
+    Method,
+    MethodAndProto,
+    Proto,
+    CallSite,
+    MethodHandle,
+}
+
+#[derive(Debug)]
+pub struct Opcode {
+    pub name: &'static str,
+    pub format: Format,
+    pub index_type: IndexType,
+}
+
+macro_rules! op {
+    ($name:expr, $format:ident, $index:ident) => (
+        Opcode { name: $name, format: Format::$format, index_type: IndexType::$index }
+    )
+}
+
+pub static OPCODES: [Opcode; 256] = [
+    /* 0x00 */ op!("nop", F10x, None),
+    /* 0x01 */ op!("move", F12x, None),
+    /* 0x02 */ op!("move/from16", F22x, None),
+    /* 0x03 */ op!("move/16", F32x, None),
+    /* 0x04 */ op!("move-wide", F12x, None),
+    /* 0x05 */ op!("move-wide/from16", F22x, None),
+    /* 0x06 */ op!("move-wide/16", F32x, None),
+    /* 0x07 */ op!("move-object", F12x, None),
+    /* 0x08 */ op!("move-object/from16", F22x, None),
+    /* 0x09 */ op!("move-object/16", F32x, None),
+    /* 0x0a */ op!("move-result", F11x, None),
+    /* 0x0b */ op!("move-result-wide", F11x, None),
+    /* 0x0c */ op!("move-result-object", F11x, None),
+    /* 0x0d */ op!("move-exception", F11x, None),
+    /* 0x0e */ op!("return-void", F10x, None),
+    /* 0x0f */ op!("return", F11x, None),
+    /* 0x10 */ op!("return-wide", F11x, None),
+    /* 0x11 */ op!("return-object", F11x, None),
+    /* 0x12 */ op!("const/4", F11n, None),
+    /* 0x13 */ op!("const/16", F21s, None),
+    /* 0x14 */ op!("const", F31i, None),
+    /* 0x15 */ op!("const/high16", F21h, None),
+    /* 0x16 */ op!("const-wide/16", F21s, None),
+    /* 0x17 */ op!("const-wide/32", F31i, None),
+    /* 0x18 */ op!("const-wide", F51l, None),
+    /* 0x19 */ op!("const-wide/high16", F21h, None),
+    /* 0x1a */ op!("const-string", F21c, String),
+    /* 0x1b */ op!("const-string/jumbo", F31c, String),
+    /* 0x1c */ op!("const-class", F21c, Type),
+    /* 0x1d */ op!("monitor-enter", F11x, None),
+    /* 0x1e */ op!("monitor-exit", F11x, None),
+    /* 0x1f */ op!("check-cast", F21c, Type),
+    /* 0x20 */ op!("instance-of", F22c, Type),
+    /* 0x21 */ op!("array-length", F12x, None),
+    /* 0x22 */ op!("new-instance", F21c, Type),
+    /* 0x23 */ op!("new-array", F22c, Type),
+    /* 0x24 */ op!("filled-new-array", F35c, Type),
+    /* 0x25 */ op!("filled-new-array/range", F3rc, Type),
+    /* 0x26 */ op!("fill-array-data", F31t, None),
+    /* 0x27 */ op!("throw", F11x, None),
+    /* 0x28 */ op!("goto", F10t, None),
+    /* 0x29 */ op!("goto/16", F20t, None),
+    /* 0x2a */ op!("goto/32", F30t, None),
+    /* 0x2b */ op!("packed-switch", F31t, None),
+    /* 0x2c */ op!("sparse-switch", F31t, None),
+    /* 0x2d */ op!("cmpl-float", F23x, None),
+    /* 0x2e */ op!("cmpg-float", F23x, None),
+    /* 0x2f */ op!("cmpl-double", F23x, None),
+    /* 0x30 */ op!("cmpg-double", F23x, None),
+    /* 0x31 */ op!("cmp-long", F23x, None),
+    /* 0x32 */ op!("if-eq", F22t, None),
+    /* 0x33 */ op!("if-ne", F22t, None),
+    /* 0x34 */ op!("if-lt", F22t, None),
+    /* 0x35 */ op!("if-ge", F22t, None),
+    /* 0x36 */ op!("if-gt", F22t, None),
+    /* 0x37 */ op!("if-le", F22t, None),
+    /* 0x38 */ op!("if-eqz", F21t, None),
+    /* 0x39 */ op!("if-nez", F21t, None),
+    /* 0x3a */ op!("if-ltz", F21t, None),
+    /* 0x3b */ op!("if-gez", F21t, None),
+    /* 0x3c */ op!("if-gtz", F21t, None),
+    /* 0x3d */ op!("if-lez", F21t, None),
+    /* 0x3e */ op!("unused-3e", Invalid, None),
+    /* 0x3f */ op!("unused-3f", Invalid, None),
+    /* 0x40 */ op!("unused-40", Invalid, None),
+    /* 0x41 */ op!("unused-41", Invalid, None),
+    /* 0x42 */ op!("unused-42", Invalid, None),
+    /* 0x43 */ op!("unused-43", Invalid, None),
+    /* 0x44 */ op!("aget", F23x, None),
+    /* 0x45 */ op!("aget-wide", F23x, None),
+    /* 0x46 */ op!("aget-object", F23x, None),
+    /* 0x47 */ op!("aget-boolean", F23x, None),
+    /* 0x48 */ op!("aget-byte", F23x, None),
+    /* 0x49 */ op!("aget-char", F23x, None),
+    /* 0x4a */ op!("aget-short", F23x, None),
+    /* 0x4b */ op!("aput", F23x, None),
+    /* 0x4c */ op!("aput-wide", F23x, None),
+    /* 0x4d */ op!("aput-object", F23x, None),
+    /* 0x4e */ op!("aput-boolean", F23x, None),
+    /* 0x4f */ op!("aput-byte", F23x, None),
+    /* 0x50 */ op!("aput-char", F23x, None),
+    /* 0x51 */ op!("aput-short", F23x, None),
+    /* 0x52 */ op!("iget", F22c, Field),
+    /* 0x53 */ op!("iget-wide", F22c, Field),
+    /* 0x54 */ op!("iget-object", F22c, Field),
+    /* 0x55 */ op!("iget-boolean", F22c, Field),
+    /* 0x56 */ op!("iget-byte", F22c, Field),
+    /* 0x57 */ op!("iget-char", F22c, Field),
+    /* 0x58 */ op!("iget-short", F22c, Field),
+    /* 0x59 */ op!("iput", F22c, Field),
+    /* 0x5a */ op!("iput-wide", F22c, Field),
+    /* 0x5b */ op!("iput-object", F22c, Field),
+    /* 0x5c */ op!("iput-boolean", F22c, Field),
+    /* 0x5d */ op!("iput-byte", F22c, Field),
+    /* 0x5e */ op!("iput-char", F22c, Field),
+    /* 0x5f */ op!("iput-short", F22c, Field),
+    /* 0x60 */ op!("sget", F21c, Field),
+    /* 0x61 */ op!("sget-wide", F21c, Field),
+    /* 0x62 */ op!("sget-object", F21c, Field),
+    /* 0x63 */ op!("sget-boolean", F21c, Field),
+    /* 0x64 */ op!("sget-byte", F21c, Field),
+    /* 0x65 */ op!("sget-char", F21c, Field),
+    /* 0x66 */ op!("sget-short", F21c, Field),
+    /* 0x67 */ op!("sput", F21c, Field),
+    /* 0x68 */ op!("sput-wide", F21c, Field),
+    /* 0x69 */ op!("sput-object", F21c, Field),
+    /* 0x6a */ op!("sput-boolean", F21c, Field),
+    /* 0x6b */ op!("sput-byte", F21c, Field),
+    /* 0x6c */ op!("sput-char", F21c, Field),
+    /* 0x6d */ op!("sput-short", F21c, Field),
+    /* 0x6e */ op!("invoke-virtual", F35c, Method),
+    /* 0x6f */ op!("invoke-super", F35c, Method),
+    /* 0x70 */ op!("invoke-direct", F35c, Method),
+    /* 0x71 */ op!("invoke-static", F35c, Method),
+    /* 0x72 */ op!("invoke-interface", F35c, Method),
+    /* 0x73 */ op!("unused-73", Invalid, None),
+    /* 0x74 */ op!("invoke-virtual/range", F3rc, Method),
+    /* 0x75 */ op!("invoke-super/range", F3rc, Method),
+    /* 0x76 */ op!("invoke-direct/range", F3rc, Method),
+    /* 0x77 */ op!("invoke-static/range", F3rc, Method),
+    /* 0x78 */ op!("invoke-interface/range", F3rc, Method),
+    /* 0x79 */ op!("unused-79", Invalid, None),
+    /* 0x7a */ op!("unused-7a", Invalid, None),
+    /* 0x7b */ op!("neg-int", F12x, None),
+    /* 0x7c */ op!("not-int", F12x, None),
+    /* 0x7d */ op!("neg-long", F12x, None),
+    /* 0x7e */ op!("not-long", F12x, None),
+    /* 0x7f */ op!("neg-float", F12x, None),
+    /* 0x80 */ op!("neg-double", F12x, None),
+    /* 0x81 */ op!("int-to-long", F12x, None),
+    /* 0x82 */ op!("int-to-float", F12x, None),
+    /* 0x83 */ op!("int-to-double", F12x, None),
+    /* 0x84 */ op!("long-to-int", F12x, None),
+    /* 0x85 */ op!("long-to-float", F12x, None),
+    /* 0x86 */ op!("long-to-double", F12x, None),
+    /* 0x87 */ op!("float-to-int", F12x, None),
+    /* 0x88 */ op!("float-to-long", F12x, None),
+    /* 0x89 */ op!("float-to-double", F12x, None),
+    /* 0x8a */ op!("double-to-int", F12x, None),
+    /* 0x8b */ op!("double-to-long", F12x, None),
+    /* 0x8c */ op!("double-to-float", F12x, None),
+    /* 0x8d */ op!("int-to-byte", F12x, None),
+    /* 0x8e */ op!("int-to-char", F12x, None),
+    /* 0x8f */ op!("int-to-short", F12x, None),
+    /* 0x90 */ op!("add-int", F23x, None),
+    /* 0x91 */ op!("sub-int", F23x, None),
+    /* 0x92 */ op!("mul-int", F23x, None),
+    /* 0x93 */ op!("div-int", F23x, None),
+    /* 0x94 */ op!("rem-int", F23x, None),
+    /* 0x95 */ op!("and-int", F23x, None),
+    /* 0x96 */ op!("or-int", F23x, None),
+    /* 0x97 */ op!("xor-int", F23x, None),
+    /* 0x98 */ op!("shl-int", F23x, None),
+    /* 0x99 */ op!("shr-int", F23x, None),
+    /* 0x9a */ op!("ushr-int", F23x, None),
+    /* 0x9b */ op!("add-long", F23x, None),
+    /* 0x9c */ op!("sub-long", F23x, None),
+    /* 0x9d */ op!("mul-long", F23x, None),
+    /* 0x9e */ op!("div-long", F23x, None),
+    /* 0x9f */ op!("rem-long", F23x, None),
+    /* 0xa0 */ op!("and-long", F23x, None),
+    /* 0xa1 */ op!("or-long", F23x, None),
+    /* 0xa2 */ op!("xor-long", F23x, None),
+    /* 0xa3 */ op!("shl-long", F23x, None),
+    /* 0xa4 */ op!("shr-long", F23x, None),
+    /* 0xa5 */ op!("ushr-long", F23x, None),
+    /* 0xa6 */ op!("add-float", F23x, None),
+    /* 0xa7 */ op!("sub-float", F23x, None),
+    /* 0xa8 */ op!("mul-float", F23x, None),
+    /* 0xa9 */ op!("div-float", F23x, None),
+    /* 0xaa */ op!("rem-float", F23x, None),
+    /* 0xab */ op!("add-double", F23x, None),
+    /* 0xac */ op!("sub-double", F23x, None),
+    /* 0xad */ op!("mul-double", F23x, None),
+    /* 0xae */ op!("div-double", F23x, None),
+    /* 0xaf */ op!("rem-double", F23x, None),
+    /* 0xb0 */ op!("add-int/2addr", F12x, None),
+    /* 0xb1 */ op!("sub-int/2addr", F12x, None),
+    /* 0xb2 */ op!("mul-int/2addr", F12x, None),
+    /* 0xb3 */ op!("div-int/2addr", F12x, None),
+    /* 0xb4 */ op!("rem-int/2addr", F12x, None),
+    /* 0xb5 */ op!("and-int/2addr", F12x, None),
+    /* 0xb6 */ op!("or-int/2addr", F12x, None),
+    /* 0xb7 */ op!("xor-int/2addr", F12x, None),
+    /* 0xb8 */ op!("shl-int/2addr", F12x, None),
+    /* 0xb9 */ op!("shr-int/2addr", F12x, None),
+    /* 0xba */ op!("ushr-int/2addr", F12x, None),
+    /* 0xbb */ op!("add-long/2addr", F12x, None),
+    /* 0xbc */ op!("sub-long/2addr", F12x, None),
+    /* 0xbd */ op!("mul-long/2addr", F12x, None),
+    /* 0xbe */ op!("div-long/2addr", F12x, None),
+    /* 0xbf */ op!("rem-long/2addr", F12x, None),
+    /* 0xc0 */ op!("and-long/2addr", F12x, None),
+    /* 0xc1 */ op!("or-long/2addr", F12x, None),
+    /* 0xc2 */ op!("xor-long/2addr", F12x, None),
+    /* 0xc3 */ op!("shl-long/2addr", F12x, None),
+    /* 0xc4 */ op!("shr-long/2addr", F12x, None),
+    /* 0xc5 */ op!("ushr-long/2addr", F12x, None),
+    /* 0xc6 */ op!("add-float/2addr", F12x, None),
+    /* 0xc7 */ op!("sub-float/2addr", F12x, None),
+    /* 0xc8 */ op!("mul-float/2addr", F12x, None),
+    /* 0xc9 */ op!("div-float/2addr", F12x, None),
+    /* 0xca */ op!("rem-float/2addr", F12x, None),
+    /* 0xcb */ op!("add-double/2addr", F12x, None),
+    /* 0xcc */ op!("sub-double/2addr", F12x, None),
+    /* 0xcd */ op!("mul-double/2addr", F12x, None),
+    /* 0xce */ op!("div-double/2addr", F12x, None),
+    /* 0xcf */ op!("rem-double/2addr", F12x, None),
+    /* 0xd0 */ op!("add-int/lit16", F22s, None),
+    /* 0xd1 */ op!("rsub-int", F22s, None),
+    /* 0xd2 */ op!("mul-int/lit16", F22s, None),
+    /* 0xd3 */ op!("div-int/lit16", F22s, None),
+    /* 0xd4 */ op!("rem-int/lit16", F22s, None),
+    /* 0xd5 */ op!("and-int/lit16", F22s, None),
+    /* 0xd6 */ op!("or-int/lit16", F22s, None),
+    /* 0xd7 */ op!("xor-int/lit16", F22s, None),
+    /* 0xd8 */ op!("add-int/lit8", F22b, None),
+    /* 0xd9 */ op!("rsub-int/lit8", F22b, None),
+    /* 0xda */ op!("mul-int/lit8", F22b, None),
+    /* 0xdb */ op!("div-int/lit8", F22b, None),
+    /* 0xdc */ op!("rem-int/lit8", F22b, None),
+    /* 0xdd */ op!("and-int/lit8", F22b, None),
+    /* 0xde */ op!("or-int/lit8", F22b, None),
+    /* 0xdf */ op!("xor-int/lit8", F22b, None),
+    /* 0xe0 */ op!("shl-int/lit8", F22b, None),
+    /* 0xe1 */ op!("shr-int/lit8", F22b, None),
+    /* 0xe2 */ op!("ushr-int/lit8", F22b, None),
+    /* 0xe3 */ op!("unused-e3", Invalid, None),
+    /* 0xe4 */ op!("unused-e4", Invalid, None),
+    /* 0xe5 */ op!("unused-e5", Invalid, None),
+    /* 0xe6 */ op!("unused-e6", Invalid, None),
+    /* 0xe7 */ op!("unused-e7", Invalid, None),
+    /* 0xe8 */ op!("unused-e8", Invalid, None),
+    /* 0xe9 */ op!("unused-e9", Invalid, None),
+    /* 0xea */ op!("unused-ea", Invalid, None),
+    /* 0xeb */ op!("unused-eb", Invalid, None),
+    /* 0xec */ op!("unused-ec", Invalid, None),
+    /* 0xed */ op!("unused-ed", Invalid, None),
+    /* 0xee */ op!("unused-ee", Invalid, None),
+    /* 0xef */ op!("unused-ef", Invalid, None),
+    /* 0xf0 */ op!("unused-f0", Invalid, None),
+    /* 0xf1 */ op!("unused-f1", Invalid, None),
+    /* 0xf2 */ op!("unused-f2", Invalid, None),
+    /* 0xf3 */ op!("unused-f3", Invalid, None),
+    /* 0xf4 */ op!("unused-f4", Invalid, None),
+    /* 0xf5 */ op!("unused-f5", Invalid, None),
+    /* 0xf6 */ op!("unused-f6", Invalid, None),
+    /* 0xf7 */ op!("unused-f7", Invalid, None),
+    /* 0xf8 */ op!("unused-f8", Invalid, None),
+    /* 0xf9 */ op!("unused-f9", Invalid, None),
+    /* 0xfa */ op!("invoke-polymorphic", F45cc, MethodAndProto),
+    /* 0xfb */ op!("invoke-polymorphic/range", F4rcc, MethodAndProto),
+    /* 0xfc */ op!("invoke-custom", F35c, CallSite),
+    /* 0xfd */ op!("invoke-custom/range", F3rc, CallSite),
+    /* 0xfe */ op!("const-method-handle", F21c, MethodHandle),
+    /* 0xff */ op!("const-method-type", F21c, Proto),
+];
+
+const PACKED_SWITCH_PAYLOAD: u16 = 0x0100;
+const SPARSE_SWITCH_PAYLOAD: u16 = 0x0200;
+const FILL_ARRAY_DATA_PAYLOAD: u16 = 0x0300;
+
+/// The pseudo-instructions that follow the regular code and carry the
+/// data for switch and fill-array-data instructions
+#[derive(Debug)]
+pub enum Payload {
+    PackedSwitch { first_key: i32, targets: Vec<i32> },
+    SparseSwitch { keys: Vec<i32>, targets: Vec<i32> },
+    FillArrayData { element_width: u16, data: Vec<u8> },
+}
+
+#[derive(Debug)]
+pub struct Instruction {
+    offset: usize,
+    opcode: u8,
+    size: usize,
+    registers: Vec<u16>,
+    literal: i64,
+    branch: i32,
+    index: u32,
+    proto_index: u32,
+    payload: Option<Payload>,
+}
+
+impl Instruction {
+    pub fn opcode(&self) -> &'static Opcode {
+        return &OPCODES[self.opcode as usize];
+    }
+
+    /// Address of the instruction in code units from the start of insns
+    pub fn offset(&self) -> usize {
+        return self.offset;
+    }
+
+    /// Width of the instruction in code units, including payload data
+    pub fn size(&self) -> usize {
+        return self.size;
+    }
+
+    /// The string, type, field, method, ... index operand
+    pub fn index(&self) -> u32 {
+        return self.index;
+    }
+
+    /// The proto index of invoke-polymorphic
+    pub fn proto_index(&self) -> u32 {
+        return self.proto_index;
+    }
+
+    pub fn payload(&self) -> Option<&Payload> {
+        return self.payload.as_ref();
+    }
+
+    /// Absolute address of the branch target or referenced payload, `None`
+    /// for other instructions and branches before the start of the code
+    pub fn target(&self) -> Option<usize> {
+        match self.opcode().format {
+            Format::F10t | Format::F20t | Format::F30t | Format::F21t | Format::F22t |
+            Format::F31t => {
+                let target = self.offset as i64 + self.branch as i64;
+                if target < 0 {
+                    return None;
+                }
+                return Some(target as usize);
+            }
+            _ => None,
+        }
+    }
+}
+
+fn unit(insns: &[u16], offset: usize) -> Option<u16> {
+    return insns.get(offset).cloned();
+}
+
+fn unit32(insns: &[u16], offset: usize) -> Option<u32> {
+    match (unit(insns, offset), unit(insns, offset + 1)) {
+        (Some(lo), Some(hi)) => Some(lo as u32 | (hi as u32) << 16),
+        _ => None,
+    }
+}
+
+fn decode_payload(insns: &[u16], offset: usize, ident: u16) -> Option<Instruction> {
+    let (payload, size) = match ident {
+        PACKED_SWITCH_PAYLOAD => {
+            let size = unit(insns, offset + 1)? as usize;
+            let first_key = unit32(insns, offset + 2)? as i32;
+            let mut targets = vec![];
+            for i in 0..size {
+                targets.push(unit32(insns, offset + 4 + i * 2)? as i32);
+            }
+            (Payload::PackedSwitch {
+                first_key: first_key,
+                targets: targets,
+            },
+             size * 2 + 4)
+        }
+        SPARSE_SWITCH_PAYLOAD => {
+            let size = unit(insns, offset + 1)? as usize;
+            let mut keys = vec![];
+            let mut targets = vec![];
+            for i in 0..size {
+                keys.push(unit32(insns, offset + 2 + i * 2)? as i32);
+                targets.push(unit32(insns, offset + 2 + (size + i) * 2)? as i32);
+            }
+            (Payload::SparseSwitch {
+                keys: keys,
+                targets: targets,
+            },
+             size * 4 + 2)
+        }
+        _ => {
+            let element_width = unit(insns, offset + 1)?;
+            let count = unit32(insns, offset + 2)? as usize;
+            let byte_count = count.checked_mul(element_width as usize)?;
+            if offset + 4 + byte_count.div_ceil(2) > insns.len() {
+                return None;
+            }
+            let mut data = Vec::with_capacity(byte_count);
+            for i in 0..byte_count {
+                let word = unit(insns, offset + 4 + i / 2)?;
+                data.push((word >> ((i % 2) * 8)) as u8);
+            }
+            (Payload::FillArrayData {
+                element_width: element_width,
+                data: data,
+            },
+             byte_count.div_ceil(2) + 4)
+        }
+    };
+
+    return Some(Instruction {
+        offset: offset,
+        opcode: 0,
+        size: size,
+        registers: vec![],
+        literal: 0,
+        branch: 0,
+        index: 0,
+        proto_index: 0,
+        payload: Some(payload),
+    });
+}
+
+/// Decodes the instruction starting at `offset` code units into `insns`.
+/// Returns `None` for unused opcodes, instructions running past the end and
+/// branches leaving the code.
+pub fn decode(insns: &[u16], offset: usize) -> Option<Instruction> {
+    let w0 = unit(insns, offset)?;
+    let opcode = (w0 & 0xFF) as u8;
+
+    match w0 {
+        PACKED_SWITCH_PAYLOAD | SPARSE_SWITCH_PAYLOAD | FILL_ARRAY_DATA_PAYLOAD => {
+            return decode_payload(insns, offset, w0)
+        }
+        _ => {}
+    }
+
+    let format = OPCODES[opcode as usize].format;
+    if format == Format::Invalid || offset + format.size() > insns.len() {
+        return None;
+    }
+
+    let w = |i: usize| insns[offset + i];
+    let aa = w0 >> 8;
+    let a = (w0 >> 8) & 0xF;
+    let b = w0 >> 12;
+
+    let mut instruction = Instruction {
+        offset: offset,
+        opcode: opcode,
+        size: format.size(),
+        registers: vec![],
+        literal: 0,
+        branch: 0,
+        index: 0,
+        proto_index: 0,
+        payload: None,
+    };
+
+    match format {
+        Format::Invalid | Format::F10x => {}
+        Format::F12x => instruction.registers = vec![a, b],
+        Format::F11n => {
+            instruction.registers = vec![a];
+            instruction.literal = ((b << 12) as i16 >> 12) as i64;
+        }
+        Format::F11x => instruction.registers = vec![aa],
+        Format::F10t => instruction.branch = aa as u8 as i8 as i32,
+        Format::F20t => instruction.branch = w(1) as i16 as i32,
+        Format::F22x => instruction.registers = vec![aa, w(1)],
+        Format::F21t => {
+            instruction.registers = vec![aa];
+            instruction.branch = w(1) as i16 as i32;
+        }
+        Format::F21s => {
+            instruction.registers = vec![aa];
+            instruction.literal = w(1) as i16 as i64;
+        }
+        Format::F21h => {
+            instruction.registers = vec![aa];
+            // const/high16 fills the top of an int, const-wide/high16 of a long
+            instruction.literal = if opcode == 0x15 {
+                ((w(1) as u32) << 16) as i32 as i64
+            } else {
+                ((w(1) as u64) << 48) as i64
+            };
+        }
+        Format::F21c => {
+            instruction.registers = vec![aa];
+            instruction.index = w(1) as u32;
+        }
+        Format::F23x => instruction.registers = vec![aa, w(1) & 0xFF, w(1) >> 8],
+        Format::F22b => {
+            instruction.registers = vec![aa, w(1) & 0xFF];
+            instruction.literal = (w(1) >> 8) as u8 as i8 as i64;
+        }
+        Format::F22t => {
+            instruction.registers = vec![a, b];
+            instruction.branch = w(1) as i16 as i32;
+        }
+        Format::F22s => {
+            instruction.registers = vec![a, b];
+            instruction.literal = w(1) as i16 as i64;
+        }
+        Format::F22c => {
+            instruction.registers = vec![a, b];
+            instruction.index = w(1) as u32;
+        }
+        Format::F30t => instruction.branch = (w(1) as u32 | (w(2) as u32) << 16) as i32,
+        Format::F32x => instruction.registers = vec![w(1), w(2)],
+        Format::F31i => {
+            instruction.registers = vec![aa];
+            instruction.literal = (w(1) as u32 | (w(2) as u32) << 16) as i32 as i64;
+        }
+        Format::F31t => {
+            instruction.registers = vec![aa];
+            instruction.branch = (w(1) as u32 | (w(2) as u32) << 16) as i32;
+        }
+        Format::F31c => {
+            instruction.registers = vec![aa];
+            instruction.index = w(1) as u32 | (w(2) as u32) << 16;
+        }
+        Format::F35c | Format::F45cc => {
+            let count = b as usize;
+            if count > 5 {
+                return None;
+            }
+            let regs = [w(2) & 0xF, (w(2) >> 4) & 0xF, (w(2) >> 8) & 0xF, w(2) >> 12, a];
+            instruction.registers = regs[..count].to_vec();
+            instruction.index = w(1) as u32;
+            if format == Format::F45cc {
+                instruction.proto_index = w(3) as u32;
+            }
+        }
+        Format::F3rc | Format::F4rcc => {
+            let first = w(2) as u32;
+            instruction.registers = (first..first + aa as u32).map(|r| r as u16).collect();
+            instruction.index = w(1) as u32;
+            if format == Format::F4rcc {
+                instruction.proto_index = w(3) as u32;
+            }
+        }
+        Format::F51l => {
+            instruction.registers = vec![aa];
+            instruction.literal = (w(1) as u64 | (w(2) as u64) << 16 | (w(3) as u64) << 32 |
+                                   (w(4) as u64) << 48) as i64;
+        }
+    }
+
+    match format {
+        Format::F10t | Format::F20t | Format::F30t | Format::F21t | Format::F22t |
+        Format::F31t => {
+            match instruction.target() {
+                Some(target) if target < insns.len() => {}
+                _ => return None,
+            }
+        }
+        _ => {}
+    }
+
+    return Some(instruction);
+}
+
+/// Formats a literal the way baksmali does: signed hex, with an `L` suffix
+/// for values that do not fit into an int.
+pub fn format_literal(value: i64) -> String {
+    let suffix = if value != value as i32 as i64 {
+        "L"
+    } else {
+        ""
+    };
+    if value < 0 {
+        return format!("-0x{:x}{}", (value as i128).abs(), suffix);
+    }
+    return format!("0x{:x}{}", value, suffix);
+}
+
+/// Escapes a string for use in a smali string literal
+pub fn escape_string(value: &str) -> String {
+    return escape(value.encode_utf16(), '"');
+}
+
+/// Escapes UTF-16 code units for use in a smali string literal. Unpaired
+/// surrogates are written as `\uXXXX` like any other non-ASCII unit.
+pub fn escape_units(units: &[u16]) -> String {
+    return escape(units.iter().cloned(), '"');
+}
+
+fn escape<I: Iterator<Item = u16>>(units: I, quote: char) -> String {
+    let mut escaped = String::new();
+    escaped.push(quote);
+    for unit in units {
+        match unit {
+            0x22 => escaped.push_str("\\\""),
+            0x27 => escaped.push_str("\\'"),
+            0x5C => escaped.push_str("\\\\"),
+            0x0A => escaped.push_str("\\n"),
+            0x0D => escaped.push_str("\\r"),
+            0x09 => escaped.push_str("\\t"),
+            0x20..=0x7E => escaped.push(unit as u8 as char),
+            _ => escaped.push_str(&format!("\\u{:04x}", unit)),
+        }
+    }
+    escaped.push(quote);
+    return escaped;
+}
+
+fn format_float(value: f64, suffix: &str) -> String {
+    if value.is_nan() {
+        return format!("NaN{}", suffix);
+    }
+    if value.is_infinite() {
+        let sign = if value < 0.0 { "-" } else { "" };
+        return format!("{}Infinity{}", sign, suffix);
+    }
+    return format!("{:?}{}", value, suffix);
+}
+
+/// Formats an encoded_value as smali. Arrays and annotations span several
+/// lines, nested content is indented by four spaces.
+pub fn format_value(dex: &DexFile, value: &EncodedValue) -> String {
+    let resolved = match *value {
+        EncodedValue::Byte(v) => Some(format!("{}t", format_literal(v as i64))),
+        EncodedValue::Short(v) => Some(format!("{}s", format_literal(v as i64))),
+        EncodedValue::Char(v) => Some(escape(Some(v).into_iter(), '\'')),
+        EncodedValue::Int(v) => Some(format_literal(v as i64)),
+        EncodedValue::Long(v) => {
+            let literal = format_literal(v);
+            Some(if literal.ends_with('L') { literal } else { literal + "L" })
+        }
+        EncodedValue::Float(v) => {
+            // Print the shortest representation of the f32, not of the f64
+            if v.is_finite() {
+                Some(format!("{:?}f", v))
+            } else {
+                Some(format_float(v as f64, "f"))
+            }
+        }
+        EncodedValue::Double(v) => Some(format_float(v, "")),
+        EncodedValue::MethodType(idx) => dex.proto_descriptor(idx),
+        EncodedValue::MethodHandle(idx) => dex.method_handle_descriptor(idx),
+        EncodedValue::String(idx) => dex.string_units(idx).map(|units| escape_units(&units)),
+        EncodedValue::Type(idx) => dex.type_descriptor(idx).map(|s| s.to_string()),
+        EncodedValue::Field(idx) => dex.field_descriptor(idx),
+        EncodedValue::Method(idx) => dex.method_descriptor(idx),
+        EncodedValue::Enum(idx) => dex.field_descriptor(idx).map(|f| format!(".enum {}", f)),
+        EncodedValue::Array(ref values) => {
+            if values.is_empty() {
+                Some("{}".to_string())
+            } else {
+                let items: Vec<String> =
+                    values.iter().map(|v| indent(&format_value(dex, v))).collect();
+                Some(format!("{{\n{}\n}}", items.join(",\n")))
+            }
+        }
+        EncodedValue::Annotation(ref annotation) => {
+            let ty = format_index(dex, IndexType::Type, annotation.type_idx());
+            let mut lines = vec![format!(".subannotation {}", ty)];
+            lines.extend(format_elements(dex, annotation));
+            lines.push(".end subannotation".to_string());
+            Some(lines.join("\n"))
+        }
+        EncodedValue::Null => Some("null".to_string()),
+        EncodedValue::Boolean(v) => Some(v.to_string()),
+    };
+
+    match resolved {
+        Some(value) => value,
+        None => {
+            let (kind, index) = match *value {
+                EncodedValue::MethodType(idx) => ("proto", idx),
+                EncodedValue::MethodHandle(idx) => ("method_handle", idx),
+                EncodedValue::String(idx) => ("string", idx),
+                EncodedValue::Type(idx) => ("type", idx),
+                EncodedValue::Method(idx) => ("method", idx),
+                EncodedValue::Field(idx) | EncodedValue::Enum(idx) => ("field", idx),
+                _ => ("index", 0),
+            };
+            format!("{}@{}", kind, index)
+        }
+    }
+}
+
+/// Formats an annotation like baksmali, one `name = value` per element
+pub fn format_annotation(dex: &DexFile, annotation: &Annotation) -> String {
+    let encoded = annotation.annotation();
+    let mut lines = vec![format!(".annotation {} {}",
+                                 annotation.visibility().name(),
+                                 format_index(dex, IndexType::Type, encoded.type_idx()))];
+    lines.extend(format_elements(dex, encoded));
+    lines.push(".end annotation".to_string());
+    return lines.join("\n");
+}
+
+fn format_elements(dex: &DexFile, annotation: &EncodedAnnotation) -> Vec<String> {
+    let mut lines = vec![];
+    for element in annotation.elements() {
+        let name = dex.string(element.name_idx()).unwrap_or("").to_string();
+        lines.push(indent(&format!("{} = {}", name, format_value(dex, element.value()))));
+    }
+    return lines;
+}
+
+fn indent(text: &str) -> String {
+    let lines: Vec<String> = text.lines().map(|line| format!("    {}", line)).collect();
+    return lines.join("\n");
+}
+
+/// Formats a call site like baksmali:
+/// `call_site_0("name", (Args)Ret, extra args...)@Lcls;->bootstrap(...)`
+fn format_call_site(dex: &DexFile, idx: u32) -> Option<String> {
+    let call_site = dex.call_site(idx)?;
+    let name = dex.string(call_site.name_idx()?)?;
+    let mut arguments = vec![escape_string(name), dex.proto_descriptor(call_site.proto_idx()?)?];
+    for argument in call_site.arguments() {
+        arguments.push(format_value(dex, argument));
+    }
+
+    let handle_idx = call_site.bootstrap()?;
+    let handle = dex.method_handle(handle_idx)?;
+    let bootstrap = if handle.handle_type() == MethodHandleType::InvokeStatic {
+        dex.method_descriptor(handle.field_or_method_id() as u32)?
+    } else {
+        dex.method_handle_descriptor(handle_idx)?
+    };
+    return Some(format!("call_site_{}({})@{}", idx, arguments.join(", "), bootstrap));
+}
+
+fn format_index(dex: &DexFile, index_type: IndexType, index: u32) -> String {
+    let resolved = match index_type {
+        IndexType::String => dex.string_units(index).map(|units| escape_units(&units)),
+        IndexType::Type => dex.type_descriptor(index).map(|s| s.to_string()),
+        IndexType::Field => dex.field_descriptor(index),
+        IndexType::Method | IndexType::MethodAndProto => dex.method_descriptor(index),
+        IndexType::Proto => dex.proto_descriptor(index),
+        IndexType::CallSite => format_call_site(dex, index),
+        IndexType::MethodHandle => dex.method_handle_descriptor(index),
+        IndexType::None => None,
+    };
+
+    match resolved {
+        Some(value) => value,
+        None => {
+            let kind = match index_type {
+                IndexType::String => "string",
+                IndexType::Type => "type",
+                IndexType::Field => "field",
+                IndexType::Method | IndexType::MethodAndProto => "method",
+                IndexType::Proto => "proto",
+                IndexType::CallSite => "call_site",
+                IndexType::MethodHandle => "method_handle",
+                IndexType::None => "index",
+            };
+            format!("{}@{}", kind, index)
+        }
+    }
+}
+
+fn format_registers(instruction: &Instruction) -> String {
+    let registers = &instruction.registers;
+    match instruction.opcode().format {
+        Format::F3rc | Format::F4rcc => {
+            if registers.is_empty() {
+                return "{}".to_string();
+            }
+            format!("{{v{} .. v{}}}", registers[0], registers[registers.len() - 1])
+        }
+        _ => {
+            let list: Vec<String> = registers.iter().map(|r| format!("v{}", r)).collect();
+            format!("{{{}}}", list.join(", "))
+        }
+    }
+}
+
+/// Assigns labels to every address referenced by a branch, switch or
+/// fill-array-data instruction, named after the referencing instruction
+/// like baksmali does (`:goto_1a`, `:cond_4`, `:pswitch_data_30`, ...).
+pub fn labels(instructions: &[Instruction]) -> BTreeMap<usize, Vec<String>> {
+    let mut labels: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    // Switch payload targets are relative to the switch, not the payload
+    let mut switches: BTreeMap<usize, usize> = BTreeMap::new();
+
+    for instruction in instructions {
+        let target = match instruction.target() {
+            Some(target) => target,
+            None => continue,
+        };
+        let prefix = match instruction.opcode {
+            0x28..=0x2a => "goto",
+            0x26 => "array",
+            0x2b => "pswitch_data",
+            0x2c => "sswitch_data",
+            _ => "cond",
+        };
+        if instruction.opcode == 0x2b || instruction.opcode == 0x2c {
+            switches.insert(target, instruction.offset);
+        }
+        add_label(&mut labels, target, format!("{}_{:x}", prefix, target));
+    }
+
+    for instruction in instructions {
+        let base = match switches.get(&instruction.offset) {
+            Some(base) => *base as i64,
+            None => continue,
+        };
+        let (prefix, targets) = match instruction.payload {
+            Some(Payload::PackedSwitch { ref targets, .. }) => ("pswitch", targets),
+            Some(Payload::SparseSwitch { ref targets, .. }) => ("sswitch", targets),
+            _ => continue,
+        };
+        for target in targets {
+            let target = (base + *target as i64) as usize;
+            add_label(&mut labels, target, format!("{}_{:x}", prefix, target));
+        }
+    }
+
+    return labels;
+}
+
+fn add_label(labels: &mut BTreeMap<usize, Vec<String>>, target: usize, label: String) {
+    let entry = labels.entry(target).or_default();
+    if !entry.contains(&label) {
+        entry.push(label);
+    }
+}
+
+fn label_at(labels: &BTreeMap<usize, Vec<String>>, target: usize) -> String {
+    match labels.get(&target).and_then(|l| l.first()) {
+        Some(label) => format!(":{}", label),
+        None => format!(":addr_{:x}", target),
+    }
+}
+
+fn format_payload(payload: &Payload, base: Option<usize>) -> Vec<String> {
+    let mut lines = vec![];
+    let target_label = |prefix: &str, target: i32| match base {
+        Some(base) => format!(":{}_{:x}", prefix, (base as i64 + target as i64) as usize),
+        None => format!("# {}", format_literal(target as i64)),
+    };
+
+    match *payload {
+        Payload::PackedSwitch { first_key, ref targets } => {
+            lines.push(format!(".packed-switch {}", format_literal(first_key as i64)));
+            for target in targets {
+                lines.push(format!("    {}", target_label("pswitch", *target)));
+            }
+            lines.push(".end packed-switch".to_string());
+        }
+        Payload::SparseSwitch { ref keys, ref targets } => {
+            lines.push(".sparse-switch".to_string());
+            for (key, target) in keys.iter().zip(targets.iter()) {
+                lines.push(format!("    {} -> {}",
+                                   format_literal(*key as i64),
+                                   target_label("sswitch", *target)));
+            }
+            lines.push(".end sparse-switch".to_string());
+        }
+        Payload::FillArrayData { element_width, ref data } => {
+            lines.push(format!(".array-data {}", element_width));
+            let width = element_width as usize;
+            if width != 0 {
+                for element in data.chunks(width) {
+                    let mut value: u64 = 0;
+                    for (i, byte) in element.iter().enumerate() {
+                        value |= (*byte as u64) << (i * 8);
+                    }
+                    let line = match width {
+                        1 => format!("{}t", format_literal(value as u8 as i8 as i64)),
+                        2 => format!("{}s", format_literal(value as u16 as i16 as i64)),
+                        4 => format_literal(value as u32 as i32 as i64),
+                        _ => {
+                            let literal = format_literal(value as i64);
+                            if literal.ends_with('L') {
+                                literal
+                            } else {
+                                literal + "L"
+                            }
+                        }
+                    };
+                    lines.push(format!("    {}", line));
+                }
+            }
+            lines.push(".end array-data".to_string());
+        }
+    }
+    return lines;
+}
+
+/// Formats a single instruction, resolving its index operand through `dex`
+/// and branch targets through `labels`.
+pub fn format_instruction(dex: &DexFile,
+                          instruction: &Instruction,
+                          labels: &BTreeMap<usize, Vec<String>>)
+                          -> String {
+    let opcode = instruction.opcode();
+    let regs = &instruction.registers;
+    let mut operands: Vec<String> = vec![];
+
+    match opcode.format {
+        Format::F35c | Format::F3rc | Format::F45cc | Format::F4rcc => {
+            operands.push(format_registers(instruction));
+        }
+        _ => {
+            for r in regs {
+                operands.push(format!("v{}", r));
+            }
+        }
+    }
+
+    match opcode.format {
+        Format::F11n | Format::F21s | Format::F21h | Format::F22b | Format::F22s |
+        Format::F31i | Format::F51l => operands.push(format_literal(instruction.literal)),
+        Format::F10t | Format::F20t | Format::F30t | Format::F21t | Format::F22t |
+        Format::F31t => operands.push(label_at(labels, instruction.target().unwrap())),
+        Format::F21c | Format::F22c | Format::F31c | Format::F35c | Format::F3rc => {
+            operands.push(format_index(dex, opcode.index_type, instruction.index))
+        }
+        Format::F45cc | Format::F4rcc => {
+            operands.push(format_index(dex, opcode.index_type, instruction.index));
+            operands.push(format_index(dex, IndexType::Proto, instruction.proto_index));
+        }
+        _ => {}
+    }
+
+    if operands.is_empty() {
+        return opcode.name.to_string();
+    }
+    return format!("{} {}", opcode.name, operands.join(", "));
+}
+
+/// Decodes a whole instruction stream. Decoding stops at the first unused
+/// opcode or truncated instruction, whose address is returned alongside.
+pub fn instructions(insns: &[u16]) -> (Vec<Instruction>, Option<usize>) {
+    let mut list = vec![];
+    let mut offset = 0;
+    while offset < insns.len() {
+        match decode(insns, offset) {
+            Some(instruction) => {
+                offset += instruction.size;
+                list.push(instruction);
+            }
+            None => return (list, Some(offset)),
+        }
+    }
+    return (list, None);
+}
+
+/// Produces a baksmali-style listing of a method's instruction stream,
+/// one line per label, instruction and payload directive.
+pub fn disassemble(dex: &DexFile, insns: &[u16]) -> Vec<String> {
+    let (instructions, invalid) = instructions(insns);
+    let labels = labels(&instructions);
+    return listing(dex, &instructions, &labels, &BTreeMap::new(), invalid);
+}
+
+/// Like `disassemble`, for callers that add their own labels such as
+/// try/catch ranges to the ones found by `labels`. `directives` are emitted
+/// verbatim ahead of the labels at their address; those at or past the end
+/// of the code follow the last instruction.
+pub fn listing(dex: &DexFile,
+               instructions: &[Instruction],
+               labels: &BTreeMap<usize, Vec<String>>,
+               directives: &BTreeMap<usize, Vec<String>>,
+               invalid: Option<usize>)
+               -> Vec<String> {
+    let mut switches: BTreeMap<usize, usize> = BTreeMap::new();
+    for instruction in instructions {
+        if instruction.opcode == 0x2b || instruction.opcode == 0x2c {
+            switches.insert(instruction.target().unwrap(), instruction.offset);
+        }
+    }
+
+    let mut lines = vec![];
+    let mut end = 0;
+    for instruction in instructions {
+        if let Some(extra) = directives.get(&instruction.offset) {
+            lines.extend(extra.iter().cloned());
+        }
+        if let Some(names) = labels.get(&instruction.offset) {
+            for name in names {
+                lines.push(format!(":{}", name));
+            }
+        }
+        match instruction.payload {
+            Some(ref payload) => {
+                let base = switches.get(&instruction.offset).cloned();
+                lines.extend(format_payload(payload, base));
+            }
+            None => lines.push(format_instruction(dex, instruction, labels)),
+        }
+        end = instruction.offset + instruction.size;
+    }
+
+    for (_, extra) in directives.range(end..) {
+        lines.extend(extra.iter().cloned());
+    }
+
+    if let Some(offset) = invalid {
+        lines.push(format!("# invalid instruction at 0x{:x}", offset));
+    }
+    return lines;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dex;
+    use writer;
+
+    fn empty_dex() -> DexFile {
+        return dex::parse(writer::write(&[]).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn decode_reads_operands_of_each_format() {
+        // const/4 v1, -0x1
+        let instruction = decode(&[0xF112], 0).unwrap();
+        assert_eq!(instruction.registers, vec![1]);
+        assert_eq!(instruction.literal, -1);
+        // const-wide/high16 v2, 0x4000000000000000L
+        let instruction = decode(&[0x0219, 0x4000], 0).unwrap();
+        assert_eq!(instruction.literal, 0x4000000000000000);
+        // invoke-virtual {v1, v2}, method@3
+        let instruction = decode(&[0x206E, 0x0003, 0x0021], 0).unwrap();
+        assert_eq!(instruction.opcode().name, "invoke-virtual");
+        assert_eq!(instruction.registers, vec![1, 2]);
+        assert_eq!(instruction.index(), 3);
+        // invoke-static/range {v4 .. v6}, method@1
+        let instruction = decode(&[0x0377, 0x0001, 0x0004], 0).unwrap();
+        assert_eq!(instruction.registers, vec![4, 5, 6]);
+        // goto -2 at address 3
+        let instruction = decode(&[0, 0, 0, 0xFE28], 3).unwrap();
+        assert_eq!(instruction.target(), Some(1));
+    }
+
+    #[test]
+    fn decode_rejects_unused_and_truncated_instructions() {
+        assert!(decode(&[0x003E], 0).is_none());
+        assert!(decode(&[0x0014, 0x0001], 0).is_none());
+        assert!(decode(&[], 0).is_none());
+        // invoke-virtual with six registers
+        assert!(decode(&[0x606E, 0, 0], 0).is_none());
+    }
+
+    #[test]
+    fn decode_rejects_truncated_payloads() {
+        assert!(decode(&[0x0100, 2, 0, 0, 1, 0], 0).is_none());
+        assert!(decode(&[0x0200, 1, 1, 0], 0).is_none());
+        // An element count whose byte size overflows
+        assert!(decode(&[0x0300, 0xFFFF, 0xFFFF, 0xFFFF], 0).is_none());
+        assert!(decode(&[0x0300, 4, 2, 0, 0], 0).is_none());
+    }
+
+    #[test]
+    fn disassemble_labels_branches_and_switches() {
+        let insns = [0x1012, // const/4 v0, 0x1
+                     0x002B, 0x0005, 0x0000, // packed-switch v0, +5
+                     0x000E, // return-void
+                     0x0000, // nop
+                     0x0100, 0x0001, 0x0001, 0x0000, 0x0003, 0x0000];
+        assert_eq!(disassemble(&empty_dex(), &insns),
+                   vec!["const/4 v0, 0x1",
+                        "packed-switch v0, :pswitch_data_6",
+                        ":pswitch_4",
+                        "return-void",
+                        "nop",
+                        ":pswitch_data_6",
+                        ".packed-switch 0x1",
+                        "    :pswitch_4",
+                        ".end packed-switch"]);
+    }
+
+    #[test]
+    fn disassemble_stops_at_invalid_instructions() {
+        let insns = [0x001A, 0x0005, 0x00FF];
+        assert_eq!(disassemble(&empty_dex(), &insns),
+                   vec!["const-string v0, string@5", "# invalid instruction at 0x2"]);
+    }
+
+    #[test]
+    fn branches_leaving_the_code_are_invalid() {
+        assert!(decode(&[0xFF28], 0).is_none());
+        assert!(decode(&[0x0000, 0x0228], 1).is_none());
+        assert_eq!(disassemble(&empty_dex(), &[0xFF28]),
+                   vec!["# invalid instruction at 0x0"]);
+    }
+
+    #[test]
+    fn literals_and_strings_are_formatted_like_baksmali() {
+        assert_eq!(format_literal(-0x10), "-0x10");
+        assert_eq!(format_literal(0x100000000), "0x100000000L");
+        assert_eq!(format_literal(i64::MIN), "-0x8000000000000000L");
+        assert_eq!(escape_string("a\"\n\u{e9}"), "\"a\\\"\\n\\u00e9\"");
+        assert_eq!(escape_units(&[0x61, 0xD800]), "\"a\\ud800\"");
+    }
+}
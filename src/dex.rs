@@ -3,25 +3,80 @@ extern crate nom;
 extern crate leb128;
 
 use nom::*;
+use std::collections::HashMap;
 use std::convert::AsMut;
-use std::str;
+use std::fmt;
 
+use digest;
 
-#[derive(Debug)]
+
+/// Marks an absent superclass_idx, source_file_idx and the like
+pub const NO_INDEX: u32 = 0xFFFFFFFF;
+
+const ENDIAN_CONSTANT: u32 = 0x12345678;
+const REVERSE_ENDIAN_CONSTANT: u32 = 0x78563412;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EndianConstant {
     EndianConstant = 0x12345678,
     ReverseEndianConstant = 0x78563412,
 }
 
+/// Why a DEX image could not be read
+#[derive(Debug, Clone, PartialEq)]
+pub enum DexErrorKind {
+    /// The data ends in the middle of an item
+    Truncated,
+    /// An offset points past the end of the file
+    OffsetOutOfBounds,
+    /// An index refers past the end of the table it points into
+    IndexOutOfBounds { index: u32, size: u32 },
+    /// The file does not start with `dex\n`
+    BadMagic,
+    /// The version is outside of what this parser understands
+    UnsupportedVersion([u8; 4]),
+    /// The endian_tag is neither ENDIAN_CONSTANT nor its byte-swapped form
+    UnknownEndianTag(u32),
+    /// A LEB128 value does not fit into 32 bits
+    BadLeb128,
+    /// A string_data_item is not valid Modified UTF-8
+    InvalidMutf8,
+    /// The utf16_size of a string_data_item differs from its contents
+    StringSizeMismatch { declared: u32, decoded: u32 },
+    /// A field holds a value the format does not define
+    InvalidValue,
+}
+
+/// A parse failure, located by the section being read and its file offset
+#[derive(Debug, Clone, PartialEq)]
+pub struct DexError {
+    section: &'static str,
+    offset: usize,
+    kind: DexErrorKind,
+}
+
+// Failure reasons the nom parsers pass up through ErrorKind::Custom. They
+// are turned into a DexErrorKind once the section is known.
+const TRUNCATED: u32 = 0;
+const BAD_LEB128: u32 = 1;
+const BAD_VALUE: u32 = 2;
+
 #[derive(Debug)]
 pub struct DexFile {
     header: DexHeader,
     method_ids: Vec<MethodID>,
     strings: Vec<String>,
-    type_descriptors: Vec<u32>,
+    /// Code units of the strings with unpaired surrogates, which `strings`
+    /// can only hold with U+FFFD in their place
+    unpaired_surrogates: HashMap<u32, Vec<u16>>,
+    type_descriptors: Vec<String>,
     proto_ids: Vec<ProtoID>,
     field_ids: Vec<FieldID>,
     class_defs: Vec<ClassDef>,
+    call_sites: Vec<CallSite>,
+    method_handles: Vec<MethodHandle>,
+    map_list: Option<Vec<MapItem>>,
+    file_len: usize,
 }
 
 #[derive(Debug)]
@@ -50,6 +105,206 @@ pub struct DexHeader {
     class_defs_off: u32,
     data_size: u32,
     data_off: u32,
+    /// Version 041 container fields, zero for older versions
+    container_size: u32,
+    header_offset: u32,
+}
+
+/// The kinds of method_handle_item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MethodHandleType {
+    StaticPut,
+    StaticGet,
+    InstancePut,
+    InstanceGet,
+    InvokeStatic,
+    InvokeInstance,
+    InvokeConstructor,
+    InvokeDirect,
+    InvokeInterface,
+}
+
+#[derive(Debug)]
+pub struct MethodHandle {
+    handle_type: MethodHandleType,
+    field_or_method_id: u16,
+}
+
+/// A call_site_item: the bootstrap method handle, the method name and
+/// method type to link, followed by any extra bootstrap arguments
+#[derive(Debug)]
+pub struct CallSite {
+    values: Vec<EncodedValue>,
+}
+
+/// An encoded_value as found in encoded_array_items and annotations.
+/// Index variants refer to the respective id tables.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncodedValue {
+    Byte(i8),
+    Short(i16),
+    Char(u16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    MethodType(u32),
+    MethodHandle(u32),
+    String(u32),
+    Type(u32),
+    Field(u32),
+    Method(u32),
+    Enum(u32),
+    Array(Vec<EncodedValue>),
+    Annotation(EncodedAnnotation),
+    Null,
+    Boolean(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodedAnnotation {
+    type_idx: u32,
+    elements: Vec<AnnotationElement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationElement {
+    name_idx: u32,
+    value: EncodedValue,
+}
+
+/// When an annotation is meant to be visible
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Visibility {
+    Build,
+    Runtime,
+    System,
+}
+
+/// An annotation_item: an annotation together with its visibility
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    visibility: Visibility,
+    annotation: EncodedAnnotation,
+}
+
+#[derive(Debug)]
+pub struct FieldAnnotation {
+    field_idx: u32,
+    annotations: Vec<Annotation>,
+}
+
+#[derive(Debug)]
+pub struct MethodAnnotation {
+    method_idx: u32,
+    annotations: Vec<Annotation>,
+}
+
+/// Annotations of each parameter of a method, in declaration order
+#[derive(Debug)]
+pub struct ParameterAnnotation {
+    method_idx: u32,
+    parameters: Vec<Vec<Annotation>>,
+}
+
+/// The annotations_directory_item of a class
+#[derive(Debug)]
+pub struct AnnotationsDirectory {
+    class_annotations: Vec<Annotation>,
+    field_annotations: Vec<FieldAnnotation>,
+    method_annotations: Vec<MethodAnnotation>,
+    parameter_annotations: Vec<ParameterAnnotation>,
+}
+
+/// The annotations of the `dalvik.annotation` package with which the
+/// compiler records information the class file attributes held
+#[derive(Debug, Clone, PartialEq)]
+pub enum SystemAnnotation {
+    /// Generic signature, split into pieces by the compiler
+    Signature(String),
+    /// Name, `None` for anonymous classes, and the original access flags
+    InnerClass(Option<String>, u32),
+    EnclosingClass(String),
+    EnclosingMethod(String),
+    MemberClasses(Vec<String>),
+    Throws(Vec<String>),
+    /// Name, `None` if absent, and access flags of each parameter
+    MethodParameters(Vec<(Option<String>, u32)>),
+    SourceDebugExtension(String),
+}
+
+/// Section types a map_list can describe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapItemType {
+    Header,
+    StringId,
+    TypeId,
+    ProtoId,
+    FieldId,
+    MethodId,
+    ClassDef,
+    CallSiteId,
+    MethodHandle,
+    MapList,
+    TypeList,
+    AnnotationSetRefList,
+    AnnotationSetItem,
+    ClassData,
+    Code,
+    StringData,
+    DebugInfo,
+    Annotation,
+    EncodedArray,
+    AnnotationsDirectory,
+    HiddenapiClassData,
+    Unknown(u16),
+}
+
+#[derive(Debug)]
+pub struct MapItem {
+    item_type: MapItemType,
+    size: u32,
+    offset: u32,
+}
+
+/// Inconsistencies between the map_list, the header and the file itself
+#[derive(Debug, PartialEq)]
+pub enum MapIssue {
+    /// map_off is zero, out of bounds or the list is truncated
+    Missing,
+    UnknownType(u16),
+    Duplicate(MapItemType),
+    /// Entries have to be sorted by offset
+    Unsorted(MapItemType),
+    /// The header describes a section differently than the map does.
+    /// Sizes and offsets are given as (header, map).
+    HeaderMismatch {
+        item_type: MapItemType,
+        size: (u32, u32),
+        offset: (u32, u32),
+    },
+    OutOfBounds {
+        item_type: MapItemType,
+        offset: u32,
+        end: u64,
+    },
+    Overlap(MapItemType, MapItemType),
+    /// Unaccounted bytes between the end of a section and the next one
+    Gap {
+        after: MapItemType,
+        offset: u64,
+        size: u64,
+    },
+}
+
+/// Checksum and signature stored in a header alongside the values
+/// computed over the file contents
+#[derive(Debug)]
+pub struct Integrity {
+    stored_checksum: u32,
+    computed_checksum: u32,
+    stored_signature: [u8; 20],
+    computed_signature: [u8; 20],
 }
 
 #[derive(Debug)]
@@ -64,6 +319,7 @@ pub struct ProtoID {
     shorty_idx: u32,
     return_type_idx: u32,
     parameters_off: u32,
+    parameters: Vec<u16>,
 }
 
 #[derive(Debug)]
@@ -83,308 +339,2820 @@ pub struct ClassDef {
     annotations_off: u32,
     class_data_off: u32,
     static_values_off: u32,
+    interfaces: Vec<u16>,
+    static_values: Vec<EncodedValue>,
+    annotations: Option<AnnotationsDirectory>,
+    class_data: Option<ClassData>,
 }
 
+#[derive(Debug)]
+pub struct ClassData {
+    static_fields: Vec<EncodedField>,
+    instance_fields: Vec<EncodedField>,
+    direct_methods: Vec<EncodedMethod>,
+    virtual_methods: Vec<EncodedMethod>,
+}
 
-fn header_endian(input: &[u8]) -> IResult<&[u8], EndianConstant> {
-    return match le_u32(input) {
-        IResult::Done(r, 0x12345678) => IResult::Done(r, EndianConstant::EndianConstant),
-        IResult::Done(r, 0x78563412) => IResult::Done(r, EndianConstant::ReverseEndianConstant),
-        IResult::Done(r, o) => {
-            println!("Unknown Endianes : 0x{:x}", o);
-            IResult::Done(r, EndianConstant::EndianConstant)
-        }
-        _ => IResult::Error(Err::Code(ErrorKind::Custom(2))),
-    };
+#[derive(Debug)]
+pub struct EncodedField {
+    field_idx: u32,
+    access_flags: u32,
 }
 
-named!(header(&[u8])->DexHeader, chain!(
-       	magic: tag!("dex\n")~
-		version: take!(4)~
-		checksum: le_u32~
-		signature: take!(20)~
-		file_size: le_u32~
-		header_size: le_u32~ // represents 0x70
-		endian_tag: header_endian~
-		link_size: le_u32~
-		link_off: le_u32~
-		map_off: le_u32~
-		string_ids_size: le_u32~
-		string_ids_off: le_u32~
-		type_ids_size: le_u32~
-		type_ids_off: le_u32~
-		proto_ids_size: le_u32~
-		proto_ids_off: le_u32~
-		field_ids_size: le_u32~
-		field_ids_off: le_u32~
-		method_ids_size: le_u32~
-		method_ids_off: le_u32~
-		class_defs_size: le_u32~
-		class_defs_off: le_u32~
-		data_size: le_u32~
-		data_off: le_u32,
-		|| DexHeader{
-		    magic:as_array(&magic[0..4]),
-	        version:as_array(&version[0..4]),
-	        checksum:checksum,
-	        signature:as_array(&signature[0..20]),
-	        file_size:file_size,
-	        header_size:header_size,
-	        endian_tag:endian_tag,
-	        link_size:link_size,
-	        link_off:link_off,
-	        map_off:map_off,
-	        string_ids_size:string_ids_size,
-	        string_ids_off:string_ids_off,
-	        type_ids_size:type_ids_size,
-	        type_ids_off:type_ids_off,
-	        proto_ids_size:proto_ids_size,
-	        proto_ids_off:proto_ids_off,
-	        field_ids_size:field_ids_size,
-	        field_ids_off:field_ids_off,
-	        method_ids_size:method_ids_size,
-	        method_ids_off:method_ids_off,
-	        class_defs_size:class_defs_size,
-	        class_defs_off:class_defs_off,
-	        data_size:data_size,
-	        data_off:data_off,
-	        })
-	   );
+#[derive(Debug)]
+pub struct EncodedMethod {
+    method_idx: u32,
+    access_flags: u32,
+    code_off: u32,
+    code: Option<CodeItem>,
+}
 
+#[derive(Debug)]
+pub struct CodeItem {
+    registers_size: u16,
+    ins_size: u16,
+    outs_size: u16,
+    debug_info_off: u32,
+    insns: Vec<u16>,
+    tries: Vec<TryItem>,
+    handlers: Vec<EncodedCatchHandler>,
+    debug_info: Option<DebugInfo>,
+}
 
-named!(proto_id(&[u8]) -> ProtoID, chain!(
-		shorty_idx: le_u32~
-	    return_type_idx: le_u32~
-	    parameters_off : le_u32,
-		|| ProtoID {
-			shorty_idx:shorty_idx,
-			return_type_idx:return_type_idx,
-			parameters_off:parameters_off
-		})
-);
+#[derive(Debug)]
+pub struct TryItem {
+    start_addr: u32,
+    insn_count: u16,
+    handler_off: u16,
+}
 
-named!(method_id(&[u8]) -> MethodID, chain!(
-		class_idx : le_u16 ~
-		proto_idx : le_u16 ~
-		name_idx : le_u32 ,
-	|| MethodID {
-		class_idx:class_idx,
-		proto_idx:proto_idx,
-		name_idx:name_idx
-	})
-);
+#[derive(Debug)]
+pub struct EncodedCatchHandler {
+    /// Byte offset from the start of the encoded_catch_handler_list,
+    /// which is what TryItem::handler_off refers to
+    offset: u16,
+    handlers: Vec<TypeAddrPair>,
+    catch_all_addr: Option<u32>,
+}
 
-named!(field_id(&[u8]) -> FieldID, chain!(
-		class_idx : le_u16 ~
-		type_idx : le_u16 ~
-		name_idx : le_u32 ,
-	|| FieldID {
-		class_idx:class_idx,
-		type_idx:type_idx,
-		name_idx:name_idx
-	})
-);
+#[derive(Debug)]
+pub struct TypeAddrPair {
+    type_idx: u32,
+    addr: u32,
+}
 
-named!(class_def(&[u8]) -> ClassDef, chain!(
-	    class_idx: le_u32 ~
-	    access_flags: le_u32 ~
-	    superclass_idx: le_u32 ~
-	    interfaces_off: le_u32 ~
-	    source_file_idx: le_u32 ~
-	    annotations_off: le_u32 ~
-	    class_data_off: le_u32 ~
-	    static_values_off: le_u32,
-		|| ClassDef {
-		    class_idx: class_idx,
-		    access_flags: access_flags,
-		    superclass_idx: superclass_idx,
-		    interfaces_off: interfaces_off,
-		    source_file_idx: source_file_idx,
-		    annotations_off: annotations_off,
-		    class_data_off: class_data_off,
-		    static_values_off: static_values_off,
-		})
-);
+/// A debug_info_item with its state machine program run: every event is
+/// kept together with the address the machine had reached
+#[derive(Debug)]
+pub struct DebugInfo {
+    line_start: u32,
+    parameter_names: Vec<u32>,
+    events: Vec<(u32, DebugEvent)>,
+}
 
-fn fields(input: &[u8], count: u32) -> IResult<&[u8], Vec<FieldID>> {
-    let mut rest = input;
-    let mut list: Vec<FieldID> = vec![];
-    for _ in 0..count {
-        let entry = field_id(rest);
-        match entry {
-            IResult::Done(i, o) => {
-                rest = i;
-                list.push(o);
-                continue;
-            }
-            _ => return IResult::Error(Err::Code(ErrorKind::Custom(1))),
-        }
-    }
-    return IResult::Done(rest, list);
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugEvent {
+    /// A position entry emitted by a special opcode
+    Line(u32),
+    StartLocal {
+        register: u32,
+        name_idx: u32,
+        type_idx: u32,
+        signature_idx: u32,
+    },
+    EndLocal(u32),
+    RestartLocal(u32),
+    PrologueEnd,
+    EpilogueBegin,
+    SetFile(u32),
 }
 
+/// An entry of the line table: code from `address` on belongs to `line`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    address: u32,
+    line: u32,
+    source_file_idx: u32,
+}
 
-fn methods(input: &[u8], count: u32) -> IResult<&[u8], Vec<MethodID>> {
-    let mut rest = input;
-    let mut list: Vec<MethodID> = vec![];
-    for _ in 0..count {
-        let entry = method_id(rest);
-        match entry {
-            IResult::Done(i, o) => {
-                rest = i;
-                list.push(o);
-                continue;
-            }
-            _ => return IResult::Error(Err::Code(ErrorKind::Custom(1))),
-        }
-    }
-    return IResult::Done(rest, list);
+/// A named register over the address range it is live in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocalVariable {
+    register: u32,
+    name_idx: u32,
+    type_idx: u32,
+    signature_idx: u32,
+    start_addr: u32,
+    end_addr: u32,
 }
 
-fn protos(input: &[u8], count: u32) -> IResult<&[u8], Vec<ProtoID>> {
-    let mut rest = input;
-    let mut list: Vec<ProtoID> = vec![];
-    for _ in 0..count {
-        let entry = proto_id(rest);
-        match entry {
-            IResult::Done(i, o) => {
-                rest = i;
-                list.push(o);
-                continue;
-            }
-            _ => return IResult::Error(Err::Code(ErrorKind::Custom(0))),
-        }
+impl DexFile {
+    pub fn header(&self) -> &DexHeader {
+        return &self.header;
     }
-    return IResult::Done(rest, list);
-}
 
+    pub fn class_defs(&self) -> &[ClassDef] {
+        return &self.class_defs;
+    }
 
-fn string_ids(input: &[u8], count: u32) -> IResult<&[u8], Vec<u32>> {
-    let mut rest = input;
-    let mut list: Vec<u32> = vec![];
-    for _ in 0..count {
-        let entry = le_u32(rest);
-        match entry {
-            IResult::Done(i, o) => {
-                rest = i;
-                list.push(o);
-                continue;
-            }
-            _ => return IResult::Error(Err::Code(ErrorKind::Custom(0))),
+    pub fn strings(&self) -> &[String] {
+        return &self.strings;
+    }
+
+    pub fn type_ids_size(&self) -> u32 {
+        return self.type_descriptors.len() as u32;
+    }
+
+    /// Descriptors of all type_ids, e.g. `I` or `Ljava/lang/String;`
+    pub fn type_descriptors(&self) -> &[String] {
+        return &self.type_descriptors;
+    }
+
+    pub fn proto_ids(&self) -> &[ProtoID] {
+        return &self.proto_ids;
+    }
+
+    pub fn field_ids(&self) -> &[FieldID] {
+        return &self.field_ids;
+    }
+
+    pub fn method_ids(&self) -> &[MethodID] {
+        return &self.method_ids;
+    }
+
+    pub fn field_id(&self, idx: u32) -> Option<&FieldID> {
+        return self.field_ids.get(idx as usize);
+    }
+
+    pub fn method_id(&self, idx: u32) -> Option<&MethodID> {
+        return self.method_ids.get(idx as usize);
+    }
+
+    /// The string at `idx`. Unpaired surrogates read as U+FFFD, see
+    /// `string_units` for the exact contents.
+    pub fn string(&self, idx: u32) -> Option<&str> {
+        return self.strings.get(idx as usize).map(|s| s.as_str());
+    }
+
+    /// The UTF-16 code units of the string at `idx`, unpaired surrogates
+    /// included
+    pub fn string_units(&self, idx: u32) -> Option<Vec<u16>> {
+        if let Some(units) = self.unpaired_surrogates.get(&idx) {
+            return Some(units.clone());
         }
+        return self.string(idx).map(|s| s.encode_utf16().collect());
     }
-    return IResult::Done(rest, list);
-}
 
+    pub fn type_descriptor(&self, idx: u32) -> Option<&str> {
+        return self.type_descriptors.get(idx as usize).map(|s| s.as_str());
+    }
 
-fn type_ids(input: &[u8], count: u32) -> IResult<&[u8], Vec<u32>> {
-    let mut rest = input;
-    let mut list: Vec<u32> = vec![];
-    for _ in 0..count {
-        let entry = le_u32(rest);
-        match entry {
-            IResult::Done(i, o) => {
-                rest = i;
-                list.push(o);
-                continue;
-            }
-            _ => return IResult::Error(Err::Code(ErrorKind::Custom(0))),
+    /// The shorty_descriptor of a proto, e.g. `VIL`
+    pub fn proto_shorty(&self, idx: u32) -> Option<&str> {
+        match self.proto_ids.get(idx as usize) {
+            Some(proto) => self.string(proto.shorty_idx),
+            None => None,
         }
     }
-    return IResult::Done(rest, list);
-}
 
-fn classes(input: &[u8], count: u32) -> IResult<&[u8], Vec<ClassDef>> {
-    let mut rest = input;
-    let mut list: Vec<ClassDef> = vec![];
-    for _ in 0..count {
-        let entry = class_def(rest);
-        match entry {
-            IResult::Done(i, o) => {
-                rest = i;
-                list.push(o);
-                continue;
-            }
-            _ => return IResult::Error(Err::Code(ErrorKind::Custom(0))),
+    pub fn proto_return_type(&self, idx: u32) -> Option<&str> {
+        match self.proto_ids.get(idx as usize) {
+            Some(proto) => self.type_descriptor(proto.return_type_idx),
+            None => None,
         }
     }
-    return IResult::Done(rest, list);
-}
 
+    /// Parameter descriptors of a proto, read from its type_list
+    pub fn proto_parameters(&self, idx: u32) -> Option<Vec<&str>> {
+        let proto = self.proto_ids.get(idx as usize)?;
+        let mut parameters: Vec<&str> = vec![];
+        for &param in proto.parameters.iter() {
+            parameters.push(self.type_descriptor(param as u32)?);
+        }
+        return Some(parameters);
+    }
 
-pub fn parse(data: Vec<u8>) -> Option<DexFile> {
-    let input = data.as_slice();
-    let h: DexHeader;
-    let s: Vec<String> = vec![];
-    let t: Vec<u32>;
-    let p: Vec<ProtoID>;
-    let f: Vec<FieldID>;
-    let m: Vec<MethodID>;
-    let c: Vec<ClassDef>;
+    /// Formats a proto as `(Args)Ret`, e.g. `(ILjava/lang/String;)V`
+    pub fn proto_descriptor(&self, idx: u32) -> Option<String> {
+        let parameters = self.proto_parameters(idx)?;
+        let ret = self.proto_return_type(idx)?;
+        return Some(format!("({}){}", parameters.concat(), ret));
+    }
 
+    /// Formats a field reference as `Lcls;->name:Type`
+    pub fn field_descriptor(&self, idx: u32) -> Option<String> {
+        let field = self.field_ids.get(idx as usize)?;
+        match (self.type_descriptor(field.class_idx as u32),
+               self.string(field.name_idx),
+               self.type_descriptor(field.type_idx as u32)) {
+            (Some(class), Some(name), Some(ty)) => Some(format!("{}->{}:{}", class, name, ty)),
+            _ => None,
+        }
+    }
 
-    match header(input) {
-        IResult::Done(_, o) => h = o,
-        _ => return None,
+    pub fn call_sites(&self) -> &[CallSite] {
+        return &self.call_sites;
     }
 
-    let si;
-    let string_id_off = h.string_ids_off as usize;
-    match string_ids(&input[string_id_off..], h.string_ids_size) {
-        IResult::Done(_, o) => si = o,
-        _ => return None,
+    pub fn method_handles(&self) -> &[MethodHandle] {
+        return &self.method_handles;
     }
 
-    for i in 0..h.string_ids_size {
-        let off = si[i as usize] as usize;
-        let mut readable = &input[off..];
-        let val = leb128::read::unsigned(&mut readable).expect("Should read number") as usize;
-        let off = off + 1;
-        let data = &input[off..off + val];
-        let s = str::from_utf8(data).unwrap();
+    pub fn call_site(&self, idx: u32) -> Option<&CallSite> {
+        return self.call_sites.get(idx as usize);
     }
 
+    pub fn method_handle(&self, idx: u32) -> Option<&MethodHandle> {
+        return self.method_handles.get(idx as usize);
+    }
 
-    let method_off = h.method_ids_off as usize;
-    match methods(&input[method_off..], h.method_ids_size) {
-        IResult::Done(_, o) => m = o,
-        _ => return None,
+    /// Formats a method handle the way smali writes it, e.g.
+    /// `invoke-static@Lcls;->name(Args)Ret` or `static-get@Lcls;->name:Type`
+    pub fn method_handle_descriptor(&self, idx: u32) -> Option<String> {
+        let handle = self.method_handles.get(idx as usize)?;
+        let member = if handle.handle_type.is_field() {
+            self.field_descriptor(handle.field_or_method_id as u32)
+        } else {
+            self.method_descriptor(handle.field_or_method_id as u32)
+        };
+        return member.map(|member| format!("{}@{}", handle.handle_type.name(), member));
     }
 
+    /// Sections as listed by the map_list, `None` if it could not be read
+    pub fn map_list(&self) -> Option<&[MapItem]> {
+        return self.map_list.as_deref();
+    }
 
-    let proto_off = h.proto_ids_off as usize;
-    match protos(&input[proto_off..], h.proto_ids_size) {
-        IResult::Done(_, o) => p = o,
-        _ => return None,
+    /// Cross-checks the map_list against the header and the file size.
+    /// Headers of malformed or obfuscated files are not to be trusted, so
+    /// everything found is reported rather than treated as a parse error.
+    pub fn validate_map(&self) -> Vec<MapIssue> {
+        let mut issues: Vec<MapIssue> = vec![];
+        let map = match self.map_list {
+            Some(ref map) => map,
+            None => {
+                issues.push(MapIssue::Missing);
+                return issues;
+            }
+        };
+
+        let h = &self.header;
+        let expected = [(MapItemType::Header, 1, 0),
+                        (MapItemType::StringId, h.string_ids_size, h.string_ids_off),
+                        (MapItemType::TypeId, h.type_ids_size, h.type_ids_off),
+                        (MapItemType::ProtoId, h.proto_ids_size, h.proto_ids_off),
+                        (MapItemType::FieldId, h.field_ids_size, h.field_ids_off),
+                        (MapItemType::MethodId, h.method_ids_size, h.method_ids_off),
+                        (MapItemType::ClassDef, h.class_defs_size, h.class_defs_off),
+                        (MapItemType::MapList, 1, h.map_off)];
+        for &(item_type, size, offset) in expected.iter() {
+            let (map_size, map_offset) = match map.iter().find(|item| item.item_type == item_type) {
+                Some(item) => (item.size, item.offset),
+                None => (0, 0),
+            };
+            // Empty sections may have any offset in the header
+            if size != map_size || (size != 0 && offset != map_offset) {
+                issues.push(MapIssue::HeaderMismatch {
+                    item_type: item_type,
+                    size: (size, map_size),
+                    offset: (offset, map_offset),
+                });
+            }
+        }
+
+        for (i, item) in map.iter().enumerate() {
+            if let MapItemType::Unknown(code) = item.item_type {
+                issues.push(MapIssue::UnknownType(code));
+            }
+            if map[..i].iter().any(|other| other.item_type == item.item_type) {
+                issues.push(MapIssue::Duplicate(item.item_type));
+            }
+            if i > 0 && item.offset < map[i - 1].offset {
+                issues.push(MapIssue::Unsorted(item.item_type));
+            }
+        }
+
+        let mut sections: Vec<&MapItem> = map.iter().filter(|item| item.size != 0).collect();
+        sections.sort_by_key(|item| item.offset);
+        for (i, item) in sections.iter().enumerate() {
+            let start = item.offset as u64;
+            let end = match item.item_type {
+                MapItemType::MapList => start + 4 + 12 * map.len() as u64,
+                _ => start + item.item_type.min_size(item.size),
+            };
+            if end > self.file_len as u64 {
+                issues.push(MapIssue::OutOfBounds {
+                    item_type: item.item_type,
+                    offset: item.offset,
+                    end: end,
+                });
+            }
+
+            let next = match sections.get(i + 1) {
+                Some(next) => next,
+                None => continue,
+            };
+            if end > next.offset as u64 {
+                issues.push(MapIssue::Overlap(item.item_type, next.item_type));
+            } else if item.item_type.is_fixed_size() || item.item_type == MapItemType::MapList {
+                // Sections following a fixed size one are 4-byte aligned
+                let padded = (end + 3) & !3;
+                if padded < next.offset as u64 {
+                    issues.push(MapIssue::Gap {
+                        after: item.item_type,
+                        offset: end,
+                        size: next.offset as u64 - end,
+                    });
+                }
+            }
+        }
+
+        return issues;
     }
 
+    /// Formats a method reference as `Lcls;->name(Args)Ret`
+    pub fn method_descriptor(&self, idx: u32) -> Option<String> {
+        let method = self.method_ids.get(idx as usize)?;
+        match (self.type_descriptor(method.class_idx as u32),
+               self.string(method.name_idx),
+               self.proto_descriptor(method.proto_idx as u32)) {
+            (Some(class), Some(name), Some(proto)) => Some(format!("{}->{}{}", class, name, proto)),
+            _ => None,
+        }
+    }
 
-    let field_off = h.field_ids_off as usize;
-    match fields(&input[field_off..], h.field_ids_size) {
-        IResult::Done(_, o) => f = o,
-        _ => return None,
+    /// Decodes one of the system annotations the compiler emits. Returns
+    /// `None` for any other annotation and for malformed ones.
+    pub fn system_annotation(&self, annotation: &Annotation) -> Option<SystemAnnotation> {
+        if annotation.visibility != Visibility::System {
+            return None;
+        }
+        let annotation = &annotation.annotation;
+        let value = self.element(annotation, "value");
+        let decoded = match self.type_descriptor(annotation.type_idx)? {
+            "Ldalvik/annotation/Signature;" => {
+                let parts = self.string_array(value?)?;
+                let parts: Option<Vec<String>> = parts.into_iter().collect();
+                SystemAnnotation::Signature(parts?.concat())
+            }
+            "Ldalvik/annotation/InnerClass;" => {
+                let name = match *self.element(annotation, "name")? {
+                    EncodedValue::Null => None,
+                    ref name => Some(self.string_value(name)?),
+                };
+                match *self.element(annotation, "accessFlags")? {
+                    EncodedValue::Int(flags) => SystemAnnotation::InnerClass(name, flags as u32),
+                    _ => return None,
+                }
+            }
+            "Ldalvik/annotation/EnclosingClass;" => {
+                match *value? {
+                    EncodedValue::Type(idx) => {
+                        SystemAnnotation::EnclosingClass(self.type_descriptor(idx)?.to_string())
+                    }
+                    _ => return None,
+                }
+            }
+            "Ldalvik/annotation/EnclosingMethod;" => {
+                match *value? {
+                    EncodedValue::Method(idx) => {
+                        SystemAnnotation::EnclosingMethod(self.method_descriptor(idx)?)
+                    }
+                    _ => return None,
+                }
+            }
+            "Ldalvik/annotation/MemberClasses;" => {
+                SystemAnnotation::MemberClasses(self.type_array(value?)?)
+            }
+            "Ldalvik/annotation/Throws;" => SystemAnnotation::Throws(self.type_array(value?)?),
+            "Ldalvik/annotation/MethodParameters;" => {
+                let names = self.string_array(self.element(annotation, "names")?)?;
+                let flags = match *self.element(annotation, "accessFlags")? {
+                    EncodedValue::Array(ref values) => values,
+                    _ => return None,
+                };
+                if names.len() != flags.len() {
+                    return None;
+                }
+                let mut parameters = vec![];
+                for (name, flags) in names.into_iter().zip(flags.iter()) {
+                    match *flags {
+                        EncodedValue::Int(flags) => parameters.push((name, flags as u32)),
+                        _ => return None,
+                    }
+                }
+                SystemAnnotation::MethodParameters(parameters)
+            }
+            "Ldalvik/annotation/SourceDebugExtension;" => {
+                SystemAnnotation::SourceDebugExtension(self.string_value(value?)?)
+            }
+            _ => return None,
+        };
+        return Some(decoded);
     }
 
-    let type_off = h.type_ids_off as usize;
-    match type_ids(&input[type_off..], h.type_ids_size) {
-        IResult::Done(_, o) => t = o,
-        _ => return None,
+    /// All system annotations of a set that could be decoded
+    pub fn system_annotations(&self, annotations: &[Annotation]) -> Vec<SystemAnnotation> {
+        return annotations.iter().filter_map(|a| self.system_annotation(a)).collect();
     }
 
-    let class_def_off = h.class_defs_off as usize;
-    match classes(&input[class_def_off..], h.class_defs_size) {
-        IResult::Done(_, o) => c = o,
-        _ => return None,
+    fn element<'a>(&self,
+                   annotation: &'a EncodedAnnotation,
+                   name: &str)
+                   -> Option<&'a EncodedValue> {
+        return annotation.elements
+            .iter()
+            .find(|element| self.string(element.name_idx) == Some(name))
+            .map(|element| &element.value);
     }
 
-    return Some(DexFile {
-        header: h,
-        method_ids: m,
+    fn string_value(&self, value: &EncodedValue) -> Option<String> {
+        match *value {
+            EncodedValue::String(idx) => self.string(idx).map(|s| s.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Strings of an array in which null stands for a missing entry
+    fn string_array(&self, value: &EncodedValue) -> Option<Vec<Option<String>>> {
+        let values = match *value {
+            EncodedValue::Array(ref values) => values,
+            _ => return None,
+        };
+        let mut strings = vec![];
+        for value in values.iter() {
+            match *value {
+                EncodedValue::Null => strings.push(None),
+                _ => strings.push(Some(self.string_value(value)?)),
+            }
+        }
+        return Some(strings);
+    }
+
+    fn type_array(&self, value: &EncodedValue) -> Option<Vec<String>> {
+        let values = match *value {
+            EncodedValue::Array(ref values) => values,
+            _ => return None,
+        };
+        let mut types = vec![];
+        for value in values.iter() {
+            match *value {
+                EncodedValue::Type(idx) => types.push(self.type_descriptor(idx)?.to_string()),
+                _ => return None,
+            }
+        }
+        return Some(types);
+    }
+}
+
+impl MethodHandleType {
+    pub fn from_code(code: u16) -> Option<MethodHandleType> {
+        match code {
+            0x00 => Some(MethodHandleType::StaticPut),
+            0x01 => Some(MethodHandleType::StaticGet),
+            0x02 => Some(MethodHandleType::InstancePut),
+            0x03 => Some(MethodHandleType::InstanceGet),
+            0x04 => Some(MethodHandleType::InvokeStatic),
+            0x05 => Some(MethodHandleType::InvokeInstance),
+            0x06 => Some(MethodHandleType::InvokeConstructor),
+            0x07 => Some(MethodHandleType::InvokeDirect),
+            0x08 => Some(MethodHandleType::InvokeInterface),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> u16 {
+        return *self as u16;
+    }
+
+    /// The keyword smali uses for the handle type, e.g. `invoke-static`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            MethodHandleType::StaticPut => "static-put",
+            MethodHandleType::StaticGet => "static-get",
+            MethodHandleType::InstancePut => "instance-put",
+            MethodHandleType::InstanceGet => "instance-get",
+            MethodHandleType::InvokeStatic => "invoke-static",
+            MethodHandleType::InvokeInstance => "invoke-instance",
+            MethodHandleType::InvokeConstructor => "invoke-constructor",
+            MethodHandleType::InvokeDirect => "invoke-direct",
+            MethodHandleType::InvokeInterface => "invoke-interface",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MethodHandleType> {
+        return (0..9).filter_map(MethodHandleType::from_code).find(|t| t.name() == name);
+    }
+
+    /// Whether field_or_method_id is a field index rather than a method index
+    pub fn is_field(&self) -> bool {
+        return self.code() <= 0x03;
+    }
+}
+
+impl MethodHandle {
+    pub fn handle_type(&self) -> MethodHandleType {
+        return self.handle_type;
+    }
+
+    pub fn field_or_method_id(&self) -> u16 {
+        return self.field_or_method_id;
+    }
+}
+
+impl EncodedValue {
+    /// Whether this is the value a field holds when it is not initialized:
+    /// zero, false or null
+    pub fn is_default(&self) -> bool {
+        match *self {
+            EncodedValue::Byte(v) => v == 0,
+            EncodedValue::Short(v) => v == 0,
+            EncodedValue::Char(v) => v == 0,
+            EncodedValue::Int(v) => v == 0,
+            EncodedValue::Long(v) => v == 0,
+            EncodedValue::Float(v) => v.to_bits() == 0,
+            EncodedValue::Double(v) => v.to_bits() == 0,
+            EncodedValue::Boolean(v) => !v,
+            EncodedValue::Null => true,
+            _ => false,
+        }
+    }
+}
+
+impl CallSite {
+    /// All values of the call site's encoded_array, starting with the three
+    /// mandatory ones
+    pub fn values(&self) -> &[EncodedValue] {
+        return &self.values;
+    }
+
+    /// Index of the bootstrap linker method handle
+    pub fn bootstrap(&self) -> Option<u32> {
+        match self.values.first() {
+            Some(&EncodedValue::MethodHandle(idx)) => Some(idx),
+            _ => None,
+        }
+    }
+
+    /// String index of the method name to resolve
+    pub fn name_idx(&self) -> Option<u32> {
+        match self.values.get(1) {
+            Some(&EncodedValue::String(idx)) => Some(idx),
+            _ => None,
+        }
+    }
+
+    /// Proto index of the method type to resolve
+    pub fn proto_idx(&self) -> Option<u32> {
+        match self.values.get(2) {
+            Some(&EncodedValue::MethodType(idx)) => Some(idx),
+            _ => None,
+        }
+    }
+
+    /// Additional arguments passed to the bootstrap method
+    pub fn arguments(&self) -> &[EncodedValue] {
+        return if self.values.len() > 3 { &self.values[3..] } else { &[] };
+    }
+}
+
+impl EncodedAnnotation {
+    pub fn type_idx(&self) -> u32 {
+        return self.type_idx;
+    }
+
+    pub fn elements(&self) -> &[AnnotationElement] {
+        return &self.elements;
+    }
+}
+
+impl AnnotationElement {
+    pub fn name_idx(&self) -> u32 {
+        return self.name_idx;
+    }
+
+    pub fn value(&self) -> &EncodedValue {
+        return &self.value;
+    }
+}
+
+impl Visibility {
+    pub fn from_code(code: u8) -> Option<Visibility> {
+        match code {
+            0 => Some(Visibility::Build),
+            1 => Some(Visibility::Runtime),
+            2 => Some(Visibility::System),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> u8 {
+        match *self {
+            Visibility::Build => 0,
+            Visibility::Runtime => 1,
+            Visibility::System => 2,
+        }
+    }
+
+    /// The keyword smali uses, e.g. `runtime`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Visibility::Build => "build",
+            Visibility::Runtime => "runtime",
+            Visibility::System => "system",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Visibility> {
+        return (0..3).filter_map(Visibility::from_code).find(|v| v.name() == name);
+    }
+}
+
+impl Annotation {
+    pub fn visibility(&self) -> Visibility {
+        return self.visibility;
+    }
+
+    pub fn annotation(&self) -> &EncodedAnnotation {
+        return &self.annotation;
+    }
+}
+
+impl FieldAnnotation {
+    pub fn field_idx(&self) -> u32 {
+        return self.field_idx;
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        return &self.annotations;
+    }
+}
+
+impl MethodAnnotation {
+    pub fn method_idx(&self) -> u32 {
+        return self.method_idx;
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        return &self.annotations;
+    }
+}
+
+impl ParameterAnnotation {
+    pub fn method_idx(&self) -> u32 {
+        return self.method_idx;
+    }
+
+    pub fn parameters(&self) -> &[Vec<Annotation>] {
+        return &self.parameters;
+    }
+}
+
+impl AnnotationsDirectory {
+    pub fn class_annotations(&self) -> &[Annotation] {
+        return &self.class_annotations;
+    }
+
+    pub fn field_annotations(&self) -> &[FieldAnnotation] {
+        return &self.field_annotations;
+    }
+
+    pub fn method_annotations(&self) -> &[MethodAnnotation] {
+        return &self.method_annotations;
+    }
+
+    pub fn parameter_annotations(&self) -> &[ParameterAnnotation] {
+        return &self.parameter_annotations;
+    }
+
+    /// Annotations of the field with the given field_idx
+    pub fn field(&self, field_idx: u32) -> &[Annotation] {
+        match self.field_annotations.iter().find(|f| f.field_idx == field_idx) {
+            Some(field) => &field.annotations,
+            None => &[],
+        }
+    }
+
+    /// Annotations of the method with the given method_idx
+    pub fn method(&self, method_idx: u32) -> &[Annotation] {
+        match self.method_annotations.iter().find(|m| m.method_idx == method_idx) {
+            Some(method) => &method.annotations,
+            None => &[],
+        }
+    }
+
+    /// Parameter annotations of the method with the given method_idx
+    pub fn parameters(&self, method_idx: u32) -> &[Vec<Annotation>] {
+        match self.parameter_annotations.iter().find(|p| p.method_idx == method_idx) {
+            Some(method) => &method.parameters,
+            None => &[],
+        }
+    }
+}
+
+impl MapItemType {
+    pub fn from_code(code: u16) -> MapItemType {
+        match code {
+            0x0000 => MapItemType::Header,
+            0x0001 => MapItemType::StringId,
+            0x0002 => MapItemType::TypeId,
+            0x0003 => MapItemType::ProtoId,
+            0x0004 => MapItemType::FieldId,
+            0x0005 => MapItemType::MethodId,
+            0x0006 => MapItemType::ClassDef,
+            0x0007 => MapItemType::CallSiteId,
+            0x0008 => MapItemType::MethodHandle,
+            0x1000 => MapItemType::MapList,
+            0x1001 => MapItemType::TypeList,
+            0x1002 => MapItemType::AnnotationSetRefList,
+            0x1003 => MapItemType::AnnotationSetItem,
+            0x2000 => MapItemType::ClassData,
+            0x2001 => MapItemType::Code,
+            0x2002 => MapItemType::StringData,
+            0x2003 => MapItemType::DebugInfo,
+            0x2004 => MapItemType::Annotation,
+            0x2005 => MapItemType::EncodedArray,
+            0x2006 => MapItemType::AnnotationsDirectory,
+            0xF000 => MapItemType::HiddenapiClassData,
+            _ => MapItemType::Unknown(code),
+        }
+    }
+
+    pub fn code(&self) -> u16 {
+        match *self {
+            MapItemType::Header => 0x0000,
+            MapItemType::StringId => 0x0001,
+            MapItemType::TypeId => 0x0002,
+            MapItemType::ProtoId => 0x0003,
+            MapItemType::FieldId => 0x0004,
+            MapItemType::MethodId => 0x0005,
+            MapItemType::ClassDef => 0x0006,
+            MapItemType::CallSiteId => 0x0007,
+            MapItemType::MethodHandle => 0x0008,
+            MapItemType::MapList => 0x1000,
+            MapItemType::TypeList => 0x1001,
+            MapItemType::AnnotationSetRefList => 0x1002,
+            MapItemType::AnnotationSetItem => 0x1003,
+            MapItemType::ClassData => 0x2000,
+            MapItemType::Code => 0x2001,
+            MapItemType::StringData => 0x2002,
+            MapItemType::DebugInfo => 0x2003,
+            MapItemType::Annotation => 0x2004,
+            MapItemType::EncodedArray => 0x2005,
+            MapItemType::AnnotationsDirectory => 0x2006,
+            MapItemType::HiddenapiClassData => 0xF000,
+            MapItemType::Unknown(code) => code,
+        }
+    }
+
+    /// Smallest number of bytes a section of `count` items can occupy.
+    /// Exact for the fixed size sections, a lower bound for the others.
+    fn min_size(&self, count: u32) -> u64 {
+        let count = count as u64;
+        match *self {
+            MapItemType::Header => 0x70,
+            MapItemType::StringId | MapItemType::TypeId | MapItemType::CallSiteId => 4 * count,
+            MapItemType::ProtoId => 12 * count,
+            MapItemType::FieldId | MapItemType::MethodId | MapItemType::MethodHandle => 8 * count,
+            MapItemType::ClassDef => 32 * count,
+            MapItemType::TypeList | MapItemType::MapList | MapItemType::AnnotationSetRefList |
+            MapItemType::AnnotationSetItem | MapItemType::HiddenapiClassData => 4 * count,
+            MapItemType::ClassData => 4 * count,
+            MapItemType::Code => 16 * count,
+            MapItemType::StringData | MapItemType::Annotation => 2 * count,
+            MapItemType::DebugInfo => 3 * count,
+            MapItemType::EncodedArray => count,
+            MapItemType::AnnotationsDirectory => 16 * count,
+            MapItemType::Unknown(_) => 0,
+        }
+    }
+
+    /// Whether `min_size` is the exact extent of the section
+    fn is_fixed_size(&self) -> bool {
+        return matches!(*self,
+                        MapItemType::Header | MapItemType::StringId | MapItemType::TypeId |
+                        MapItemType::ProtoId | MapItemType::FieldId | MapItemType::MethodId |
+                        MapItemType::ClassDef | MapItemType::CallSiteId |
+                        MapItemType::MethodHandle);
+    }
+}
+
+impl MapItem {
+    pub fn item_type(&self) -> MapItemType {
+        return self.item_type;
+    }
+
+    /// Number of items in the section
+    pub fn size(&self) -> u32 {
+        return self.size;
+    }
+
+    pub fn offset(&self) -> u32 {
+        return self.offset;
+    }
+}
+
+impl fmt::Display for MapIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MapIssue::Missing => write!(f, "map_list is missing or truncated"),
+            MapIssue::UnknownType(code) => write!(f, "unknown map item type 0x{:04x}", code),
+            MapIssue::Duplicate(ty) => write!(f, "{:?} is listed more than once", ty),
+            MapIssue::Unsorted(ty) => write!(f, "{:?} is not sorted by offset", ty),
+            MapIssue::HeaderMismatch { item_type, size, offset } => {
+                write!(f,
+                       "{:?}: header says {} items at 0x{:x}, map says {} at 0x{:x}",
+                       item_type,
+                       size.0,
+                       offset.0,
+                       size.1,
+                       offset.1)
+            }
+            MapIssue::OutOfBounds { item_type, offset, end } => {
+                write!(f, "{:?} at 0x{:x}..0x{:x} exceeds the file", item_type, offset, end)
+            }
+            MapIssue::Overlap(a, b) => write!(f, "{:?} overlaps {:?}", a, b),
+            MapIssue::Gap { after, offset, size } => {
+                write!(f, "{} unused bytes at 0x{:x} after {:?}", size, offset, after)
+            }
+        }
+    }
+}
+
+impl Integrity {
+    pub fn stored_checksum(&self) -> u32 {
+        return self.stored_checksum;
+    }
+
+    /// Adler-32 of everything following the checksum field
+    pub fn computed_checksum(&self) -> u32 {
+        return self.computed_checksum;
+    }
+
+    pub fn stored_signature(&self) -> [u8; 20] {
+        return self.stored_signature;
+    }
+
+    /// SHA-1 of everything following the signature field
+    pub fn computed_signature(&self) -> [u8; 20] {
+        return self.computed_signature;
+    }
+
+    pub fn checksum_valid(&self) -> bool {
+        return self.stored_checksum == self.computed_checksum;
+    }
+
+    pub fn signature_valid(&self) -> bool {
+        return self.stored_signature == self.computed_signature;
+    }
+
+    pub fn is_valid(&self) -> bool {
+        return self.checksum_valid() && self.signature_valid();
+    }
+}
+
+impl EndianConstant {
+    /// Whether the file is stored big-endian, i.e. the tag reads as
+    /// REVERSE_ENDIAN_CONSTANT in little-endian order
+    pub fn is_big_endian(&self) -> bool {
+        return *self == EndianConstant::ReverseEndianConstant;
+    }
+}
+
+impl DexError {
+    fn new(section: &'static str, offset: usize, kind: DexErrorKind) -> DexError {
+        return DexError {
+            section: section,
+            offset: offset,
+            kind: kind,
+        };
+    }
+
+    /// Name of the section or item that failed, e.g. `code_item`
+    pub fn section(&self) -> &'static str {
+        return self.section;
+    }
+
+    /// File offset of the failing item, or of its section for id lists
+    pub fn offset(&self) -> usize {
+        return self.offset;
+    }
+
+    pub fn kind(&self) -> &DexErrorKind {
+        return &self.kind;
+    }
+}
+
+impl fmt::Display for DexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DexErrorKind::Truncated => write!(f, "data ends unexpectedly"),
+            DexErrorKind::OffsetOutOfBounds => write!(f, "offset is outside of the file"),
+            DexErrorKind::IndexOutOfBounds { index, size } => {
+                write!(f, "index {} is out of bounds for {} entries", index, size)
+            }
+            DexErrorKind::BadMagic => write!(f, "bad magic"),
+            DexErrorKind::UnsupportedVersion(version) => {
+                write!(f,
+                       "unsupported version {}",
+                       String::from_utf8_lossy(&version[..3]))
+            }
+            DexErrorKind::UnknownEndianTag(tag) => write!(f, "unknown endian_tag 0x{:08x}", tag),
+            DexErrorKind::BadLeb128 => write!(f, "malformed LEB128 value"),
+            DexErrorKind::InvalidMutf8 => write!(f, "invalid Modified UTF-8"),
+            DexErrorKind::StringSizeMismatch { declared, decoded } => {
+                write!(f,
+                       "utf16_size is {} but the data holds {} code units",
+                       declared,
+                       decoded)
+            }
+            DexErrorKind::InvalidValue => write!(f, "invalid value"),
+        }
+    }
+}
+
+impl fmt::Display for DexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at 0x{:x}: {}", self.section, self.offset, self.kind)
+    }
+}
+
+impl DexHeader {
+    /// Always `dex\n`
+    pub fn magic(&self) -> [u8; 4] {
+        return self.magic;
+    }
+
+    /// The version digits following the magic, e.g. `035\0`
+    pub fn version(&self) -> [u8; 4] {
+        return self.version;
+    }
+
+    /// The version as a number, e.g. 35. `None` unless three ASCII digits
+    /// followed by a NUL.
+    pub fn version_number(&self) -> Option<u32> {
+        let digits = &self.version;
+        if digits[3] != 0 || !digits[..3].iter().all(|d| d.is_ascii_digit()) {
+            return None;
+        }
+        return Some(digits[..3].iter().fold(0, |n, d| n * 10 + (*d - b'0') as u32));
+    }
+
+    pub fn endian_tag(&self) -> EndianConstant {
+        return self.endian_tag;
+    }
+
+    pub fn checksum(&self) -> u32 {
+        return self.checksum;
+    }
+
+    /// Size of the whole container in version 041 files, zero otherwise
+    pub fn container_size(&self) -> u32 {
+        return self.container_size;
+    }
+
+    /// Position of this header inside a version 041 container
+    pub fn header_offset(&self) -> u32 {
+        return self.header_offset;
+    }
+
+    /// Size of this DEX image. In a version 041 container this is the
+    /// distance to the next header or to the end of the container.
+    pub fn file_size(&self) -> u32 {
+        return self.file_size;
+    }
+
+    pub fn signature(&self) -> [u8; 20] {
+        return self.signature;
+    }
+
+    /// Size of the link section, zero unless the file is statically linked
+    pub fn link_size(&self) -> u32 {
+        return self.link_size;
+    }
+
+    pub fn link_off(&self) -> u32 {
+        return self.link_off;
+    }
+
+    /// Size of the data section, which holds everything the ids point to
+    pub fn data_size(&self) -> u32 {
+        return self.data_size;
+    }
+
+    pub fn data_off(&self) -> u32 {
+        return self.data_off;
+    }
+}
+
+impl ProtoID {
+    pub fn shorty_idx(&self) -> u32 {
+        return self.shorty_idx;
+    }
+
+    pub fn return_type_idx(&self) -> u32 {
+        return self.return_type_idx;
+    }
+
+    /// Type indices of the parameters, read from the type_list at parameters_off
+    pub fn parameters(&self) -> &[u16] {
+        return &self.parameters;
+    }
+}
+
+impl FieldID {
+    pub fn class_idx(&self) -> u16 {
+        return self.class_idx;
+    }
+
+    pub fn type_idx(&self) -> u16 {
+        return self.type_idx;
+    }
+
+    pub fn name_idx(&self) -> u32 {
+        return self.name_idx;
+    }
+}
+
+impl MethodID {
+    pub fn class_idx(&self) -> u16 {
+        return self.class_idx;
+    }
+
+    pub fn proto_idx(&self) -> u16 {
+        return self.proto_idx;
+    }
+
+    pub fn name_idx(&self) -> u32 {
+        return self.name_idx;
+    }
+}
+
+impl ClassDef {
+    pub fn class_idx(&self) -> u32 {
+        return self.class_idx;
+    }
+
+    pub fn access_flags(&self) -> u32 {
+        return self.access_flags;
+    }
+
+    /// Type index of the superclass, `NO_INDEX` for java.lang.Object
+    pub fn superclass_idx(&self) -> u32 {
+        return self.superclass_idx;
+    }
+
+    /// String index of the source file name, or `NO_INDEX`
+    pub fn source_file_idx(&self) -> u32 {
+        return self.source_file_idx;
+    }
+
+    /// Type indices of the interfaces the class implements directly
+    pub fn interfaces(&self) -> &[u16] {
+        return &self.interfaces;
+    }
+
+    /// Initial values of the static fields, in the order of the class_data.
+    /// Trailing fields that keep their default value may be left out.
+    pub fn static_values(&self) -> &[EncodedValue] {
+        return &self.static_values;
+    }
+
+    /// Initial value of the `idx`-th static field, `None` for the trailing
+    /// ones left at their default
+    pub fn static_value(&self, idx: usize) -> Option<&EncodedValue> {
+        return self.static_values.get(idx);
+    }
+
+    /// Annotations of the class and its members, `None` if there are none
+    pub fn annotations(&self) -> Option<&AnnotationsDirectory> {
+        return self.annotations.as_ref();
+    }
+
+    pub fn class_data(&self) -> Option<&ClassData> {
+        return self.class_data.as_ref();
+    }
+}
+
+impl ClassData {
+    pub fn static_fields(&self) -> &[EncodedField] {
+        return &self.static_fields;
+    }
+
+    pub fn instance_fields(&self) -> &[EncodedField] {
+        return &self.instance_fields;
+    }
+
+    pub fn direct_methods(&self) -> &[EncodedMethod] {
+        return &self.direct_methods;
+    }
+
+    pub fn virtual_methods(&self) -> &[EncodedMethod] {
+        return &self.virtual_methods;
+    }
+}
+
+impl EncodedField {
+    pub fn field_idx(&self) -> u32 {
+        return self.field_idx;
+    }
+
+    pub fn access_flags(&self) -> u32 {
+        return self.access_flags;
+    }
+}
+
+impl EncodedMethod {
+    pub fn method_idx(&self) -> u32 {
+        return self.method_idx;
+    }
+
+    pub fn access_flags(&self) -> u32 {
+        return self.access_flags;
+    }
+
+    pub fn code(&self) -> Option<&CodeItem> {
+        return self.code.as_ref();
+    }
+}
+
+impl CodeItem {
+    pub fn registers_size(&self) -> u16 {
+        return self.registers_size;
+    }
+
+    pub fn ins_size(&self) -> u16 {
+        return self.ins_size;
+    }
+
+    pub fn outs_size(&self) -> u16 {
+        return self.outs_size;
+    }
+
+    pub fn insns(&self) -> &[u16] {
+        return &self.insns;
+    }
+
+    pub fn tries(&self) -> &[TryItem] {
+        return &self.tries;
+    }
+
+    /// Looks up the catch handler a try_item points to
+    pub fn handler(&self, try_item: &TryItem) -> Option<&EncodedCatchHandler> {
+        return self.handlers.iter().find(|h| h.offset == try_item.handler_off);
+    }
+
+    pub fn debug_info(&self) -> Option<&DebugInfo> {
+        return self.debug_info.as_ref();
+    }
+}
+
+impl DebugInfo {
+    /// The initial value of the line register
+    pub fn line_start(&self) -> u32 {
+        return self.line_start;
+    }
+
+    /// The string_idx of each parameter's name, NO_INDEX if it has none.
+    /// `this` is not listed.
+    pub fn parameter_names(&self) -> &[u32] {
+        return &self.parameter_names;
+    }
+
+    pub fn events(&self) -> &[(u32, DebugEvent)] {
+        return &self.events;
+    }
+
+    /// The line table. Positions start out in `source_file_idx`, which is
+    /// the source_file_idx of the class, until DBG_SET_FILE changes it.
+    pub fn positions(&self, source_file_idx: u32) -> Vec<Position> {
+        let mut positions = vec![];
+        let mut source_file_idx = source_file_idx;
+        for &(address, event) in self.events.iter() {
+            match event {
+                DebugEvent::Line(line) => {
+                    positions.push(Position {
+                        address: address,
+                        line: line,
+                        source_file_idx: source_file_idx,
+                    })
+                }
+                DebugEvent::SetFile(idx) => source_file_idx = idx,
+                _ => {}
+            }
+        }
+        return positions;
+    }
+
+    /// The position the instruction at `address` belongs to, as needed to
+    /// map a stack trace back to the source
+    pub fn position_at(&self, source_file_idx: u32, address: u32) -> Option<Position> {
+        return self.positions(source_file_idx)
+            .into_iter()
+            .take_while(|position| position.address <= address)
+            .last();
+    }
+
+    /// The local variables with their live ranges. Variables still live at
+    /// the end of the program end at `code_size`, the length of the insns.
+    pub fn locals(&self, code_size: u32) -> Vec<LocalVariable> {
+        let mut locals: Vec<LocalVariable> = vec![];
+        let mut live: Vec<usize> = vec![];
+        for &(address, event) in self.events.iter() {
+            let register = match event {
+                DebugEvent::StartLocal { register, .. } |
+                DebugEvent::EndLocal(register) |
+                DebugEvent::RestartLocal(register) => register,
+                _ => continue,
+            };
+
+            // Whatever the register held before ends here
+            if let Some(i) = live.iter().position(|&i| locals[i].register == register) {
+                locals[live.remove(i)].end_addr = address;
+            }
+            let local = match event {
+                DebugEvent::StartLocal { name_idx, type_idx, signature_idx, .. } => {
+                    LocalVariable {
+                        register: register,
+                        name_idx: name_idx,
+                        type_idx: type_idx,
+                        signature_idx: signature_idx,
+                        start_addr: address,
+                        end_addr: code_size,
+                    }
+                }
+                DebugEvent::RestartLocal(_) => {
+                    match locals.iter().rev().find(|local| local.register == register) {
+                        Some(&previous) => {
+                            LocalVariable {
+                                start_addr: address,
+                                end_addr: code_size,
+                                ..previous
+                            }
+                        }
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+            live.push(locals.len());
+            locals.push(local);
+        }
+        return locals;
+    }
+}
+
+impl Position {
+    pub fn address(&self) -> u32 {
+        return self.address;
+    }
+
+    pub fn line(&self) -> u32 {
+        return self.line;
+    }
+
+    /// NO_INDEX if the file is unknown
+    pub fn source_file_idx(&self) -> u32 {
+        return self.source_file_idx;
+    }
+}
+
+impl LocalVariable {
+    pub fn register(&self) -> u32 {
+        return self.register;
+    }
+
+    /// NO_INDEX for an unnamed variable
+    pub fn name_idx(&self) -> u32 {
+        return self.name_idx;
+    }
+
+    pub fn type_idx(&self) -> u32 {
+        return self.type_idx;
+    }
+
+    /// The generic signature of the type, NO_INDEX if there is none
+    pub fn signature_idx(&self) -> u32 {
+        return self.signature_idx;
+    }
+
+    pub fn start_addr(&self) -> u32 {
+        return self.start_addr;
+    }
+
+    /// First address the variable is no longer live at
+    pub fn end_addr(&self) -> u32 {
+        return self.end_addr;
+    }
+}
+
+impl TryItem {
+    pub fn start_addr(&self) -> u32 {
+        return self.start_addr;
+    }
+
+    /// First address past the end of the covered range
+    pub fn end_addr(&self) -> u32 {
+        return self.start_addr.saturating_add(self.insn_count as u32);
+    }
+}
+
+impl EncodedCatchHandler {
+    pub fn handlers(&self) -> &[TypeAddrPair] {
+        return &self.handlers;
+    }
+
+    pub fn catch_all_addr(&self) -> Option<u32> {
+        return self.catch_all_addr;
+    }
+}
+
+impl TypeAddrPair {
+    pub fn type_idx(&self) -> u32 {
+        return self.type_idx;
+    }
+
+    pub fn addr(&self) -> u32 {
+        return self.addr;
+    }
+}
+
+/// Reads the endian_tag at the start of a header, which decides the byte
+/// order of every other field in the file
+pub fn endianness(header: &[u8]) -> Result<EndianConstant, DexError> {
+    return endianness_at(header, 0);
+}
+
+fn endianness_at(input: &[u8], base: usize) -> Result<EndianConstant, DexError> {
+    let off = base + 40;
+    let tag = match input.get(off..off + 4) {
+        Some(tag) => (tag[0] as u32) | (tag[1] as u32) << 8 | (tag[2] as u32) << 16 |
+                     (tag[3] as u32) << 24,
+        None => return Err(DexError::new("header", base, DexErrorKind::Truncated)),
+    };
+    match tag {
+        ENDIAN_CONSTANT => Ok(EndianConstant::EndianConstant),
+        REVERSE_ENDIAN_CONSTANT => Ok(EndianConstant::ReverseEndianConstant),
+        _ => Err(DexError::new("header", off, DexErrorKind::UnknownEndianTag(tag))),
+    }
+}
+
+fn fail<'a, O>(reason: u32) -> IResult<&'a [u8], O> {
+    return IResult::Error(Err::Code(ErrorKind::Custom(reason)));
+}
+
+/// Hands the failure of a nested parser on to the caller, keeping its reason
+fn forward<O, P>(failed: IResult<&[u8], O>) -> IResult<&[u8], P> {
+    match failed {
+        IResult::Error(Err::Code(ErrorKind::Custom(reason))) => fail(reason),
+        _ => fail(TRUNCATED),
+    }
+}
+
+/// Runs `parser` on the data at file offset `off` and attributes a failure
+/// to `section`
+fn parse_at<'a, O, F>(input: &'a [u8],
+                      off: usize,
+                      section: &'static str,
+                      parser: F)
+                      -> Result<O, DexError>
+    where F: FnOnce(&'a [u8]) -> IResult<&'a [u8], O>
+{
+    let data = match input.get(off..) {
+        Some(data) => data,
+        None => return Err(DexError::new(section, off, DexErrorKind::OffsetOutOfBounds)),
+    };
+    let kind = match parser(data) {
+        IResult::Done(_, o) => return Ok(o),
+        IResult::Error(Err::Code(ErrorKind::Custom(BAD_LEB128))) => DexErrorKind::BadLeb128,
+        IResult::Error(Err::Code(ErrorKind::Custom(BAD_VALUE))) => DexErrorKind::InvalidValue,
+        _ => DexErrorKind::Truncated,
+    };
+    return Err(DexError::new(section, off, kind));
+}
+
+/// Fails unless `index` is below `size`, or NO_INDEX where that is allowed
+fn check_index(section: &'static str,
+               offset: usize,
+               index: u32,
+               size: usize,
+               optional: bool)
+               -> Result<(), DexError> {
+    if (index as usize) < size || (optional && index == NO_INDEX) {
+        return Ok(());
+    }
+    return Err(DexError::new(section,
+                             offset,
+                             DexErrorKind::IndexOutOfBounds {
+                                 index: index,
+                                 size: size as u32,
+                             }));
+}
+
+fn header_endian(input: &[u8], big_endian: bool) -> IResult<&[u8], EndianConstant> {
+    // Read in the file's own byte order the tag is always ENDIAN_CONSTANT
+    return match u32!(input, big_endian) {
+        IResult::Done(r, ENDIAN_CONSTANT) if big_endian => {
+            IResult::Done(r, EndianConstant::ReverseEndianConstant)
+        }
+        IResult::Done(r, ENDIAN_CONSTANT) => IResult::Done(r, EndianConstant::EndianConstant),
+        IResult::Done(..) => fail(BAD_VALUE),
+        failed => forward(failed),
+    };
+}
+
+fn header(input: &[u8], big_endian: bool) -> IResult<&[u8], DexHeader> {
+    return chain!(input,
+       	magic: tag!("dex\n")~
+		version: take!(4)~
+		checksum: u32!(big_endian)~
+		signature: take!(20)~
+		file_size: u32!(big_endian)~
+		header_size: u32!(big_endian)~ // represents 0x70
+		endian_tag: apply!(header_endian, big_endian)~
+		link_size: u32!(big_endian)~
+		link_off: u32!(big_endian)~
+		map_off: u32!(big_endian)~
+		string_ids_size: u32!(big_endian)~
+		string_ids_off: u32!(big_endian)~
+		type_ids_size: u32!(big_endian)~
+		type_ids_off: u32!(big_endian)~
+		proto_ids_size: u32!(big_endian)~
+		proto_ids_off: u32!(big_endian)~
+		field_ids_size: u32!(big_endian)~
+		field_ids_off: u32!(big_endian)~
+		method_ids_size: u32!(big_endian)~
+		method_ids_off: u32!(big_endian)~
+		class_defs_size: u32!(big_endian)~
+		class_defs_off: u32!(big_endian)~
+		data_size: u32!(big_endian)~
+		data_off: u32!(big_endian),
+		|| DexHeader{
+		    magic:as_array(&magic[0..4]),
+	        version:as_array(&version[0..4]),
+	        checksum:checksum,
+	        signature:as_array(&signature[0..20]),
+	        file_size:file_size,
+	        header_size:header_size,
+	        endian_tag:endian_tag,
+	        link_size:link_size,
+	        link_off:link_off,
+	        map_off:map_off,
+	        string_ids_size:string_ids_size,
+	        string_ids_off:string_ids_off,
+	        type_ids_size:type_ids_size,
+	        type_ids_off:type_ids_off,
+	        proto_ids_size:proto_ids_size,
+	        proto_ids_off:proto_ids_off,
+	        field_ids_size:field_ids_size,
+	        field_ids_off:field_ids_off,
+	        method_ids_size:method_ids_size,
+	        method_ids_off:method_ids_off,
+	        class_defs_size:class_defs_size,
+	        class_defs_off:class_defs_off,
+	        data_size:data_size,
+	        data_off:data_off,
+	        container_size: 0,
+	        header_offset: 0,
+	        });
+}
+
+
+fn proto_id(input: &[u8], big_endian: bool) -> IResult<&[u8], ProtoID> {
+    return chain!(input,
+		shorty_idx: u32!(big_endian)~
+	    return_type_idx: u32!(big_endian)~
+	    parameters_off : u32!(big_endian),
+		|| ProtoID {
+			shorty_idx:shorty_idx,
+			return_type_idx:return_type_idx,
+			parameters_off:parameters_off,
+			parameters: vec![],
+		});
+}
+
+fn method_id(input: &[u8], big_endian: bool) -> IResult<&[u8], MethodID> {
+    return chain!(input,
+		class_idx : u16!(big_endian) ~
+		proto_idx : u16!(big_endian) ~
+		name_idx : u32!(big_endian) ,
+	|| MethodID {
+		class_idx:class_idx,
+		proto_idx:proto_idx,
+		name_idx:name_idx
+	});
+}
+
+fn field_id(input: &[u8], big_endian: bool) -> IResult<&[u8], FieldID> {
+    return chain!(input,
+		class_idx : u16!(big_endian) ~
+		type_idx : u16!(big_endian) ~
+		name_idx : u32!(big_endian) ,
+	|| FieldID {
+		class_idx:class_idx,
+		type_idx:type_idx,
+		name_idx:name_idx
+	});
+}
+
+fn class_def(input: &[u8], big_endian: bool) -> IResult<&[u8], ClassDef> {
+    return chain!(input,
+	    class_idx: u32!(big_endian) ~
+	    access_flags: u32!(big_endian) ~
+	    superclass_idx: u32!(big_endian) ~
+	    interfaces_off: u32!(big_endian) ~
+	    source_file_idx: u32!(big_endian) ~
+	    annotations_off: u32!(big_endian) ~
+	    class_data_off: u32!(big_endian) ~
+	    static_values_off: u32!(big_endian),
+		|| ClassDef {
+		    class_idx: class_idx,
+		    access_flags: access_flags,
+		    superclass_idx: superclass_idx,
+		    interfaces_off: interfaces_off,
+		    source_file_idx: source_file_idx,
+		    annotations_off: annotations_off,
+		    class_data_off: class_data_off,
+		    static_values_off: static_values_off,
+		    interfaces: vec![],
+		    static_values: vec![],
+		    annotations: None,
+		    class_data: None,
+		});
+}
+
+fn uleb128(input: &[u8]) -> IResult<&[u8], u32> {
+    let mut rest = input;
+    match leb128::read::unsigned(&mut rest) {
+        Ok(value) if value <= 0xFFFFFFFF => IResult::Done(rest, value as u32),
+        Err(leb128::read::Error::IoError(_)) => fail(TRUNCATED),
+        _ => fail(BAD_LEB128),
+    }
+}
+
+/// Reads an encoded_field list. Field indices are stored as the difference
+/// to the previous entry of the same list.
+fn encoded_fields(input: &[u8], count: u32) -> IResult<&[u8], Vec<EncodedField>> {
+    let mut rest = input;
+    let mut list: Vec<EncodedField> = vec![];
+    let mut field_idx: u32 = 0;
+    for _ in 0..count {
+        let entry = chain!(rest,
+            field_idx_diff: uleb128 ~
+            access_flags: uleb128,
+            || (field_idx_diff, access_flags));
+        match entry {
+            IResult::Done(i, (diff, access_flags)) => {
+                rest = i;
+                field_idx = field_idx.wrapping_add(diff);
+                list.push(EncodedField {
+                    field_idx: field_idx,
+                    access_flags: access_flags,
+                });
+                continue;
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+/// Reads an encoded_method list. Like fields, method indices are delta
+/// encoded against the previous entry.
+fn encoded_methods(input: &[u8], count: u32) -> IResult<&[u8], Vec<EncodedMethod>> {
+    let mut rest = input;
+    let mut list: Vec<EncodedMethod> = vec![];
+    let mut method_idx: u32 = 0;
+    for _ in 0..count {
+        let entry = chain!(rest,
+            method_idx_diff: uleb128 ~
+            access_flags: uleb128 ~
+            code_off: uleb128,
+            || (method_idx_diff, access_flags, code_off));
+        match entry {
+            IResult::Done(i, (diff, access_flags, code_off)) => {
+                rest = i;
+                method_idx = method_idx.wrapping_add(diff);
+                list.push(EncodedMethod {
+                    method_idx: method_idx,
+                    access_flags: access_flags,
+                    code_off: code_off,
+                    code: None,
+                });
+                continue;
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+named!(class_data(&[u8]) -> ClassData, chain!(
+        static_fields_size: uleb128 ~
+        instance_fields_size: uleb128 ~
+        direct_methods_size: uleb128 ~
+        virtual_methods_size: uleb128 ~
+        static_fields: apply!(encoded_fields, static_fields_size) ~
+        instance_fields: apply!(encoded_fields, instance_fields_size) ~
+        direct_methods: apply!(encoded_methods, direct_methods_size) ~
+        virtual_methods: apply!(encoded_methods, virtual_methods_size),
+    || ClassData {
+        static_fields: static_fields,
+        instance_fields: instance_fields,
+        direct_methods: direct_methods,
+        virtual_methods: virtual_methods,
+    })
+);
+
+fn sleb128(input: &[u8]) -> IResult<&[u8], i32> {
+    let mut rest = input;
+    match leb128::read::signed(&mut rest) {
+        Ok(value) if (-0x80000000..=0x7FFFFFFF).contains(&value) => {
+            IResult::Done(rest, value as i32)
+        }
+        Err(leb128::read::Error::IoError(_)) => fail(TRUNCATED),
+        _ => fail(BAD_LEB128),
+    }
+}
+
+/// Reads a type_list: a u32 size followed by that many u16 type indices.
+fn type_list(input: &[u8], big_endian: bool) -> IResult<&[u8], Vec<u16>> {
+    let (mut rest, size) = match u32!(input, big_endian) {
+        IResult::Done(i, o) => (i, o),
+        failed => return forward(failed),
+    };
+    let mut list: Vec<u16> = vec![];
+    for _ in 0..size {
+        match u16!(rest, big_endian) {
+            IResult::Done(i, o) => {
+                rest = i;
+                list.push(o);
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+fn code_units(input: &[u8], count: u32, big_endian: bool) -> IResult<&[u8], Vec<u16>> {
+    if input.len() / 2 < count as usize {
+        return fail(TRUNCATED);
+    }
+    let mut rest = input;
+    let mut list: Vec<u16> = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        match u16!(rest, big_endian) {
+            IResult::Done(i, o) => {
+                rest = i;
+                list.push(o);
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+fn try_item(input: &[u8], big_endian: bool) -> IResult<&[u8], TryItem> {
+    return chain!(input,
+        start_addr: u32!(big_endian) ~
+        insn_count: u16!(big_endian) ~
+        handler_off: u16!(big_endian),
+    || TryItem {
+        start_addr: start_addr,
+        insn_count: insn_count,
+        handler_off: handler_off,
+    });
+}
+
+fn encoded_catch_handler(input: &[u8], offset: u16) -> IResult<&[u8], EncodedCatchHandler> {
+    let (mut rest, size) = match sleb128(input) {
+        IResult::Done(i, o) => (i, o),
+        failed => return forward(failed),
+    };
+
+    // A non-positive size announces a trailing catch-all handler
+    let mut handlers: Vec<TypeAddrPair> = vec![];
+    for _ in 0..(size as i64).abs() {
+        let entry = chain!(rest,
+            type_idx: uleb128 ~
+            addr: uleb128,
+            || TypeAddrPair { type_idx: type_idx, addr: addr });
+        match entry {
+            IResult::Done(i, o) => {
+                rest = i;
+                handlers.push(o);
+            }
+            failed => return forward(failed),
+        }
+    }
+
+    let mut catch_all_addr = None;
+    if size <= 0 {
+        match uleb128(rest) {
+            IResult::Done(i, o) => {
+                rest = i;
+                catch_all_addr = Some(o);
+            }
+            failed => return forward(failed),
+        }
+    }
+
+    return IResult::Done(rest,
+                         EncodedCatchHandler {
+                             offset: offset,
+                             handlers: handlers,
+                             catch_all_addr: catch_all_addr,
+                         });
+}
+
+fn encoded_catch_handler_list(input: &[u8]) -> IResult<&[u8], Vec<EncodedCatchHandler>> {
+    let (mut rest, size) = match uleb128(input) {
+        IResult::Done(i, o) => (i, o),
+        failed => return forward(failed),
+    };
+    let mut list: Vec<EncodedCatchHandler> = vec![];
+    for _ in 0..size {
+        // try_items address handlers with a u16 offset
+        let offset = input.len() - rest.len();
+        if offset > u16::MAX as usize {
+            return fail(BAD_VALUE);
+        }
+        match encoded_catch_handler(rest, offset as u16) {
+            IResult::Done(i, o) => {
+                rest = i;
+                list.push(o);
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+fn code_item(input: &[u8], big_endian: bool) -> IResult<&[u8], CodeItem> {
+    let header = chain!(input,
+        registers_size: u16!(big_endian) ~
+        ins_size: u16!(big_endian) ~
+        outs_size: u16!(big_endian) ~
+        tries_size: u16!(big_endian) ~
+        debug_info_off: u32!(big_endian) ~
+        insns_size: u32!(big_endian),
+        || (registers_size, ins_size, outs_size, tries_size, debug_info_off, insns_size));
+    let (rest, (registers_size, ins_size, outs_size, tries_size, debug_info_off, insns_size)) =
+        match header {
+            IResult::Done(i, o) => (i, o),
+            failed => return forward(failed),
+        };
+
+    let (mut rest, insns) = match code_units(rest, insns_size, big_endian) {
+        IResult::Done(i, o) => (i, o),
+        failed => return forward(failed),
+    };
+
+    let mut tries: Vec<TryItem> = vec![];
+    let mut handlers: Vec<EncodedCatchHandler> = vec![];
+    if tries_size != 0 {
+        // try_items are 4-byte aligned
+        if insns_size % 2 == 1 {
+            if rest.len() < 2 {
+                return fail(TRUNCATED);
+            }
+            rest = &rest[2..];
+        }
+        for _ in 0..tries_size {
+            match try_item(rest, big_endian) {
+                IResult::Done(i, o) => {
+                    rest = i;
+                    tries.push(o);
+                }
+                failed => return forward(failed),
+            }
+        }
+        match encoded_catch_handler_list(rest) {
+            IResult::Done(i, o) => {
+                rest = i;
+                handlers = o;
+            }
+            failed => return forward(failed),
+        }
+    }
+
+    return IResult::Done(rest,
+                         CodeItem {
+                             registers_size: registers_size,
+                             ins_size: ins_size,
+                             outs_size: outs_size,
+                             debug_info_off: debug_info_off,
+                             insns: insns,
+                             tries: tries,
+                             handlers: handlers,
+                             debug_info: None,
+                         });
+}
+
+pub const DBG_END_SEQUENCE: u8 = 0x00;
+pub const DBG_ADVANCE_PC: u8 = 0x01;
+pub const DBG_ADVANCE_LINE: u8 = 0x02;
+pub const DBG_START_LOCAL: u8 = 0x03;
+pub const DBG_START_LOCAL_EXTENDED: u8 = 0x04;
+pub const DBG_END_LOCAL: u8 = 0x05;
+pub const DBG_RESTART_LOCAL: u8 = 0x06;
+pub const DBG_SET_PROLOGUE_END: u8 = 0x07;
+pub const DBG_SET_EPILOGUE_BEGIN: u8 = 0x08;
+pub const DBG_SET_FILE: u8 = 0x09;
+pub const DBG_FIRST_SPECIAL: u8 = 0x0a;
+pub const DBG_LINE_BASE: i32 = -4;
+pub const DBG_LINE_RANGE: u8 = 15;
+
+/// A uleb128p1, in which 0 stands for NO_INDEX
+fn uleb128p1(input: &[u8]) -> IResult<&[u8], u32> {
+    match uleb128(input) {
+        IResult::Done(i, value) => IResult::Done(i, value.wrapping_sub(1)),
+        failed => forward(failed),
+    }
+}
+
+/// Reads a debug_info_item, running its state machine up to
+/// DBG_END_SEQUENCE
+fn debug_info(input: &[u8]) -> IResult<&[u8], DebugInfo> {
+    let header = chain!(input,
+        line_start: uleb128 ~
+        parameters_size: uleb128,
+        || (line_start, parameters_size));
+    let (mut rest, (line_start, parameters_size)) = match header {
+        IResult::Done(i, o) => (i, o),
+        failed => return forward(failed),
+    };
+    let mut parameter_names: Vec<u32> = vec![];
+    for _ in 0..parameters_size {
+        match uleb128p1(rest) {
+            IResult::Done(i, name_idx) => {
+                rest = i;
+                parameter_names.push(name_idx);
+            }
+            failed => return forward(failed),
+        }
+    }
+
+    let mut events: Vec<(u32, DebugEvent)> = vec![];
+    let mut address: u32 = 0;
+    let mut line = line_start;
+    loop {
+        let opcode = match rest.split_first() {
+            Some((&opcode, tail)) => {
+                rest = tail;
+                opcode
+            }
+            None => return fail(TRUNCATED),
+        };
+        let operands = match opcode {
+            DBG_END_SEQUENCE => {
+                return IResult::Done(rest,
+                                     DebugInfo {
+                                         line_start: line_start,
+                                         parameter_names: parameter_names,
+                                         events: events,
+                                     })
+            }
+            DBG_ADVANCE_PC | DBG_END_LOCAL | DBG_RESTART_LOCAL => {
+                chain!(rest, value: uleb128, || (value, 0, 0, 0))
+            }
+            DBG_ADVANCE_LINE => chain!(rest, diff: sleb128, || (diff as u32, 0, 0, 0)),
+            DBG_START_LOCAL => {
+                chain!(rest,
+                    register: uleb128 ~
+                    name_idx: uleb128p1 ~
+                    type_idx: uleb128p1,
+                    || (register, name_idx, type_idx, NO_INDEX))
+            }
+            DBG_START_LOCAL_EXTENDED => {
+                chain!(rest,
+                    register: uleb128 ~
+                    name_idx: uleb128p1 ~
+                    type_idx: uleb128p1 ~
+                    signature_idx: uleb128p1,
+                    || (register, name_idx, type_idx, signature_idx))
+            }
+            DBG_SET_FILE => chain!(rest, name_idx: uleb128p1, || (name_idx, 0, 0, 0)),
+            _ => IResult::Done(rest, (0, 0, 0, 0)),
+        };
+        let (value, name_idx, type_idx, signature_idx) = match operands {
+            IResult::Done(i, o) => {
+                rest = i;
+                o
+            }
+            failed => return forward(failed),
+        };
+        let event = match opcode {
+            DBG_ADVANCE_PC => {
+                address = address.wrapping_add(value);
+                continue;
+            }
+            DBG_ADVANCE_LINE => {
+                line = line.wrapping_add(value);
+                continue;
+            }
+            DBG_START_LOCAL | DBG_START_LOCAL_EXTENDED => {
+                DebugEvent::StartLocal {
+                    register: value,
+                    name_idx: name_idx,
+                    type_idx: type_idx,
+                    signature_idx: signature_idx,
+                }
+            }
+            DBG_END_LOCAL => DebugEvent::EndLocal(value),
+            DBG_RESTART_LOCAL => DebugEvent::RestartLocal(value),
+            DBG_SET_PROLOGUE_END => DebugEvent::PrologueEnd,
+            DBG_SET_EPILOGUE_BEGIN => DebugEvent::EpilogueBegin,
+            DBG_SET_FILE => DebugEvent::SetFile(value),
+            _ => {
+                // Special opcodes advance both registers and emit a position
+                let adjusted = opcode - DBG_FIRST_SPECIAL;
+                let line_diff = DBG_LINE_BASE + (adjusted % DBG_LINE_RANGE) as i32;
+                line = line.wrapping_add(line_diff as u32);
+                address = address.wrapping_add((adjusted / DBG_LINE_RANGE) as u32);
+                DebugEvent::Line(line)
+            }
+        };
+        events.push((address, event));
+    }
+}
+
+fn fields(input: &[u8], count: u32, big_endian: bool) -> IResult<&[u8], Vec<FieldID>> {
+    let mut rest = input;
+    let mut list: Vec<FieldID> = vec![];
+    for _ in 0..count {
+        let entry = field_id(rest, big_endian);
+        match entry {
+            IResult::Done(i, o) => {
+                rest = i;
+                list.push(o);
+                continue;
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+
+fn methods(input: &[u8], count: u32, big_endian: bool) -> IResult<&[u8], Vec<MethodID>> {
+    let mut rest = input;
+    let mut list: Vec<MethodID> = vec![];
+    for _ in 0..count {
+        let entry = method_id(rest, big_endian);
+        match entry {
+            IResult::Done(i, o) => {
+                rest = i;
+                list.push(o);
+                continue;
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+fn protos(input: &[u8], count: u32, big_endian: bool) -> IResult<&[u8], Vec<ProtoID>> {
+    let mut rest = input;
+    let mut list: Vec<ProtoID> = vec![];
+    for _ in 0..count {
+        let entry = proto_id(rest, big_endian);
+        match entry {
+            IResult::Done(i, o) => {
+                rest = i;
+                list.push(o);
+                continue;
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+
+fn string_ids(input: &[u8], count: u32, big_endian: bool) -> IResult<&[u8], Vec<u32>> {
+    let mut rest = input;
+    let mut list: Vec<u32> = vec![];
+    for _ in 0..count {
+        let entry = u32!(rest, big_endian);
+        match entry {
+            IResult::Done(i, o) => {
+                rest = i;
+                list.push(o);
+                continue;
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+
+fn type_ids(input: &[u8], count: u32, big_endian: bool) -> IResult<&[u8], Vec<u32>> {
+    let mut rest = input;
+    let mut list: Vec<u32> = vec![];
+    for _ in 0..count {
+        let entry = u32!(rest, big_endian);
+        match entry {
+            IResult::Done(i, o) => {
+                rest = i;
+                list.push(o);
+                continue;
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+fn call_site_ids(input: &[u8], count: u32, big_endian: bool) -> IResult<&[u8], Vec<u32>> {
+    let mut rest = input;
+    let mut list: Vec<u32> = vec![];
+    for _ in 0..count {
+        let entry = u32!(rest, big_endian);
+        match entry {
+            IResult::Done(i, o) => {
+                rest = i;
+                list.push(o);
+                continue;
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+fn classes(input: &[u8], count: u32, big_endian: bool) -> IResult<&[u8], Vec<ClassDef>> {
+    let mut rest = input;
+    let mut list: Vec<ClassDef> = vec![];
+    for _ in 0..count {
+        let entry = class_def(rest, big_endian);
+        match entry {
+            IResult::Done(i, o) => {
+                rest = i;
+                list.push(o);
+                continue;
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+fn method_handle(input: &[u8], big_endian: bool) -> IResult<&[u8], (u16, u16)> {
+    return chain!(input,
+		handle_type: u16!(big_endian) ~
+		u16!(big_endian) ~
+		field_or_method_id: u16!(big_endian) ~
+		u16!(big_endian) ,
+	|| (handle_type, field_or_method_id)
+	);
+}
+
+fn method_handles(input: &[u8], count: u32, big_endian: bool) -> IResult<&[u8], Vec<MethodHandle>> {
+    let mut rest = input;
+    let mut list: Vec<MethodHandle> = vec![];
+    for _ in 0..count {
+        match method_handle(rest, big_endian) {
+            IResult::Done(i, (handle_type, id)) => {
+                let handle_type = match MethodHandleType::from_code(handle_type) {
+                    Some(handle_type) => handle_type,
+                    None => return fail(BAD_VALUE),
+                };
+                rest = i;
+                list.push(MethodHandle {
+                    handle_type: handle_type,
+                    field_or_method_id: id,
+                });
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+/// Nesting limit for arrays and annotations inside encoded values, so that
+/// crafted input cannot exhaust the stack
+const MAX_VALUE_DEPTH: u32 = 32;
+
+/// Reads `size` little-endian bytes
+fn value_bytes(input: &[u8], size: usize) -> IResult<&[u8], u64> {
+    if input.len() < size {
+        return fail(TRUNCATED);
+    }
+    let mut value: u64 = 0;
+    for (i, byte) in input[..size].iter().enumerate() {
+        value |= (*byte as u64) << (i * 8);
+    }
+    return IResult::Done(&input[size..], value);
+}
+
+fn encoded_value_at(input: &[u8], depth: u32) -> IResult<&[u8], EncodedValue> {
+    if depth > MAX_VALUE_DEPTH {
+        return fail(BAD_VALUE);
+    }
+    if input.is_empty() {
+        return fail(TRUNCATED);
+    }
+    let value_type = input[0] & 0x1F;
+    let value_arg = (input[0] >> 5) as usize;
+    let rest = &input[1..];
+    let size = value_arg + 1;
+
+    // Nested arrays and annotations, and the types without payload
+    match value_type {
+        0x1C => {
+            return match encoded_array_at(rest, depth + 1) {
+                IResult::Done(i, o) => IResult::Done(i, EncodedValue::Array(o)),
+                failed => forward(failed),
+            }
+        }
+        0x1D => {
+            return match encoded_annotation_at(rest, depth + 1) {
+                IResult::Done(i, o) => IResult::Done(i, EncodedValue::Annotation(o)),
+                failed => forward(failed),
+            }
+        }
+        0x1E => return IResult::Done(rest, EncodedValue::Null),
+        0x1F => return IResult::Done(rest, EncodedValue::Boolean(value_arg != 0)),
+        _ => {}
+    }
+
+    let (rest, raw) = match value_bytes(rest, size) {
+        IResult::Done(i, o) => (i, o),
+        failed => return forward(failed),
+    };
+    // Integers are sign- or zero-extended, floating point values are
+    // right-extended with zero bytes
+    let shift = 64 - size as u32 * 8;
+    let signed = ((raw << shift) as i64) >> shift;
+    let value = match (value_type, size) {
+        (0x00, 1) => EncodedValue::Byte(signed as i8),
+        (0x02, 1..=2) => EncodedValue::Short(signed as i16),
+        (0x03, 1..=2) => EncodedValue::Char(raw as u16),
+        (0x04, 1..=4) => EncodedValue::Int(signed as i32),
+        (0x06, _) => EncodedValue::Long(signed),
+        (0x10, 1..=4) => EncodedValue::Float(f32::from_bits((raw << (32 - size * 8)) as u32)),
+        (0x11, _) => EncodedValue::Double(f64::from_bits(raw << shift)),
+        (0x15, 1..=4) => EncodedValue::MethodType(raw as u32),
+        (0x16, 1..=4) => EncodedValue::MethodHandle(raw as u32),
+        (0x17, 1..=4) => EncodedValue::String(raw as u32),
+        (0x18, 1..=4) => EncodedValue::Type(raw as u32),
+        (0x19, 1..=4) => EncodedValue::Field(raw as u32),
+        (0x1A, 1..=4) => EncodedValue::Method(raw as u32),
+        (0x1B, 1..=4) => EncodedValue::Enum(raw as u32),
+        _ => return fail(BAD_VALUE),
+    };
+    return IResult::Done(rest, value);
+}
+
+fn encoded_array_at(input: &[u8], depth: u32) -> IResult<&[u8], Vec<EncodedValue>> {
+    let (mut rest, size) = match uleb128(input) {
+        IResult::Done(i, o) => (i, o),
+        failed => return forward(failed),
+    };
+    let mut values: Vec<EncodedValue> = vec![];
+    for _ in 0..size {
+        match encoded_value_at(rest, depth) {
+            IResult::Done(i, o) => {
+                rest = i;
+                values.push(o);
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, values);
+}
+
+fn encoded_annotation_at(input: &[u8], depth: u32) -> IResult<&[u8], EncodedAnnotation> {
+    let (mut rest, type_idx) = match uleb128(input) {
+        IResult::Done(i, o) => (i, o),
+        failed => return forward(failed),
+    };
+    let size = match uleb128(rest) {
+        IResult::Done(i, o) => {
+            rest = i;
+            o
+        }
+        failed => return forward(failed),
+    };
+    let mut elements: Vec<AnnotationElement> = vec![];
+    for _ in 0..size {
+        let name_idx = match uleb128(rest) {
+            IResult::Done(i, o) => {
+                rest = i;
+                o
+            }
+            failed => return forward(failed),
+        };
+        match encoded_value_at(rest, depth) {
+            IResult::Done(i, o) => {
+                rest = i;
+                elements.push(AnnotationElement {
+                    name_idx: name_idx,
+                    value: o,
+                });
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest,
+                         EncodedAnnotation {
+                             type_idx: type_idx,
+                             elements: elements,
+                         });
+}
+
+/// Reads an encoded_array_item, as used for static values and call sites
+fn encoded_array(input: &[u8]) -> IResult<&[u8], Vec<EncodedValue>> {
+    return encoded_array_at(input, 0);
+}
+
+fn map_item(input: &[u8], big_endian: bool) -> IResult<&[u8], MapItem> {
+    return chain!(input,
+		item_type: u16!(big_endian) ~
+		u16!(big_endian) ~
+		size: u32!(big_endian) ~
+		offset: u32!(big_endian) ,
+	|| MapItem {
+		item_type: MapItemType::from_code(item_type),
+		size: size,
+		offset: offset,
+	});
+}
+
+fn map_list(input: &[u8], big_endian: bool) -> IResult<&[u8], Vec<MapItem>> {
+    let (mut rest, count) = match u32!(input, big_endian) {
+        IResult::Done(i, o) => (i, o),
+        failed => return forward(failed),
+    };
+    // Every entry takes 12 bytes, don't trust a count the input can't hold
+    if count as usize > rest.len() / 12 {
+        return fail(TRUNCATED);
+    }
+    let mut list: Vec<MapItem> = vec![];
+    for _ in 0..count {
+        match map_item(rest, big_endian) {
+            IResult::Done(i, o) => {
+                rest = i;
+                list.push(o);
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+/// Reads a u32 size followed by that many u32 offsets, the layout of both
+/// annotation_set_item and annotation_set_ref_list
+fn offset_list(input: &[u8], big_endian: bool) -> IResult<&[u8], Vec<u32>> {
+    let (mut rest, size) = match u32!(input, big_endian) {
+        IResult::Done(i, o) => (i, o),
+        failed => return forward(failed),
+    };
+    let mut list: Vec<u32> = vec![];
+    for _ in 0..size {
+        match u32!(rest, big_endian) {
+            IResult::Done(i, o) => {
+                rest = i;
+                list.push(o);
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+/// Reads `count` (index, offset) pairs of an annotations_directory_item
+fn annotation_offsets(input: &[u8],
+                      count: u32,
+                      big_endian: bool)
+                      -> IResult<&[u8], Vec<(u32, u32)>> {
+    let mut rest = input;
+    let mut list: Vec<(u32, u32)> = vec![];
+    for _ in 0..count {
+        match pair!(rest, u32!(big_endian), u32!(big_endian)) {
+            IResult::Done(i, o) => {
+                rest = i;
+                list.push(o);
+            }
+            failed => return forward(failed),
+        }
+    }
+    return IResult::Done(rest, list);
+}
+
+/// The offsets an annotations_directory_item is made of: class annotations,
+/// then pairs of field, method and parameter annotations
+type DirectoryOffsets = (u32, Vec<(u32, u32)>, Vec<(u32, u32)>, Vec<(u32, u32)>);
+
+fn annotations_directory(input: &[u8], big_endian: bool) -> IResult<&[u8], DirectoryOffsets> {
+    return chain!(input,
+        class_annotations_off: u32!(big_endian) ~
+        fields_size: u32!(big_endian) ~
+        methods_size: u32!(big_endian) ~
+        parameters_size: u32!(big_endian) ~
+        fields: apply!(annotation_offsets, fields_size, big_endian) ~
+        methods: apply!(annotation_offsets, methods_size, big_endian) ~
+        parameters: apply!(annotation_offsets, parameters_size, big_endian),
+        || (class_annotations_off, fields, methods, parameters));
+}
+
+fn annotation_item(input: &[u8]) -> IResult<&[u8], Annotation> {
+    let visibility = match input.first() {
+        Some(&code) => Visibility::from_code(code),
+        None => return fail(TRUNCATED),
+    };
+    let visibility = match visibility {
+        Some(visibility) => visibility,
+        None => return fail(BAD_VALUE),
+    };
+    return match encoded_annotation_at(&input[1..], 0) {
+        IResult::Done(rest, annotation) => {
+            IResult::Done(rest,
+                          Annotation {
+                              visibility: visibility,
+                              annotation: annotation,
+                          })
+        }
+        failed => forward(failed),
+    };
+}
+
+/// Reads the annotation_set_item at `off`, where 0 stands for an empty set
+fn annotation_set(input: &[u8], off: usize, big_endian: bool) -> Result<Vec<Annotation>, DexError> {
+    let mut set: Vec<Annotation> = vec![];
+    if off == 0 {
+        return Ok(set);
+    }
+    let entries = parse_at(input, off, "annotation_set_item", |i| offset_list(i, big_endian))?;
+    for entry in entries {
+        set.push(parse_at(input, entry as usize, "annotation_item", annotation_item)?);
+    }
+    return Ok(set);
+}
+
+/// Reads an annotations_directory_item along with every set it refers to
+fn annotations(input: &[u8],
+               off: usize,
+               big_endian: bool,
+               fields_size: usize,
+               methods_size: usize)
+               -> Result<AnnotationsDirectory, DexError> {
+    let section = "annotations_directory_item";
+    let (class_off, fields, methods, parameters) =
+        parse_at(input, off, section, |i| annotations_directory(i, big_endian))?;
+
+    let mut directory = AnnotationsDirectory {
+        class_annotations: annotation_set(input, class_off as usize, big_endian)?,
+        field_annotations: vec![],
+        method_annotations: vec![],
+        parameter_annotations: vec![],
+    };
+    for (field_idx, set_off) in fields {
+        check_index(section, off, field_idx, fields_size, false)?;
+        directory.field_annotations.push(FieldAnnotation {
+            field_idx: field_idx,
+            annotations: annotation_set(input, set_off as usize, big_endian)?,
+        });
+    }
+    for (method_idx, set_off) in methods {
+        check_index(section, off, method_idx, methods_size, false)?;
+        directory.method_annotations.push(MethodAnnotation {
+            method_idx: method_idx,
+            annotations: annotation_set(input, set_off as usize, big_endian)?,
+        });
+    }
+    for (method_idx, list_off) in parameters {
+        check_index(section, off, method_idx, methods_size, false)?;
+        let list_off = list_off as usize;
+        let sets = parse_at(input,
+                            list_off,
+                            "annotation_set_ref_list",
+                            |i| offset_list(i, big_endian))?;
+        let mut parameters = vec![];
+        for set_off in sets {
+            parameters.push(annotation_set(input, set_off as usize, big_endian)?);
+        }
+        directory.parameter_annotations.push(ParameterAnnotation {
+            method_idx: method_idx,
+            parameters: parameters,
+        });
+    }
+    return Ok(directory);
+}
+
+/// Reads the string_data_item at `off`: a ULEB128 encoded utf16_size
+/// followed by the Modified UTF-8 bytes and a terminating NUL. Returns the
+/// UTF-16 code units.
+fn string_data(input: &[u8], off: usize) -> Result<Vec<u16>, DexError> {
+    let (size, data) = parse_at(input, off, "string_data_item", |i| {
+        let (rest, size) = match uleb128(i) {
+            IResult::Done(rest, size) => (rest, size),
+            failed => return forward(failed),
+        };
+        match rest.iter().position(|&b| b == 0) {
+            Some(end) => IResult::Done(&rest[end..], (size, &rest[..end])),
+            None => fail(TRUNCATED),
+        }
+    })?;
+    let units = match mutf8_decode(data) {
+        Some(units) => units,
+        None => return Err(DexError::new("string_data_item", off, DexErrorKind::InvalidMutf8)),
+    };
+    if units.len() != size as usize {
+        let kind = DexErrorKind::StringSizeMismatch {
+            declared: size,
+            decoded: units.len() as u32,
+        };
+        return Err(DexError::new("string_data_item", off, kind));
+    }
+    return Ok(units);
+}
+
+/// Decodes Modified UTF-8 as used by the DEX format into UTF-16 code units.
+///
+/// MUTF-8 encodes UTF-16 code units rather than code points, so characters
+/// outside the BMP arrive as two separately encoded surrogates and U+0000 is
+/// written as 0xC0 0x80. Malformed byte sequences yield `None`. Surrogates
+/// are kept as they are, paired or not.
+pub fn mutf8_decode(data: &[u8]) -> Option<Vec<u16>> {
+    let mut units: Vec<u16> = Vec::with_capacity(data.len());
+    let mut i = 0;
+
+    while i < data.len() {
+        let a = data[i] as u16;
+        match data[i] >> 4 {
+            0x0..=0x7 if a != 0 => {
+                units.push(a);
+                i += 1;
+            }
+            0xC | 0xD => {
+                if i + 1 >= data.len() || data[i + 1] & 0xC0 != 0x80 {
+                    return None;
+                }
+                let b = data[i + 1] as u16;
+                units.push(((a & 0x1F) << 6) | (b & 0x3F));
+                i += 2;
+            }
+            0xE => {
+                if i + 2 >= data.len() || data[i + 1] & 0xC0 != 0x80 ||
+                   data[i + 2] & 0xC0 != 0x80 {
+                    return None;
+                }
+                let b = data[i + 1] as u16;
+                let c = data[i + 2] as u16;
+                units.push(((a & 0x0F) << 12) | ((b & 0x3F) << 6) | (c & 0x3F));
+                i += 3;
+            }
+            _ => return None,
+        }
+    }
+
+    return Some(units);
+}
+
+/// Encodes UTF-16 code units as Modified UTF-8, the inverse of
+/// `mutf8_decode`. The terminating NUL is not included.
+pub fn mutf8_encode(units: &[u16]) -> Vec<u8> {
+    let mut data: Vec<u8> = Vec::with_capacity(units.len());
+    for &unit in units {
+        match unit {
+            0x01..=0x7F => data.push(unit as u8),
+            0x00 | 0x80..=0x7FF => {
+                data.push(0xC0 | (unit >> 6) as u8);
+                data.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                data.push(0xE0 | (unit >> 12) as u8);
+                data.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                data.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    return data;
+}
+
+/// The checksum is stored in the byte order of the file. An unreadable
+/// endian_tag is treated as little-endian, which is what nearly all files use.
+fn stored_big_endian(data: &[u8]) -> bool {
+    return endianness(data).map(|e| e.is_big_endian()).unwrap_or(false);
+}
+
+/// Computes checksum and signature of a DEX image and compares them with
+/// the ones in its header. `None` if the data is too short to hold them.
+pub fn verify_integrity(data: &[u8]) -> Option<Integrity> {
+    if data.len() < 32 {
+        return None;
+    }
+    let stored_checksum = match u32!(&data[8..12], stored_big_endian(data)) {
+        IResult::Done(_, o) => o,
+        _ => return None,
+    };
+    return Some(Integrity {
+        stored_checksum: stored_checksum,
+        computed_checksum: digest::adler32(&data[12..]),
+        stored_signature: as_array(&data[12..32]),
+        computed_signature: digest::sha1(&data[32..]),
+    });
+}
+
+/// Rewrites checksum and signature of a DEX image to match its contents.
+/// Returns whether anything had to be changed.
+pub fn repair_integrity(data: &mut [u8]) -> bool {
+    if data.len() < 32 {
+        return false;
+    }
+    // The checksum covers the signature, so that has to be fixed first
+    let signature = digest::sha1(&data[32..]);
+    let mut changed = data[12..32] != signature;
+    data[12..32].copy_from_slice(&signature);
+
+    let checksum = digest::adler32(&data[12..]);
+    let bytes = if stored_big_endian(data) {
+        checksum.to_be_bytes()
+    } else {
+        checksum.to_le_bytes()
+    };
+    changed |= data[8..12] != bytes;
+    data[8..12].copy_from_slice(&bytes);
+    return changed;
+}
+
+/// Oldest and newest format version understood by `parse`
+const MIN_VERSION: u32 = 35;
+const MAX_VERSION: u32 = 41;
+
+/// From version 041 on the header carries container_size and header_offset
+const CONTAINER_VERSION: u32 = 41;
+const CONTAINER_HEADER_SIZE: u32 = 0x78;
+
+/// Parses a DEX file. For a version 041 container this is the first image.
+pub fn parse(data: Vec<u8>) -> Result<DexFile, DexError> {
+    return parse_image(&data, 0);
+}
+
+/// Parses every image of a version 041 container, in which several DEX
+/// files follow each other and share the data section. Older files hold a
+/// single image.
+pub fn parse_container(data: Vec<u8>) -> Result<Vec<DexFile>, DexError> {
+    let mut images: Vec<DexFile> = vec![];
+    let mut base = 0;
+    loop {
+        let dex = parse_image(&data, base)?;
+        let next = base + dex.header.file_size as usize;
+        let end = ::std::cmp::min(dex.header.container_size as usize, data.len());
+        images.push(dex);
+        if next <= base || next >= end {
+            return Ok(images);
+        }
+        base = next;
+    }
+}
+
+/// Parses the image whose header starts at `base`. Like ART, the id
+/// sections are located relative to the header and everything in the data
+/// section relative to the start of the container.
+fn parse_image(input: &[u8], base: usize) -> Result<DexFile, DexError> {
+    let mut s: Vec<String> = vec![];
+    let mut unpaired_surrogates: HashMap<u32, Vec<u16>> = HashMap::new();
+
+    match input.get(base..base + 4) {
+        Some(magic) if magic == b"dex\n" => {}
+        Some(_) => return Err(DexError::new("header", base, DexErrorKind::BadMagic)),
+        None => return Err(DexError::new("header", base, DexErrorKind::Truncated)),
+    }
+    let big_endian = endianness_at(input, base)?.is_big_endian();
+    let mut h = parse_at(input, base, "header", |i| header(i, big_endian))?;
+
+    let version = match h.version_number() {
+        Some(version) if (MIN_VERSION..=MAX_VERSION).contains(&version) => version,
+        _ => {
+            return Err(DexError::new("header",
+                                     base + 4,
+                                     DexErrorKind::UnsupportedVersion(h.version)))
+        }
+    };
+    if version >= CONTAINER_VERSION && h.header_size >= CONTAINER_HEADER_SIZE {
+        let (container_size, header_offset) = parse_at(input, base + 0x70, "header", |i| {
+            pair!(i, u32!(big_endian), u32!(big_endian))
+        })?;
+        if header_offset as usize != base {
+            return Err(DexError::new("header", base + 0x74, DexErrorKind::InvalidValue));
+        }
+        h.container_size = container_size;
+        h.header_offset = header_offset;
+    }
+
+    let string_id_off = base + h.string_ids_off as usize;
+    let si = parse_at(input, string_id_off, "string_ids", |i| {
+        string_ids(i, h.string_ids_size, big_endian)
+    })?;
+    for off in si {
+        let units = string_data(input, off as usize)?;
+        match String::from_utf16(&units) {
+            Ok(string) => s.push(string),
+            Err(_) => {
+                s.push(String::from_utf16_lossy(&units));
+                unpaired_surrogates.insert(s.len() as u32 - 1, units);
+            }
+        }
+    }
+
+    let type_off = base + h.type_ids_off as usize;
+    let descriptor_ids = parse_at(input,
+                                  type_off,
+                                  "type_ids",
+                                  |i| type_ids(i, h.type_ids_size, big_endian))?;
+    let mut t: Vec<String> = vec![];
+    for (i, descriptor_idx) in descriptor_ids.into_iter().enumerate() {
+        check_index("type_ids", type_off + i * 4, descriptor_idx, s.len(), false)?;
+        t.push(s[descriptor_idx as usize].clone());
+    }
+
+    let proto_off = base + h.proto_ids_off as usize;
+    let mut p = parse_at(input,
+                         proto_off,
+                         "proto_ids",
+                         |i| protos(i, h.proto_ids_size, big_endian))?;
+    for (i, proto) in p.iter_mut().enumerate() {
+        let item_off = proto_off + i * 12;
+        check_index("proto_ids", item_off, proto.shorty_idx, s.len(), false)?;
+        check_index("proto_ids", item_off, proto.return_type_idx, t.len(), false)?;
+
+        let off = proto.parameters_off as usize;
+        if off == 0 {
+            continue;
+        }
+        proto.parameters = parse_at(input, off, "type_list", |i| type_list(i, big_endian))?;
+        for &type_idx in proto.parameters.iter() {
+            check_index("type_list", off, type_idx as u32, t.len(), false)?;
+        }
+    }
+
+    let field_off = base + h.field_ids_off as usize;
+    let f = parse_at(input,
+                     field_off,
+                     "field_ids",
+                     |i| fields(i, h.field_ids_size, big_endian))?;
+    for (i, field) in f.iter().enumerate() {
+        let item_off = field_off + i * 8;
+        check_index("field_ids", item_off, field.class_idx as u32, t.len(), false)?;
+        check_index("field_ids", item_off, field.type_idx as u32, t.len(), false)?;
+        check_index("field_ids", item_off, field.name_idx, s.len(), false)?;
+    }
+
+    let method_off = base + h.method_ids_off as usize;
+    let m = parse_at(input,
+                     method_off,
+                     "method_ids",
+                     |i| methods(i, h.method_ids_size, big_endian))?;
+    for (i, method) in m.iter().enumerate() {
+        let item_off = method_off + i * 8;
+        check_index("method_ids", item_off, method.class_idx as u32, t.len(), false)?;
+        check_index("method_ids", item_off, method.proto_idx as u32, p.len(), false)?;
+        check_index("method_ids", item_off, method.name_idx, s.len(), false)?;
+    }
+
+    let class_def_off = base + h.class_defs_off as usize;
+    let mut c = parse_at(input,
+                         class_def_off,
+                         "class_defs",
+                         |i| classes(i, h.class_defs_size, big_endian))?;
+    for (i, class) in c.iter_mut().enumerate() {
+        let item_off = class_def_off + i * 32;
+        check_index("class_defs", item_off, class.class_idx, t.len(), false)?;
+        check_index("class_defs", item_off, class.superclass_idx, t.len(), true)?;
+        check_index("class_defs", item_off, class.source_file_idx, s.len(), true)?;
+
+        let off = class.static_values_off as usize;
+        if off != 0 {
+            class.static_values = parse_at(input, off, "encoded_array_item", encoded_array)?;
+        }
+
+        let off = class.annotations_off as usize;
+        if off != 0 {
+            class.annotations = Some(annotations(input, off, big_endian, f.len(), m.len())?);
+        }
+
+        let off = class.interfaces_off as usize;
+        if off == 0 {
+            continue;
+        }
+        class.interfaces = parse_at(input, off, "type_list", |i| type_list(i, big_endian))?;
+        for &type_idx in class.interfaces.iter() {
+            check_index("type_list", off, type_idx as u32, t.len(), false)?;
+        }
+    }
+
+    // A broken map_list is reported by validate_map instead of failing here
+    let off = h.map_off as usize;
+    let map = match map_list(input.get(off..).unwrap_or(&[]), big_endian) {
+        IResult::Done(_, o) if off != 0 => Some(o),
+        _ => None,
+    };
+
+    // Call sites and method handles are only listed in the map_list
+    let mut call_sites: Vec<CallSite> = vec![];
+    let mut handles: Vec<MethodHandle> = vec![];
+    for item in map.iter().flat_map(|map| map.iter()) {
+        let section_off = base + item.offset as usize;
+        match item.item_type {
+            MapItemType::CallSiteId => {
+                let offsets = parse_at(input,
+                                       section_off,
+                                       "call_site_ids",
+                                       |i| call_site_ids(i, item.size, big_endian))?;
+                for off in offsets {
+                    let values =
+                        parse_at(input, off as usize, "encoded_array_item", encoded_array)?;
+                    call_sites.push(CallSite { values: values });
+                }
+            }
+            MapItemType::MethodHandle => {
+                handles = parse_at(input,
+                                   section_off,
+                                   "method_handles",
+                                   |i| method_handles(i, item.size, big_endian))?;
+                for (i, handle) in handles.iter().enumerate() {
+                    let size = if handle.handle_type.is_field() {
+                        f.len()
+                    } else {
+                        m.len()
+                    };
+                    check_index("method_handles",
+                                section_off + i * 8,
+                                handle.field_or_method_id as u32,
+                                size,
+                                false)?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for class in c.iter_mut() {
+        // Marker interfaces and the like have no class_data_item at all
+        let off = class.class_data_off as usize;
+        if off == 0 {
+            continue;
+        }
+        let mut data = parse_at(input, off, "class_data_item", class_data)?;
+        for field in data.static_fields.iter().chain(data.instance_fields.iter()) {
+            check_index("class_data_item", off, field.field_idx, f.len(), false)?;
+        }
+        for method in data.direct_methods.iter_mut().chain(data.virtual_methods.iter_mut()) {
+            check_index("class_data_item", off, method.method_idx, m.len(), false)?;
+
+            // Abstract and native methods carry no code
+            let code_off = method.code_off as usize;
+            if code_off == 0 {
+                continue;
+            }
+            let mut code = parse_at(input, code_off, "code_item", |i| code_item(i, big_endian))?;
+            let debug_off = code.debug_info_off as usize;
+            if debug_off != 0 {
+                let info = parse_at(input, debug_off, "debug_info_item", debug_info)?;
+                for &name_idx in info.parameter_names.iter() {
+                    check_index("debug_info_item", debug_off, name_idx, s.len(), true)?;
+                }
+                for &(_, event) in info.events.iter() {
+                    match event {
+                        DebugEvent::StartLocal { name_idx, type_idx, signature_idx, .. } => {
+                            check_index("debug_info_item", debug_off, name_idx, s.len(), true)?;
+                            check_index("debug_info_item", debug_off, type_idx, t.len(), true)?;
+                            check_index("debug_info_item",
+                                        debug_off,
+                                        signature_idx,
+                                        s.len(),
+                                        true)?;
+                        }
+                        DebugEvent::SetFile(name_idx) => {
+                            check_index("debug_info_item", debug_off, name_idx, s.len(), true)?;
+                        }
+                        _ => {}
+                    }
+                }
+                code.debug_info = Some(info);
+            }
+            method.code = Some(code);
+        }
+        class.class_data = Some(data);
+    }
+
+    return Ok(DexFile {
+        header: h,
+        method_ids: m,
         strings: s,
+        unpaired_surrogates: unpaired_surrogates,
         proto_ids: p,
         type_descriptors: t,
         field_ids: f,
         class_defs: c,
+        call_sites: call_sites,
+        method_handles: handles,
+        map_list: map,
+        file_len: input.len(),
     });
 }
 
@@ -396,3 +3164,808 @@ fn as_array<A, T>(slice: &[T]) -> A
     <A as AsMut<[T]>>::as_mut(&mut array).clone_from_slice(slice);
     return array;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A DEX image holding nothing but the given string_data_items
+    fn strings_image(items: &[&[u8]]) -> Vec<u8> {
+        let ids_off = 0x70;
+        let mut data = vec![0u8; ids_off + items.len() * 4];
+        data[..4].copy_from_slice(b"dex\n");
+        data[4..8].copy_from_slice(b"035\0");
+        for (i, item) in items.iter().enumerate() {
+            let off = data.len() as u32;
+            data[ids_off + i * 4..ids_off + i * 4 + 4].copy_from_slice(&u32_le(off));
+            data.extend_from_slice(item);
+        }
+        let size = data.len() as u32;
+        data[32..36].copy_from_slice(&u32_le(size));
+        data[36..40].copy_from_slice(&u32_le(0x70));
+        data[40..44].copy_from_slice(&u32_le(ENDIAN_CONSTANT));
+        data[56..60].copy_from_slice(&u32_le(items.len() as u32));
+        data[60..64].copy_from_slice(&u32_le(ids_off as u32));
+        return data;
+    }
+
+    fn u32_le(value: u32) -> [u8; 4] {
+        return [value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8];
+    }
+
+    #[test]
+    fn mutf8_decodes_every_sequence_length() {
+        assert_eq!(mutf8_decode(b"abc"), Some(vec![0x61, 0x62, 0x63]));
+        // U+0000 is encoded in two bytes, U+00E9 and U+20AC in two and three
+        assert_eq!(mutf8_decode(&[0xC0, 0x80]), Some(vec![0]));
+        assert_eq!(mutf8_decode(&[0xC3, 0xA9]), Some(vec![0xE9]));
+        assert_eq!(mutf8_decode(&[0xE2, 0x82, 0xAC]), Some(vec![0x20AC]));
+        // U+1F600 as a surrogate pair, each half encoded separately
+        assert_eq!(mutf8_decode(&[0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]),
+                   Some(vec![0xD83D, 0xDE00]));
+    }
+
+    #[test]
+    fn mutf8_keeps_unpaired_surrogates() {
+        assert_eq!(mutf8_decode(&[0x61, 0xED, 0xA0, 0x80]), Some(vec![0x61, 0xD800]));
+        assert_eq!(mutf8_encode(&[0x61, 0xD800]), vec![0x61, 0xED, 0xA0, 0x80]);
+    }
+
+    #[test]
+    fn mutf8_rejects_malformed_data() {
+        // A raw NUL, a truncated sequence, a bad continuation byte and a
+        // four byte UTF-8 sequence, which MUTF-8 never uses
+        assert_eq!(mutf8_decode(&[0x61, 0x00]), None);
+        assert_eq!(mutf8_decode(&[0xE2, 0x82]), None);
+        assert_eq!(mutf8_decode(&[0xC3, 0x29]), None);
+        assert_eq!(mutf8_decode(&[0xF0, 0x9F, 0x98, 0x80]), None);
+    }
+
+    #[test]
+    fn mutf8_encode_reverses_decode() {
+        let units: Vec<u16> = "a\u{0}\u{e9}\u{20ac}\u{1f600}".encode_utf16().collect();
+        assert_eq!(mutf8_decode(&mutf8_encode(&units)), Some(units));
+    }
+
+    #[test]
+    fn parse_fills_the_string_table() {
+        let long = vec![b'x'; 200];
+        let mut long_item = vec![0xC8, 0x01];
+        long_item.extend_from_slice(&long);
+        long_item.push(0);
+        let dex = parse(strings_image(&[b"\x00\x00", b"\x02\xc3\xa9\xc0\x80\x00", &long_item]))
+            .unwrap();
+        assert_eq!(dex.strings(), &["".to_string(),
+                                    "\u{e9}\u{0}".to_string(),
+                                    String::from_utf8(long).unwrap()][..]);
+    }
+
+    #[test]
+    fn unpaired_surrogates_are_kept_as_code_units() {
+        let dex = parse(strings_image(&[b"\x02a\xed\xa0\x80\x00"])).unwrap();
+        assert_eq!(dex.string(0), Some("a\u{fffd}"));
+        assert_eq!(dex.string_units(0), Some(vec![0x61, 0xD800]));
+    }
+
+    #[test]
+    fn utf16_size_has_to_match_the_data() {
+        let error = parse(strings_image(&[b"\x03ab\x00"])).unwrap_err();
+        assert_eq!(error.section(), "string_data_item");
+        assert_eq!(error.kind(),
+                   &DexErrorKind::StringSizeMismatch {
+                       declared: 3,
+                       decoded: 2,
+                   });
+    }
+
+    #[test]
+    fn malformed_string_data_is_an_error() {
+        let error = parse(strings_image(&[b"\x02\xe2\x82\x00"])).unwrap_err();
+        assert_eq!(error.kind(), &DexErrorKind::InvalidMutf8);
+        // No terminating NUL before the end of the file
+        let error = parse(strings_image(&[b"\x02ab"])).unwrap_err();
+        assert_eq!(error.kind(), &DexErrorKind::Truncated);
+    }
+
+    #[test]
+    fn class_data_indices_are_delta_encoded_per_list() {
+        let data = [1, 1, 0, 3, // sizes
+                    5, 0x09, // static field 5
+                    7, 0x02, // instance field 7
+                    3, 0x81, 0x80, 0x04, 0, // <init>, method 3
+                    2, 0x01, 0x80, 0x01, // method 5 with code at 128
+                    1, 0x01, 0x90, 0x01]; // method 6 with code at 144
+        let data = match class_data(&data) {
+            IResult::Done(rest, data) => {
+                assert!(rest.is_empty());
+                data
+            }
+            _ => panic!("class_data_item did not parse"),
+        };
+        assert_eq!(data.static_fields()[0].field_idx(), 5);
+        assert_eq!(data.static_fields()[0].access_flags(), 0x09);
+        assert_eq!(data.instance_fields()[0].field_idx(), 7);
+        assert!(data.direct_methods().is_empty());
+        let methods: Vec<(u32, u32, u32)> = data.virtual_methods()
+            .iter()
+            .map(|m| (m.method_idx(), m.access_flags(), m.code_off))
+            .collect();
+        assert_eq!(methods, vec![(3, 0x10001, 0), (5, 1, 128), (6, 1, 144)]);
+    }
+
+    #[test]
+    fn malformed_class_data_is_an_error() {
+        // One static field announced but only its index present
+        let error = parse_at(&[1, 0, 0, 0, 5], 0, "class_data_item", class_data).unwrap_err();
+        assert_eq!(error.kind(), &DexErrorKind::Truncated);
+        // A uleb128 running past 32 bits
+        let error = parse_at(&[0xFF, 0xFF, 0xFF, 0xFF, 0x7F, 0, 0, 0],
+                             0,
+                             "class_data_item",
+                             class_data)
+            .unwrap_err();
+        assert_eq!(error.kind(), &DexErrorKind::BadLeb128);
+        let error = parse_at(&[0, 0, 0, 0], 8, "class_data_item", class_data).unwrap_err();
+        assert_eq!(error.kind(), &DexErrorKind::OffsetOutOfBounds);
+    }
+
+    #[test]
+    fn code_item_reads_padded_tries_and_handlers() {
+        let data = [2, 0, 1, 0, 0, 0, 1, 0, // registers, ins, outs, tries
+                    0, 0, 0, 0, 1, 0, 0, 0, // debug_info_off, insns_size
+                    0x0E, 0x00, // return-void
+                    0, 0, // padding ahead of the try_items
+                    0, 0, 0, 0, 1, 0, 1, 0, // try_item with handler at 1
+                    1, 0x7F, 2, 0, 0]; // one typed handler plus catch-all
+        let code = match code_item(&data, false) {
+            IResult::Done(rest, code) => {
+                assert!(rest.is_empty());
+                code
+            }
+            _ => panic!("code_item did not parse"),
+        };
+        assert_eq!(code.registers_size(), 2);
+        assert_eq!(code.insns(), &[0x000E]);
+        let handler = code.handler(&code.tries()[0]).unwrap();
+        assert_eq!(handler.handlers.len(), 1);
+        assert_eq!(handler.handlers[0].type_idx, 2);
+        assert_eq!(handler.catch_all_addr, Some(0));
+    }
+
+    #[test]
+    fn try_item_end_does_not_overflow() {
+        let data = [0xFF, 0xFF, 0xFF, 0xFF, 4, 0, 0, 0];
+        let try_item = match try_item(&data, false) {
+            IResult::Done(_, try_item) => try_item,
+            _ => panic!("try_item did not parse"),
+        };
+        assert_eq!(try_item.start_addr(), u32::MAX);
+        assert_eq!(try_item.end_addr(), u32::MAX);
+    }
+
+    #[test]
+    fn truncated_code_item_is_an_error() {
+        // insns_size claims far more code units than there are bytes
+        let data = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0x7F, 0x0E, 0];
+        let error = parse_at(&data, 0, "code_item", |i| code_item(i, false)).unwrap_err();
+        assert_eq!(error.kind(), &DexErrorKind::Truncated);
+        // A try_item without the handler list it points into
+        let data = [1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0x00, 0x00, 0x0E, 0x00,
+                    0, 0, 0, 0, 1, 0, 1, 0];
+        let error = parse_at(&data, 0, "code_item", |i| code_item(i, false)).unwrap_err();
+        assert_eq!(error.kind(), &DexErrorKind::Truncated);
+    }
+
+    /// Position of the map_list entry for `code` in a written file
+    fn map_entry(data: &[u8], code: u16) -> usize {
+        let map_off = u32::from_le_bytes([data[0x34], data[0x35], data[0x36], data[0x37]]);
+        let mut entry = map_off as usize + 4;
+        while data[entry..entry + 2] != code.to_le_bytes() {
+            entry += 12;
+        }
+        return entry;
+    }
+
+    #[test]
+    fn written_files_have_a_consistent_map() {
+        let dex = parse(::writer::write(&[]).unwrap()).unwrap();
+        assert_eq!(dex.validate_map(), vec![]);
+        let types: Vec<MapItemType> = dex.map_list().unwrap().iter().map(|i| i.item_type).collect();
+        assert_eq!(types, vec![MapItemType::Header, MapItemType::MapList]);
+        assert_eq!(parse(strings_image(&[])).unwrap().validate_map(), vec![MapIssue::Missing]);
+    }
+
+    #[test]
+    fn validate_map_reports_what_the_header_hides() {
+        // An empty file maps the header at 0 and the map_list at 0x70
+        let original = ::writer::write(&[]).unwrap();
+        let header = map_entry(&original, 0x0000);
+        let patched = |offset: usize, bytes: &[u8]| {
+            let mut data = original.clone();
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            return parse(data).unwrap().validate_map();
+        };
+
+        let issues = patched(header, &[0x03, 0x00]);
+        assert!(issues.contains(&MapIssue::HeaderMismatch {
+            item_type: MapItemType::ProtoId,
+            size: (0, 1),
+            offset: (0, 0),
+        }));
+        assert!(issues.contains(&MapIssue::Gap {
+            after: MapItemType::ProtoId,
+            offset: 12,
+            size: 0x70 - 12,
+        }));
+
+        let issues = patched(header, &[0x77, 0x77]);
+        assert!(issues.contains(&MapIssue::UnknownType(0x7777)));
+        assert!(issues.contains(&MapIssue::HeaderMismatch {
+            item_type: MapItemType::Header,
+            size: (1, 0),
+            offset: (0, 0),
+        }));
+
+        let issues = patched(header, &[0x01, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        assert!(issues.contains(&MapIssue::OutOfBounds {
+            item_type: MapItemType::StringId,
+            offset: 0,
+            end: 0x400,
+        }));
+        assert!(issues.contains(&MapIssue::Overlap(MapItemType::StringId, MapItemType::MapList)));
+
+        let issues = patched(header + 12, &[0x00, 0x00]);
+        assert!(issues.contains(&MapIssue::Duplicate(MapItemType::Header)));
+        let issues = patched(header + 8, &[0x80]);
+        assert!(issues.contains(&MapIssue::Unsorted(MapItemType::MapList)));
+
+        // A map_list past the end of the file or longer than the file
+        assert_eq!(patched(0x34, &u32_le(0x1000)), vec![MapIssue::Missing]);
+        assert_eq!(patched(header - 4, &u32_le(1000)), vec![MapIssue::Missing]);
+    }
+
+    #[test]
+    fn integrity_detects_and_repairs_modifications() {
+        let mut data = ::writer::write(&[]).unwrap();
+        assert!(verify_integrity(&data).unwrap().is_valid());
+        assert!(!repair_integrity(&mut data));
+
+        // Past the signature, so both values are off
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        let integrity = verify_integrity(&data).unwrap();
+        assert!(!integrity.checksum_valid() && !integrity.signature_valid());
+        assert_eq!(integrity.computed_signature(), ::digest::sha1(&data[32..]));
+        assert!(repair_integrity(&mut data));
+        assert!(verify_integrity(&data).unwrap().is_valid());
+        assert_eq!(parse(data.clone()).unwrap().header().checksum(),
+                   ::digest::adler32(&data[12..]));
+
+        // Only the checksum covers the signature itself
+        data[12] ^= 1;
+        let integrity = verify_integrity(&data).unwrap();
+        assert!(!integrity.checksum_valid() && !integrity.signature_valid());
+        data[8] ^= 1;
+        data[12] ^= 1;
+        let integrity = verify_integrity(&data).unwrap();
+        assert!(!integrity.checksum_valid() && integrity.signature_valid());
+    }
+
+    #[test]
+    fn integrity_needs_a_complete_header_prefix() {
+        assert!(verify_integrity(&[0; 31]).is_none());
+        let mut data = [0u8; 31];
+        assert!(!repair_integrity(&mut data));
+        assert_eq!(data, [0u8; 31]);
+        let mut data = [0u8; 32];
+        assert!(repair_integrity(&mut data));
+        assert!(verify_integrity(&data).unwrap().is_valid());
+    }
+
+    /// A version 041 header at `base` with empty id sections
+    fn container_image(base: u32, file_size: u32, container_size: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 0x78];
+        data[..8].copy_from_slice(b"dex\n041\0");
+        data[32..36].copy_from_slice(&u32_le(file_size));
+        data[36..40].copy_from_slice(&u32_le(0x78));
+        data[40..44].copy_from_slice(&u32_le(ENDIAN_CONSTANT));
+        data[0x70..0x74].copy_from_slice(&u32_le(container_size));
+        data[0x74..0x78].copy_from_slice(&u32_le(base));
+        return data;
+    }
+
+    #[test]
+    fn versions_outside_035_to_041_are_rejected() {
+        let mut data = strings_image(&[]);
+        for version in [b"035\0", b"038\0", b"039\0", b"041\0"].iter() {
+            data[4..8].copy_from_slice(*version);
+            assert!(parse(data.clone()).is_ok());
+        }
+        for version in [b"034\0", b"042\0", b"03a\0", b"0350"].iter() {
+            data[4..8].copy_from_slice(*version);
+            let error = parse(data.clone()).unwrap_err();
+            assert_eq!(error.kind(), &DexErrorKind::UnsupportedVersion(**version));
+            assert_eq!(error.offset(), 4);
+        }
+        data[4..8].copy_from_slice(b"039\0");
+        assert_eq!(parse(data).unwrap().header().version_number(), Some(39));
+    }
+
+    #[test]
+    fn parse_container_reads_every_image() {
+        let mut data = container_image(0, 0x78, 0xF0);
+        data.extend_from_slice(&container_image(0x78, 0x78, 0xF0));
+        let images = parse_container(data.clone()).unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[1].header().header_offset(), 0x78);
+        assert_eq!(images[1].header().container_size(), 0xF0);
+
+        // A container_size covering only the first image ends the walk
+        data[0x70] = 0x78;
+        assert_eq!(parse_container(data.clone()).unwrap().len(), 1);
+        // As does a file_size of zero
+        data[0x70] = 0xF0;
+        data[32] = 0;
+        assert_eq!(parse_container(data.clone()).unwrap().len(), 1);
+
+        // Every header has to know where it is
+        data[32] = 0x78;
+        data[0x78 + 0x74] = 0;
+        let error = parse_container(data).unwrap_err();
+        assert_eq!(error.kind(), &DexErrorKind::InvalidValue);
+        assert_eq!(error.offset(), 0x78 + 0x74);
+    }
+
+    #[test]
+    fn encoded_values_are_extended_by_type() {
+        let value = |data: &[u8]| match encoded_value_at(data, 0) {
+            IResult::Done(rest, value) => {
+                assert!(rest.is_empty());
+                value
+            }
+            _ => panic!("{:?} did not parse", data),
+        };
+        assert_eq!(value(&[0x00, 0xFF]), EncodedValue::Byte(-1));
+        assert_eq!(value(&[0x02, 0x80]), EncodedValue::Short(-128));
+        assert_eq!(value(&[0x03, 0x80]), EncodedValue::Char(0x80));
+        assert_eq!(value(&[0x24, 0xFF, 0x7F]), EncodedValue::Int(0x7FFF));
+        assert_eq!(value(&[0x26, 0x00, 0x80]), EncodedValue::Long(-0x8000));
+        // Floating point values are filled up with zero bytes on the right
+        assert_eq!(value(&[0x30, 0x80, 0x3F]), EncodedValue::Float(1.0));
+        assert_eq!(value(&[0x11, 0x40]), EncodedValue::Double(2.0));
+        assert_eq!(value(&[0x16, 0x03]), EncodedValue::MethodHandle(3));
+        assert_eq!(value(&[0x3F]), EncodedValue::Boolean(true));
+        assert_eq!(value(&[0x1C, 0x02, 0x1E, 0x00, 0x05]),
+                   EncodedValue::Array(vec![EncodedValue::Null, EncodedValue::Byte(5)]));
+    }
+
+    #[test]
+    fn malformed_encoded_values_are_an_error() {
+        let fails = |data: &[u8]| !encoded_value_at(data, 0).is_done();
+        assert!(fails(&[]));
+        // Too wide for the type, or an undefined type
+        assert!(fails(&[0x20, 0x00, 0x00]));
+        assert!(fails(&[0x84, 0, 0, 0, 0, 0]));
+        assert!(fails(&[0x05, 0x00]));
+        // Truncated payload and array
+        assert!(fails(&[0x64, 0x00]));
+        assert!(fails(&[0x1C, 0x02, 0x1E]));
+        // Nesting deeper than MAX_VALUE_DEPTH
+        let mut nested: Vec<u8> = vec![];
+        for _ in 0..MAX_VALUE_DEPTH + 2 {
+            nested.extend_from_slice(&[0x1C, 0x01]);
+        }
+        nested.push(0x1E);
+        assert!(fails(&nested));
+    }
+
+    /// `strings_image` with every header field and string_id byte-swapped
+    fn big_endian_strings_image(items: &[&[u8]]) -> Vec<u8> {
+        let mut data = strings_image(items);
+        for field in (8..0x70).step_by(4).chain((0x70..0x70 + items.len() * 4).step_by(4)) {
+            data[field..field + 4].reverse();
+        }
+        return data;
+    }
+
+    #[test]
+    fn big_endian_images_parse_like_little_endian_ones() {
+        let items: [&[u8]; 2] = [&[2, b'h', b'i', 0], &[0, 0]];
+        let data = big_endian_strings_image(&items);
+        assert_eq!(&data[40..44], &[0x12, 0x34, 0x56, 0x78]);
+        assert!(endianness(&data).unwrap().is_big_endian());
+        let dex = parse(data).unwrap();
+        assert_eq!(dex.strings(), &["hi".to_string(), "".to_string()]);
+        assert_eq!(dex.header().string_ids_size, 2);
+    }
+
+    #[test]
+    fn big_endian_checksums_are_stored_big_endian() {
+        let mut data = big_endian_strings_image(&[&[1, b'a', 0]]);
+        assert!(repair_integrity(&mut data));
+        let checksum = ::digest::adler32(&data[12..]);
+        assert_eq!(&data[8..12], &checksum.to_be_bytes());
+        assert!(verify_integrity(&data).unwrap().is_valid());
+        assert_eq!(parse(data).unwrap().header().checksum(), checksum);
+    }
+
+    #[test]
+    fn unknown_endian_tags_are_an_error() {
+        let mut data = strings_image(&[]);
+        data[40..44].copy_from_slice(&u32_le(0x11223344));
+        let error = parse(data).unwrap_err();
+        assert_eq!(error.kind(), &DexErrorKind::UnknownEndianTag(0x11223344));
+        assert_eq!(error.offset(), 40);
+        let error = endianness(&[0; 42]).unwrap_err();
+        assert_eq!(error.kind(), &DexErrorKind::Truncated);
+    }
+
+    #[test]
+    fn parse_errors_name_section_and_offset() {
+        let error = parse(vec![]).unwrap_err();
+        assert_eq!((error.section(), error.kind()), ("header", &DexErrorKind::Truncated));
+        let error = parse(vec![b'x'; 0x70]).unwrap_err();
+        assert_eq!(error.kind(), &DexErrorKind::BadMagic);
+        assert_eq!(error.to_string(), "header at 0x0: bad magic");
+        let mut data = strings_image(&[]);
+        data.truncate(0x50);
+        assert_eq!(parse(data).unwrap_err().kind(), &DexErrorKind::Truncated);
+
+        // string_ids past the end of the file, then longer than the file
+        let mut data = strings_image(&[&[0, 0]]);
+        data[60..64].copy_from_slice(&u32_le(0x1000));
+        let error = parse(data).unwrap_err();
+        assert_eq!(error, DexError::new("string_ids", 0x1000, DexErrorKind::OffsetOutOfBounds));
+        let mut data = strings_image(&[&[0, 0]]);
+        data[56..60].copy_from_slice(&u32_le(0x4000_0000));
+        let error = parse(data).unwrap_err();
+        assert_eq!(error, DexError::new("string_ids", 0x70, DexErrorKind::Truncated));
+        let mut data = strings_image(&[&[0, 0]]);
+        data[0x70..0x74].copy_from_slice(&u32_le(0xFFFF_FFFF));
+        let error = parse(data).unwrap_err();
+        assert_eq!(error.kind(), &DexErrorKind::OffsetOutOfBounds);
+        assert_eq!(error.section(), "string_data_item");
+    }
+
+    #[test]
+    fn indices_are_checked_against_their_tables() {
+        let mut data = strings_image(&[&[1, b'I', 0]]);
+        let type_ids_off = data.len() as u32;
+        data.extend_from_slice(&u32_le(5));
+        data[64..68].copy_from_slice(&u32_le(1));
+        data[68..72].copy_from_slice(&u32_le(type_ids_off));
+        let error = parse(data.clone()).unwrap_err();
+        let kind = DexErrorKind::IndexOutOfBounds { index: 5, size: 1 };
+        assert_eq!(error, DexError::new("type_ids", type_ids_off as usize, kind));
+        assert_eq!(error.kind().to_string(), "index 5 is out of bounds for 1 entries");
+
+        let end = data.len();
+        data[end - 4..].copy_from_slice(&u32_le(0));
+        assert_eq!(parse(data).unwrap().type_descriptor(0), Some("I"));
+    }
+
+    /// Parses the file assembled from a single smali class
+    fn assembled(source: &str) -> DexFile {
+        return parse(::assembler::assemble(&[source]).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn ids_resolve_to_descriptors() {
+        let dex = assembled("
+.class public LA;
+.super Ljava/lang/Object;
+.field static s:[Ljava/lang/String;
+.method static f(IJLA;)Z
+    .registers 4
+    const/4 v0, 0x0
+    return v0
+.end method
+");
+        let descriptors: Vec<&str> = dex.type_descriptors().iter().map(|s| s.as_str()).collect();
+        assert_eq!(descriptors,
+                   vec!["I", "J", "LA;", "Ljava/lang/Object;", "Z", "[Ljava/lang/String;"]);
+        assert_eq!(dex.field_descriptor(0).unwrap(), "LA;->s:[Ljava/lang/String;");
+        assert_eq!(dex.method_descriptor(0).unwrap(), "LA;->f(IJLA;)Z");
+
+        let proto = dex.method_id(0).unwrap().proto_idx() as u32;
+        assert_eq!(dex.proto_shorty(proto), Some("ZIJL"));
+        assert_eq!(dex.proto_return_type(proto), Some("Z"));
+        assert_eq!(dex.proto_parameters(proto).unwrap(), vec!["I", "J", "LA;"]);
+        assert_eq!(dex.proto_descriptor(proto).unwrap(), "(IJLA;)Z");
+
+        assert_eq!(dex.type_descriptor(6), None);
+        assert_eq!(dex.proto_descriptor(1), None);
+        assert_eq!(dex.field_descriptor(1), None);
+        assert_eq!(dex.method_descriptor(1), None);
+    }
+
+    #[test]
+    fn interfaces_are_read_from_their_type_list() {
+        let source = "
+.class public LA;
+.super Ljava/lang/Object;
+.implements Ljava/lang/Runnable;
+.implements LB;
+";
+        let data = ::assembler::assemble(&[source]).unwrap();
+        let dex = parse(data.clone()).unwrap();
+        let interfaces: Vec<&str> = dex.class_defs()[0]
+            .interfaces()
+            .iter()
+            .map(|&idx| dex.type_descriptor(idx as u32).unwrap())
+            .collect();
+        assert_eq!(interfaces, vec!["Ljava/lang/Runnable;", "LB;"]);
+
+        // interfaces_off is the fourth field of the class_def
+        let class_def = u32::from_le_bytes([data[0x64], data[0x65], data[0x66], data[0x67]]);
+        let at = class_def as usize + 12;
+        let list = u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+        let mut broken = data.clone();
+        broken[list as usize + 4..list as usize + 6].copy_from_slice(&[0xFF, 0xFF]);
+        let error = parse(broken).unwrap_err();
+        assert_eq!(error.section(), "type_list");
+        assert_eq!(error.kind(), &DexErrorKind::IndexOutOfBounds { index: 0xFFFF, size: 4 });
+        let mut broken = data.clone();
+        broken[at..at + 4].copy_from_slice(&u32_le(data.len() as u32 - 4));
+        broken[data.len() - 4..].copy_from_slice(&u32_le(2));
+        let error = parse(broken).unwrap_err();
+        assert_eq!((error.section(), error.kind()), ("type_list", &DexErrorKind::Truncated));
+    }
+
+    #[test]
+    fn static_values_belong_to_the_static_fields_in_order() {
+        let source = "
+.class public LA;
+.super Ljava/lang/Object;
+.field static a:I = 0x7
+.field static b:Ljava/lang/String; = \"key\"
+.field static c:Z
+";
+        let data = ::assembler::assemble(&[source]).unwrap();
+        let dex = parse(data.clone()).unwrap();
+        let class = &dex.class_defs()[0];
+        let values = class.static_values();
+        assert_eq!(values[0], EncodedValue::Int(7));
+        match values[1] {
+            EncodedValue::String(idx) => assert_eq!(dex.string(idx), Some("key")),
+            ref other => panic!("unexpected {:?}", other),
+        }
+        assert!(class.static_value(2).is_none_or(|value| value.is_default()));
+        assert_eq!(class.static_value(3), None);
+
+        // static_values_off is the last field of the class_def
+        let class_def = u32::from_le_bytes([data[0x64], data[0x65], data[0x66], data[0x67]]);
+        let at = class_def as usize + 28;
+        let mut broken = data.clone();
+        broken[at..at + 4].copy_from_slice(&u32_le(0x7FFF_FFFF));
+        let error = parse(broken).unwrap_err();
+        assert_eq!(error, DexError::new("encoded_array_item",
+                                        0x7FFF_FFFF,
+                                        DexErrorKind::OffsetOutOfBounds));
+        let mut broken = data.clone();
+        broken[at..at + 4].copy_from_slice(&u32_le(data.len() as u32 - 2));
+        let end = data.len();
+        broken[end - 2..].copy_from_slice(&[0x05, 0x04]);
+        let error = parse(broken).unwrap_err();
+        assert_eq!(error.section(), "encoded_array_item");
+    }
+
+    #[test]
+    fn default_values_are_zero_false_and_null() {
+        assert!(EncodedValue::Int(0).is_default());
+        assert!(EncodedValue::Double(0.0).is_default());
+        assert!(!EncodedValue::Double(-0.0).is_default());
+        assert!(EncodedValue::Boolean(false).is_default());
+        assert!(EncodedValue::Null.is_default());
+        assert!(!EncodedValue::Long(1).is_default());
+        // Even string 0 is a value
+        assert!(!EncodedValue::String(0).is_default());
+    }
+
+    #[test]
+    fn annotations_are_found_for_class_members_and_parameters() {
+        let dex = assembled("
+.class public LA;
+.super Ljava/lang/Object;
+
+.annotation system Ldalvik/annotation/Signature;
+    value = {
+        \"Ljava/lang/Object;\",
+        \"Ljava/lang/Comparable<\",
+        \"LA;>;\"
+    }
+.end annotation
+
+.annotation system Ldalvik/annotation/InnerClass;
+    accessFlags = 0x9
+    name = null
+.end annotation
+
+.field static f:I
+    .annotation runtime LNonNull;
+    .end annotation
+.end field
+
+.method static m(I)V
+    .registers 1
+    .annotation system Ldalvik/annotation/Throws;
+        value = {
+            Ljava/io/IOException;
+        }
+    .end annotation
+    .param p0, \"count\"
+        .annotation build LSize;
+            max = 0x3
+        .end annotation
+    .end param
+    return-void
+.end method
+");
+        let class = &dex.class_defs()[0];
+        let directory = class.annotations().unwrap();
+        // Sets are ordered by type
+        let signature = "Ljava/lang/Object;Ljava/lang/Comparable<LA;>;".to_string();
+        assert_eq!(dex.system_annotations(directory.class_annotations()),
+                   vec![SystemAnnotation::InnerClass(None, 9),
+                        SystemAnnotation::Signature(signature)]);
+
+        let field = directory.field(0);
+        assert_eq!(field.len(), 1);
+        assert_eq!(field[0].visibility(), Visibility::Runtime);
+        assert_eq!(dex.type_descriptor(field[0].annotation().type_idx()), Some("LNonNull;"));
+        assert!(dex.system_annotation(&field[0]).is_none());
+
+        assert_eq!(dex.system_annotations(directory.method(0)),
+                   vec![SystemAnnotation::Throws(vec!["Ljava/io/IOException;".to_string()])]);
+        let parameters = directory.parameters(0);
+        assert_eq!(parameters.len(), 1);
+        assert_eq!(parameters[0][0].visibility(), Visibility::Build);
+        assert_eq!(parameters[0][0].annotation().elements()[0].value(), &EncodedValue::Int(3));
+        assert!(directory.method(1).is_empty() && directory.parameters(1).is_empty());
+    }
+
+    #[test]
+    fn catch_handlers_past_u16_offsets_are_an_error() {
+        // Two byte catch-all handlers after a three byte size: with 32767 of
+        // them the last one starts at 0xffff, one more does not fit
+        let mut data = vec![0xFF, 0xFF, 0x01];
+        data.extend(vec![0u8; 0x10000]);
+        assert!(encoded_catch_handler_list(&data).is_done());
+        data[0] = 0x80;
+        data[1] = 0x80;
+        data[2] = 0x02;
+        match encoded_catch_handler_list(&data) {
+            IResult::Error(Err::Code(ErrorKind::Custom(reason))) => assert_eq!(reason, BAD_VALUE),
+            _ => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn malformed_annotations_are_an_error() {
+        let item = |data: &[u8]| match annotation_item(data) {
+            IResult::Done(_, annotation) => Ok(annotation),
+            IResult::Error(Err::Code(ErrorKind::Custom(reason))) => Err(reason),
+            _ => Err(TRUNCATED),
+        };
+        // type 0, no elements
+        assert_eq!(item(&[0x02, 0x00, 0x00]).unwrap().visibility(), Visibility::System);
+        assert_eq!(item(&[0x03, 0x00, 0x00]).unwrap_err(), BAD_VALUE);
+        assert_eq!(item(&[]).unwrap_err(), TRUNCATED);
+        assert_eq!(item(&[0x01, 0x00, 0x01, 0x00]).unwrap_err(), TRUNCATED);
+
+        let data = [0x70, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let error = annotations(&data, 0, false, 0, 0).unwrap_err();
+        assert_eq!(error.section(), "annotations_directory_item");
+        assert_eq!(error.kind(), &DexErrorKind::Truncated);
+        // An annotation for field 0 of a class without fields
+        let data = [0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let error = annotations(&data, 0, false, 0, 0).unwrap_err();
+        assert_eq!(error.kind(), &DexErrorKind::IndexOutOfBounds { index: 0, size: 0 });
+        // A set claiming more entries than the file holds
+        let data = [0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF];
+        let error = annotations(&data, 0, false, 0, 0).unwrap_err();
+        assert_eq!(error, DexError::new("annotation_set_item", 0x10, DexErrorKind::Truncated));
+    }
+
+    /// A debug_info_item for a method of two parameters, the first unnamed
+    const DEBUG_INFO: [u8; 24] = [
+        10, 2, 0, 3,             // line_start 10, parameter names NO_INDEX and 2
+        DBG_SET_PROLOGUE_END,
+        0x0f,                    // line 11 at 0
+        DBG_START_LOCAL, 0, 2, 2,
+        0x3d,                    // line 13 at 3
+        DBG_END_LOCAL, 0,
+        DBG_ADVANCE_PC, 2,
+        DBG_RESTART_LOCAL, 0,
+        DBG_SET_FILE, 5,
+        0x0f,                    // line 14 at 5
+        DBG_ADVANCE_LINE, 0x7e,  // -2
+        0x0f,                    // line 13 at 5
+        DBG_END_SEQUENCE,
+    ];
+
+    fn decoded_debug_info() -> DebugInfo {
+        match debug_info(&DEBUG_INFO) {
+            IResult::Done(rest, info) => {
+                assert!(rest.is_empty());
+                return info;
+            }
+            failed => panic!("{:?}", failed),
+        }
+    }
+
+    #[test]
+    fn debug_info_decodes_the_state_machine() {
+        let info = decoded_debug_info();
+        assert_eq!(info.line_start(), 10);
+        assert_eq!(info.parameter_names(), &[NO_INDEX, 2]);
+        assert_eq!(info.events()[0], (0, DebugEvent::PrologueEnd));
+        assert_eq!(info.events()[1], (0, DebugEvent::Line(11)));
+        assert_eq!(info.events()[2],
+                   (0,
+                    DebugEvent::StartLocal {
+                        register: 0,
+                        name_idx: 1,
+                        type_idx: 1,
+                        signature_idx: NO_INDEX,
+                    }));
+        assert_eq!(&info.events()[3..],
+                   &[(3, DebugEvent::Line(13)),
+                     (3, DebugEvent::EndLocal(0)),
+                     (5, DebugEvent::RestartLocal(0)),
+                     (5, DebugEvent::SetFile(4)),
+                     (5, DebugEvent::Line(14)),
+                     (5, DebugEvent::Line(13))]);
+    }
+
+    #[test]
+    fn debug_info_line_table_follows_set_file() {
+        let info = decoded_debug_info();
+        let positions: Vec<(u32, u32, u32)> = info.positions(1)
+            .iter()
+            .map(|p| (p.address(), p.line(), p.source_file_idx()))
+            .collect();
+        assert_eq!(positions, vec![(0, 11, 1), (3, 13, 1), (5, 14, 4), (5, 13, 4)]);
+
+        assert_eq!(info.position_at(1, 0).map(|p| p.line()), Some(11));
+        assert_eq!(info.position_at(1, 4).map(|p| p.line()), Some(13));
+        assert_eq!(info.position_at(1, 7).map(|p| p.line()), Some(13));
+
+        let before = DebugInfo {
+            line_start: 1,
+            parameter_names: vec![],
+            events: vec![(2, DebugEvent::Line(1))],
+        };
+        assert_eq!(before.position_at(NO_INDEX, 1), None);
+    }
+
+    #[test]
+    fn debug_info_locals_end_and_restart() {
+        let locals = decoded_debug_info().locals(8);
+        assert_eq!(locals.len(), 2);
+        assert_eq!((locals[0].start_addr(), locals[0].end_addr()), (0, 3));
+        assert_eq!((locals[1].start_addr(), locals[1].end_addr()), (5, 8));
+        assert_eq!(locals[1].name_idx(), 1);
+        assert_eq!(locals[1].type_idx(), 1);
+
+        // Restarting a register that never held a variable is ignored
+        let info = DebugInfo {
+            line_start: 1,
+            parameter_names: vec![],
+            events: vec![(0, DebugEvent::RestartLocal(3))],
+        };
+        assert!(info.locals(4).is_empty());
+    }
+
+    #[test]
+    fn debug_info_rejects_truncated_programs() {
+        // No DBG_END_SEQUENCE
+        assert!(!debug_info(&DEBUG_INFO[..23]).is_done());
+        // DBG_START_LOCAL without its operands
+        assert!(!debug_info(&[1, 0, DBG_START_LOCAL, 0]).is_done());
+        // A parameter count larger than the names that follow
+        assert!(!debug_info(&[1, 0x80, 0x80, 0x04]).is_done());
+        // An unterminated LEB128
+        assert!(!debug_info(&[0x80, 0x80]).is_done());
+    }
+}
@@ -0,0 +1,1619 @@
+extern crate leb128;
+
+use dex::{ClassDef, DexFile, EncodedAnnotation, EncodedValue, Visibility as AnnotationVisibility};
+use std::fmt;
+
+const METADATA_TYPE: &str = "Lkotlin/Metadata;";
+
+/// Nesting limit for type arguments, which may also form a cycle through
+/// the type table
+const MAX_TYPE_DEPTH: u32 = 64;
+
+/// Strings the compiler refers to by position instead of storing them in d2
+static PREDEFINED_STRINGS: [&str; 44] =
+    ["kotlin/Any",
+     "kotlin/Nothing",
+     "kotlin/Unit",
+     "kotlin/Throwable",
+     "kotlin/Number",
+     "kotlin/Byte",
+     "kotlin/Double",
+     "kotlin/Float",
+     "kotlin/Int",
+     "kotlin/Long",
+     "kotlin/Short",
+     "kotlin/Boolean",
+     "kotlin/Char",
+     "kotlin/CharSequence",
+     "kotlin/String",
+     "kotlin/Comparable",
+     "kotlin/Enum",
+     "kotlin/Array",
+     "kotlin/ByteArray",
+     "kotlin/DoubleArray",
+     "kotlin/FloatArray",
+     "kotlin/IntArray",
+     "kotlin/LongArray",
+     "kotlin/ShortArray",
+     "kotlin/BooleanArray",
+     "kotlin/CharArray",
+     "kotlin/Cloneable",
+     "kotlin/Annotation",
+     "kotlin/collections/Iterable",
+     "kotlin/collections/MutableIterable",
+     "kotlin/collections/Collection",
+     "kotlin/collections/MutableCollection",
+     "kotlin/collections/List",
+     "kotlin/collections/MutableList",
+     "kotlin/collections/Set",
+     "kotlin/collections/MutableSet",
+     "kotlin/collections/Map",
+     "kotlin/collections/MutableMap",
+     "kotlin/collections/Map.Entry",
+     "kotlin/collections/MutableMap.MutableEntry",
+     "kotlin/collections/Iterator",
+     "kotlin/collections/MutableIterator",
+     "kotlin/collections/ListIterator",
+     "kotlin/collections/MutableListIterator"];
+
+/// The `k` element of `kotlin.Metadata`, telling what d1 describes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetadataKind {
+    Class,
+    File,
+    SyntheticClass,
+    MultiFileClassFacade,
+    MultiFileClassPart,
+    Unknown(i32),
+}
+
+/// The raw elements of a `kotlin.Metadata` annotation
+#[derive(Debug)]
+pub struct Metadata {
+    kind: MetadataKind,
+    version: Vec<i32>,
+    data1: Vec<String>,
+    data2: Vec<String>,
+    extra_string: Option<String>,
+    package_name: Option<String>,
+    extra_int: i32,
+}
+
+/// What the metadata of a class file declares
+#[derive(Debug)]
+pub enum Declaration {
+    Class(Class),
+    /// Top level functions and properties of a file facade or a part of a
+    /// multi-file class
+    Package(Package),
+    /// A lambda compiled to its own class
+    Lambda(Function),
+    /// A `@JvmMultifileClass` facade, listing the internal names of its parts
+    MultiFileFacade(Vec<String>),
+    /// A synthetic class without further data, e.g. `$WhenMappings`
+    Synthetic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClassKind {
+    Class,
+    Interface,
+    EnumClass,
+    EnumEntry,
+    AnnotationClass,
+    Object,
+    CompanionObject,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    Internal,
+    Private,
+    Protected,
+    Public,
+    PrivateToThis,
+    Local,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Modality {
+    Final,
+    Open,
+    Abstract,
+    Sealed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    In,
+    Out,
+    Invariant,
+    Star,
+}
+
+/// Class names are kept as Kotlin ClassIds, e.g. `kotlin/collections/Map.Entry`
+#[derive(Debug)]
+pub struct Class {
+    name: String,
+    flags: u32,
+    type_parameters: Vec<String>,
+    supertypes: Vec<Type>,
+    companion_object: Option<String>,
+    nested_classes: Vec<String>,
+    constructors: Vec<Constructor>,
+    functions: Vec<Function>,
+    properties: Vec<Property>,
+    enum_entries: Vec<String>,
+    sealed_subclasses: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Package {
+    functions: Vec<Function>,
+    properties: Vec<Property>,
+}
+
+#[derive(Debug)]
+pub struct Constructor {
+    flags: u32,
+    parameters: Vec<Parameter>,
+    jvm_signature: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Function {
+    name: String,
+    flags: u32,
+    type_parameters: Vec<String>,
+    receiver: Option<Type>,
+    parameters: Vec<Parameter>,
+    return_type: Type,
+    jvm_signature: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Property {
+    name: String,
+    flags: u32,
+    type_parameters: Vec<String>,
+    receiver: Option<Type>,
+    ty: Type,
+    jvm_field: Option<String>,
+    jvm_getter: Option<String>,
+    jvm_setter: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Parameter {
+    name: String,
+    flags: u32,
+    ty: Type,
+    vararg_element: Option<Type>,
+}
+
+/// A type as written in Kotlin source. `name` is a ClassId, a type
+/// parameter or a type alias.
+#[derive(Debug, Clone)]
+pub struct Type {
+    name: String,
+    arguments: Vec<TypeArgument>,
+    nullable: bool,
+    suspend: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeArgument {
+    projection: Projection,
+    ty: Option<Type>,
+}
+
+/// Finds and reads the `kotlin.Metadata` annotation of a class
+pub fn metadata(dex: &DexFile, class: &ClassDef) -> Option<Metadata> {
+    let annotation = class.annotations()?
+        .class_annotations()
+        .iter()
+        .find(|annotation| {
+            annotation.visibility() == AnnotationVisibility::Runtime &&
+            dex.type_descriptor(annotation.annotation().type_idx()) == Some(METADATA_TYPE)
+        })?
+        .annotation();
+
+    let kind = match element(dex, annotation, "k") {
+        Some(&EncodedValue::Int(kind)) => MetadataKind::from_code(kind),
+        Some(_) => return None,
+        None => MetadataKind::Class,
+    };
+    let version = match element(dex, annotation, "mv") {
+        Some(value) => int_array(value)?,
+        None => vec![],
+    };
+    let extra_int = match element(dex, annotation, "xi") {
+        Some(&EncodedValue::Int(flags)) => flags,
+        Some(_) => return None,
+        None => 0,
+    };
+    return Some(Metadata {
+        kind: kind,
+        version: version,
+        data1: string_array(dex, element(dex, annotation, "d1"))?,
+        data2: string_array(dex, element(dex, annotation, "d2"))?,
+        extra_string: string_value(dex, element(dex, annotation, "xs"))?,
+        package_name: string_value(dex, element(dex, annotation, "pn"))?,
+        extra_int: extra_int,
+    });
+}
+
+fn element<'a>(dex: &DexFile,
+               annotation: &'a EncodedAnnotation,
+               name: &str)
+               -> Option<&'a EncodedValue> {
+    return annotation.elements()
+        .iter()
+        .find(|element| dex.string(element.name_idx()) == Some(name))
+        .map(|element| element.value());
+}
+
+fn int_array(value: &EncodedValue) -> Option<Vec<i32>> {
+    let values = match *value {
+        EncodedValue::Array(ref values) => values,
+        _ => return None,
+    };
+    let mut ints = vec![];
+    for value in values.iter() {
+        match *value {
+            EncodedValue::Int(v) => ints.push(v),
+            _ => return None,
+        }
+    }
+    return Some(ints);
+}
+
+/// A missing array reads as empty, one of the wrong type as None
+fn string_array(dex: &DexFile, value: Option<&EncodedValue>) -> Option<Vec<String>> {
+    let values = match value {
+        Some(EncodedValue::Array(values)) => values,
+        Some(_) => return None,
+        None => return Some(vec![]),
+    };
+    let mut strings = vec![];
+    for value in values.iter() {
+        match *value {
+            EncodedValue::String(idx) => strings.push(dex.string(idx)?.to_string()),
+            _ => return None,
+        }
+    }
+    return Some(strings);
+}
+
+/// Like string_array, an absent string is Some(None)
+fn string_value(dex: &DexFile, value: Option<&EncodedValue>) -> Option<Option<String>> {
+    match value {
+        Some(&EncodedValue::String(idx)) => Some(Some(dex.string(idx)?.to_string())),
+        Some(_) => None,
+        None => Some(None),
+    }
+}
+
+impl MetadataKind {
+    pub fn from_code(code: i32) -> MetadataKind {
+        match code {
+            1 => MetadataKind::Class,
+            2 => MetadataKind::File,
+            3 => MetadataKind::SyntheticClass,
+            4 => MetadataKind::MultiFileClassFacade,
+            5 => MetadataKind::MultiFileClassPart,
+            _ => MetadataKind::Unknown(code),
+        }
+    }
+}
+
+impl Metadata {
+    pub fn kind(&self) -> MetadataKind {
+        return self.kind;
+    }
+
+    /// The metadata version (`mv`), e.g. `[1, 9, 0]`
+    pub fn version(&self) -> &[i32] {
+        return &self.version;
+    }
+
+    /// The encoded protobuf message (`d1`)
+    pub fn data1(&self) -> &[String] {
+        return &self.data1;
+    }
+
+    /// The string table the message refers to (`d2`)
+    pub fn data2(&self) -> &[String] {
+        return &self.data2;
+    }
+
+    /// For multi-file class parts the internal name of the facade (`xs`)
+    pub fn extra_string(&self) -> Option<&str> {
+        return self.extra_string.as_deref();
+    }
+
+    /// The package of a file facade whose JVM package differs (`pn`)
+    pub fn package_name(&self) -> Option<&str> {
+        return self.package_name.as_deref();
+    }
+
+    pub fn extra_int(&self) -> i32 {
+        return self.extra_int;
+    }
+
+    /// Decodes d1 and d2 into the declarations they describe
+    pub fn decode(&self) -> Result<Declaration, String> {
+        match self.kind {
+            MetadataKind::MultiFileClassFacade => {
+                return Ok(Declaration::MultiFileFacade(self.data1.clone()))
+            }
+            MetadataKind::SyntheticClass if self.data1.is_empty() => {
+                return Ok(Declaration::Synthetic)
+            }
+            MetadataKind::Unknown(kind) => return Err(format!("unknown metadata kind {}", kind)),
+            _ => {}
+        }
+
+        // d1 holds the string table types followed by the actual message
+        let bytes = decode_bytes(&self.data1);
+        let mut rest = &bytes[..];
+        let length = varint(&mut rest)? as usize;
+        if length > rest.len() {
+            return Err("string table types are truncated".to_string());
+        }
+        let names = Names::new(&Message::parse(&rest[..length])?, &self.data2)?;
+        let message = Message::parse(&rest[length..])?;
+        let context = Context {
+            names: &names,
+            types: vec![],
+            first_nullable: None,
+            type_parameters: vec![],
+        };
+
+        match self.kind {
+            MetadataKind::Class => Ok(Declaration::Class(context.class(&message)?)),
+            MetadataKind::SyntheticClass => Ok(Declaration::Lambda(context.function(&message)?)),
+            _ => Ok(Declaration::Package(context.package(&message)?)),
+        }
+    }
+}
+
+/// Turns the d1 strings back into the bytes they encode. Current compilers
+/// store one byte per char behind a leading U+0000, older ones packed seven
+/// bits into each char.
+fn decode_bytes(data: &[String]) -> Vec<u8> {
+    let mut chars = data.iter().flat_map(|s| s.chars()).peekable();
+    match chars.peek() {
+        Some(&'\u{0}') => {
+            chars.next();
+            return chars.map(|c| c as u32 as u8).collect();
+        }
+        Some(&'\u{FFFF}') => {
+            chars.next();
+        }
+        _ => {}
+    }
+
+    // Each char was incremented by one to keep U+0000 out of the strings
+    let data: Vec<u8> = chars.map(|c| (c as u32 as u8).wrapping_add(0x7F) & 0x7F).collect();
+    let mut bytes = Vec::with_capacity(data.len() * 7 / 8);
+    let mut index = 0;
+    let mut bit = 0;
+    for _ in 0..data.len() * 7 / 8 {
+        let first = (data[index] as u32) >> bit;
+        index += 1;
+        let second = ((data[index] as u32) & ((1 << (bit + 1)) - 1)) << (7 - bit);
+        bytes.push((first + second) as u8);
+        if bit == 6 {
+            index += 1;
+            bit = 0;
+        } else {
+            bit += 1;
+        }
+    }
+    return bytes;
+}
+
+fn varint(input: &mut &[u8]) -> Result<u64, String> {
+    return leb128::read::unsigned(input).map_err(|_| "invalid varint".to_string());
+}
+
+/// A protobuf field value by wire type
+#[derive(Debug, Clone)]
+enum Wire<'a> {
+    Varint(u64),
+    Fixed(u64),
+    Bytes(&'a [u8]),
+}
+
+/// A protobuf message, kept as its fields in wire order and interpreted on
+/// access since nothing is known about a message before it has been read
+/// completely.
+#[derive(Debug, Clone)]
+struct Message<'a> {
+    fields: Vec<(u32, Wire<'a>)>,
+}
+
+impl<'a> Message<'a> {
+    fn parse(input: &'a [u8]) -> Result<Message<'a>, String> {
+        let mut rest = input;
+        let mut fields = vec![];
+        while !rest.is_empty() {
+            let key = varint(&mut rest)?;
+            let value = match key & 7 {
+                0 => Wire::Varint(varint(&mut rest)?),
+                1 | 5 => {
+                    let size = if key & 7 == 1 { 8 } else { 4 };
+                    if rest.len() < size {
+                        return Err("message is truncated".to_string());
+                    }
+                    let value = rest[..size]
+                        .iter()
+                        .rev()
+                        .fold(0, |value, &byte| value << 8 | byte as u64);
+                    rest = &rest[size..];
+                    Wire::Fixed(value)
+                }
+                2 => {
+                    let length = varint(&mut rest)? as usize;
+                    if length > rest.len() {
+                        return Err("message is truncated".to_string());
+                    }
+                    let (bytes, tail) = rest.split_at(length);
+                    rest = tail;
+                    Wire::Bytes(bytes)
+                }
+                wire_type => return Err(format!("unsupported wire type {}", wire_type)),
+            };
+            fields.push(((key >> 3) as u32, value));
+        }
+        return Ok(Message { fields: fields });
+    }
+
+    /// The last value of a scalar field wins, as in protobuf
+    fn int(&self, field: u32) -> Option<i32> {
+        return self.fields
+            .iter()
+            .filter(|&&(number, _)| number == field)
+            .filter_map(|(_, value)| {
+                match *value {
+                    Wire::Varint(v) | Wire::Fixed(v) => Some(v as i32),
+                    Wire::Bytes(_) => None,
+                }
+            })
+            .next_back();
+    }
+
+    fn bool(&self, field: u32) -> bool {
+        return self.int(field).unwrap_or(0) != 0;
+    }
+
+    /// A repeated int32 field, which may be packed or not
+    fn ints(&self, field: u32) -> Result<Vec<i32>, String> {
+        let mut ints = vec![];
+        for (_, value) in self.fields.iter().filter(|&&(number, _)| number == field) {
+            match *value {
+                Wire::Varint(v) | Wire::Fixed(v) => ints.push(v as i32),
+                Wire::Bytes(mut packed) => {
+                    while !packed.is_empty() {
+                        ints.push(varint(&mut packed)? as i32);
+                    }
+                }
+            }
+        }
+        return Ok(ints);
+    }
+
+    fn string(&self, field: u32) -> Option<String> {
+        return self.fields
+            .iter()
+            .filter(|&&(number, _)| number == field)
+            .filter_map(|(_, value)| {
+                match *value {
+                    Wire::Bytes(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+                    _ => None,
+                }
+            })
+            .next_back();
+    }
+
+    fn messages(&self, field: u32) -> Result<Vec<Message<'a>>, String> {
+        let mut messages = vec![];
+        for (_, value) in self.fields.iter().filter(|&&(number, _)| number == field) {
+            match *value {
+                Wire::Bytes(bytes) => messages.push(Message::parse(bytes)?),
+                _ => return Err(format!("field {} is not a message", field)),
+            }
+        }
+        return Ok(messages);
+    }
+
+    fn message(&self, field: u32) -> Result<Option<Message<'a>>, String> {
+        return Ok(self.messages(field)?.pop());
+    }
+}
+
+/// One entry of `StringTableTypes`, telling how to derive a string from d2
+#[derive(Debug, Clone)]
+struct Record {
+    predefined_index: Option<i32>,
+    string: Option<String>,
+    operation: i32,
+    substring_index: Vec<i32>,
+    replace_char: Vec<i32>,
+}
+
+/// Resolves the string indices of a message like the JVM name resolver
+struct Names {
+    strings: Vec<String>,
+    records: Vec<Record>,
+}
+
+impl Names {
+    fn new(types: &Message, strings: &[String]) -> Result<Names, String> {
+        let mut records = vec![];
+        for record in types.messages(1)? {
+            // Each record stands for `range` consecutive strings of d2
+            let range = record.int(1).unwrap_or(1);
+            if range < 0 || records.len() + range as usize > strings.len() {
+                return Err("string table types do not match d2".to_string());
+            }
+            let record = Record {
+                predefined_index: record.int(2),
+                string: record.string(6),
+                operation: record.int(3).unwrap_or(0),
+                substring_index: record.ints(4)?,
+                replace_char: record.ints(5)?,
+            };
+            for _ in 0..range {
+                records.push(record.clone());
+            }
+        }
+        return Ok(Names {
+            strings: strings.to_vec(),
+            records: records,
+        });
+    }
+
+    fn string(&self, index: i32) -> Result<String, String> {
+        let plain = self.strings.get(index as usize);
+        let record = match self.records.get(index as usize) {
+            Some(record) => record,
+            None => return plain.cloned().ok_or(format!("string {} is out of bounds", index)),
+        };
+
+        let predefined = record.predefined_index
+            .and_then(|i| PREDEFINED_STRINGS.get(i as usize))
+            .map(|s| s.to_string());
+        let mut string = match (record.string.clone(), predefined, plain) {
+            (Some(string), _, _) => string,
+            (None, Some(string), _) => string,
+            (None, None, Some(string)) => string.clone(),
+            (None, None, None) => return Err(format!("string {} is out of bounds", index)),
+        };
+
+        if record.substring_index.len() >= 2 {
+            let (begin, end) = (record.substring_index[0], record.substring_index[1]);
+            if 0 <= begin && begin <= end && end as usize <= string.chars().count() {
+                string = string.chars().skip(begin as usize).take((end - begin) as usize).collect();
+            }
+        }
+        if record.replace_char.len() >= 2 {
+            let from = ::std::char::from_u32(record.replace_char[0] as u32);
+            let to = ::std::char::from_u32(record.replace_char[1] as u32);
+            if let (Some(from), Some(to)) = (from, to) {
+                string = string.replace(from, &to.to_string());
+            }
+        }
+        match record.operation {
+            // INTERNAL_TO_CLASS_ID
+            1 => string = string.replace('$', "."),
+            // DESC_TO_CLASS_ID
+            2 => {
+                let count = string.chars().count();
+                if count >= 2 {
+                    string = string.chars().skip(1).take(count - 2).collect();
+                }
+                string = string.replace('$', ".");
+            }
+            _ => {}
+        }
+        return Ok(string);
+    }
+}
+
+/// The type table and type parameters visible to the message being decoded
+#[derive(Clone)]
+struct Context<'a, 'b> {
+    names: &'b Names,
+    types: Vec<Message<'a>>,
+    first_nullable: Option<usize>,
+    type_parameters: Vec<(i32, String)>,
+}
+
+impl<'a, 'b> Context<'a, 'b> {
+    /// A context for a declaration with its own type table or type parameters
+    fn child(&self,
+             message: &Message<'a>,
+             type_parameter_field: u32)
+             -> Result<Context<'a, 'b>, String> {
+        let mut context = self.clone();
+        if let Some(table) = message.message(30)? {
+            context.types = table.messages(1)?;
+            context.first_nullable = table.int(2).and_then(|first| {
+                if first >= 0 { Some(first as usize) } else { None }
+            });
+        }
+        for parameter in message.messages(type_parameter_field)? {
+            let name = self.names.string(parameter.int(2).unwrap_or(0))?;
+            context.type_parameters.push((parameter.int(1).unwrap_or(0), name));
+        }
+        return Ok(context);
+    }
+
+    /// Names of the type parameters a declaration introduces
+    fn own_type_parameters(&self,
+                           message: &Message<'a>,
+                           field: u32)
+                           -> Result<Vec<String>, String> {
+        let mut names = vec![];
+        for parameter in message.messages(field)? {
+            names.push(self.names.string(parameter.int(2).unwrap_or(0))?);
+        }
+        return Ok(names);
+    }
+
+    fn class(&self, message: &Message<'a>) -> Result<Class, String> {
+        let context = self.child(message, 5)?;
+        let mut supertypes = vec![];
+        for supertype in message.messages(6)? {
+            supertypes.push(context.ty(&supertype, false, 0)?);
+        }
+        for id in message.ints(2)? {
+            supertypes.push(context.table_type(id, 0)?);
+        }
+        let mut constructors = vec![];
+        for constructor in message.messages(8)? {
+            constructors.push(Constructor {
+                flags: constructor.int(1).unwrap_or(6) as u32,
+                parameters: context.parameters(&constructor, 2)?,
+                jvm_signature: context.method_signature(&constructor, "<init>")?,
+            });
+        }
+        let mut enum_entries = vec![];
+        for entry in message.messages(13)? {
+            enum_entries.push(self.names.string(entry.int(1).unwrap_or(0))?);
+        }
+        let companion_object = match message.int(4) {
+            Some(name) => Some(self.names.string(name)?),
+            None => None,
+        };
+        return Ok(Class {
+            name: self.names.string(message.int(3).ok_or("class without a name")?)?,
+            flags: message.int(1).unwrap_or(6) as u32,
+            type_parameters: self.own_type_parameters(message, 5)?,
+            supertypes: supertypes,
+            companion_object: companion_object,
+            nested_classes: self.strings(message.ints(7)?)?,
+            constructors: constructors,
+            functions: context.functions(message, 9)?,
+            properties: context.properties(message, 10)?,
+            enum_entries: enum_entries,
+            sealed_subclasses: self.strings(message.ints(16)?)?,
+        });
+    }
+
+    fn package(&self, message: &Message<'a>) -> Result<Package, String> {
+        let context = self.child(message, 0)?;
+        return Ok(Package {
+            functions: context.functions(message, 3)?,
+            properties: context.properties(message, 4)?,
+        });
+    }
+
+    fn strings(&self, indices: Vec<i32>) -> Result<Vec<String>, String> {
+        return indices.into_iter().map(|index| self.names.string(index)).collect();
+    }
+
+    fn functions(&self, message: &Message<'a>, field: u32) -> Result<Vec<Function>, String> {
+        return message.messages(field)?.iter().map(|function| self.function(function)).collect();
+    }
+
+    fn properties(&self, message: &Message<'a>, field: u32) -> Result<Vec<Property>, String> {
+        return message.messages(field)?.iter().map(|property| self.property(property)).collect();
+    }
+
+    fn function(&self, message: &Message<'a>) -> Result<Function, String> {
+        let context = self.child(message, 4)?;
+        let name = self.names.string(message.int(2).ok_or("function without a name")?)?;
+        let flags = match message.int(9) {
+            Some(flags) => flags,
+            None => old_flags(message.int(1).unwrap_or(6)),
+        };
+        let return_type = context.type_ref(message, 3, 7)?.ok_or("function without a return type")?;
+        return Ok(Function {
+            jvm_signature: context.method_signature(message, &name)?,
+            name: name,
+            flags: flags as u32,
+            type_parameters: self.own_type_parameters(message, 4)?,
+            receiver: context.type_ref(message, 5, 8)?,
+            parameters: context.parameters(message, 6)?,
+            return_type: return_type,
+        });
+    }
+
+    fn property(&self, message: &Message<'a>) -> Result<Property, String> {
+        let context = self.child(message, 4)?;
+        let name = self.names.string(message.int(2).ok_or("property without a name")?)?;
+        let flags = match message.int(11) {
+            Some(flags) => flags,
+            None => old_flags(message.int(1).unwrap_or(2054)),
+        };
+        let ty = context.type_ref(message, 3, 9)?.ok_or("property without a type")?;
+
+        // The JVM signature extension tells where the property ended up
+        let mut jvm_field = None;
+        let mut jvm_getter = None;
+        let mut jvm_setter = None;
+        if let Some(signature) = message.message(100)? {
+            if let Some(field) = signature.message(1)? {
+                let field_name = match field.int(1) {
+                    Some(index) => self.names.string(index)?,
+                    None => name.clone(),
+                };
+                let descriptor = match field.int(2) {
+                    Some(index) => self.names.string(index)?,
+                    None => String::new(),
+                };
+                jvm_field = Some(format!("{}:{}", field_name, descriptor));
+            }
+            if let Some(getter) = signature.message(3)? {
+                jvm_getter = context.jvm_method(&getter, "")?;
+            }
+            if let Some(setter) = signature.message(4)? {
+                jvm_setter = context.jvm_method(&setter, "")?;
+            }
+        }
+
+        return Ok(Property {
+            name: name,
+            flags: flags as u32,
+            type_parameters: self.own_type_parameters(message, 4)?,
+            receiver: context.type_ref(message, 5, 10)?,
+            ty: ty,
+            jvm_field: jvm_field,
+            jvm_getter: jvm_getter,
+            jvm_setter: jvm_setter,
+        });
+    }
+
+    fn parameters(&self, message: &Message<'a>, field: u32) -> Result<Vec<Parameter>, String> {
+        let mut parameters = vec![];
+        for parameter in message.messages(field)? {
+            parameters.push(Parameter {
+                name: self.names.string(parameter.int(2).ok_or("parameter without a name")?)?,
+                flags: parameter.int(1).unwrap_or(0) as u32,
+                ty: self.type_ref(&parameter, 3, 5)?.ok_or("parameter without a type")?,
+                vararg_element: self.type_ref(&parameter, 4, 6)?,
+            });
+        }
+        return Ok(parameters);
+    }
+
+    /// The JVM method signature extension of a function or constructor
+    fn method_signature(&self,
+                        message: &Message<'a>,
+                        name: &str)
+                        -> Result<Option<String>, String> {
+        match message.message(100)? {
+            Some(signature) => self.jvm_method(&signature, name),
+            None => Ok(None),
+        }
+    }
+
+    /// A JvmMethodSignature as `name(descriptor)`; the name defaults to the
+    /// Kotlin one
+    fn jvm_method(&self, signature: &Message<'a>, name: &str) -> Result<Option<String>, String> {
+        let method_name = match signature.int(1) {
+            Some(index) => self.names.string(index)?,
+            None => name.to_string(),
+        };
+        match signature.int(2) {
+            Some(index) => Ok(Some(format!("{}{}", method_name, self.names.string(index)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// A type given inline in `field` or by its index into the type table
+    fn type_ref(&self,
+                message: &Message<'a>,
+                field: u32,
+                id_field: u32)
+                -> Result<Option<Type>, String> {
+        return self.nested_type_ref(message, field, id_field, 0);
+    }
+
+    fn nested_type_ref(&self,
+                       message: &Message<'a>,
+                       field: u32,
+                       id_field: u32,
+                       depth: u32)
+                       -> Result<Option<Type>, String> {
+        if let Some(ty) = message.message(field)? {
+            return Ok(Some(self.ty(&ty, false, depth)?));
+        }
+        match message.int(id_field) {
+            Some(id) => Ok(Some(self.table_type(id, depth)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Types from `first_nullable` on are nullable without saying so
+    fn table_type(&self, id: i32, depth: u32) -> Result<Type, String> {
+        let ty = self.types
+            .get(id as usize)
+            .ok_or(format!("type {} is not in the type table", id))?;
+        let nullable = match self.first_nullable {
+            Some(first) => id as usize >= first,
+            None => false,
+        };
+        return self.ty(ty, nullable, depth);
+    }
+
+    /// Reads a type at nesting level `depth`. Type table entries can refer
+    /// to each other, so the level is limited.
+    fn ty(&self, message: &Message<'a>, nullable: bool, depth: u32) -> Result<Type, String> {
+        if depth > MAX_TYPE_DEPTH {
+            return Err("types are nested too deeply".to_string());
+        }
+        let name = if let Some(index) = message.int(6) {
+            self.names.string(index)?
+        } else if let Some(id) = message.int(7) {
+            // Inner classes may use type parameters of their outer class,
+            // which this metadata does not declare
+            match self.type_parameters.iter().rev().find(|&&(parameter, _)| parameter == id) {
+                Some((_, name)) => name.clone(),
+                None => format!("T#{}", id),
+            }
+        } else if let Some(index) = message.int(9) {
+            self.names.string(index)?
+        } else if let Some(index) = message.int(12) {
+            self.names.string(index)?
+        } else {
+            return Err("type without a class, type parameter or alias".to_string());
+        };
+
+        let mut arguments = vec![];
+        for argument in message.messages(2)? {
+            let projection = match argument.int(1).unwrap_or(2) {
+                0 => Projection::In,
+                1 => Projection::Out,
+                3 => Projection::Star,
+                _ => Projection::Invariant,
+            };
+            let ty = match projection {
+                Projection::Star => None,
+                _ => self.nested_type_ref(&argument, 2, 3, depth + 1)?,
+            };
+            arguments.push(TypeArgument {
+                projection: projection,
+                ty: ty,
+            });
+        }
+
+        return Ok(Type {
+            name: name,
+            arguments: arguments,
+            nullable: nullable || message.bool(3),
+            suspend: message.int(1).unwrap_or(0) & 1 != 0,
+        });
+    }
+}
+
+/// Flags of metadata from before Kotlin 1.1 had two more bits after the
+/// modality
+fn old_flags(flags: i32) -> i32 {
+    return (flags & 0x3F) + ((flags >> 8) << 6);
+}
+
+fn visibility(flags: u32) -> Visibility {
+    match (flags >> 1) & 7 {
+        0 => Visibility::Internal,
+        1 => Visibility::Private,
+        2 => Visibility::Protected,
+        4 => Visibility::PrivateToThis,
+        5 => Visibility::Local,
+        _ => Visibility::Public,
+    }
+}
+
+fn modality(flags: u32) -> Modality {
+    match (flags >> 4) & 3 {
+        0 => Modality::Final,
+        1 => Modality::Open,
+        2 => Modality::Abstract,
+        _ => Modality::Sealed,
+    }
+}
+
+fn flag(flags: u32, bit: u32) -> bool {
+    return flags & (1 << bit) != 0;
+}
+
+/// `kotlin/collections/Map.Entry` as written in source
+fn class_name(class_id: &str) -> String {
+    return class_id.replace('/', ".");
+}
+
+impl Class {
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn flags(&self) -> u32 {
+        return self.flags;
+    }
+
+    pub fn kind(&self) -> ClassKind {
+        match (self.flags >> 6) & 7 {
+            1 => ClassKind::Interface,
+            2 => ClassKind::EnumClass,
+            3 => ClassKind::EnumEntry,
+            4 => ClassKind::AnnotationClass,
+            5 => ClassKind::Object,
+            6 => ClassKind::CompanionObject,
+            _ => ClassKind::Class,
+        }
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        return visibility(self.flags);
+    }
+
+    pub fn modality(&self) -> Modality {
+        return modality(self.flags);
+    }
+
+    pub fn is_inner(&self) -> bool {
+        return flag(self.flags, 9);
+    }
+
+    pub fn is_data(&self) -> bool {
+        return flag(self.flags, 10);
+    }
+
+    pub fn is_external(&self) -> bool {
+        return flag(self.flags, 11);
+    }
+
+    pub fn is_expect(&self) -> bool {
+        return flag(self.flags, 12);
+    }
+
+    /// Inline or value class
+    pub fn is_value(&self) -> bool {
+        return flag(self.flags, 13);
+    }
+
+    pub fn is_fun_interface(&self) -> bool {
+        return flag(self.flags, 14);
+    }
+
+    pub fn type_parameters(&self) -> &[String] {
+        return &self.type_parameters;
+    }
+
+    pub fn supertypes(&self) -> &[Type] {
+        return &self.supertypes;
+    }
+
+    /// Simple name of the companion object, usually `Companion`
+    pub fn companion_object(&self) -> Option<&str> {
+        return self.companion_object.as_deref();
+    }
+
+    /// Simple names of the nested classes
+    pub fn nested_classes(&self) -> &[String] {
+        return &self.nested_classes;
+    }
+
+    pub fn constructors(&self) -> &[Constructor] {
+        return &self.constructors;
+    }
+
+    pub fn functions(&self) -> &[Function] {
+        return &self.functions;
+    }
+
+    pub fn properties(&self) -> &[Property] {
+        return &self.properties;
+    }
+
+    pub fn enum_entries(&self) -> &[String] {
+        return &self.enum_entries;
+    }
+
+    pub fn sealed_subclasses(&self) -> &[String] {
+        return &self.sealed_subclasses;
+    }
+}
+
+impl Package {
+    pub fn functions(&self) -> &[Function] {
+        return &self.functions;
+    }
+
+    pub fn properties(&self) -> &[Property] {
+        return &self.properties;
+    }
+}
+
+impl Constructor {
+    pub fn flags(&self) -> u32 {
+        return self.flags;
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        return visibility(self.flags);
+    }
+
+    pub fn is_secondary(&self) -> bool {
+        return flag(self.flags, 4);
+    }
+
+    pub fn parameters(&self) -> &[Parameter] {
+        return &self.parameters;
+    }
+
+    /// The JVM method as `<init>(descriptor)`
+    pub fn jvm_signature(&self) -> Option<&str> {
+        return self.jvm_signature.as_deref();
+    }
+}
+
+impl Function {
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn flags(&self) -> u32 {
+        return self.flags;
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        return visibility(self.flags);
+    }
+
+    pub fn modality(&self) -> Modality {
+        return modality(self.flags);
+    }
+
+    pub fn is_operator(&self) -> bool {
+        return flag(self.flags, 8);
+    }
+
+    pub fn is_infix(&self) -> bool {
+        return flag(self.flags, 9);
+    }
+
+    pub fn is_inline(&self) -> bool {
+        return flag(self.flags, 10);
+    }
+
+    pub fn is_tailrec(&self) -> bool {
+        return flag(self.flags, 11);
+    }
+
+    pub fn is_external(&self) -> bool {
+        return flag(self.flags, 12);
+    }
+
+    /// Suspend functions take an extra Continuation parameter on the JVM
+    pub fn is_suspend(&self) -> bool {
+        return flag(self.flags, 13);
+    }
+
+    pub fn type_parameters(&self) -> &[String] {
+        return &self.type_parameters;
+    }
+
+    /// The receiver type of an extension function
+    pub fn receiver(&self) -> Option<&Type> {
+        return self.receiver.as_ref();
+    }
+
+    pub fn parameters(&self) -> &[Parameter] {
+        return &self.parameters;
+    }
+
+    pub fn return_type(&self) -> &Type {
+        return &self.return_type;
+    }
+
+    /// The JVM method as `name(descriptor)`
+    pub fn jvm_signature(&self) -> Option<&str> {
+        return self.jvm_signature.as_deref();
+    }
+}
+
+impl Property {
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn flags(&self) -> u32 {
+        return self.flags;
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        return visibility(self.flags);
+    }
+
+    pub fn modality(&self) -> Modality {
+        return modality(self.flags);
+    }
+
+    pub fn is_var(&self) -> bool {
+        return flag(self.flags, 8);
+    }
+
+    pub fn has_getter(&self) -> bool {
+        return flag(self.flags, 9);
+    }
+
+    pub fn has_setter(&self) -> bool {
+        return flag(self.flags, 10);
+    }
+
+    pub fn is_const(&self) -> bool {
+        return flag(self.flags, 11);
+    }
+
+    pub fn is_lateinit(&self) -> bool {
+        return flag(self.flags, 12);
+    }
+
+    pub fn is_delegated(&self) -> bool {
+        return flag(self.flags, 15);
+    }
+
+    pub fn type_parameters(&self) -> &[String] {
+        return &self.type_parameters;
+    }
+
+    pub fn receiver(&self) -> Option<&Type> {
+        return self.receiver.as_ref();
+    }
+
+    pub fn ty(&self) -> &Type {
+        return &self.ty;
+    }
+
+    /// The backing field as `name:descriptor`
+    pub fn jvm_field(&self) -> Option<&str> {
+        return self.jvm_field.as_deref();
+    }
+
+    /// The getter as `name(descriptor)`
+    pub fn jvm_getter(&self) -> Option<&str> {
+        return self.jvm_getter.as_deref();
+    }
+
+    /// The setter as `name(descriptor)`
+    pub fn jvm_setter(&self) -> Option<&str> {
+        return self.jvm_setter.as_deref();
+    }
+}
+
+impl Parameter {
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn flags(&self) -> u32 {
+        return self.flags;
+    }
+
+    pub fn declares_default_value(&self) -> bool {
+        return flag(self.flags, 1);
+    }
+
+    pub fn is_crossinline(&self) -> bool {
+        return flag(self.flags, 2);
+    }
+
+    pub fn is_noinline(&self) -> bool {
+        return flag(self.flags, 3);
+    }
+
+    pub fn ty(&self) -> &Type {
+        return &self.ty;
+    }
+
+    /// The element type of a vararg parameter, whose type is the array
+    pub fn vararg_element(&self) -> Option<&Type> {
+        return self.vararg_element.as_ref();
+    }
+}
+
+impl Type {
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn arguments(&self) -> &[TypeArgument] {
+        return &self.arguments;
+    }
+
+    pub fn is_nullable(&self) -> bool {
+        return self.nullable;
+    }
+
+    /// A suspend function type
+    pub fn is_suspend(&self) -> bool {
+        return self.suspend;
+    }
+}
+
+impl TypeArgument {
+    pub fn projection(&self) -> Projection {
+        return self.projection;
+    }
+
+    /// None for a star projection
+    pub fn ty(&self) -> Option<&Type> {
+        return self.ty.as_ref();
+    }
+}
+
+impl Visibility {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Visibility::Internal => "internal",
+            Visibility::Private | Visibility::PrivateToThis => "private",
+            Visibility::Protected => "protected",
+            Visibility::Public => "public",
+            Visibility::Local => "local",
+        }
+    }
+}
+
+impl Modality {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Modality::Final => "final",
+            Modality::Open => "open",
+            Modality::Abstract => "abstract",
+            Modality::Sealed => "sealed",
+        }
+    }
+}
+
+impl ClassKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ClassKind::Class => "class",
+            ClassKind::Interface => "interface",
+            ClassKind::EnumClass => "enum class",
+            ClassKind::EnumEntry => "enum entry",
+            ClassKind::AnnotationClass => "annotation class",
+            ClassKind::Object => "object",
+            ClassKind::CompanionObject => "companion object",
+        }
+    }
+}
+
+fn type_parameter_list(names: &[String]) -> String {
+    if names.is_empty() {
+        return String::new();
+    }
+    return format!("<{}> ", names.join(", "));
+}
+
+fn parameter_list(parameters: &[Parameter]) -> String {
+    let parameters: Vec<String> = parameters.iter().map(|p| p.to_string()).collect();
+    return parameters.join(", ");
+}
+
+fn receiver(receiver: Option<&Type>) -> String {
+    match receiver {
+        Some(ty) => format!("{}.", ty),
+        None => String::new(),
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.suspend {
+            write!(f, "suspend ")?;
+        }
+        write!(f, "{}", class_name(&self.name))?;
+        if !self.arguments.is_empty() {
+            let arguments: Vec<String> = self.arguments
+                .iter()
+                .map(|argument| {
+                    match (argument.projection, argument.ty.as_ref()) {
+                        (Projection::In, Some(ty)) => format!("in {}", ty),
+                        (Projection::Out, Some(ty)) => format!("out {}", ty),
+                        (Projection::Invariant, Some(ty)) => ty.to_string(),
+                        _ => "*".to_string(),
+                    }
+                })
+                .collect();
+            write!(f, "<{}>", arguments.join(", "))?;
+        }
+        if self.nullable {
+            write!(f, "?")?;
+        }
+        return Ok(());
+    }
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.vararg_element {
+            Some(ref element) => write!(f, "vararg {}: {}", self.name, element)?,
+            None => write!(f, "{}: {}", self.name, self.ty)?,
+        }
+        if self.declares_default_value() {
+            write!(f, " = ...")?;
+        }
+        return Ok(());
+    }
+}
+
+impl fmt::Display for Constructor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{} constructor({})",
+               self.visibility().name(),
+               parameter_list(&self.parameters))
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut modifiers = vec![self.visibility().name(), self.modality().name()];
+        let keywords = [(self.is_suspend(), "suspend"),
+                        (self.is_inline(), "inline"),
+                        (self.is_operator(), "operator"),
+                        (self.is_infix(), "infix"),
+                        (self.is_tailrec(), "tailrec"),
+                        (self.is_external(), "external")];
+        modifiers.extend(keywords.iter().filter(|k| k.0).map(|k| k.1));
+        write!(f,
+               "{} fun {}{}{}({}): {}",
+               modifiers.join(" "),
+               type_parameter_list(&self.type_parameters),
+               receiver(self.receiver()),
+               self.name,
+               parameter_list(&self.parameters),
+               self.return_type)
+    }
+}
+
+impl fmt::Display for Property {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut modifiers = vec![self.visibility().name(), self.modality().name()];
+        if self.is_const() {
+            modifiers.push("const");
+        }
+        if self.is_lateinit() {
+            modifiers.push("lateinit");
+        }
+        modifiers.push(if self.is_var() { "var" } else { "val" });
+        write!(f,
+               "{} {}{}{}: {}",
+               modifiers.join(" "),
+               type_parameter_list(&self.type_parameters),
+               receiver(self.receiver()),
+               self.name,
+               self.ty)?;
+        if self.is_delegated() {
+            write!(f, " by ...")?;
+        }
+        return Ok(());
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut modifiers = vec![self.visibility().name()];
+        if self.kind() != ClassKind::Interface || self.modality() != Modality::Abstract {
+            modifiers.push(self.modality().name());
+        }
+        let keywords = [(self.is_data(), "data"),
+                        (self.is_inner(), "inner"),
+                        (self.is_value(), "value"),
+                        (self.is_fun_interface(), "fun"),
+                        (self.is_external(), "external"),
+                        (self.is_expect(), "expect")];
+        modifiers.extend(keywords.iter().filter(|k| k.0).map(|k| k.1));
+        modifiers.push(self.kind().name());
+        write!(f, "{} {}", modifiers.join(" "), class_name(&self.name))?;
+        if !self.type_parameters.is_empty() {
+            write!(f, "<{}>", self.type_parameters.join(", "))?;
+        }
+        if !self.supertypes.is_empty() {
+            let supertypes: Vec<String> = self.supertypes.iter().map(|t| t.to_string()).collect();
+            write!(f, " : {}", supertypes.join(", "))?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint_field(number: u32, value: u64) -> Vec<u8> {
+        let mut data = vec![];
+        leb128::write::unsigned(&mut data, (number as u64) << 3).unwrap();
+        leb128::write::unsigned(&mut data, value).unwrap();
+        return data;
+    }
+
+    fn message_field(number: u32, message: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        leb128::write::unsigned(&mut data, (number as u64) << 3 | 2).unwrap();
+        leb128::write::unsigned(&mut data, message.len() as u64).unwrap();
+        data.extend_from_slice(message);
+        return data;
+    }
+
+    /// Metadata with an empty string table types message, d1 in the current
+    /// one byte per char encoding
+    fn class_metadata(kind: MetadataKind, message: &[u8], strings: &[&str]) -> Metadata {
+        return metadata_with_types(kind, &[], message, strings);
+    }
+
+    fn metadata_with_types(kind: MetadataKind,
+                           types: &[u8],
+                           message: &[u8],
+                           strings: &[&str])
+                           -> Metadata {
+        let mut bytes = vec![types.len() as u8];
+        bytes.extend_from_slice(types);
+        bytes.extend_from_slice(message);
+        let d1: String = "\u{0}".chars().chain(bytes.iter().map(|&b| b as char)).collect();
+        return Metadata {
+            kind: kind,
+            version: vec![1, 9, 0],
+            data1: vec![d1],
+            data2: strings.iter().map(|s| s.to_string()).collect(),
+            extra_string: None,
+            package_name: None,
+            extra_int: 0,
+        };
+    }
+
+    fn class_type(name: u64, nullable: bool) -> Vec<u8> {
+        let mut ty = varint_field(6, name);
+        if nullable {
+            ty.extend(varint_field(3, 1));
+        }
+        return ty;
+    }
+
+    #[test]
+    fn decodes_classes_with_functions_and_properties() {
+        let strings = ["com/example/Foo",
+                       "get",
+                       "kotlin/Int",
+                       "()I",
+                       "load",
+                       "kotlin/String",
+                       "(Lkotlin/coroutines/Continuation;)Ljava/lang/Object;",
+                       "name",
+                       "getName",
+                       "()Ljava/lang/String;"];
+        let mut get = varint_field(2, 1);
+        get.extend(message_field(3, &class_type(2, false)));
+        get.extend(varint_field(9, 6));
+        get.extend(message_field(100, &varint_field(2, 3)));
+        let mut load = varint_field(2, 4);
+        load.extend(message_field(3, &class_type(5, true)));
+        load.extend(varint_field(9, 6 | 1 << 13));
+        load.extend(message_field(100, &varint_field(2, 6)));
+        let mut getter = varint_field(1, 8);
+        getter.extend(varint_field(2, 9));
+        let mut name = varint_field(2, 7);
+        name.extend(message_field(3, &class_type(5, false)));
+        name.extend(varint_field(11, 6));
+        name.extend(message_field(100, &message_field(3, &getter)));
+
+        let mut class = varint_field(1, 6);
+        class.extend(varint_field(3, 0));
+        class.extend(message_field(9, &get));
+        class.extend(message_field(9, &load));
+        class.extend(message_field(10, &name));
+
+        let class = match class_metadata(MetadataKind::Class, &class, &strings).decode() {
+            Ok(Declaration::Class(class)) => class,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(class.to_string(), "public final class com.example.Foo");
+        let functions: Vec<String> = class.functions().iter().map(|f| f.to_string()).collect();
+        assert_eq!(functions,
+                   vec!["public final fun get(): kotlin.Int",
+                        "public final suspend fun load(): kotlin.String?"]);
+        assert_eq!(class.functions()[0].jvm_signature(), Some("get()I"));
+        assert!(class.functions()[1].return_type().is_nullable());
+        let property = &class.properties()[0];
+        assert_eq!(property.to_string(), "public final val name: kotlin.String");
+        assert_eq!(property.jvm_getter(), Some("getName()Ljava/lang/String;"));
+    }
+
+    #[test]
+    fn string_table_records_rewrite_d2() {
+        // One record for string 0 turning a descriptor into a ClassId, one
+        // for string 1 taking a predefined string
+        let mut record = varint_field(3, 2);
+        record.extend(varint_field(1, 1));
+        let mut types = message_field(1, &record);
+        types.extend(message_field(1, &varint_field(2, 8)));
+        let mut class = varint_field(3, 0);
+        class.extend(varint_field(4, 1));
+        let metadata = metadata_with_types(MetadataKind::Class,
+                                           &types,
+                                           &class,
+                                           &["Lcom/example/Outer$Ä;", ""]);
+        match metadata.decode() {
+            Ok(Declaration::Class(class)) => {
+                assert_eq!(class.name(), "com/example/Outer.Ä");
+                assert_eq!(class.to_string(), "public final class com.example.Outer.Ä");
+                assert_eq!(class.companion_object(), Some("kotlin/Int"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // A range covering far more strings than d2 holds
+        let types = message_field(1, &varint_field(1, 0x7FFF_FFFF));
+        let metadata = metadata_with_types(MetadataKind::Class, &types, &class, &["A", "B"]);
+        assert_eq!(metadata.decode().unwrap_err(), "string table types do not match d2");
+    }
+
+    #[test]
+    fn malformed_metadata_is_an_error() {
+        let strings = ["A", "f", "T"];
+        // A type table entry with itself as type argument
+        let argument = message_field(2, &varint_field(3, 0));
+        let mut ty = varint_field(6, 2);
+        ty.extend(argument);
+        let mut function = varint_field(2, 1);
+        function.extend(varint_field(7, 0));
+        function.extend(message_field(30, &message_field(1, &ty)));
+        let mut class = varint_field(3, 0);
+        class.extend(message_field(9, &function));
+        let metadata = class_metadata(MetadataKind::Class, &class, &strings);
+        assert_eq!(metadata.decode().unwrap_err(), "types are nested too deeply");
+
+        // A message running past its end, a bad wire type, a missing name
+        let metadata = class_metadata(MetadataKind::Class, &[0x4A, 0x10, 0x10], &strings);
+        assert_eq!(metadata.decode().unwrap_err(), "message is truncated");
+        let metadata = class_metadata(MetadataKind::Class, &[0x0B], &strings);
+        assert_eq!(metadata.decode().unwrap_err(), "unsupported wire type 3");
+        let metadata = class_metadata(MetadataKind::Class, &[], &strings);
+        assert_eq!(metadata.decode().unwrap_err(), "class without a name");
+        let metadata = class_metadata(MetadataKind::Unknown(9), &[], &strings);
+        assert_eq!(metadata.decode().unwrap_err(), "unknown metadata kind 9");
+        let metadata = class_metadata(MetadataKind::Class, &varint_field(3, 7), &strings);
+        assert_eq!(metadata.decode().unwrap_err(), "string 7 is out of bounds");
+    }
+
+    #[test]
+    fn old_d1_strings_pack_seven_bits_per_char() {
+        let bytes = [0x01u8, 0x80, 0xFF];
+        let mut bits: u32 = 0;
+        for (i, &byte) in bytes.iter().enumerate() {
+            bits |= (byte as u32) << (i * 8);
+        }
+        let packed: String = (0..4)
+            .map(|i| ((((bits >> (i * 7)) & 0x7F) + 1) & 0x7F) as u8 as char)
+            .collect();
+        assert_eq!(decode_bytes(&[packed]), bytes.to_vec());
+        assert_eq!(decode_bytes(&["\u{0}\u{1}\u{ff}".to_string()]), vec![1, 0xFF]);
+        assert_eq!(decode_bytes(&[]), Vec::<u8>::new());
+        assert_eq!(decode_bytes(&["\u{2}".to_string()]), Vec::<u8>::new());
+    }
+}
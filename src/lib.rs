@@ -0,0 +1,19 @@
+// Every module spells out `return` and `field: field` initializers, the style
+// the crate was written in, so the two lints are allowed here once rather
+// than at the top of each module.
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+extern crate nom;
+extern crate zip;
+
+pub mod apk;
+pub mod arsc;
+pub mod attrs;
+pub mod axml;
+pub mod dex;
+pub mod dalvik;
+pub mod smali;
+pub mod digest;
+pub mod writer;
+pub mod assembler;
+pub mod kotlin;
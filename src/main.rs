@@ -1,14 +1,11 @@
 extern crate zip;
 extern crate xml;
+extern crate andromeda;
 
-#[macro_use]
-extern crate nom;
-
-mod dex;
+use andromeda::{apk, arsc, axml, smali};
 
 use std::io::Read;
 use std::io::{Write, BufWriter};
-use std::error::Error;
 use std::path::Path;
 use std::fs::File;
 use std::fs;
@@ -18,8 +15,8 @@ use zip::read::ZipFile;
 
 fn main() {
     let path = Path::new("E:\\andromeda\\HelloWorld.apk");
-    let file: File = match File::open(&path) {
-        Err(why) => panic!("couldn't open {}: {}", path.display(), why.description()),
+    let file: File = match File::open(path) {
+        Err(why) => panic!("couldn't open {}: {}", path.display(), why),
         Ok(file) => {
             println!("Reading APK {}", path.display());
             file
@@ -29,6 +26,24 @@ fn main() {
     // Unzip APK
     let mut zip = ZipArchive::new(&file).unwrap();
 
+    // Read resources, used to give the references in the manifest names
+    let resources = {
+        let mut data: Vec<u8> = Vec::new();
+        match zip.by_name("resources.arsc") {
+            Ok(mut file) => {
+                file.read_to_end(&mut data).expect("Unable to read data");
+                match arsc::decode(&data) {
+                    Ok(table) => Some(table),
+                    Err(error) => {
+                        println!("Unable to decode resources.arsc: {}", error);
+                        None
+                    }
+                }
+            }
+            Err(_) => None,
+        }
+    };
+
     // Read Manifest
     {
         let manifest_name = "AndroidManifest.xml";
@@ -37,19 +52,56 @@ fn main() {
 
         let mut data: Vec<u8> = Vec::new();
         manifest.read_to_end(&mut data).expect("Unable to read data");
-        // println!("AndroidManifest is {} ", axml::decode(&data));
+        match axml::decode(&data) {
+            Ok(document) => {
+                println!("AndroidManifest is {}",
+                         axml::to_xml_with_resources(&document, resources.as_ref(), false))
+            }
+            Err(error) => println!("Unable to decode {}: {}", manifest_name, error),
+        }
 
     }
-    // Read dex
-    {
-        let classes_name = "classes.dex";
-
-        let mut dex_file: ZipFile = zip.by_name(classes_name).unwrap();
-
-        let mut data: Vec<u8> = Vec::new();
-        dex_file.read_to_end(&mut data).expect("Unable to read data");
-
-        dex::parse(data);
+    // Read dex, all of classes.dex, classes2.dex, ... for multidex apps
+    match apk::load(&mut zip) {
+        Ok(program) => {
+            for (name, integrity) in program.integrity() {
+                if !integrity.checksum_valid() {
+                    println!("{}: checksum is 0x{:08x}, expected 0x{:08x}",
+                             name,
+                             integrity.stored_checksum(),
+                             integrity.computed_checksum());
+                }
+                if !integrity.signature_valid() {
+                    println!("{}: signature does not match the contents", name);
+                }
+            }
+            for entry in program.entries() {
+                for issue in entry.dex().validate_map() {
+                    println!("{}: {}", entry.name(), issue);
+                }
+            }
+            for name in program.skipped() {
+                println!("{}: not loaded, the numbering has a gap before it", name);
+            }
+            for duplicate in program.duplicates() {
+                println!("{}: {} is already defined in {}",
+                         program.entries()[duplicate.duplicate()].name(),
+                         duplicate.descriptor(),
+                         program.entries()[duplicate.first()].name());
+            }
+            for (dex, class) in program.classes() {
+                let path = Path::new("out").join("smali").join(smali::class_path(dex, class));
+                if let Err(error) = fs::create_dir_all(path.parent().unwrap()) {
+                    println!("Unable to create {}: {}", path.display(), error);
+                    continue;
+                }
+                let dest = File::create(path).unwrap();
+                let mut dest = BufWriter::new(dest);
+
+                dest.write_all(smali::class_to_smali(dex, class).as_bytes()).unwrap();
+            }
+        }
+        Err(error) => println!("Unable to load classes: {}", error),
     }
     println!("Unzippping");
     for i in 0..zip.len() {
@@ -66,7 +118,10 @@ fn main() {
         // println!("Unzippping: {}", file_name);
         let path = path.join(file_name);
 
-        fs::create_dir_all(path.parent().unwrap());
+        if let Err(error) = fs::create_dir_all(path.parent().unwrap()) {
+            println!("Unable to create {}: {}", path.display(), error);
+            continue;
+        }
         let dest = File::create(path).unwrap();
         let mut dest = BufWriter::new(dest);
 
@@ -0,0 +1,567 @@
+use dalvik;
+use kotlin::{self, Declaration, MetadataKind};
+use dex::{Annotation, DexFile, ClassDef, DebugEvent, EncodedField, EncodedMethod, EncodedValue,
+          CodeItem, NO_INDEX};
+use std::collections::BTreeMap;
+use std::fmt;
+
+const CLASS: u8 = 1;
+const FIELD: u8 = 2;
+const METHOD: u8 = 4;
+
+const ACC_STATIC: u32 = 0x8;
+
+/// Access flags in the order baksmali prints them. Some bits mean different
+/// things depending on whether they are set on a class, field or method.
+static ACCESS_FLAGS: [(u32, &str, u8); 19] =
+    [(0x1, "public", CLASS | FIELD | METHOD),
+     (0x2, "private", CLASS | FIELD | METHOD),
+     (0x4, "protected", CLASS | FIELD | METHOD),
+     (0x8, "static", CLASS | FIELD | METHOD),
+     (0x10, "final", CLASS | FIELD | METHOD),
+     (0x20, "synchronized", METHOD),
+     (0x40, "volatile", FIELD),
+     (0x40, "bridge", METHOD),
+     (0x80, "transient", FIELD),
+     (0x80, "varargs", METHOD),
+     (0x100, "native", METHOD),
+     (0x200, "interface", CLASS),
+     (0x400, "abstract", CLASS | METHOD),
+     (0x800, "strictfp", METHOD),
+     (0x1000, "synthetic", CLASS | FIELD | METHOD),
+     (0x2000, "annotation", CLASS),
+     (0x4000, "enum", CLASS | FIELD),
+     (0x10000, "constructor", METHOD),
+     (0x20000, "declared-synchronized", METHOD)];
+
+/// Renders access flags as smali keywords, each followed by a space
+fn access_flags(flags: u32, target: u8) -> String {
+    let mut out = String::new();
+    for &(flag, name, applies) in ACCESS_FLAGS.iter() {
+        if flags & flag != 0 && applies & target != 0 {
+            out.push_str(name);
+            out.push(' ');
+        }
+    }
+    return out;
+}
+
+/// Looks up the access flag bit for a smali keyword such as `public`
+pub fn access_flag(name: &str) -> Option<u32> {
+    return ACCESS_FLAGS.iter().find(|&&(_, keyword, _)| keyword == name).map(|&(flag, _, _)| flag);
+}
+
+fn type_name(dex: &DexFile, idx: u32) -> String {
+    match dex.type_descriptor(idx) {
+        Some(descriptor) => descriptor.to_string(),
+        None => format!("type@{}", idx),
+    }
+}
+
+/// Writes annotations indented by `indent` spaces, separated by blank lines
+fn write_annotations(out: &mut String, dex: &DexFile, annotations: &[Annotation], indent: usize) {
+    for (i, annotation) in annotations.iter().enumerate() {
+        if i != 0 {
+            out.push('\n');
+        }
+        for line in dalvik::format_annotation(dex, annotation).lines() {
+            out.push_str(&" ".repeat(indent));
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+}
+
+fn field_annotations(class: &ClassDef, field_idx: u32) -> &[Annotation] {
+    match class.annotations() {
+        Some(directory) => directory.field(field_idx),
+        None => &[],
+    }
+}
+
+fn write_field(out: &mut String,
+               dex: &DexFile,
+               field: &EncodedField,
+               value: Option<&EncodedValue>,
+               annotations: &[Annotation]) {
+    let (name, ty) = match dex.field_id(field.field_idx()) {
+        Some(id) => {
+            (dex.string(id.name_idx()).unwrap_or("").to_string(),
+             type_name(dex, id.type_idx() as u32))
+        }
+        None => (format!("field@{}", field.field_idx()), String::new()),
+    };
+    out.push_str(&format!(".field {}{}:{}",
+                          access_flags(field.access_flags(), FIELD),
+                          name,
+                          ty));
+    // Like baksmali, leave out values the field would have anyway
+    if let Some(value) = value {
+        if !value.is_default() {
+            out.push_str(" = ");
+            out.push_str(&dalvik::format_value(dex, value));
+        }
+    }
+    out.push('\n');
+    if !annotations.is_empty() {
+        write_annotations(out, dex, annotations, 4);
+        out.push_str(".end field\n");
+    }
+}
+
+fn write_code(out: &mut String, dex: &DexFile, code: &CodeItem) {
+    let (instructions, invalid) = dalvik::instructions(code.insns());
+    let mut labels = dalvik::labels(&instructions);
+    let mut directives: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+
+    for try_item in code.tries() {
+        let start = try_item.start_addr() as usize;
+        let end = try_item.end_addr() as usize;
+        let range = format!("{{:try_start_{:x} .. :try_end_{:x}}}", start, end);
+        add_line(labels.entry(start).or_default(),
+                 format!("try_start_{:x}", start));
+
+        let lines = directives.entry(end).or_default();
+        add_line(lines, format!(":try_end_{:x}", end));
+
+        let handler = match code.handler(try_item) {
+            Some(handler) => handler,
+            None => continue,
+        };
+        for pair in handler.handlers() {
+            let addr = pair.addr() as usize;
+            lines.push(format!(".catch {} {} :catch_{:x}",
+                               type_name(dex, pair.type_idx()),
+                               range,
+                               addr));
+            add_line(labels.entry(addr).or_default(),
+                     format!("catch_{:x}", addr));
+        }
+        if let Some(addr) = handler.catch_all_addr() {
+            let addr = addr as usize;
+            lines.push(format!(".catchall {} :catchall_{:x}", range, addr));
+            add_line(labels.entry(addr).or_default(),
+                     format!("catchall_{:x}", addr));
+        }
+    }
+
+    if let Some(debug_info) = code.debug_info() {
+        // `.end local` and `.restart local` repeat the variable as a comment
+        let mut locals: BTreeMap<u32, String> = BTreeMap::new();
+        for &(address, event) in debug_info.events() {
+            let line = match event {
+                DebugEvent::Line(line) => format!(".line {}", line),
+                DebugEvent::StartLocal { register, name_idx, type_idx, signature_idx } => {
+                    let mut local = format!("{}:{}",
+                                            debug_string(dex, name_idx),
+                                            debug_type(dex, type_idx));
+                    if signature_idx != NO_INDEX {
+                        local.push_str(&format!(", {}", debug_string(dex, signature_idx)));
+                    }
+                    let line = format!(".local v{}, {}", register, local);
+                    locals.insert(register, local);
+                    line
+                }
+                DebugEvent::EndLocal(register) => {
+                    local_line(format!(".end local v{}", register), locals.get(&register))
+                }
+                DebugEvent::RestartLocal(register) => {
+                    local_line(format!(".restart local v{}", register), locals.get(&register))
+                }
+                DebugEvent::PrologueEnd => ".prologue".to_string(),
+                DebugEvent::EpilogueBegin => ".epilogue".to_string(),
+                DebugEvent::SetFile(name_idx) => format!(".source {}", debug_string(dex, name_idx)),
+            };
+            directives.entry(address as usize).or_default().push(line);
+        }
+    }
+
+    for line in dalvik::listing(dex, &instructions, &labels, &directives, invalid) {
+        out.push_str("    ");
+        out.push_str(&line);
+        out.push('\n');
+    }
+}
+
+/// A string of the debug info, `null` where it has none
+fn debug_string(dex: &DexFile, idx: u32) -> String {
+    match dex.string(idx) {
+        Some(string) if idx != NO_INDEX => dalvik::escape_string(string),
+        _ => "null".to_string(),
+    }
+}
+
+fn debug_type(dex: &DexFile, idx: u32) -> String {
+    if idx == NO_INDEX {
+        return "null".to_string();
+    }
+    return type_name(dex, idx);
+}
+
+fn local_line(line: String, local: Option<&String>) -> String {
+    match local {
+        Some(local) => format!("{}    # {}", line, local),
+        None => line,
+    }
+}
+
+fn add_line(lines: &mut Vec<String>, line: String) {
+    if !lines.contains(&line) {
+        lines.push(line);
+    }
+}
+
+/// Writes a `.param` line for every parameter with a name in the debug
+/// info, and a `.param` block for every one that carries annotations.
+/// Parameters are named by their `p` register, wide types take two.
+fn write_parameters(out: &mut String,
+                    dex: &DexFile,
+                    method: &EncodedMethod,
+                    parameters: &[Vec<Annotation>]) {
+    let types = match dex.method_id(method.method_idx()) {
+        Some(id) => dex.proto_parameters(id.proto_idx() as u32).unwrap_or(vec![]),
+        None => vec![],
+    };
+    let names = match method.code().and_then(|code| code.debug_info()) {
+        Some(debug_info) => debug_info.parameter_names(),
+        None => &[],
+    };
+    let mut register = if method.access_flags() & ACC_STATIC != 0 { 0 } else { 1 };
+    for (i, ty) in types.iter().enumerate() {
+        let name = names.get(i).cloned().unwrap_or(NO_INDEX);
+        let annotations = parameters.get(i).map(|a| a.as_slice()).unwrap_or(&[]);
+        if name != NO_INDEX || !annotations.is_empty() {
+            out.push_str(&format!("    .param p{}", register));
+            if name != NO_INDEX {
+                out.push_str(&format!(", {}", debug_string(dex, name)));
+            }
+            out.push_str(&format!("    # {}\n", ty));
+        }
+        if !annotations.is_empty() {
+            write_annotations(out, dex, annotations, 8);
+            out.push_str("    .end param\n");
+        }
+        register += if *ty == "J" || *ty == "D" { 2 } else { 1 };
+    }
+}
+
+fn write_method(out: &mut String, dex: &DexFile, class: &ClassDef, method: &EncodedMethod) {
+    let (annotations, parameters) = match class.annotations() {
+        Some(directory) => {
+            (directory.method(method.method_idx()), directory.parameters(method.method_idx()))
+        }
+        None => (&[][..], &[][..]),
+    };
+    let signature = match dex.method_id(method.method_idx()) {
+        Some(id) => {
+            format!("{}{}",
+                    dex.string(id.name_idx()).unwrap_or(""),
+                    dex.proto_descriptor(id.proto_idx() as u32).unwrap_or_default())
+        }
+        None => format!("method@{}", method.method_idx()),
+    };
+    out.push_str(&format!(".method {}{}\n",
+                          access_flags(method.access_flags(), METHOD),
+                          signature));
+    if let Some(code) = method.code() {
+        out.push_str(&format!("    .registers {}\n", code.registers_size()));
+    }
+    let start = out.len();
+    write_parameters(out, dex, method, parameters);
+    if !annotations.is_empty() {
+        if method.code().is_some() || out.len() != start {
+            out.push('\n');
+        }
+        write_annotations(out, dex, annotations, 4);
+    }
+    if let Some(code) = method.code() {
+        out.push('\n');
+        write_code(out, dex, code);
+    }
+    out.push_str(".end method\n");
+}
+
+fn write_section<T, F>(out: &mut String, title: &str, items: &[T], mut write: F)
+    where F: FnMut(&mut String, &T)
+{
+    if items.is_empty() {
+        return;
+    }
+    out.push_str(&format!("\n\n# {}\n", title));
+    for (i, item) in items.iter().enumerate() {
+        if i != 0 {
+            out.push('\n');
+        }
+        write(out, item);
+    }
+}
+
+/// A Kotlin declaration followed by the JVM method it compiles to, if known
+fn member<T: fmt::Display>(declaration: &T, jvm_signature: Option<&str>) -> String {
+    match jvm_signature {
+        Some(signature) => format!("    {} // {}", declaration, signature),
+        None => format!("    {}", declaration),
+    }
+}
+
+/// Summarizes what `kotlin.Metadata` says about the source declarations as
+/// comments, since the compiled class often hides them
+fn write_kotlin(out: &mut String, dex: &DexFile, class: &ClassDef) {
+    let metadata = match kotlin::metadata(dex, class) {
+        Some(metadata) => metadata,
+        None => return,
+    };
+    let mut lines: Vec<String> = vec![];
+    match metadata.decode() {
+        Ok(Declaration::Class(class)) => {
+            lines.push(class.to_string());
+            lines.extend(class.constructors().iter().map(|c| member(c, c.jvm_signature())));
+            lines.extend(class.enum_entries().iter().map(|e| format!("    enum entry {}", e)));
+            lines.extend(class.properties().iter().map(|p| format!("    {}", p)));
+            lines.extend(class.functions().iter().map(|f| member(f, f.jvm_signature())));
+        }
+        Ok(Declaration::Package(package)) => {
+            match (metadata.kind(), metadata.extra_string()) {
+                (MetadataKind::MultiFileClassPart, Some(facade)) => {
+                    lines.push(format!("part of multi-file class {}", facade))
+                }
+                _ => lines.push("file facade".to_string()),
+            }
+            lines.extend(package.properties().iter().map(|p| format!("    {}", p)));
+            lines.extend(package.functions().iter().map(|f| member(f, f.jvm_signature())));
+        }
+        Ok(Declaration::Lambda(function)) => {
+            lines.push("lambda".to_string());
+            lines.push(member(&function, function.jvm_signature()));
+        }
+        Ok(Declaration::MultiFileFacade(parts)) => {
+            lines.push("multi-file class facade".to_string());
+            lines.extend(parts.iter().map(|part| format!("    part {}", part)));
+        }
+        Ok(Declaration::Synthetic) => lines.push("synthetic class".to_string()),
+        Err(error) => lines.push(format!("unable to decode metadata: {}", error)),
+    }
+
+    out.push_str("\n\n# kotlin\n");
+    for line in lines {
+        out.push_str(&format!("# {}\n", line));
+    }
+}
+
+/// Renders a class definition as smali source, laid out like baksmali
+/// output so the result can be diffed against it.
+pub fn class_to_smali(dex: &DexFile, class: &ClassDef) -> String {
+    return write_class(dex, class, false);
+}
+
+/// Like `class_to_smali`, with a `# kotlin` comment block after the class
+/// annotations that summarizes the `kotlin.Metadata` of the class
+pub fn class_to_smali_with_kotlin(dex: &DexFile, class: &ClassDef) -> String {
+    return write_class(dex, class, true);
+}
+
+fn write_class(dex: &DexFile, class: &ClassDef, kotlin: bool) -> String {
+    let mut out = String::new();
+
+    out.push_str(&format!(".class {}{}\n",
+                          access_flags(class.access_flags(), CLASS),
+                          type_name(dex, class.class_idx())));
+    if class.superclass_idx() != NO_INDEX {
+        out.push_str(&format!(".super {}\n", type_name(dex, class.superclass_idx())));
+    }
+    if class.source_file_idx() != NO_INDEX {
+        if let Some(source) = dex.string(class.source_file_idx()) {
+            out.push_str(&format!(".source {}\n", dalvik::escape_string(source)));
+        }
+    }
+
+    if !class.interfaces().is_empty() {
+        out.push_str("\n\n# interfaces\n");
+        for &interface in class.interfaces() {
+            out.push_str(&format!(".implements {}\n", type_name(dex, interface as u32)));
+        }
+    }
+
+    if let Some(directory) = class.annotations() {
+        let annotations = directory.class_annotations();
+        if !annotations.is_empty() {
+            out.push_str("\n\n# annotations\n");
+            write_annotations(&mut out, dex, annotations, 0);
+        }
+    }
+    if kotlin {
+        write_kotlin(&mut out, dex, class);
+    }
+
+    if let Some(data) = class.class_data() {
+        let mut idx = 0;
+        write_section(&mut out, "static fields", data.static_fields(), |out, f| {
+            let annotations = field_annotations(class, f.field_idx());
+            write_field(out, dex, f, class.static_value(idx), annotations);
+            idx += 1;
+        });
+        write_section(&mut out, "instance fields", data.instance_fields(), |out, f| {
+            write_field(out, dex, f, None, field_annotations(class, f.field_idx()))
+        });
+        write_section(&mut out,
+                      "direct methods",
+                      data.direct_methods(),
+                      |out, m| write_method(out, dex, class, m));
+        write_section(&mut out,
+                      "virtual methods",
+                      data.virtual_methods(),
+                      |out, m| write_method(out, dex, class, m));
+    }
+
+    return out;
+}
+
+/// Relative path baksmali would write a class to, e.g. `com/example/Foo.smali`
+pub fn class_path(dex: &DexFile, class: &ClassDef) -> String {
+    return descriptor_path(&type_name(dex, class.class_idx()));
+}
+
+/// Turns a class descriptor into a relative `.smali` path. The descriptor
+/// comes from the file, so components that could leave the output
+/// directory (empty, `.` or `..`) and separators other than `/` are escaped
+/// as `#<hex>;`, as is `#` itself to keep the mapping unambiguous.
+pub fn descriptor_path(descriptor: &str) -> String {
+    let name = descriptor.strip_prefix('L').and_then(|name| name.strip_suffix(';'));
+    let name = name.unwrap_or(descriptor);
+    let mut components: Vec<String> = vec![];
+    for component in name.split('/') {
+        let escaped = match component {
+            "" => "#;".to_string(),
+            "." => "#2e;".to_string(),
+            ".." => "#2e;#2e;".to_string(),
+            _ => {
+                component.chars()
+                    .map(|c| match c {
+                        '#' | '\\' | '\0' => format!("#{:x};", c as u32),
+                        _ => c.to_string(),
+                    })
+                    .collect()
+            }
+        };
+        components.push(escaped);
+    }
+    return format!("{}.smali", components.join("/"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assembler;
+    use dex;
+
+    #[test]
+    fn descriptor_path_strips_one_l_and_semicolon() {
+        assert_eq!(descriptor_path("Lcom/example/Foo;"), "com/example/Foo.smali");
+        assert_eq!(descriptor_path("LLog;"), "Log.smali");
+        assert_eq!(descriptor_path("LFoo$1;"), "Foo$1.smali");
+    }
+
+    #[test]
+    fn descriptor_path_stays_inside_the_output_directory() {
+        assert_eq!(descriptor_path("L../../etc/passwd;"), "#2e;#2e;/#2e;#2e;/etc/passwd.smali");
+        assert_eq!(descriptor_path("L/abs//./x;"), "#;/abs/#;/#2e;/x.smali");
+        assert_eq!(descriptor_path("La\\b#c;"), "a#5c;b#23;c.smali");
+        assert_eq!(descriptor_path("type@7"), "type@7.smali");
+    }
+
+    #[test]
+    fn class_to_smali_lays_out_a_class_like_baksmali() {
+        let source = ".class public final Lcom/example/Foo;
+.super Ljava/lang/Object;
+.source \"Foo.java\"
+
+.field private static count:I = 0x2a
+
+.method public static get()I
+    .registers 1
+    const/16 v0, 0x2a
+    return v0
+.end method
+";
+        let dex = dex::parse(assembler::assemble(&[source]).unwrap()).unwrap();
+        let class = &dex.class_defs()[0];
+        assert_eq!(class_path(&dex, class), "com/example/Foo.smali");
+        assert_eq!(class_to_smali(&dex, class),
+                   ".class public final Lcom/example/Foo;
+.super Ljava/lang/Object;
+.source \"Foo.java\"
+
+
+# static fields
+.field private static count:I = 0x2a
+
+
+# direct methods
+.method public static get()I
+    .registers 1
+
+    const/16 v0, 0x2a
+    return v0
+.end method
+");
+    }
+
+    #[test]
+    fn interfaces_are_listed_after_the_header() {
+        let source = ".class public LA;
+.super Ljava/lang/Object;
+.implements Ljava/lang/Runnable;
+";
+        let dex = dex::parse(assembler::assemble(&[source]).unwrap()).unwrap();
+        assert_eq!(class_to_smali(&dex, &dex.class_defs()[0]),
+                   ".class public LA;
+.super Ljava/lang/Object;
+
+
+# interfaces
+.implements Ljava/lang/Runnable;
+");
+    }
+
+    #[test]
+    fn static_fields_show_values_other_than_the_default() {
+        let source = ".class public LA;
+.super Ljava/lang/Object;
+.field static a:I = 0x0
+.field static b:Ljava/lang/String; = \"x\"
+.field static c:J = -0x1L
+";
+        let dex = dex::parse(assembler::assemble(&[source]).unwrap()).unwrap();
+        let smali = class_to_smali(&dex, &dex.class_defs()[0]);
+        assert!(smali.contains("
+# static fields
+.field static a:I
+
+.field static b:Ljava/lang/String; = \"x\"
+
+.field static c:J = -0x1L
+"), "{}", smali);
+    }
+
+    #[test]
+    fn kotlin_metadata_is_only_summarized_on_request() {
+        let source = ".class public final La/Facade;
+.super Ljava/lang/Object;
+
+.annotation runtime Lkotlin/Metadata;
+    d1 = {
+        \"a/Facade__PartKt\"
+    }
+    k = 0x4
+.end annotation
+";
+        let dex = dex::parse(assembler::assemble(&[source]).unwrap()).unwrap();
+        let class = &dex.class_defs()[0];
+        let plain = class_to_smali(&dex, class);
+        assert!(!plain.contains("# kotlin"));
+        let annotated = class_to_smali_with_kotlin(&dex, class);
+        // Without members the block ends the file
+        let kotlin = "\n\n# kotlin\n# multi-file class facade\n#     part a/Facade__PartKt\n";
+        assert_eq!(annotated, plain + kotlin);
+    }
+}
@@ -0,0 +1,1883 @@
+extern crate leb128;
+
+use dalvik::{self, Format, IndexType};
+use dex::{self, DexFile, EncodedValue, MapItemType, MethodHandleType, Visibility};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+const HEADER_SIZE: u32 = 0x70;
+const ENDIAN_CONSTANT: u32 = 0x12345678;
+const ACC_INTERFACE: u32 = 0x200;
+
+const TYPE_HEADER_ITEM: u16 = 0x0000;
+const TYPE_STRING_ID_ITEM: u16 = 0x0001;
+const TYPE_TYPE_ID_ITEM: u16 = 0x0002;
+const TYPE_PROTO_ID_ITEM: u16 = 0x0003;
+const TYPE_FIELD_ID_ITEM: u16 = 0x0004;
+const TYPE_METHOD_ID_ITEM: u16 = 0x0005;
+const TYPE_CLASS_DEF_ITEM: u16 = 0x0006;
+const TYPE_CALL_SITE_ID_ITEM: u16 = 0x0007;
+const TYPE_METHOD_HANDLE_ITEM: u16 = 0x0008;
+const TYPE_MAP_LIST: u16 = 0x1000;
+const TYPE_TYPE_LIST: u16 = 0x1001;
+const TYPE_ANNOTATION_SET_REF_LIST: u16 = 0x1002;
+const TYPE_ANNOTATION_SET_ITEM: u16 = 0x1003;
+const TYPE_CLASS_DATA_ITEM: u16 = 0x2000;
+const TYPE_CODE_ITEM: u16 = 0x2001;
+const TYPE_STRING_DATA_ITEM: u16 = 0x2002;
+const TYPE_DEBUG_INFO_ITEM: u16 = 0x2003;
+const TYPE_ANNOTATION_ITEM: u16 = 0x2004;
+const TYPE_ENCODED_ARRAY_ITEM: u16 = 0x2005;
+const TYPE_ANNOTATIONS_DIRECTORY_ITEM: u16 = 0x2006;
+
+/// A method prototype in terms of type descriptors
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Proto {
+    pub parameters: Vec<String>,
+    pub return_type: String,
+}
+
+impl Proto {
+    /// The shorty_descriptor: one character per type, all reference types
+    /// collapsed to `L`
+    pub fn shorty(&self) -> String {
+        let short = |ty: &String| match ty.chars().next() {
+            Some('[') | None => 'L',
+            Some(c) => c,
+        };
+        let mut shorty = String::new();
+        shorty.push(short(&self.return_type));
+        for param in self.parameters.iter() {
+            shorty.push(short(param));
+        }
+        return shorty;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldRef {
+    pub class: String,
+    pub name: String,
+    pub ty: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodRef {
+    pub class: String,
+    pub name: String,
+    pub proto: Proto,
+}
+
+/// The member a method handle operates on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MethodHandleTarget {
+    Field(FieldRef),
+    Method(MethodRef),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodHandleRef {
+    pub kind: MethodHandleType,
+    pub target: MethodHandleTarget,
+}
+
+/// The target of an invoke-custom: a bootstrap method handle that links
+/// `name` with type `proto`, called with any extra `arguments`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallSiteRef {
+    pub bootstrap: MethodHandleRef,
+    pub name: String,
+    pub proto: Proto,
+    pub arguments: Vec<Value>,
+}
+
+/// Something an instruction refers to by index
+#[derive(Debug, Clone)]
+pub enum Reference {
+    /// UTF-16 code units, which unlike a `String` can hold unpaired surrogates
+    String(Vec<u16>),
+    Type(String),
+    Field(FieldRef),
+    Method(MethodRef),
+    Proto(Proto),
+    CallSite(CallSiteRef),
+    MethodHandle(MethodHandleRef),
+}
+
+/// An index operand inside `Code::insns` that is filled in once the id
+/// tables have been laid out. `position` is in code units; `wide` operands
+/// span two units (const-string/jumbo), all others one.
+#[derive(Debug, Clone)]
+pub struct Fixup {
+    pub position: usize,
+    pub wide: bool,
+    pub reference: Reference,
+}
+
+/// An encoded_annotation: the annotation type and its elements by name
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationValue {
+    pub ty: String,
+    pub elements: Vec<(String, Value)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub visibility: Visibility,
+    pub value: AnnotationValue,
+}
+
+/// Initial value of a static field, argument of a call site or element of
+/// an annotation
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Byte(i8),
+    Short(i16),
+    Char(u16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    MethodType(Proto),
+    MethodHandle(MethodHandleRef),
+    /// UTF-16 code units, as for `Reference::String`
+    String(Vec<u16>),
+    Type(String),
+    Field(FieldRef),
+    Method(MethodRef),
+    Enum(FieldRef),
+    Array(Vec<Value>),
+    Annotation(AnnotationValue),
+    Null,
+    Boolean(bool),
+}
+
+#[derive(Debug, Clone)]
+pub struct Try {
+    pub start_addr: u32,
+    pub insn_count: u16,
+    /// Exception type descriptor and handler address, in match order
+    pub handlers: Vec<(String, u32)>,
+    pub catch_all: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugEvent {
+    Line(u32),
+    StartLocal {
+        register: u32,
+        name: Option<String>,
+        ty: Option<String>,
+        signature: Option<String>,
+    },
+    EndLocal(u32),
+    RestartLocal(u32),
+    PrologueEnd,
+    EpilogueBegin,
+    SetFile(Option<String>),
+}
+
+/// The line table and local variables of a method. Events are in program
+/// order, each with the address it applies from.
+#[derive(Debug, Clone)]
+pub struct DebugInfo {
+    /// Name of each parameter, `this` excluded
+    pub parameter_names: Vec<Option<String>>,
+    pub events: Vec<(u32, DebugEvent)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Code {
+    pub registers_size: u16,
+    pub ins_size: u16,
+    pub outs_size: u16,
+    pub insns: Vec<u16>,
+    pub fixups: Vec<Fixup>,
+    pub tries: Vec<Try>,
+    pub debug_info: Option<DebugInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub field: FieldRef,
+    pub access_flags: u32,
+    pub value: Option<Value>,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Method {
+    pub method: MethodRef,
+    pub access_flags: u32,
+    pub code: Option<Code>,
+    pub annotations: Vec<Annotation>,
+    /// Annotations of each parameter, empty if none has any
+    pub parameter_annotations: Vec<Vec<Annotation>>,
+}
+
+/// A class definition with all references kept symbolic, ready to be
+/// assigned indices and written by `write`.
+#[derive(Debug, Clone)]
+pub struct Class {
+    pub descriptor: String,
+    pub access_flags: u32,
+    pub superclass: Option<String>,
+    pub interfaces: Vec<String>,
+    pub source_file: Option<String>,
+    pub annotations: Vec<Annotation>,
+    pub static_fields: Vec<Field>,
+    pub instance_fields: Vec<Field>,
+    pub direct_methods: Vec<Method>,
+    pub virtual_methods: Vec<Method>,
+}
+
+#[derive(Default)]
+struct Collector {
+    strings: HashSet<Vec<u16>>,
+    types: HashSet<String>,
+    protos: HashSet<Proto>,
+    fields: HashSet<FieldRef>,
+    methods: HashSet<MethodRef>,
+    // Neither table is sorted, so both keep the order of first use
+    call_sites: Vec<CallSiteRef>,
+    method_handles: Vec<MethodHandleRef>,
+}
+
+impl Collector {
+    fn string(&mut self, value: &str) {
+        self.strings.insert(value.encode_utf16().collect());
+    }
+
+    fn units(&mut self, units: &[u16]) {
+        self.strings.insert(units.to_vec());
+    }
+
+    fn ty(&mut self, descriptor: &str) {
+        self.string(descriptor);
+        self.types.insert(descriptor.to_string());
+    }
+
+    fn proto(&mut self, proto: &Proto) {
+        self.string(&proto.shorty());
+        self.ty(&proto.return_type);
+        for param in proto.parameters.iter() {
+            self.ty(param);
+        }
+        self.protos.insert(proto.clone());
+    }
+
+    fn field(&mut self, field: &FieldRef) {
+        self.ty(&field.class);
+        self.string(&field.name);
+        self.ty(&field.ty);
+        self.fields.insert(field.clone());
+    }
+
+    fn method(&mut self, method: &MethodRef) {
+        self.ty(&method.class);
+        self.string(&method.name);
+        self.proto(&method.proto);
+        self.methods.insert(method.clone());
+    }
+
+    fn debug_info(&mut self, debug_info: &DebugInfo) {
+        for name in debug_info.parameter_names.iter().flat_map(|n| n.iter()) {
+            self.string(name);
+        }
+        for (_, event) in debug_info.events.iter() {
+            match *event {
+                DebugEvent::StartLocal { ref name, ref ty, ref signature, .. } => {
+                    for string in name.iter().chain(signature.iter()) {
+                        self.string(string);
+                    }
+                    for ty in ty.iter() {
+                        self.ty(ty);
+                    }
+                }
+                DebugEvent::SetFile(Some(ref name)) => self.string(name),
+                _ => {}
+            }
+        }
+    }
+
+    fn method_handle(&mut self, handle: &MethodHandleRef) {
+        match handle.target {
+            MethodHandleTarget::Field(ref field) => self.field(field),
+            MethodHandleTarget::Method(ref method) => self.method(method),
+        }
+        if !self.method_handles.contains(handle) {
+            self.method_handles.push(handle.clone());
+        }
+    }
+
+    fn call_site(&mut self, call_site: &CallSiteRef) {
+        self.method_handle(&call_site.bootstrap);
+        self.string(&call_site.name);
+        self.proto(&call_site.proto);
+        for argument in call_site.arguments.iter() {
+            self.value(argument);
+        }
+        if !self.call_sites.contains(call_site) {
+            self.call_sites.push(call_site.clone());
+        }
+    }
+
+    fn reference(&mut self, reference: &Reference) {
+        match *reference {
+            Reference::String(ref value) => self.units(value),
+            Reference::Type(ref descriptor) => self.ty(descriptor),
+            Reference::Field(ref field) => self.field(field),
+            Reference::Method(ref method) => self.method(method),
+            Reference::Proto(ref proto) => self.proto(proto),
+            Reference::CallSite(ref call_site) => self.call_site(call_site),
+            Reference::MethodHandle(ref handle) => self.method_handle(handle),
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        match *value {
+            Value::MethodType(ref proto) => self.proto(proto),
+            Value::MethodHandle(ref handle) => self.method_handle(handle),
+            Value::String(ref value) => self.units(value),
+            Value::Type(ref descriptor) => self.ty(descriptor),
+            Value::Field(ref field) | Value::Enum(ref field) => self.field(field),
+            Value::Method(ref method) => self.method(method),
+            Value::Array(ref values) => {
+                for value in values.iter() {
+                    self.value(value);
+                }
+            }
+            Value::Annotation(ref annotation) => self.annotation(annotation),
+            _ => {}
+        }
+    }
+
+    fn annotation(&mut self, annotation: &AnnotationValue) {
+        self.ty(&annotation.ty);
+        for (name, value) in annotation.elements.iter() {
+            self.string(name);
+            self.value(value);
+        }
+    }
+
+    fn class(&mut self, class: &Class) {
+        self.ty(&class.descriptor);
+        if let Some(ref superclass) = class.superclass {
+            self.ty(superclass);
+        }
+        for interface in class.interfaces.iter() {
+            self.ty(interface);
+        }
+        if let Some(ref source_file) = class.source_file {
+            self.string(source_file);
+        }
+        for annotation in all_annotations(class) {
+            self.annotation(&annotation.value);
+        }
+        for field in class.static_fields.iter().chain(class.instance_fields.iter()) {
+            self.field(&field.field);
+            if let Some(ref value) = field.value {
+                self.value(value);
+            }
+        }
+        for method in class.direct_methods.iter().chain(class.virtual_methods.iter()) {
+            self.method(&method.method);
+            if let Some(ref code) = method.code {
+                for fixup in code.fixups.iter() {
+                    self.reference(&fixup.reference);
+                }
+                for try_item in code.tries.iter() {
+                    for (ty, _) in try_item.handlers.iter() {
+                        self.ty(ty);
+                    }
+                }
+                if let Some(ref debug_info) = code.debug_info {
+                    self.debug_info(debug_info);
+                }
+            }
+        }
+    }
+}
+
+/// The sorted id tables of the file being written
+struct Pools {
+    strings: Vec<Vec<u16>>,
+    string_idx: HashMap<Vec<u16>, u32>,
+    types: Vec<String>,
+    type_idx: HashMap<String, u32>,
+    protos: Vec<Proto>,
+    proto_idx: HashMap<Proto, u32>,
+    fields: Vec<FieldRef>,
+    field_idx: HashMap<FieldRef, u32>,
+    methods: Vec<MethodRef>,
+    method_idx: HashMap<MethodRef, u32>,
+    call_sites: Vec<CallSiteRef>,
+    method_handles: Vec<MethodHandleRef>,
+    method_handle_idx: HashMap<MethodHandleRef, u32>,
+}
+
+fn index_map<T: Clone + Eq + ::std::hash::Hash>(items: &[T]) -> HashMap<T, u32> {
+    return items.iter().enumerate().map(|(i, item)| (item.clone(), i as u32)).collect();
+}
+
+impl Pools {
+    /// Sorts every table in the order the format mandates: strings by
+    /// UTF-16 code units, everything else by the indices it is made of.
+    fn new(collector: Collector) -> Pools {
+        let mut strings: Vec<Vec<u16>> = collector.strings.into_iter().collect();
+        strings.sort();
+        let string_idx = index_map(&strings);
+        let string = |value: &str| string_idx[&value.encode_utf16().collect::<Vec<u16>>()];
+
+        let mut types: Vec<String> = collector.types.into_iter().collect();
+        types.sort_by_key(|t| string(t));
+        let type_idx = index_map(&types);
+
+        let mut protos: Vec<Proto> = collector.protos.into_iter().collect();
+        protos.sort_by_key(|p| {
+            (type_idx[&p.return_type],
+             p.parameters.iter().map(|t| type_idx[t]).collect::<Vec<u32>>())
+        });
+        let proto_idx = index_map(&protos);
+
+        let mut fields: Vec<FieldRef> = collector.fields.into_iter().collect();
+        fields.sort_by_key(|f| (type_idx[&f.class], string(&f.name), type_idx[&f.ty]));
+        let field_idx = index_map(&fields);
+
+        let mut methods: Vec<MethodRef> = collector.methods.into_iter().collect();
+        methods.sort_by_key(|m| (type_idx[&m.class], string(&m.name), proto_idx[&m.proto]));
+        let method_idx = index_map(&methods);
+        let method_handle_idx = index_map(&collector.method_handles);
+
+        return Pools {
+            strings: strings,
+            string_idx: string_idx,
+            types: types,
+            type_idx: type_idx,
+            protos: protos,
+            proto_idx: proto_idx,
+            fields: fields,
+            field_idx: field_idx,
+            methods: methods,
+            method_idx: method_idx,
+            call_sites: collector.call_sites,
+            method_handles: collector.method_handles,
+            method_handle_idx: method_handle_idx,
+        };
+    }
+
+    fn string(&self, value: &str) -> u32 {
+        return self.string_idx[&value.encode_utf16().collect::<Vec<u16>>()];
+    }
+
+    fn reference(&self, reference: &Reference) -> u32 {
+        match *reference {
+            Reference::String(ref value) => self.string_idx[value],
+            Reference::Type(ref descriptor) => self.type_idx[descriptor],
+            Reference::Field(ref field) => self.field_idx[field],
+            Reference::Method(ref method) => self.method_idx[method],
+            Reference::Proto(ref proto) => self.proto_idx[proto],
+            Reference::CallSite(ref call_site) => {
+                self.call_sites.iter().position(|c| c == call_site).unwrap() as u32
+            }
+            Reference::MethodHandle(ref handle) => self.method_handle_idx[handle],
+        }
+    }
+
+    fn type_list(&self, types: &[String]) -> Vec<u16> {
+        return types.iter().map(|t| self.type_idx[t] as u16).collect();
+    }
+}
+
+fn string_p1(pools: &Pools, string: &Option<String>) -> u32 {
+    match *string {
+        Some(ref string) => pools.string(string) + 1,
+        None => 0,
+    }
+}
+
+/// Encodes the events as a state machine program. Positions use a special
+/// opcode where the line and address deltas fit into one.
+fn write_debug_info(out: &mut Output, pools: &Pools, debug_info: &DebugInfo) {
+    let line_start = debug_info.events
+        .iter()
+        .filter_map(|(_, event)| {
+            match *event {
+                DebugEvent::Line(line) => Some(line),
+                _ => None,
+            }
+        })
+        .next()
+        .unwrap_or(0);
+    out.uleb128(line_start);
+    out.uleb128(debug_info.parameter_names.len() as u32);
+    for name in debug_info.parameter_names.iter() {
+        out.uleb128(string_p1(pools, name));
+    }
+
+    let mut address = 0;
+    let mut line = line_start;
+    for &(event_address, ref event) in debug_info.events.iter() {
+        let mut address_diff = event_address.wrapping_sub(address);
+        address = event_address;
+        if let DebugEvent::Line(event_line) = *event {
+            let mut line_diff = event_line.wrapping_sub(line) as i32;
+            line = event_line;
+            if line_diff < dex::DBG_LINE_BASE ||
+               line_diff >= dex::DBG_LINE_BASE + dex::DBG_LINE_RANGE as i32 {
+                out.u8(dex::DBG_ADVANCE_LINE);
+                out.sleb128(line_diff);
+                line_diff = 0;
+            }
+            let adjusted = (line_diff - dex::DBG_LINE_BASE) as u32 +
+                           address_diff.saturating_mul(dex::DBG_LINE_RANGE as u32);
+            if adjusted > (0xFF - dex::DBG_FIRST_SPECIAL) as u32 {
+                out.u8(dex::DBG_ADVANCE_PC);
+                out.uleb128(address_diff);
+                address_diff = 0;
+            }
+            let adjusted = (line_diff - dex::DBG_LINE_BASE) as u32 +
+                           address_diff * dex::DBG_LINE_RANGE as u32;
+            out.u8(dex::DBG_FIRST_SPECIAL + adjusted as u8);
+            continue;
+        }
+
+        if address_diff != 0 {
+            out.u8(dex::DBG_ADVANCE_PC);
+            out.uleb128(address_diff);
+        }
+        match *event {
+            DebugEvent::StartLocal { register, ref name, ref ty, ref signature } => {
+                out.u8(if signature.is_some() {
+                    dex::DBG_START_LOCAL_EXTENDED
+                } else {
+                    dex::DBG_START_LOCAL
+                });
+                out.uleb128(register);
+                out.uleb128(string_p1(pools, name));
+                out.uleb128(match *ty {
+                    Some(ref ty) => pools.type_idx[ty] + 1,
+                    None => 0,
+                });
+                if signature.is_some() {
+                    out.uleb128(string_p1(pools, signature));
+                }
+            }
+            DebugEvent::EndLocal(register) => {
+                out.u8(dex::DBG_END_LOCAL);
+                out.uleb128(register);
+            }
+            DebugEvent::RestartLocal(register) => {
+                out.u8(dex::DBG_RESTART_LOCAL);
+                out.uleb128(register);
+            }
+            DebugEvent::PrologueEnd => out.u8(dex::DBG_SET_PROLOGUE_END),
+            DebugEvent::EpilogueBegin => out.u8(dex::DBG_SET_EPILOGUE_BEGIN),
+            DebugEvent::SetFile(ref name) => {
+                out.u8(dex::DBG_SET_FILE);
+                out.uleb128(string_p1(pools, name));
+            }
+            DebugEvent::Line(_) => {}
+        }
+    }
+    out.u8(dex::DBG_END_SEQUENCE);
+}
+
+/// Every annotation of a class and its members
+fn all_annotations(class: &Class) -> Vec<&Annotation> {
+    let mut annotations: Vec<&Annotation> = class.annotations.iter().collect();
+    for field in class.static_fields.iter().chain(class.instance_fields.iter()) {
+        annotations.extend(field.annotations.iter());
+    }
+    for method in class.direct_methods.iter().chain(class.virtual_methods.iter()) {
+        annotations.extend(method.annotations.iter());
+        for parameter in method.parameter_annotations.iter() {
+            annotations.extend(parameter.iter());
+        }
+    }
+    return annotations;
+}
+
+/// Orders classes so that superclasses and interfaces defined in the same
+/// file precede the classes deriving from them, as the verifier requires.
+fn class_order(classes: &[Class]) -> Vec<usize> {
+    fn visit(i: usize,
+             classes: &[Class],
+             index: &HashMap<&str, usize>,
+             state: &mut Vec<u8>,
+             order: &mut Vec<usize>) {
+        if state[i] != 0 {
+            return;
+        }
+        state[i] = 1;
+        let class = &classes[i];
+        for dep in class.superclass.iter().chain(class.interfaces.iter()) {
+            if let Some(&j) = index.get(dep.as_str()) {
+                visit(j, classes, index, state, order);
+            }
+        }
+        state[i] = 2;
+        order.push(i);
+    }
+
+    let index: HashMap<&str, usize> =
+        classes.iter().enumerate().map(|(i, c)| (c.descriptor.as_str(), i)).collect();
+    let mut state = vec![0u8; classes.len()];
+    let mut order = vec![];
+    for i in 0..classes.len() {
+        visit(i, classes, &index, &mut state, &mut order);
+    }
+    return order;
+}
+
+struct Output {
+    data: Vec<u8>,
+}
+
+impl Output {
+    fn position(&self) -> u32 {
+        return self.data.len() as u32;
+    }
+
+    fn align(&mut self, alignment: usize) {
+        while !self.data.len().is_multiple_of(alignment) {
+            self.data.push(0);
+        }
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.data.push(value as u8);
+        self.data.push((value >> 8) as u8);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.u16(value as u16);
+        self.u16((value >> 16) as u16);
+    }
+
+    fn uleb128(&mut self, value: u32) {
+        leb128::write::unsigned(&mut self.data, value as u64).unwrap();
+    }
+
+    fn sleb128(&mut self, value: i32) {
+        leb128::write::signed(&mut self.data, value as i64).unwrap();
+    }
+
+    fn set_u32(&mut self, position: u32, value: u32) {
+        let position = position as usize;
+        for i in 0..4 {
+            self.data[position + i] = (value >> (i * 8)) as u8;
+        }
+    }
+}
+
+/// Writes a value with the smallest size that sign-extends back to it
+fn write_signed(out: &mut Output, value_type: u8, value: i64) {
+    let mut size = 1;
+    while size < 8 {
+        let shift = 64 - size * 8;
+        if (value << shift) >> shift == value {
+            break;
+        }
+        size += 1;
+    }
+    out.u8((((size - 1) as u8) << 5) | value_type);
+    for i in 0..size {
+        out.u8((value >> (i * 8)) as u8);
+    }
+}
+
+/// Writes a value with the smallest size that zero-extends back to it
+fn write_unsigned(out: &mut Output, value_type: u8, value: u64) {
+    let mut size = 1;
+    while size < 8 && value >> (size * 8) != 0 {
+        size += 1;
+    }
+    out.u8((((size - 1) as u8) << 5) | value_type);
+    for i in 0..size {
+        out.u8((value >> (i * 8)) as u8);
+    }
+}
+
+/// Floating point values drop their low-order zero bytes instead
+fn write_right_extended(out: &mut Output, value_type: u8, value: u64, width: usize) {
+    let mut value = value;
+    let mut size = width;
+    while size > 1 && value & 0xFF == 0 {
+        value >>= 8;
+        size -= 1;
+    }
+    out.u8((((size - 1) as u8) << 5) | value_type);
+    for i in 0..size {
+        out.u8((value >> (i * 8)) as u8);
+    }
+}
+
+fn write_value(out: &mut Output, pools: &Pools, value: &Value) {
+    match *value {
+        Value::Byte(v) => {
+            out.u8(0x00);
+            out.u8(v as u8);
+        }
+        Value::Short(v) => write_signed(out, 0x02, v as i64),
+        Value::Char(v) => write_unsigned(out, 0x03, v as u64),
+        Value::Int(v) => write_signed(out, 0x04, v as i64),
+        Value::Long(v) => write_signed(out, 0x06, v),
+        Value::Float(v) => write_right_extended(out, 0x10, v.to_bits() as u64, 4),
+        Value::Double(v) => write_right_extended(out, 0x11, v.to_bits(), 8),
+        Value::MethodType(ref v) => write_unsigned(out, 0x15, pools.proto_idx[v] as u64),
+        Value::MethodHandle(ref v) => {
+            write_unsigned(out, 0x16, pools.method_handle_idx[v] as u64)
+        }
+        Value::String(ref v) => write_unsigned(out, 0x17, pools.string_idx[v] as u64),
+        Value::Type(ref v) => write_unsigned(out, 0x18, pools.type_idx[v] as u64),
+        Value::Field(ref v) => write_unsigned(out, 0x19, pools.field_idx[v] as u64),
+        Value::Method(ref v) => write_unsigned(out, 0x1A, pools.method_idx[v] as u64),
+        Value::Enum(ref v) => write_unsigned(out, 0x1B, pools.field_idx[v] as u64),
+        Value::Array(ref values) => {
+            out.u8(0x1C);
+            out.uleb128(values.len() as u32);
+            for value in values.iter() {
+                write_value(out, pools, value);
+            }
+        }
+        Value::Annotation(ref annotation) => {
+            out.u8(0x1D);
+            write_annotation_value(out, pools, annotation);
+        }
+        Value::Null => out.u8(0x1E),
+        Value::Boolean(v) => out.u8(((v as u8) << 5) | 0x1F),
+    }
+}
+
+/// Writes an encoded_annotation, its elements sorted by name
+fn write_annotation_value(out: &mut Output, pools: &Pools, annotation: &AnnotationValue) {
+    let mut elements: Vec<&(String, Value)> = annotation.elements.iter().collect();
+    elements.sort_by_key(|element| pools.string(&element.0));
+    out.uleb128(pools.type_idx[&annotation.ty]);
+    out.uleb128(elements.len() as u32);
+    for &(name, value) in elements.iter() {
+        out.uleb128(pools.string(name));
+        write_value(out, pools, value);
+    }
+}
+
+fn annotation_item(pools: &Pools, annotation: &Annotation) -> Vec<u8> {
+    let mut out = Output { data: vec![annotation.visibility.code()] };
+    write_annotation_value(&mut out, pools, &annotation.value);
+    return out.data;
+}
+
+/// The value a static field holds when the encoded_array stops short of it
+fn default_value(ty: &str) -> Value {
+    match ty {
+        "Z" => Value::Boolean(false),
+        "B" => Value::Byte(0),
+        "S" => Value::Short(0),
+        "C" => Value::Char(0),
+        "I" => Value::Int(0),
+        "J" => Value::Long(0),
+        "F" => Value::Float(0.0),
+        "D" => Value::Double(0.0),
+        _ => Value::Null,
+    }
+}
+
+fn write_code(out: &mut Output, pools: &Pools, code: &Code) -> Result<u32, String> {
+    let mut insns = code.insns.clone();
+    for fixup in code.fixups.iter() {
+        let index = pools.reference(&fixup.reference);
+        if fixup.wide {
+            insns[fixup.position] = index as u16;
+            insns[fixup.position + 1] = (index >> 16) as u16;
+        } else if index > 0xFFFF {
+            return Err(format!("index {} of {:?} does not fit into 16 bits",
+                               index,
+                               fixup.reference));
+        } else {
+            insns[fixup.position] = index as u16;
+        }
+    }
+
+    // Identical handler lists are shared between try blocks
+    let mut handler_list = Output { data: vec![] };
+    let mut handler_offsets: Vec<u16> = vec![];
+    let mut unique: Vec<Vec<u8>> = vec![];
+    for try_item in code.tries.iter() {
+        let mut handler = Output { data: vec![] };
+        let size = try_item.handlers.len() as i32;
+        handler.sleb128(if try_item.catch_all.is_some() { -size } else { size });
+        for &(ref ty, addr) in try_item.handlers.iter() {
+            handler.uleb128(pools.type_idx[ty]);
+            handler.uleb128(addr);
+        }
+        if let Some(addr) = try_item.catch_all {
+            handler.uleb128(addr);
+        }
+        let position = match unique.iter().position(|h| *h == handler.data) {
+            Some(position) => position,
+            None => {
+                unique.push(handler.data);
+                unique.len() - 1
+            }
+        };
+        handler_offsets.push(position as u16);
+    }
+    handler_list.uleb128(unique.len() as u32);
+    let mut offsets: Vec<u16> = vec![];
+    for handler in unique.iter() {
+        offsets.push(handler_list.position() as u16);
+        handler_list.data.extend_from_slice(handler);
+    }
+
+    out.align(4);
+    let offset = out.position();
+    out.u16(code.registers_size);
+    out.u16(code.ins_size);
+    out.u16(code.outs_size);
+    out.u16(code.tries.len() as u16);
+    out.u32(0);
+    out.u32(insns.len() as u32);
+    for unit in insns.iter() {
+        out.u16(*unit);
+    }
+    if !code.tries.is_empty() {
+        if insns.len() % 2 == 1 {
+            out.u16(0);
+        }
+        for (try_item, handler) in code.tries.iter().zip(handler_offsets.iter()) {
+            out.u32(try_item.start_addr);
+            out.u16(try_item.insn_count);
+            out.u16(offsets[*handler as usize]);
+        }
+        out.data.extend_from_slice(&handler_list.data);
+    }
+    return Ok(offset);
+}
+
+/// Writes the annotation_items, annotation_set_items, annotation_set_ref_lists
+/// and annotations_directory_items of all classes, sharing identical items,
+/// sets and lists. Returns the directory offset of every annotated class.
+fn write_annotations(out: &mut Output,
+                     pools: &Pools,
+                     classes: &[Class],
+                     order: &[usize],
+                     map: &mut Vec<(u16, u32, u32)>)
+                     -> HashMap<usize, u32> {
+    let mut item_offs: HashMap<Vec<u8>, u32> = HashMap::new();
+    let start = out.position();
+    for &i in order.iter() {
+        for annotation in all_annotations(&classes[i]) {
+            let item = annotation_item(pools, annotation);
+            if !item_offs.contains_key(&item) {
+                item_offs.insert(item.clone(), out.position());
+                out.data.extend(item);
+            }
+        }
+    }
+    if !item_offs.is_empty() {
+        map.push((TYPE_ANNOTATION_ITEM, item_offs.len() as u32, start));
+    }
+
+    // Sets list their annotations ordered by type
+    let set_key = |set: &[Annotation]| -> Vec<u32> {
+        let mut sorted: Vec<&Annotation> = set.iter().collect();
+        sorted.sort_by_key(|annotation| pools.type_idx[&annotation.value.ty]);
+        return sorted.iter()
+            .map(|annotation| item_offs[&annotation_item(pools, annotation)])
+            .collect();
+    };
+    let mut set_offs: HashMap<Vec<u32>, u32> = HashMap::new();
+    out.align(4);
+    let start = out.position();
+    for &i in order.iter() {
+        let class = &classes[i];
+        let mut sets: Vec<&[Annotation]> = vec![&class.annotations];
+        for field in class.static_fields.iter().chain(class.instance_fields.iter()) {
+            sets.push(&field.annotations);
+        }
+        for method in class.direct_methods.iter().chain(class.virtual_methods.iter()) {
+            sets.push(&method.annotations);
+            for parameter in method.parameter_annotations.iter() {
+                sets.push(parameter);
+            }
+        }
+        for set in sets.into_iter().filter(|set| !set.is_empty()) {
+            let key = set_key(set);
+            if !set_offs.contains_key(&key) {
+                set_offs.insert(key.clone(), out.position());
+                out.u32(key.len() as u32);
+                for offset in key {
+                    out.u32(offset);
+                }
+            }
+        }
+    }
+    if !set_offs.is_empty() {
+        map.push((TYPE_ANNOTATION_SET_ITEM, set_offs.len() as u32, start));
+    }
+
+    // Parameters without annotations refer to no set at all
+    let set_off = |set: &[Annotation]| if set.is_empty() { 0 } else { set_offs[&set_key(set)] };
+    let ref_list_key = |method: &Method| -> Vec<u32> {
+        return method.parameter_annotations.iter().map(|set| set_off(set)).collect();
+    };
+    let annotated = |method: &&Method| {
+        method.parameter_annotations.iter().any(|set| !set.is_empty())
+    };
+    let mut ref_list_offs: HashMap<Vec<u32>, u32> = HashMap::new();
+    let start = out.position();
+    for &i in order.iter() {
+        let class = &classes[i];
+        let methods = class.direct_methods.iter().chain(class.virtual_methods.iter());
+        for method in methods.filter(&annotated) {
+            let key = ref_list_key(method);
+            if !ref_list_offs.contains_key(&key) {
+                ref_list_offs.insert(key.clone(), out.position());
+                out.u32(key.len() as u32);
+                for offset in key {
+                    out.u32(offset);
+                }
+            }
+        }
+    }
+    if !ref_list_offs.is_empty() {
+        map.push((TYPE_ANNOTATION_SET_REF_LIST, ref_list_offs.len() as u32, start));
+    }
+
+    let mut directory_offs: HashMap<usize, u32> = HashMap::new();
+    let start = out.position();
+    for &i in order.iter() {
+        let class = &classes[i];
+        let mut fields: Vec<(u32, u32)> = class.static_fields
+            .iter()
+            .chain(class.instance_fields.iter())
+            .filter(|field| !field.annotations.is_empty())
+            .map(|field| (pools.field_idx[&field.field], set_off(&field.annotations)))
+            .collect();
+        let methods = || class.direct_methods.iter().chain(class.virtual_methods.iter());
+        let mut method_sets: Vec<(u32, u32)> = methods()
+            .filter(|method| !method.annotations.is_empty())
+            .map(|method| (pools.method_idx[&method.method], set_off(&method.annotations)))
+            .collect();
+        let mut parameters: Vec<(u32, u32)> = methods()
+            .filter(&annotated)
+            .map(|method| (pools.method_idx[&method.method], ref_list_offs[&ref_list_key(method)]))
+            .collect();
+        if class.annotations.is_empty() && fields.is_empty() && method_sets.is_empty() &&
+           parameters.is_empty() {
+            continue;
+        }
+        fields.sort();
+        method_sets.sort();
+        parameters.sort();
+        directory_offs.insert(i, out.position());
+        out.u32(set_off(&class.annotations));
+        out.u32(fields.len() as u32);
+        out.u32(method_sets.len() as u32);
+        out.u32(parameters.len() as u32);
+        for &(idx, offset) in fields.iter().chain(method_sets.iter()).chain(parameters.iter()) {
+            out.u32(idx);
+            out.u32(offset);
+        }
+    }
+    if !directory_offs.is_empty() {
+        map.push((TYPE_ANNOTATIONS_DIRECTORY_ITEM, directory_offs.len() as u32, start));
+    }
+    return directory_offs;
+}
+
+/// Lowest DEX version able to hold the given classes: 037 for code in
+/// interfaces, 038 for invoke-polymorphic and invoke-custom, 039 for
+/// const-method-handle and const-method-type
+fn required_version(classes: &[Class]) -> [u8; 4] {
+    let mut version = *b"035\0";
+    for class in classes {
+        for method in class.direct_methods.iter().chain(class.virtual_methods.iter()) {
+            let code = match method.code {
+                Some(ref code) => code,
+                None => continue,
+            };
+            if class.access_flags & ACC_INTERFACE != 0 && method.method.name != "<clinit>" {
+                version = ::std::cmp::max(version, *b"037\0");
+            }
+            for instruction in dalvik::instructions(&code.insns).0 {
+                match instruction.opcode().name {
+                    "const-method-handle" | "const-method-type" => return *b"039\0",
+                    "invoke-polymorphic" | "invoke-polymorphic/range" |
+                    "invoke-custom" | "invoke-custom/range" => {
+                        version = ::std::cmp::max(version, *b"038\0")
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    return version;
+}
+
+/// Serializes classes into a complete DEX file: sorted id sections, the
+/// data section, map_list, and a valid checksum and signature.
+pub fn write(classes: &[Class]) -> Result<Vec<u8>, String> {
+    return write_file(classes, &[], required_version(classes));
+}
+
+/// Writes a parsed file back out. Every id of the original is kept, even
+/// when nothing refers to it, so all indices stay the same. Sections the
+/// writer cannot reproduce, such as link data or hiddenapi flags, are
+/// an error rather than left out.
+pub fn write_dex(dex: &DexFile) -> Result<Vec<u8>, String> {
+    if dex.header().link_size() != 0 {
+        return Err("statically linked files are not supported".to_string());
+    }
+    for item in dex.map_list().unwrap_or(&[]) {
+        match item.item_type() {
+            MapItemType::HiddenapiClassData => {
+                return Err("hiddenapi_class_data_item is not supported".to_string())
+            }
+            MapItemType::Unknown(code) => {
+                return Err(format!("unknown map item type 0x{:04x}", code))
+            }
+            _ => {}
+        }
+    }
+    let classes = classes(dex)?;
+    // Containers (041) are written as a plain single file
+    let version = ::std::cmp::min(dex.header().version(), *b"040\0");
+    let version = ::std::cmp::max(version, required_version(&classes));
+    return write_file(&classes, &ids(dex)?, version);
+}
+
+fn write_file(classes: &[Class], ids: &[Reference], version: [u8; 4]) -> Result<Vec<u8>, String> {
+    for class in classes {
+        if let Some(field) = class.instance_fields.iter().find(|f| f.value.is_some()) {
+            // Only static fields have a place for an initial value
+            return Err(format!("instance field {}.{} has a value",
+                               class.descriptor,
+                               field.field.name));
+        }
+    }
+    let mut collector = Collector::default();
+    for reference in ids {
+        collector.reference(reference);
+    }
+    for class in classes {
+        collector.class(class);
+    }
+    let pools = Pools::new(collector);
+    let order = class_order(classes);
+
+    let string_ids_off = HEADER_SIZE;
+    let type_ids_off = string_ids_off + pools.strings.len() as u32 * 4;
+    let proto_ids_off = type_ids_off + pools.types.len() as u32 * 4;
+    let field_ids_off = proto_ids_off + pools.protos.len() as u32 * 12;
+    let method_ids_off = field_ids_off + pools.fields.len() as u32 * 8;
+    let class_defs_off = method_ids_off + pools.methods.len() as u32 * 8;
+    let call_site_ids_off = class_defs_off + classes.len() as u32 * 32;
+    let method_handles_off = call_site_ids_off + pools.call_sites.len() as u32 * 4;
+    let data_off = method_handles_off + pools.method_handles.len() as u32 * 8;
+
+    let mut out = Output { data: vec![0; data_off as usize] };
+    let mut map: Vec<(u16, u32, u32)> = vec![(TYPE_HEADER_ITEM, 1, 0)];
+    let sections = [(TYPE_STRING_ID_ITEM, pools.strings.len(), string_ids_off),
+                    (TYPE_TYPE_ID_ITEM, pools.types.len(), type_ids_off),
+                    (TYPE_PROTO_ID_ITEM, pools.protos.len(), proto_ids_off),
+                    (TYPE_FIELD_ID_ITEM, pools.fields.len(), field_ids_off),
+                    (TYPE_METHOD_ID_ITEM, pools.methods.len(), method_ids_off),
+                    (TYPE_CLASS_DEF_ITEM, classes.len(), class_defs_off),
+                    (TYPE_CALL_SITE_ID_ITEM, pools.call_sites.len(), call_site_ids_off),
+                    (TYPE_METHOD_HANDLE_ITEM, pools.method_handles.len(), method_handles_off)];
+    for &(item_type, size, offset) in sections.iter() {
+        if size != 0 {
+            map.push((item_type, size as u32, offset));
+        }
+    }
+
+    // code_items
+    let mut code_offs: HashMap<(usize, MethodRef), u32> = HashMap::new();
+    let start = out.position();
+    let mut count = 0;
+    for &i in order.iter() {
+        let class = &classes[i];
+        for method in class.direct_methods.iter().chain(class.virtual_methods.iter()) {
+            if let Some(ref code) = method.code {
+                let offset = write_code(&mut out, &pools, code)?;
+                code_offs.insert((i, method.method.clone()), offset);
+                count += 1;
+            }
+        }
+    }
+    if count != 0 {
+        map.push((TYPE_CODE_ITEM, count, (start + 3) & !3));
+    }
+
+    // type_lists, shared between protos and interface lists
+    let mut type_lists: BTreeMap<Vec<u16>, u32> = BTreeMap::new();
+    for proto in pools.protos.iter().filter(|p| !p.parameters.is_empty()) {
+        type_lists.insert(pools.type_list(&proto.parameters), 0);
+    }
+    for class in classes.iter().filter(|c| !c.interfaces.is_empty()) {
+        type_lists.insert(pools.type_list(&class.interfaces), 0);
+    }
+    if !type_lists.is_empty() {
+        out.align(4);
+        map.push((TYPE_TYPE_LIST, type_lists.len() as u32, out.position()));
+        for (list, offset) in type_lists.iter_mut() {
+            out.align(4);
+            *offset = out.position();
+            out.u32(list.len() as u32);
+            for ty in list.iter() {
+                out.u16(*ty);
+            }
+        }
+    }
+
+    // string_data_items
+    let mut string_offs: Vec<u32> = vec![];
+    if !pools.strings.is_empty() {
+        map.push((TYPE_STRING_DATA_ITEM, pools.strings.len() as u32, out.position()));
+    }
+    for units in pools.strings.iter() {
+        string_offs.push(out.position());
+        out.uleb128(units.len() as u32);
+        out.data.extend(dex::mutf8_encode(units));
+        out.u8(0);
+    }
+
+    // debug_info_items, referenced from the code_items written above
+    let start = out.position();
+    let mut count = 0;
+    for &i in order.iter() {
+        let class = &classes[i];
+        for method in class.direct_methods.iter().chain(class.virtual_methods.iter()) {
+            let debug_info = match method.code {
+                Some(Code { debug_info: Some(ref debug_info), .. }) => debug_info,
+                _ => continue,
+            };
+            let code_off = code_offs[&(i, method.method.clone())];
+            let offset = out.position();
+            write_debug_info(&mut out, &pools, debug_info);
+            out.set_u32(code_off + 8, offset);
+            count += 1;
+        }
+    }
+    if count != 0 {
+        map.push((TYPE_DEBUG_INFO_ITEM, count, start));
+    }
+
+    // encoded_array_items of call sites, followed by static field values
+    let start = out.position();
+    let mut call_site_offs: Vec<u32> = vec![];
+    for call_site in pools.call_sites.iter() {
+        call_site_offs.push(out.position());
+        out.uleb128(3 + call_site.arguments.len() as u32);
+        write_value(&mut out, &pools, &Value::MethodHandle(call_site.bootstrap.clone()));
+        write_value(&mut out, &pools, &Value::String(call_site.name.encode_utf16().collect()));
+        write_value(&mut out, &pools, &Value::MethodType(call_site.proto.clone()));
+        for argument in call_site.arguments.iter() {
+            write_value(&mut out, &pools, argument);
+        }
+    }
+    let mut static_values_offs: HashMap<usize, u32> = HashMap::new();
+    for &i in order.iter() {
+        let mut fields: Vec<&Field> = classes[i].static_fields.iter().collect();
+        fields.sort_by_key(|f| pools.field_idx[&f.field]);
+        let count = match fields.iter().rposition(|f| f.value.is_some()) {
+            Some(last) => last + 1,
+            None => continue,
+        };
+        static_values_offs.insert(i, out.position());
+        out.uleb128(count as u32);
+        for field in fields[..count].iter() {
+            match field.value {
+                Some(ref value) => write_value(&mut out, &pools, value),
+                None => write_value(&mut out, &pools, &default_value(&field.field.ty)),
+            }
+        }
+    }
+    let count = call_site_offs.len() + static_values_offs.len();
+    if count != 0 {
+        map.push((TYPE_ENCODED_ARRAY_ITEM, count as u32, start));
+    }
+
+    let annotations_offs = write_annotations(&mut out, &pools, classes, &order, &mut map);
+
+    // class_data_items
+    let mut class_data_offs: HashMap<usize, u32> = HashMap::new();
+    let start = out.position();
+    for &i in order.iter() {
+        let class = &classes[i];
+        if class.static_fields.is_empty() && class.instance_fields.is_empty() &&
+           class.direct_methods.is_empty() && class.virtual_methods.is_empty() {
+            continue;
+        }
+        class_data_offs.insert(i, out.position());
+        out.uleb128(class.static_fields.len() as u32);
+        out.uleb128(class.instance_fields.len() as u32);
+        out.uleb128(class.direct_methods.len() as u32);
+        out.uleb128(class.virtual_methods.len() as u32);
+        for list in [&class.static_fields, &class.instance_fields].iter() {
+            let mut fields: Vec<(u32, u32)> =
+                list.iter().map(|f| (pools.field_idx[&f.field], f.access_flags)).collect();
+            fields.sort();
+            let mut previous = 0;
+            for (idx, access_flags) in fields {
+                out.uleb128(idx - previous);
+                out.uleb128(access_flags);
+                previous = idx;
+            }
+        }
+        for list in [&class.direct_methods, &class.virtual_methods].iter() {
+            let mut methods: Vec<(u32, u32, u32)> = list.iter()
+                .map(|m| {
+                    (pools.method_idx[&m.method],
+                     m.access_flags,
+                     code_offs.get(&(i, m.method.clone())).cloned().unwrap_or(0))
+                })
+                .collect();
+            methods.sort();
+            let mut previous = 0;
+            for (idx, access_flags, code_off) in methods {
+                out.uleb128(idx - previous);
+                out.uleb128(access_flags);
+                out.uleb128(code_off);
+                previous = idx;
+            }
+        }
+    }
+    if !class_data_offs.is_empty() {
+        map.push((TYPE_CLASS_DATA_ITEM, class_data_offs.len() as u32, start));
+    }
+
+    // map_list
+    out.align(4);
+    let map_off = out.position();
+    map.push((TYPE_MAP_LIST, 1, map_off));
+    out.u32(map.len() as u32);
+    for &(item_type, size, offset) in map.iter() {
+        out.u16(item_type);
+        out.u16(0);
+        out.u32(size);
+        out.u32(offset);
+    }
+
+    // id sections
+    for (i, offset) in string_offs.iter().enumerate() {
+        out.set_u32(string_ids_off + i as u32 * 4, *offset);
+    }
+    for (i, ty) in pools.types.iter().enumerate() {
+        out.set_u32(type_ids_off + i as u32 * 4, pools.string(ty));
+    }
+    for (i, proto) in pools.protos.iter().enumerate() {
+        let position = proto_ids_off + i as u32 * 12;
+        let parameters_off = if proto.parameters.is_empty() {
+            0
+        } else {
+            type_lists[&pools.type_list(&proto.parameters)]
+        };
+        out.set_u32(position, pools.string(&proto.shorty()));
+        out.set_u32(position + 4, pools.type_idx[&proto.return_type]);
+        out.set_u32(position + 8, parameters_off);
+    }
+    for (i, field) in pools.fields.iter().enumerate() {
+        let position = (field_ids_off + i as u32 * 8) as usize;
+        let class_idx = pools.type_idx[&field.class];
+        let type_idx = pools.type_idx[&field.ty];
+        out.data[position] = class_idx as u8;
+        out.data[position + 1] = (class_idx >> 8) as u8;
+        out.data[position + 2] = type_idx as u8;
+        out.data[position + 3] = (type_idx >> 8) as u8;
+        out.set_u32(position as u32 + 4, pools.string(&field.name));
+    }
+    for (i, method) in pools.methods.iter().enumerate() {
+        let position = (method_ids_off + i as u32 * 8) as usize;
+        let class_idx = pools.type_idx[&method.class];
+        let proto_idx = pools.proto_idx[&method.proto];
+        out.data[position] = class_idx as u8;
+        out.data[position + 1] = (class_idx >> 8) as u8;
+        out.data[position + 2] = proto_idx as u8;
+        out.data[position + 3] = (proto_idx >> 8) as u8;
+        out.set_u32(position as u32 + 4, pools.string(&method.name));
+    }
+    for (n, &i) in order.iter().enumerate() {
+        let class = &classes[i];
+        let position = class_defs_off + n as u32 * 32;
+        let interfaces_off = if class.interfaces.is_empty() {
+            0
+        } else {
+            type_lists[&pools.type_list(&class.interfaces)]
+        };
+        out.set_u32(position, pools.type_idx[&class.descriptor]);
+        out.set_u32(position + 4, class.access_flags);
+        out.set_u32(position + 8,
+                    class.superclass.as_ref().map_or(dex::NO_INDEX, |s| pools.type_idx[s]));
+        out.set_u32(position + 12, interfaces_off);
+        out.set_u32(position + 16,
+                    class.source_file.as_ref().map_or(dex::NO_INDEX, |s| pools.string(s)));
+        out.set_u32(position + 20, annotations_offs.get(&i).cloned().unwrap_or(0));
+        out.set_u32(position + 24, class_data_offs.get(&i).cloned().unwrap_or(0));
+        out.set_u32(position + 28, static_values_offs.get(&i).cloned().unwrap_or(0));
+    }
+    for (i, offset) in call_site_offs.iter().enumerate() {
+        out.set_u32(call_site_ids_off + i as u32 * 4, *offset);
+    }
+    for (i, handle) in pools.method_handles.iter().enumerate() {
+        let position = (method_handles_off + i as u32 * 8) as usize;
+        let id = match handle.target {
+            MethodHandleTarget::Field(ref field) => pools.field_idx[field],
+            MethodHandleTarget::Method(ref method) => pools.method_idx[method],
+        };
+        if id > 0xFFFF {
+            return Err(format!("member index {} of {:?} does not fit into 16 bits", id, handle));
+        }
+        for (n, half) in [handle.kind.code(), 0, id as u16, 0].iter().enumerate() {
+            out.data[position + n * 2] = *half as u8;
+            out.data[position + n * 2 + 1] = (*half >> 8) as u8;
+        }
+    }
+
+    // header
+    let file_size = out.position();
+    out.data[0..4].copy_from_slice(b"dex\n");
+    out.data[4..8].copy_from_slice(&version);
+    out.set_u32(32, file_size);
+    out.set_u32(36, HEADER_SIZE);
+    out.set_u32(40, ENDIAN_CONSTANT);
+    out.set_u32(52, map_off);
+    let id_sections = [(pools.strings.len(), string_ids_off),
+                       (pools.types.len(), type_ids_off),
+                       (pools.protos.len(), proto_ids_off),
+                       (pools.fields.len(), field_ids_off),
+                       (pools.methods.len(), method_ids_off),
+                       (classes.len(), class_defs_off)];
+    for (n, &(size, offset)) in id_sections.iter().enumerate() {
+        out.set_u32(56 + n as u32 * 8, size as u32);
+        out.set_u32(60 + n as u32 * 8, if size == 0 { 0 } else { offset });
+    }
+    out.set_u32(104, file_size - data_off);
+    out.set_u32(108, data_off);
+
+    dex::repair_integrity(&mut out.data);
+
+    return Ok(out.data);
+}
+
+fn type_from_dex(dex: &DexFile, idx: u32) -> Result<String, String> {
+    match dex.type_descriptor(idx) {
+        Some(descriptor) => Ok(descriptor.to_string()),
+        None => Err(format!("invalid type index {}", idx)),
+    }
+}
+
+/// A string used as a name or descriptor, which has to be valid UTF-16
+fn string_from_dex(dex: &DexFile, idx: u32) -> Result<String, String> {
+    let units = string_units_from_dex(dex, idx)?;
+    return String::from_utf16(&units)
+        .map_err(|_| format!("string {} holds an unpaired surrogate", idx));
+}
+
+fn string_units_from_dex(dex: &DexFile, idx: u32) -> Result<Vec<u16>, String> {
+    match dex.string_units(idx) {
+        Some(units) => Ok(units),
+        None => Err(format!("invalid string index {}", idx)),
+    }
+}
+
+fn proto_from_dex(dex: &DexFile, idx: u32) -> Result<Proto, String> {
+    let proto = match dex.proto_ids().get(idx as usize) {
+        Some(proto) => proto,
+        None => return Err(format!("invalid proto index {}", idx)),
+    };
+    let mut parameters = vec![];
+    for param in proto.parameters() {
+        parameters.push(type_from_dex(dex, *param as u32)?);
+    }
+    return Ok(Proto {
+        parameters: parameters,
+        return_type: type_from_dex(dex, proto.return_type_idx())?,
+    });
+}
+
+fn field_from_dex(dex: &DexFile, idx: u32) -> Result<FieldRef, String> {
+    let field = match dex.field_id(idx) {
+        Some(field) => field,
+        None => return Err(format!("invalid field index {}", idx)),
+    };
+    return Ok(FieldRef {
+        class: type_from_dex(dex, field.class_idx() as u32)?,
+        name: string_from_dex(dex, field.name_idx())?,
+        ty: type_from_dex(dex, field.type_idx() as u32)?,
+    });
+}
+
+fn method_from_dex(dex: &DexFile, idx: u32) -> Result<MethodRef, String> {
+    let method = match dex.method_id(idx) {
+        Some(method) => method,
+        None => return Err(format!("invalid method index {}", idx)),
+    };
+    return Ok(MethodRef {
+        class: type_from_dex(dex, method.class_idx() as u32)?,
+        name: string_from_dex(dex, method.name_idx())?,
+        proto: proto_from_dex(dex, method.proto_idx() as u32)?,
+    });
+}
+
+fn method_handle_from_dex(dex: &DexFile, idx: u32) -> Result<MethodHandleRef, String> {
+    let handle = match dex.method_handle(idx) {
+        Some(handle) => handle,
+        None => return Err(format!("invalid method handle index {}", idx)),
+    };
+    let id = handle.field_or_method_id() as u32;
+    let target = if handle.handle_type().is_field() {
+        MethodHandleTarget::Field(field_from_dex(dex, id)?)
+    } else {
+        MethodHandleTarget::Method(method_from_dex(dex, id)?)
+    };
+    return Ok(MethodHandleRef {
+        kind: handle.handle_type(),
+        target: target,
+    });
+}
+
+fn call_site_from_dex(dex: &DexFile, idx: u32) -> Result<CallSiteRef, String> {
+    let call_site = match dex.call_site(idx) {
+        Some(call_site) => call_site,
+        None => return Err(format!("invalid call site index {}", idx)),
+    };
+    match (call_site.bootstrap(), call_site.name_idx(), call_site.proto_idx()) {
+        (Some(bootstrap), Some(name), Some(proto)) => {
+            let mut arguments = vec![];
+            for argument in call_site.arguments() {
+                arguments.push(value_from_dex(dex, argument)?);
+            }
+            Ok(CallSiteRef {
+                bootstrap: method_handle_from_dex(dex, bootstrap)?,
+                name: string_from_dex(dex, name)?,
+                proto: proto_from_dex(dex, proto)?,
+                arguments: arguments,
+            })
+        }
+        _ => Err(format!("call site {} is malformed", idx)),
+    }
+}
+
+fn value_from_dex(dex: &DexFile, value: &EncodedValue) -> Result<Value, String> {
+    let value = match *value {
+        EncodedValue::Byte(v) => Value::Byte(v),
+        EncodedValue::Short(v) => Value::Short(v),
+        EncodedValue::Char(v) => Value::Char(v),
+        EncodedValue::Int(v) => Value::Int(v),
+        EncodedValue::Long(v) => Value::Long(v),
+        EncodedValue::Float(v) => Value::Float(v),
+        EncodedValue::Double(v) => Value::Double(v),
+        EncodedValue::MethodType(idx) => Value::MethodType(proto_from_dex(dex, idx)?),
+        EncodedValue::MethodHandle(idx) => Value::MethodHandle(method_handle_from_dex(dex, idx)?),
+        EncodedValue::String(idx) => Value::String(string_units_from_dex(dex, idx)?),
+        EncodedValue::Type(idx) => Value::Type(type_from_dex(dex, idx)?),
+        EncodedValue::Field(idx) => Value::Field(field_from_dex(dex, idx)?),
+        EncodedValue::Method(idx) => Value::Method(method_from_dex(dex, idx)?),
+        EncodedValue::Enum(idx) => Value::Enum(field_from_dex(dex, idx)?),
+        EncodedValue::Array(ref values) => {
+            let mut list = vec![];
+            for value in values {
+                list.push(value_from_dex(dex, value)?);
+            }
+            Value::Array(list)
+        }
+        EncodedValue::Annotation(ref annotation) => {
+            Value::Annotation(annotation_value_from_dex(dex, annotation)?)
+        }
+        EncodedValue::Null => Value::Null,
+        EncodedValue::Boolean(v) => Value::Boolean(v),
+    };
+    return Ok(value);
+}
+
+fn annotation_value_from_dex(dex: &DexFile,
+                             annotation: &dex::EncodedAnnotation)
+                             -> Result<AnnotationValue, String> {
+    let mut elements = vec![];
+    for element in annotation.elements() {
+        elements.push((string_from_dex(dex, element.name_idx())?,
+                       value_from_dex(dex, element.value())?));
+    }
+    return Ok(AnnotationValue {
+        ty: type_from_dex(dex, annotation.type_idx())?,
+        elements: elements,
+    });
+}
+
+fn annotations_from_dex(dex: &DexFile,
+                        annotations: &[dex::Annotation])
+                        -> Result<Vec<Annotation>, String> {
+    let mut list = vec![];
+    for annotation in annotations {
+        list.push(Annotation {
+            visibility: annotation.visibility(),
+            value: annotation_value_from_dex(dex, annotation.annotation())?,
+        });
+    }
+    return Ok(list);
+}
+
+/// Every entry of the id tables of a parsed file
+pub fn ids(dex: &DexFile) -> Result<Vec<Reference>, String> {
+    let mut ids: Vec<Reference> = vec![];
+    for idx in 0..dex.strings().len() {
+        ids.push(Reference::String(string_units_from_dex(dex, idx as u32)?));
+    }
+    for idx in 0..dex.type_ids_size() {
+        ids.push(Reference::Type(type_from_dex(dex, idx)?));
+    }
+    for idx in 0..dex.proto_ids().len() {
+        ids.push(Reference::Proto(proto_from_dex(dex, idx as u32)?));
+    }
+    for idx in 0..dex.field_ids().len() {
+        ids.push(Reference::Field(field_from_dex(dex, idx as u32)?));
+    }
+    for idx in 0..dex.method_ids().len() {
+        ids.push(Reference::Method(method_from_dex(dex, idx as u32)?));
+    }
+    for idx in 0..dex.call_sites().len() {
+        ids.push(Reference::CallSite(call_site_from_dex(dex, idx as u32)?));
+    }
+    for idx in 0..dex.method_handles().len() {
+        ids.push(Reference::MethodHandle(method_handle_from_dex(dex, idx as u32)?));
+    }
+    return Ok(ids);
+}
+
+fn code_from_dex(dex: &DexFile, code: &dex::CodeItem) -> Result<Code, String> {
+    let mut fixups: Vec<Fixup> = vec![];
+    let (instructions, invalid) = dalvik::instructions(code.insns());
+    if let Some(offset) = invalid {
+        // Index operands past this point could not be carried over
+        return Err(format!("invalid instruction at 0x{:x}", offset));
+    }
+    for instruction in instructions.iter().filter(|i| i.payload().is_none()) {
+        let opcode = instruction.opcode();
+        let index = instruction.index();
+        let reference = match opcode.index_type {
+            IndexType::String => Reference::String(string_units_from_dex(dex, index)?),
+            IndexType::Type => Reference::Type(type_from_dex(dex, index)?),
+            IndexType::Field => Reference::Field(field_from_dex(dex, index)?),
+            IndexType::Method | IndexType::MethodAndProto => {
+                Reference::Method(method_from_dex(dex, index)?)
+            }
+            IndexType::Proto => Reference::Proto(proto_from_dex(dex, index)?),
+            IndexType::CallSite => Reference::CallSite(call_site_from_dex(dex, index)?),
+            IndexType::MethodHandle => {
+                Reference::MethodHandle(method_handle_from_dex(dex, index)?)
+            }
+            IndexType::None => continue,
+        };
+        fixups.push(Fixup {
+            position: instruction.offset() + 1,
+            wide: opcode.format == Format::F31c,
+            reference: reference,
+        });
+        if opcode.index_type == IndexType::MethodAndProto {
+            fixups.push(Fixup {
+                position: instruction.offset() + 3,
+                wide: false,
+                reference: Reference::Proto(proto_from_dex(dex, instruction.proto_index())?),
+            });
+        }
+    }
+
+    let mut tries: Vec<Try> = vec![];
+    for try_item in code.tries() {
+        let handler = match code.handler(try_item) {
+            Some(handler) => handler,
+            None => return Err(format!("try at 0x{:x} has no handler", try_item.start_addr())),
+        };
+        let mut handlers = vec![];
+        for pair in handler.handlers() {
+            handlers.push((type_from_dex(dex, pair.type_idx())?, pair.addr()));
+        }
+        tries.push(Try {
+            start_addr: try_item.start_addr(),
+            insn_count: (try_item.end_addr() - try_item.start_addr()) as u16,
+            handlers: handlers,
+            catch_all: handler.catch_all_addr(),
+        });
+    }
+
+    let debug_info = match code.debug_info() {
+        Some(debug_info) => Some(debug_info_from_dex(dex, debug_info)?),
+        None => None,
+    };
+
+    return Ok(Code {
+        registers_size: code.registers_size(),
+        ins_size: code.ins_size(),
+        outs_size: code.outs_size(),
+        insns: code.insns().to_vec(),
+        fixups: fixups,
+        tries: tries,
+        debug_info: debug_info,
+    });
+}
+
+fn optional_string_from_dex(dex: &DexFile, idx: u32) -> Result<Option<String>, String> {
+    if idx == dex::NO_INDEX {
+        return Ok(None);
+    }
+    return Ok(Some(string_from_dex(dex, idx)?));
+}
+
+fn debug_info_from_dex(dex: &DexFile, debug_info: &dex::DebugInfo) -> Result<DebugInfo, String> {
+    let mut parameter_names = vec![];
+    for &name_idx in debug_info.parameter_names() {
+        parameter_names.push(optional_string_from_dex(dex, name_idx)?);
+    }
+    let mut events = vec![];
+    for &(address, event) in debug_info.events() {
+        let event = match event {
+            dex::DebugEvent::Line(line) => DebugEvent::Line(line),
+            dex::DebugEvent::StartLocal { register, name_idx, type_idx, signature_idx } => {
+                DebugEvent::StartLocal {
+                    register: register,
+                    name: optional_string_from_dex(dex, name_idx)?,
+                    ty: if type_idx == dex::NO_INDEX {
+                        None
+                    } else {
+                        Some(type_from_dex(dex, type_idx)?)
+                    },
+                    signature: optional_string_from_dex(dex, signature_idx)?,
+                }
+            }
+            dex::DebugEvent::EndLocal(register) => DebugEvent::EndLocal(register),
+            dex::DebugEvent::RestartLocal(register) => DebugEvent::RestartLocal(register),
+            dex::DebugEvent::PrologueEnd => DebugEvent::PrologueEnd,
+            dex::DebugEvent::EpilogueBegin => DebugEvent::EpilogueBegin,
+            dex::DebugEvent::SetFile(name_idx) => {
+                DebugEvent::SetFile(optional_string_from_dex(dex, name_idx)?)
+            }
+        };
+        events.push((address, event));
+    }
+    return Ok(DebugInfo {
+        parameter_names: parameter_names,
+        events: events,
+    });
+}
+
+fn fields_from_dex(dex: &DexFile,
+                   class_def: &dex::ClassDef,
+                   fields: &[dex::EncodedField],
+                   values: &[EncodedValue])
+                   -> Result<Vec<Field>, String> {
+    let mut list = vec![];
+    for (i, field) in fields.iter().enumerate() {
+        let value = match values.get(i) {
+            Some(value) => Some(value_from_dex(dex, value)?),
+            None => None,
+        };
+        let annotations = match class_def.annotations() {
+            Some(directory) => annotations_from_dex(dex, directory.field(field.field_idx()))?,
+            None => vec![],
+        };
+        list.push(Field {
+            field: field_from_dex(dex, field.field_idx())?,
+            access_flags: field.access_flags(),
+            value: value,
+            annotations: annotations,
+        });
+    }
+    return Ok(list);
+}
+
+fn methods_from_dex(dex: &DexFile,
+                    class_def: &dex::ClassDef,
+                    methods: &[dex::EncodedMethod])
+                    -> Result<Vec<Method>, String> {
+    let mut list = vec![];
+    for method in methods {
+        let code = match method.code() {
+            Some(code) => Some(code_from_dex(dex, code)?),
+            None => None,
+        };
+        let mut annotations = vec![];
+        let mut parameter_annotations = vec![];
+        if let Some(directory) = class_def.annotations() {
+            annotations = annotations_from_dex(dex, directory.method(method.method_idx()))?;
+            for parameter in directory.parameters(method.method_idx()) {
+                parameter_annotations.push(annotations_from_dex(dex, parameter)?);
+            }
+        }
+        list.push(Method {
+            method: method_from_dex(dex, method.method_idx())?,
+            access_flags: method.access_flags(),
+            code: code,
+            annotations: annotations,
+            parameter_annotations: parameter_annotations,
+        });
+    }
+    return Ok(list);
+}
+
+/// Fails with a description of the first member that carries annotations
+/// but is not part of the class data, since those would be lost
+fn check_annotated_members(directory: &dex::AnnotationsDirectory,
+                           data: &dex::ClassData)
+                           -> Result<(), String> {
+    let fields: HashSet<u32> = data.static_fields()
+        .iter()
+        .chain(data.instance_fields().iter())
+        .map(|f| f.field_idx())
+        .collect();
+    let methods: HashSet<u32> = data.direct_methods()
+        .iter()
+        .chain(data.virtual_methods().iter())
+        .map(|m| m.method_idx())
+        .collect();
+    for field in directory.field_annotations() {
+        if !fields.contains(&field.field_idx()) {
+            return Err(format!("field {}", field.field_idx()));
+        }
+    }
+    let method_idxs = directory.method_annotations()
+        .iter()
+        .map(|m| m.method_idx())
+        .chain(directory.parameter_annotations().iter().map(|p| p.method_idx()));
+    for method_idx in method_idxs {
+        if !methods.contains(&method_idx) {
+            return Err(format!("method {}", method_idx));
+        }
+    }
+    return Ok(());
+}
+
+/// Converts the class definitions of a parsed file into the symbolic model
+/// `write` works on, e.g. to modify them before writing a new file.
+pub fn classes(dex: &DexFile) -> Result<Vec<Class>, String> {
+    let mut classes = vec![];
+    for class_def in dex.class_defs() {
+        let mut class = Class {
+            descriptor: type_from_dex(dex, class_def.class_idx())?,
+            access_flags: class_def.access_flags(),
+            superclass: None,
+            interfaces: vec![],
+            source_file: None,
+            annotations: vec![],
+            static_fields: vec![],
+            instance_fields: vec![],
+            direct_methods: vec![],
+            virtual_methods: vec![],
+        };
+        if class_def.superclass_idx() != dex::NO_INDEX {
+            class.superclass = Some(type_from_dex(dex, class_def.superclass_idx())?);
+        }
+        if class_def.source_file_idx() != dex::NO_INDEX {
+            class.source_file = Some(string_from_dex(dex, class_def.source_file_idx())?);
+        }
+        for &interface in class_def.interfaces() {
+            class.interfaces.push(type_from_dex(dex, interface as u32)?);
+        }
+        if let Some(directory) = class_def.annotations() {
+            class.annotations = annotations_from_dex(dex, directory.class_annotations())?;
+        }
+        if let Some(data) = class_def.class_data() {
+            if class_def.static_values().len() > data.static_fields().len() {
+                return Err(format!("{} has more static values than static fields",
+                                   class.descriptor));
+            }
+            if let Some(directory) = class_def.annotations() {
+                check_annotated_members(directory, data)
+                    .map_err(|member| format!("{} annotates {} it does not define",
+                                              class.descriptor,
+                                              member))?;
+            }
+            class.static_fields = fields_from_dex(dex,
+                                                  class_def,
+                                                  data.static_fields(),
+                                                  class_def.static_values())?;
+            class.instance_fields = fields_from_dex(dex, class_def, data.instance_fields(), &[])?;
+            class.direct_methods = methods_from_dex(dex, class_def, data.direct_methods())?;
+            class.virtual_methods = methods_from_dex(dex, class_def, data.virtual_methods())?;
+        }
+        classes.push(class);
+    }
+    return Ok(classes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assembler;
+
+    const SOURCE: &str = "
+.class public LTest;
+.super Ljava/lang/Object;
+.field public static s:Ljava/lang/String; = \"\\ud800x\"
+.method public static f()I
+    .registers 1
+    const v0, 0x7ead1234
+    return v0
+.end method
+";
+
+    fn position(data: &[u8], pattern: &[u8]) -> usize {
+        return data.windows(pattern.len()).position(|w| w == pattern).unwrap();
+    }
+
+    #[test]
+    fn write_dex_round_trips_unpaired_surrogates() {
+        let original = assembler::assemble(&[SOURCE]).unwrap();
+        let dex = dex::parse(original.clone()).unwrap();
+        let written = write_dex(&dex).unwrap();
+        assert_eq!(written, original);
+
+        let dex = dex::parse(written).unwrap();
+        let idx = dex.strings().iter().position(|s| s == "\u{fffd}x").unwrap();
+        assert_eq!(dex.string_units(idx as u32).unwrap(), vec![0xd800, 0x78]);
+    }
+
+    #[test]
+    fn write_dex_refuses_to_drop_data() {
+        let original = assembler::assemble(&[SOURCE]).unwrap();
+
+        // const is 0x14, 0x3e is unused
+        let mut data = original.clone();
+        let insn = position(&data, &[0x14, 0x00, 0x34, 0x12, 0xad, 0x7e]);
+        data[insn] = 0x3e;
+        let error = write_dex(&dex::parse(data).unwrap()).unwrap_err();
+        assert_eq!(error, "invalid instruction at 0x0");
+
+        let mut data = original.clone();
+        data[0x2c] = 4;
+        data[0x30] = 0x70;
+        let error = write_dex(&dex::parse(data).unwrap()).unwrap_err();
+        assert!(error.contains("statically linked"));
+    }
+
+    #[test]
+    fn instance_fields_cannot_have_values() {
+        let class = Class {
+            descriptor: "LTest;".to_string(),
+            access_flags: 1,
+            superclass: Some("Ljava/lang/Object;".to_string()),
+            interfaces: vec![],
+            source_file: None,
+            annotations: vec![],
+            static_fields: vec![],
+            instance_fields: vec![Field {
+                                      field: FieldRef {
+                                          class: "LTest;".to_string(),
+                                          name: "x".to_string(),
+                                          ty: "I".to_string(),
+                                      },
+                                      access_flags: 1,
+                                      value: Some(Value::Int(1)),
+                                      annotations: vec![],
+                                  }],
+            direct_methods: vec![],
+            virtual_methods: vec![],
+        };
+        assert_eq!(write(&[class]).unwrap_err(), "instance field LTest;.x has a value");
+    }
+}